@@ -0,0 +1,383 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use regex::Regex;
+
+use crate::editor::Editor;
+use crate::input::{Input, Key};
+use crate::textarea::{BytePosition, CursorPosition, HistoryAction};
+
+/// One command of a `--batch` script. Lines are one-based, as written in the script.
+#[derive(Debug)]
+pub enum Command {
+    /// `replace:/pattern/replacement/`, with a `g` after it for every match on a line instead of the first.
+    Replace {
+        pattern: Regex,
+        replacement: String,
+        global: bool,
+    },
+    /// `goto:LINE` moves to the start of a line, where the following commands work.
+    Goto(usize),
+    /// `insert:TEXT` inserts the text as whole lines before the current one, `\n` separating them.
+    Insert(Vec<String>),
+    /// `delete`, `delete:LINE` or `delete:FIRST,LAST` deletes the current line or the given ones.
+    Delete(Option<RangeInclusive<usize>>),
+    /// `indent` and `dedent`, taking lines like `delete`, change their indent by a level.
+    Indent {
+        lines: Option<RangeInclusive<usize>>,
+        dedent: bool,
+    },
+}
+
+/// A command and where it is in the script, for errors about it.
+#[derive(Debug)]
+pub struct Step {
+    pub command: Command,
+    pub at: Location,
+}
+
+/// One-based line and column in the script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub line: usize,
+    pub col: usize,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error {
+    pub at: Location,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.at.line, self.at.col, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses a script: commands separated by `;` or newlines, with `#` starting a comment.
+pub fn parse(script: &str) -> Result<Vec<Step>, Error> {
+    let mut parser = Parser { script, pos: 0 };
+    let mut steps = Vec::new();
+    loop {
+        parser.skip_separators();
+        if parser.peek().is_none() {
+            return Ok(steps);
+        }
+        let at = parser.location(parser.pos);
+        let command = parser.command()?;
+        steps.push(Step { command, at });
+
+        parser.skip_blanks();
+        match parser.peek() {
+            None | Some(';' | '\n' | '#') => {}
+            Some(_) => return Err(parser.error(parser.pos, "expected ; or a new line after the command")),
+        }
+    }
+}
+
+struct Parser<'s> {
+    script: &'s str,
+    /// Byte offset of the next char.
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.script[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_blanks(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t' || c == '\r') {
+            self.bump();
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() || c == ';' => {
+                    self.bump();
+                }
+                Some('#') => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn location(&self, pos: usize) -> Location {
+        let before = &self.script[..pos];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        Location {
+            line: before.matches('\n').count() + 1,
+            col: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    fn error(&self, pos: usize, message: impl Into<String>) -> Error {
+        Error {
+            at: self.location(pos),
+            message: message.into(),
+        }
+    }
+
+    fn command(&mut self) -> Result<Command, Error> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.bump();
+        }
+        let name = &self.script[start..self.pos];
+        let has_argument = self.peek() == Some(':');
+        if has_argument {
+            self.bump();
+        }
+
+        match name {
+            "replace" if has_argument => self.replace(),
+            "goto" if has_argument => Ok(Command::Goto(self.number()?)),
+            "insert" if has_argument => Ok(Command::Insert(self.text().split('\n').map(String::from).collect())),
+            "delete" => Ok(Command::Delete(self.lines(has_argument)?)),
+            "indent" | "dedent" => Ok(Command::Indent {
+                lines: self.lines(has_argument)?,
+                dedent: name == "dedent",
+            }),
+            "replace" | "goto" | "insert" => Err(self.error(self.pos, format!("expected : after {name}"))),
+            "" => Err(self.error(start, "expected a command")),
+            _ => Err(self.error(start, format!("unknown command {name}"))),
+        }
+    }
+
+    fn replace(&mut self) -> Result<Command, Error> {
+        let start = self.pos;
+        let delimiter = match self.bump() {
+            Some(c) if !c.is_alphanumeric() && !c.is_whitespace() && c != '\\' && c != ';' => c,
+            _ => return Err(self.error(start, "expected a delimiter like / around the pattern")),
+        };
+
+        let pattern_start = self.pos;
+        let pattern = self.delimited(delimiter)?;
+        let pattern =
+            Regex::new(&pattern).map_err(|err| self.error(pattern_start, format!("invalid pattern: {err}")))?;
+        let replacement = self.delimited(delimiter)?;
+
+        let mut global = false;
+        while let Some(c) = self.peek().filter(char::is_ascii_alphabetic) {
+            if c != 'g' {
+                return Err(self.error(self.pos, format!("unknown flag {c}, only g is known")));
+            }
+            global = true;
+            self.bump();
+        }
+        Ok(Command::Replace { pattern, replacement, global })
+    }
+
+    /// Text up to the next unescaped `delimiter`, which `\` escapes. Other escapes are kept for the regex.
+    fn delimited(&mut self, delimiter: char) -> Result<String, Error> {
+        let start = self.pos;
+        let mut text = String::new();
+        loop {
+            match self.bump() {
+                Some(c) if c == delimiter => return Ok(text),
+                Some('\\') if self.peek() == Some(delimiter) => text.push(self.bump().unwrap()),
+                Some('\n') | None => {
+                    return Err(self.error(start, format!("expected {delimiter} to end this")));
+                }
+                Some(c) => text.push(c),
+            }
+        }
+    }
+
+    /// Text up to the end of the command, with `\;`, `\n`, `\t` and `\\` escapes.
+    fn text(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(c) = self.peek().filter(|&c| c != ';' && c != '\n') {
+            self.bump();
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+            match self.bump() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some(c) => text.push(c),
+                None => text.push('\\'),
+            }
+        }
+        text.trim_end_matches('\r').to_string()
+    }
+
+    fn number(&mut self) -> Result<usize, Error> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.bump();
+        }
+        match self.script[start..self.pos].parse() {
+            Ok(0) => Err(self.error(start, "lines start at 1")),
+            Ok(n) => Ok(n),
+            Err(_) => Err(self.error(start, "expected a line number")),
+        }
+    }
+
+    /// `LINE` or `FIRST,LAST`, if the command has an argument.
+    fn lines(&mut self, has_argument: bool) -> Result<Option<RangeInclusive<usize>>, Error> {
+        if !has_argument {
+            return Ok(None);
+        }
+        let start = self.pos;
+        let first = self.number()?;
+        let last = if self.peek() == Some(',') {
+            self.bump();
+            self.number()?
+        } else {
+            first
+        };
+        if last < first {
+            return Err(self.error(start, format!("{first},{last} ends before it starts")));
+        }
+        Ok(Some(first..=last))
+    }
+}
+
+/// Runs `steps` on the text of `editor`, starting at its first line, returning what they did for a summary. The text
+/// is left as it was if a step fails.
+pub fn run(editor: &mut Editor, steps: &[Step]) -> Result<Vec<String>, Error> {
+    editor.textarea.set_cursor(CursorPosition::default(), false);
+    let transaction = editor.textarea.begin_transaction();
+    let done = run_steps(editor, steps);
+    editor.textarea.end_transaction(transaction, done.is_ok());
+    done
+}
+
+fn run_steps(editor: &mut Editor, steps: &[Step]) -> Result<Vec<String>, Error> {
+    let mut done = Vec::new();
+    for Step { command, at } in steps {
+        let textarea = &mut editor.textarea;
+        // an empty line after the last linebreak isn't a line of its own
+        let line_count =
+            textarea.lines.len() - usize::from(textarea.lines.len() > 1 && textarea.lines.last().unwrap().is_empty());
+        let error = |message: String| Error { at: *at, message };
+        let rows = |lines: &Option<RangeInclusive<usize>>, row: usize| match lines {
+            Some(lines) if *lines.end() > line_count => Err(error(format!(
+                "line {} is past the end, there are {line_count}",
+                lines.end()
+            ))),
+            Some(lines) => Ok(lines.start() - 1..=lines.end() - 1),
+            None => Ok(row..=row),
+        };
+
+        match command {
+            Command::Replace { pattern, replacement, global } => {
+                let count = textarea.replace_all(pattern, replacement, if *global { 0 } else { 1 });
+                done.push(format!("replaced {count}"));
+            }
+            Command::Goto(line) => {
+                if *line > line_count + 1 {
+                    return Err(error(format!("line {line} is past the end, there are {line_count}")));
+                }
+                textarea.set_cursor(CursorPosition { row: line - 1, col: 0 }, false);
+            }
+            Command::Insert(lines) => {
+                let cursor = textarea.cursor();
+                let after = CursorPosition {
+                    row: cursor.row + lines.len(),
+                    col: 0,
+                };
+                textarea.do_action(HistoryAction::InsertLines {
+                    lines: lines.iter().cloned().chain([String::new()]).collect(),
+                    position: BytePosition { row: cursor.row, col: 0 },
+                    cursor: (cursor, after),
+                });
+                textarea.set_cursor(after, false);
+                done.push(plural("inserted", lines.len()));
+            }
+            Command::Delete(lines) => {
+                let rows = rows(lines, textarea.cursor().row)?;
+                let count = rows.clone().count();
+                textarea.select_lines(*rows.start(), *rows.end());
+                textarea.input(Input {
+                    key: Key::Delete,
+                    ..Default::default()
+                });
+                done.push(plural("deleted", count));
+            }
+            Command::Indent { lines, dedent } => {
+                let rows = rows(lines, textarea.cursor().row)?;
+                let count = editor.indent_rows(rows, *dedent);
+                done.push(plural(if *dedent { "dedented" } else { "indented" }, count));
+            }
+        }
+    }
+    Ok(done)
+}
+
+fn plural(done: &str, lines: usize) -> String {
+    format!("{done} {lines} line{}", if lines == 1 { "" } else { "s" })
+}
+
+#[test]
+fn parse_errors() {
+    let error = |script| parse(script).unwrap_err().to_string();
+    assert_eq!(error("goto:3; frob"), "1:9: unknown command frob");
+    assert_eq!(error("goto:x"), "1:6: expected a line number");
+    assert_eq!(error("goto:0"), "1:6: lines start at 1");
+    assert_eq!(error("goto 3"), "1:5: expected : after goto");
+    assert!(error("# fix\nreplace:/a(/b/").starts_with("2:10: invalid pattern: "));
+    assert_eq!(error("replace:/a/b"), "1:12: expected / to end this");
+    assert_eq!(error("replace:/a/b/x"), "1:14: unknown flag x, only g is known");
+    assert_eq!(error("delete:5,3"), "1:8: 5,3 ends before it starts");
+    assert_eq!(error("delete:2 3"), "1:10: expected ; or a new line after the command");
+    assert_eq!(error("  ;:"), "1:4: expected a command");
+    assert!(parse(" ;\n# nothing\n").unwrap().is_empty());
+}
+
+#[test]
+fn run_script() {
+    let mut editor = Editor::default();
+    editor.textarea.lines = ["fn a() {", "x", "}", "a/b a/b", ""].map(String::from).into();
+    let steps = parse(
+        "replace:|a/b|c|; replace:#^x#  y#g # comment\n\
+         goto:3; insert:fn b() {\\n}; indent:3,4; dedent:2\n\
+         delete:6",
+    )
+    .unwrap();
+    let done = run(&mut editor, &steps).unwrap();
+    assert_eq!(
+        editor.textarea.lines,
+        ["fn a() {", "y", "    fn b() {", "    }", "}", ""]
+    );
+    assert_eq!(
+        done,
+        [
+            "replaced 1",
+            "replaced 1",
+            "inserted 2 lines",
+            "indented 2 lines",
+            "dedented 1 line",
+            "deleted 1 line"
+        ]
+    );
+
+    let steps = parse("goto:2\ndelete:9").unwrap();
+    assert_eq!(
+        run(&mut editor, &steps).unwrap_err().to_string(),
+        "2:1: line 9 is past the end, there are 5"
+    );
+
+    // what a failing script did before the error is taken back
+    let lines = editor.textarea.lines.clone();
+    let steps = parse("replace:/y/z/; delete:1; goto:9").unwrap();
+    assert!(run(&mut editor, &steps).is_err());
+    assert_eq!(editor.textarea.lines, lines);
+}
@@ -0,0 +1,120 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Line listing the parent directory, above the entries of every directory that has one.
+const PARENT: &str = "../";
+
+/// The entries of a directory, shown one per line in a buffer opened on the directory instead of a file. Directories
+/// come first, with a slash after their name.
+pub struct Browser {
+    dir: PathBuf,
+    /// Whether entries whose name starts with a dot are listed.
+    show_hidden: bool,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    name: OsString,
+    is_dir: bool,
+}
+
+impl Browser {
+    /// Reads the entries of `dir`, which should be absolute for the parent to be listed.
+    pub fn new(dir: PathBuf, show_hidden: bool) -> io::Result<Self> {
+        let mut entries = fs::read_dir(&dir)?
+            .filter_map(Result::ok)
+            .map(|entry| Entry {
+                name: entry.file_name(),
+                // a symlink to a directory is gone into like one
+                is_dir: entry.path().is_dir(),
+            })
+            .filter(|entry| show_hidden || !entry.name.as_encoded_bytes().starts_with(b"."))
+            .collect::<Vec<_>>();
+        entries.sort_by_cached_key(|entry| {
+            let name = entry.name.to_string_lossy();
+            (!entry.is_dir, name.to_lowercase(), entry.name.clone())
+        });
+        Ok(Self { dir, show_hidden, entries })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn show_hidden(&self) -> bool {
+        self.show_hidden
+    }
+
+    /// The lines the listing is shown as.
+    pub fn lines(&self) -> Vec<String> {
+        let parent = self.dir.parent().map(|_| PARENT.to_string());
+        let entries = self.entries.iter().map(|entry| {
+            let name = entry.name.to_string_lossy();
+            if entry.is_dir {
+                format!("{name}/")
+            } else {
+                name.into_owned()
+            }
+        });
+        let lines = parent.into_iter().chain(entries).collect::<Vec<_>>();
+        if lines.is_empty() { vec![String::new()] } else { lines }
+    }
+
+    /// What is listed on `row`, and whether it is a directory.
+    pub fn entry(&self, row: usize) -> Option<(PathBuf, bool)> {
+        let row = match self.dir.parent() {
+            Some(parent) if row == 0 => return Some((parent.to_path_buf(), true)),
+            Some(_) => row - 1,
+            None => row,
+        };
+        let entry = self.entries.get(row)?;
+        Some((self.dir.join(&entry.name), entry.is_dir))
+    }
+
+    /// The row listing `name`, or the first entry if it isn't listed.
+    pub fn row_of(&self, name: Option<&OsStr>) -> usize {
+        let offset = usize::from(self.dir.parent().is_some());
+        let row = name.and_then(|name| self.entries.iter().position(|entry| entry.name == name));
+        match row {
+            Some(row) => row + offset,
+            None if self.entries.is_empty() => 0,
+            None => offset,
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let dir = std::env::temp_dir().join("ded-test-browser");
+    _ = fs::remove_dir_all(&dir);
+    for sub in ["src", "Docs", ".git"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    for file in ["b.txt", "A.md", ".hidden"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+
+    let browser = Browser::new(dir.clone(), false).unwrap();
+    assert_eq!(browser.lines(), ["../", "Docs/", "src/", "A.md", "b.txt"]);
+    assert_eq!(browser.entry(0), Some((std::env::temp_dir(), true)));
+    assert_eq!(browser.entry(2), Some((dir.join("src"), true)));
+    assert_eq!(browser.entry(4), Some((dir.join("b.txt"), false)));
+    assert_eq!(browser.entry(5), None);
+    assert_eq!(browser.row_of(Some(OsStr::new("A.md"))), 3);
+    assert_eq!(browser.row_of(Some(OsStr::new(".git"))), 1);
+
+    let browser = Browser::new(dir.clone(), true).unwrap();
+    assert_eq!(
+        browser.lines(),
+        ["../", ".git/", "Docs/", "src/", ".hidden", "A.md", "b.txt"]
+    );
+    assert_eq!(browser.row_of(Some(OsStr::new(".git"))), 1);
+
+    let browser = Browser::new(dir.join("src"), false).unwrap();
+    assert_eq!(browser.lines(), ["../"]);
+    assert_eq!(browser.row_of(None), 0);
+    assert!(Browser::new(dir.join("A.md"), false).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,261 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Widget};
+
+use crate::input::{Input, Key};
+use crate::textarea::{CursorPosition, TextArea};
+
+const RECENT_LEN: usize = 16;
+
+/// Symbols that can be looked up by name, for the characters most keyboards can't type.
+const NAMED_CHARS: &[(&str, char)] = &[
+    ("em dash", '—'),
+    ("en dash", '–'),
+    ("minus", '−'),
+    ("ellipsis", '…'),
+    ("bullet", '•'),
+    ("middle dot", '·'),
+    ("non-breaking space", '\u{a0}'),
+    ("left double quote", '“'),
+    ("right double quote", '”'),
+    ("left single quote", '‘'),
+    ("right single quote", '’'),
+    ("left guillemet", '«'),
+    ("right guillemet", '»'),
+    ("left arrow", '←'),
+    ("up arrow", '↑'),
+    ("right arrow", '→'),
+    ("down arrow", '↓'),
+    ("left right arrow", '↔'),
+    ("double right arrow", '⇒'),
+    ("double left arrow", '⇐'),
+    ("multiplication", '×'),
+    ("division", '÷'),
+    ("plus minus", '±'),
+    ("not equal", '≠'),
+    ("less or equal", '≤'),
+    ("greater or equal", '≥'),
+    ("approximately", '≈'),
+    ("infinity", '∞'),
+    ("degree", '°'),
+    ("micro", 'µ'),
+    ("pi", 'π'),
+    ("lambda", 'λ'),
+    ("check mark", '✓'),
+    ("cross mark", '✗'),
+    ("section", '§'),
+    ("copyright", '©'),
+    ("registered", '®'),
+    ("trademark", '™'),
+    ("euro", '€'),
+    ("pound", '£'),
+    ("box horizontal", '─'),
+    ("box vertical", '│'),
+    ("box down right", '┌'),
+    ("box down left", '┐'),
+    ("box up right", '└'),
+    ("box up left", '┘'),
+    ("box vertical right", '├'),
+    ("box vertical left", '┤'),
+    ("box down horizontal", '┬'),
+    ("box up horizontal", '┴'),
+    ("box cross", '┼'),
+];
+
+/// Prompt for inserting a character by hex codepoint or by name.
+pub struct CharBox<'a> {
+    pub textarea: TextArea,
+    border_block: Block<'a>,
+    open: bool,
+    /// Most recently inserted first, browsed with Up/Down.
+    recent: Vec<char>,
+    recent_idx: Option<usize>,
+}
+
+impl<'a> Default for CharBox<'a> {
+    fn default() -> Self {
+        let mut textarea = TextArea::default();
+        textarea.line_numbers = false;
+        textarea.single_line = true;
+
+        Self {
+            textarea,
+            border_block: Self::block(None),
+            open: false,
+            recent: Vec::new(),
+            recent_idx: None,
+        }
+    }
+}
+
+impl<'a> CharBox<'a> {
+    pub fn open(&mut self) {
+        self.open = true;
+        self.recent_idx = None;
+        self.set_text("");
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Handles `input`, returning the character to insert once Enter is pressed on a valid one.
+    pub fn input(&mut self, input: Input) -> Option<char> {
+        match input {
+            Input { key: Key::Enter, .. } => match self.parse() {
+                Ok(char) => {
+                    self.recent.retain(|&c| c != char);
+                    self.recent.insert(0, char);
+                    self.recent.truncate(RECENT_LEN);
+                    self.close();
+                    return Some(char);
+                }
+                Err(err) => self.border_block = Self::block(Some(Err(err))),
+            },
+            Input { key: Key::Esc, .. } => self.close(),
+            Input { key: Key::Up, .. } => {
+                let idx = self.recent_idx.map_or(0, |idx| idx + 1);
+                if let Some(&char) = self.recent.get(idx) {
+                    self.recent_idx = Some(idx);
+                    self.set_text(&format!("{:04X}", u32::from(char)));
+                }
+            }
+            Input { key: Key::Down, .. } => match self.recent_idx {
+                Some(0) | None => {
+                    self.recent_idx = None;
+                    self.set_text("");
+                }
+                Some(idx) => {
+                    self.recent_idx = Some(idx - 1);
+                    self.set_text(&format!("{:04X}", u32::from(self.recent[idx - 1])));
+                }
+            },
+            input => {
+                if self.textarea.input(input) {
+                    self.border_block = Self::block(Some(self.parse()));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.textarea.set_lines(vec![text.to_string()]);
+        self.textarea.set_cursor(
+            CursorPosition {
+                row: 0,
+                col: text.chars().count(),
+            },
+            false,
+        );
+        self.border_block = Self::block((!text.is_empty()).then(|| self.parse()));
+    }
+
+    /// Looks the prompt up in [`NAMED_CHARS`], or else reads it as a hex codepoint, so a name being typed like "de"
+    /// isn't taken for one. `U+` or `0x` in front makes it a codepoint right away.
+    fn parse(&self) -> Result<char, &'static str> {
+        let text = self.textarea.lines[0].trim();
+        let hex = ["U+", "u+", "0x", "0X"]
+            .iter()
+            .find_map(|prefix| text.strip_prefix(prefix));
+        if hex.is_none() {
+            let text = text.to_lowercase();
+            if let Some(&(_, char)) = NAMED_CHARS
+                .iter()
+                .find(|(name, _)| !text.is_empty() && name.contains(&text))
+            {
+                return Ok(char);
+            }
+        }
+
+        let codepoint = u32::from_str_radix(hex.unwrap_or(text), 16).map_err(|_| "no such character")?;
+        char::from_u32(codepoint).ok_or("not a valid codepoint")
+    }
+
+    /// Shows what Enter would insert, or why it can't.
+    fn block(preview: Option<Result<char, &'static str>>) -> Block<'a> {
+        let block = Block::default().borders(Borders::ALL);
+        match preview {
+            Some(Ok(char)) => block.title(format!(" Unicode: {char} U+{:04X} ", u32::from(char))),
+            Some(Err(err)) => block
+                .title(format!(" Unicode: {err} "))
+                .style(Style::default().fg(Color::Red)),
+            None => block.title(" Unicode (hex or name): "),
+        }
+    }
+}
+
+impl<'a> Widget for &CharBox<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        if area.is_empty() {
+            return;
+        }
+
+        (&self.border_block).render(area, buf);
+        self.textarea.render(self.border_block.inner(area), buf);
+    }
+}
+
+#[cfg(test)]
+fn type_text(charbox: &mut CharBox, text: &str) {
+    for c in text.chars() {
+        charbox.input(Input {
+            key: Key::Char(c),
+            ctrl: false,
+            alt: false,
+            shift: false,
+        });
+    }
+}
+
+#[test]
+fn test() {
+    let enter = Input {
+        key: Key::Enter,
+        ctrl: false,
+        alt: false,
+        shift: false,
+    };
+    let up = Input { key: Key::Up, ..enter };
+    let mut charbox = CharBox::default();
+
+    charbox.open();
+    type_text(&mut charbox, "2014");
+    assert_eq!(charbox.input(enter.clone()), Some('—'));
+    assert!(!charbox.is_open());
+
+    charbox.open();
+    type_text(&mut charbox, "Right Arr");
+    assert_eq!(charbox.input(enter.clone()), Some('→'));
+
+    // surrogates and codepoints past the last plane are rejected
+    for text in ["D800", "110000", "nonsense"] {
+        charbox.open();
+        type_text(&mut charbox, text);
+        assert_eq!(charbox.input(enter.clone()), None);
+        assert!(charbox.is_open());
+    }
+
+    charbox.open();
+    charbox.input(up.clone());
+    charbox.input(up.clone());
+    assert_eq!(charbox.textarea.lines[0], "2014");
+    assert_eq!(charbox.input(enter.clone()), Some('—'));
+    assert_eq!(charbox.recent, ['—', '→']);
+
+    // names go before codepoints, unless one is asked for
+    for (text, char) in [("de", '°'), ("U+de", 'Þ'), ("0xDE", 'Þ')] {
+        charbox.open();
+        type_text(&mut charbox, text);
+        assert_eq!(charbox.input(enter.clone()), Some(char));
+    }
+}
@@ -0,0 +1,276 @@
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow, bail};
+
+use crate::path::split_location;
+use crate::textarea::CursorPosition;
+
+pub const HELP: &str = "\
+ded, a terminal text editor with keyboard shortcuts that are not some insane magic incantations
+
+USAGE:
+    ded [OPTIONS] [+LINE] FILE[:LINE[:COL]]...
+    ded --batch SCRIPT FILE...
+    ded --batch-file SCRIPT_FILE FILE...
+
+A directory opens a listing to pick files from.
+
+OPTIONS:
+    +LINE                Opens the file after it at LINE, unless its name ends in :LINE
+    -                    Edits what is piped in on stdin
+    --readonly           Opens the files without allowing changes
+    --config PATH        Reads the settings from PATH instead of ~/.config/ded/config.toml
+    --vim                Edits with vim's keys
+    --mouse              Takes mouse clicks for the editor, instead of leaving them to the terminal to select text
+    --pipe               Writes the text from stdin to stdout on quitting, and draws on stderr meanwhile
+    --diff               Shows the differences between the two files
+    --batch SCRIPT       Runs SCRIPT on each file and saves it, without opening the editor
+    --batch-file PATH    Like --batch, with the script read from PATH
+    --                   Takes everything after it as a file, even if it starts with - or +
+    -h, --help           Prints this help
+    -V, --version        Prints the version
+";
+
+/// Flags that take no value.
+const FLAGS: [&str; 9] = [
+    "--readonly",
+    "--vim",
+    "--mouse",
+    "--pipe",
+    "--diff",
+    "--help",
+    "-h",
+    "--version",
+    "-V",
+];
+
+/// Flags followed by a value.
+const OPTIONS: [&str; 3] = ["--config", "--batch", "--batch-file"];
+
+/// What the command line asks for.
+#[derive(Debug, Default, PartialEq)]
+pub struct CliOptions {
+    pub help: bool,
+    pub version: bool,
+    pub readonly: bool,
+    pub vim: bool,
+    pub mouse: bool,
+    pub pipe: bool,
+    pub diff: bool,
+    pub config: Option<PathBuf>,
+    pub batch: Option<Batch>,
+    /// In the order given, which is the order of the buffers.
+    pub files: Vec<FileArg>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Batch {
+    Script(String),
+    File(PathBuf),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FileArg {
+    Stdin,
+    /// A file and where to put the cursor in it, from a `:line[:col]` suffix or the `+LINE` before it.
+    Path(PathBuf, Option<CursorPosition>),
+}
+
+impl CliOptions {
+    /// Reads the arguments after the program name.
+    pub fn parse<I>(args: I) -> Result<Self>
+    where
+        I: IntoIterator,
+        I::Item: Into<OsString>,
+    {
+        let mut options = Self::default();
+        let mut args = args.into_iter().map(Into::into);
+        let mut line = None;
+        let mut only_files = false;
+
+        while let Some(arg) = args.next() {
+            let Some(text) = arg.to_str().filter(|_| !only_files) else {
+                options.push_file(PathBuf::from(arg), line.take(), only_files);
+                continue;
+            };
+            // a value can also be joined to its flag with =
+            let (flag, joined) = match text.split_once('=') {
+                Some((flag, value)) if OPTIONS.contains(&flag) => (flag, Some(OsString::from(value))),
+                _ => (text, None),
+            };
+
+            match flag {
+                "--" => only_files = true,
+                "-" => options.files.push(FileArg::Stdin),
+                "-h" | "--help" => options.help = true,
+                "-V" | "--version" => options.version = true,
+                "--readonly" => options.readonly = true,
+                "--vim" => options.vim = true,
+                "--mouse" => options.mouse = true,
+                "--pipe" => options.pipe = true,
+                "--diff" => options.diff = true,
+                "--config" | "--batch" | "--batch-file" => {
+                    let value = joined
+                        .or_else(|| args.next())
+                        .ok_or_else(|| anyhow!("{flag} needs a value, see ded --help"))?;
+                    match flag {
+                        "--config" => options.config = Some(value.into()),
+                        "--batch" => {
+                            let script = value
+                                .into_string()
+                                .map_err(|_| anyhow!("The script isn't valid UTF-8"))?;
+                            options.batch = Some(Batch::Script(script));
+                        }
+                        _ => options.batch = Some(Batch::File(value.into())),
+                    }
+                }
+                _ if let Some(n) = text.strip_prefix('+').and_then(|n| n.parse::<usize>().ok()) => {
+                    line = Some(n.saturating_sub(1));
+                }
+                _ if text.starts_with('-') => bail!("{}", unknown_flag(text)),
+                _ => options.push_file(PathBuf::from(text), line.take(), false),
+            }
+        }
+
+        if line.is_some() {
+            bail!("+LINE goes before the file it is for");
+        }
+        if options.help || options.version {
+            return Ok(options);
+        }
+        if options.files.is_empty() {
+            bail!("No file to edit, ded --help shows how to give one");
+        }
+        if options.batch.is_some() && options.files.contains(&FileArg::Stdin) {
+            bail!("--batch only runs on files, not on stdin");
+        }
+        Ok(options)
+    }
+
+    /// Adds the file `path`. A `:line[:col]` suffix of a file that exists without it is split off unless `as_is`,
+    /// and wins over `line`.
+    fn push_file(&mut self, path: PathBuf, line: Option<usize>, as_is: bool) {
+        let line = line.map(|row| CursorPosition { row, col: 0 });
+        let file = match path.to_str() {
+            Some(arg) if !as_is => {
+                let (path, position) = split_location(arg);
+                FileArg::Path(path.into(), position.or(line))
+            }
+            _ => FileArg::Path(path, line),
+        };
+        self.files.push(file);
+    }
+
+    /// The files given, without the positions, for `--batch`.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files
+            .iter()
+            .filter_map(|file| match file {
+                FileArg::Path(path, _) => Some(path.clone()),
+                FileArg::Stdin => None,
+            })
+            .collect()
+    }
+}
+
+/// The error for `flag`, suggesting the flag that is closest to it, if any is close.
+fn unknown_flag(flag: &str) -> String {
+    let closest = FLAGS
+        .iter()
+        .chain(&OPTIONS)
+        .filter(|known| known.starts_with("--"))
+        .map(|known| (edit_distance(flag, known), known))
+        .min();
+    let suggestion = match closest {
+        Some((distance, known)) if distance <= 2 || known.starts_with(flag) && flag.len() > 2 => {
+            format!(", did you mean {known}?")
+        }
+        _ => String::new(),
+    };
+    format!("Unknown option {flag}{suggestion} A file named like that is opened with ded -- {flag}")
+}
+
+/// How many chars have to be inserted, removed or replaced to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(a != b);
+            current.push(replace.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[test]
+fn test() {
+    let parse = |args: &[&str]| CliOptions::parse(args.iter().copied());
+    let path = |path: &str, position: Option<(usize, usize)>| {
+        FileArg::Path(path.into(), position.map(|(row, col)| CursorPosition { row, col }))
+    };
+
+    let options = parse(&["--vim", "--readonly", "-", "--config", "ded.toml", "a.txt"]).unwrap();
+    assert!(options.vim && options.readonly && !options.pipe && !options.mouse);
+    assert!(parse(&["--mouse", "a.txt"]).unwrap().mouse);
+    assert_eq!(options.config, Some("ded.toml".into()));
+    assert_eq!(options.files, [FileArg::Stdin, path("a.txt", None)]);
+    assert_eq!(parse(&["--config=x.toml", "a"]).unwrap().config, Some("x.toml".into()));
+
+    // everything after -- is a file, even what looks like a flag or a line
+    let options = parse(&["--", "-weird-name", "+3", "--help", "-"]).unwrap();
+    assert!(!options.help);
+    assert_eq!(
+        options.files,
+        [
+            path("-weird-name", None),
+            path("+3", None),
+            path("--help", None),
+            path("-", None)
+        ]
+    );
+
+    // a location in the name wins over +LINE, which only applies to the next file
+    let options = parse(&["+20", "src/main.rs:3", "+5", "Cargo.toml", "src/cli.rs"]).unwrap();
+    assert_eq!(
+        options.files,
+        [
+            path("src/main.rs", Some((2, 0))),
+            path("Cargo.toml", Some((4, 0))),
+            path("src/cli.rs", None),
+        ]
+    );
+    // one that doesn't exist is a name like any other
+    assert_eq!(
+        parse(&["+20", "foo.txt:3"]).unwrap().files,
+        [path("foo.txt:3", Some((19, 0)))]
+    );
+    assert_eq!(parse(&["+x"]).unwrap().files, [path("+x", None)]);
+
+    assert!(parse(&["--help"]).unwrap().help);
+    assert!(parse(&["a", "-V"]).unwrap().version);
+    let batch = parse(&["--batch", "goto:1", "a", "b"]).unwrap();
+    assert_eq!(batch.batch, Some(Batch::Script("goto:1".into())));
+    assert_eq!(batch.paths(), [PathBuf::from("a"), PathBuf::from("b")]);
+
+    let error = |args: &[&str]| parse(args).unwrap_err().to_string();
+    assert_eq!(
+        error(&["--redonly", "a"]),
+        "Unknown option --redonly, did you mean --readonly? A file named like that is opened with ded -- --redonly"
+    );
+    assert_eq!(
+        error(&["--batc", "x"]),
+        "Unknown option --batc, did you mean --batch? A file named like that is opened with ded -- --batc"
+    );
+    assert!(error(&["-x", "a"]).starts_with("Unknown option -x A file"));
+    assert_eq!(error(&["a", "--config"]), "--config needs a value, see ded --help");
+    assert_eq!(error(&["a", "+3"]), "+LINE goes before the file it is for");
+    assert_eq!(error(&[]), "No file to edit, ded --help shows how to give one");
+    assert_eq!(
+        error(&["--batch", "goto:1"]),
+        "No file to edit, ded --help shows how to give one"
+    );
+}
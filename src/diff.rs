@@ -0,0 +1,227 @@
+/// Edits beyond which the rest of a diff is given up on and shown as replaced, which keeps the memory for the
+/// search, quadratic in the number of edits, bounded.
+const MAX_EDITS: usize = 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Same,
+    /// On both sides but different.
+    Changed,
+    /// Only on the left.
+    Removed,
+    /// Only on the right.
+    Added,
+}
+
+/// One row of two texts lined up: the line from each side, or `None` where the other side has lines the row is a
+/// filler for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Row {
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub kind: Kind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Lines `left` up with `right`. Removed and added lines next to each other are paired up as changed, the rest get
+/// a filler on the other side.
+pub fn rows<T: PartialEq>(left: &[T], right: &[T]) -> Vec<Row> {
+    let mut rows = Vec::new();
+    let (mut l, mut r) = (0, 0);
+    let edits = edits(left, right);
+    let mut edits = edits.iter().peekable();
+    while let Some(edit) = edits.next() {
+        if *edit == Edit::Equal {
+            rows.push(Row {
+                left: Some(l),
+                right: Some(r),
+                kind: Kind::Same,
+            });
+            l += 1;
+            r += 1;
+            continue;
+        }
+
+        let (mut deleted, mut inserted) = (0, 0);
+        let mut count = |edit| match edit {
+            Edit::Delete => deleted += 1,
+            _ => inserted += 1,
+        };
+        count(*edit);
+        while let Some(&&edit) = edits.peek()
+            && edit != Edit::Equal
+        {
+            count(edit);
+            edits.next();
+        }
+        for idx in 0..deleted.max(inserted) {
+            let (left, right) = ((idx < deleted).then_some(l + idx), (idx < inserted).then_some(r + idx));
+            let kind = match (left, right) {
+                (Some(_), Some(_)) => Kind::Changed,
+                (Some(_), None) => Kind::Removed,
+                _ => Kind::Added,
+            };
+            rows.push(Row { left, right, kind });
+        }
+        l += deleted;
+        r += inserted;
+    }
+    rows
+}
+
+/// Indexes of the rows starting a run of differences.
+pub fn hunks(rows: &[Row]) -> Vec<usize> {
+    (0..rows.len())
+        .filter(|&idx| rows[idx].kind != Kind::Same && (idx == 0 || rows[idx - 1].kind == Kind::Same))
+        .collect()
+}
+
+/// The shortest edit script turning `a` into `b`, found with Myers' algorithm after taking off the common start and
+/// end.
+fn edits<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let prefix = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let middle = myers(&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut edits = vec![Edit::Equal; prefix];
+    edits.extend(middle);
+    edits.extend(vec![Edit::Equal; suffix]);
+    edits
+}
+
+fn myers<T: PartialEq>(a: &[T], b: &[T]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let replaced = || {
+        let mut edits = vec![Edit::Delete; a.len()];
+        edits.extend(vec![Edit::Insert; b.len()]);
+        edits
+    };
+
+    // v[k] is the furthest x reached on diagonal k = x - y, trace[d] the part of v that edit d started from
+    let offset = n + m + 1;
+    let mut v = vec![0; 2 * offset as usize + 1];
+    let mut trace = Vec::new();
+    let mut done = n == 0 && m == 0;
+    for d in 0..=n + m {
+        if done {
+            break;
+        }
+        if d as usize > MAX_EDITS {
+            return replaced();
+        }
+        trace.push(v[(offset - d - 1) as usize..=(offset + d + 1) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let idx = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                done = true;
+                break;
+            }
+        }
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let get = |k: isize| v[(k + d + 1) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = get(prev_k);
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                edits.push(Edit::Insert);
+                y -= 1;
+            } else {
+                edits.push(Edit::Delete);
+                x -= 1;
+            }
+        }
+    }
+    edits.reverse();
+    edits
+}
+
+#[test]
+fn test() {
+    let kinds = |a: &str, b: &str| {
+        let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+        rows(&a, &b)
+            .iter()
+            .map(|row| match row.kind {
+                Kind::Same => '=',
+                Kind::Changed => '~',
+                Kind::Removed => '-',
+                Kind::Added => '+',
+            })
+            .collect::<String>()
+    };
+
+    assert_eq!(kinds("", ""), "");
+    assert_eq!(kinds("abc", "abc"), "===");
+    assert_eq!(kinds("abc", ""), "---");
+    assert_eq!(kinds("", "ab"), "++");
+    assert_eq!(kinds("abcd", "axcd"), "=~==");
+    assert_eq!(kinds("abcabba", "cbabac"), "--=+==-=+");
+    assert_eq!(kinds("abcd", "abxyd"), "==~+=");
+
+    let (a, b) = (['a', 'b', 'c', 'd'], ['x', 'a', 'c', 'd', 'e']);
+    let lined_up = rows(&a, &b);
+    assert_eq!(
+        lined_up[..3],
+        [
+            Row {
+                left: None,
+                right: Some(0),
+                kind: Kind::Added
+            },
+            Row {
+                left: Some(0),
+                right: Some(1),
+                kind: Kind::Same
+            },
+            Row {
+                left: Some(1),
+                right: None,
+                kind: Kind::Removed
+            },
+        ]
+    );
+    assert_eq!(hunks(&lined_up), [0, 2, 5]);
+
+    // too different to search, but still lined up
+    let a = (0..MAX_EDITS * 2).collect::<Vec<_>>();
+    let b = (0..MAX_EDITS * 2).map(|i| i + 1_000_000).collect::<Vec<_>>();
+    assert!(rows(&a, &b).iter().all(|row| row.kind == Kind::Changed));
+}
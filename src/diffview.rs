@@ -0,0 +1,280 @@
+use std::cell::Cell;
+use std::cmp;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Position, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Paragraph, Widget};
+
+use crate::diff::{self, Kind, Row};
+use crate::textarea::{DisplayWidth, TextArea};
+
+const CHANGED: Style = Style::new().fg(Color::Yellow);
+const REMOVED: Style = Style::new().fg(Color::Red);
+const ADDED: Style = Style::new().fg(Color::Green);
+const FILLER: Style = Style::new().fg(Color::DarkGray);
+
+/// Two buffers compared line by line, started with `--diff`. Each is edited as usual, the comparison catches up
+/// once typing pauses.
+pub struct DiffView {
+    /// Indexes of the compared buffers.
+    pub left: usize,
+    pub right: usize,
+    /// Shows the differences in one column, removed lines above added ones, instead of side by side.
+    pub unified: bool,
+    rows: Vec<Row>,
+    /// Text versions the rows were computed for.
+    versions: (usize, usize),
+    /// First row shown, shared by both sides so they stay lined up.
+    top: Cell<usize>,
+}
+
+impl DiffView {
+    pub fn new(left: (usize, &TextArea), right: (usize, &TextArea)) -> Self {
+        let mut view = Self {
+            left: left.0,
+            right: right.0,
+            unified: false,
+            rows: Vec::new(),
+            versions: (0, 0),
+            top: Cell::new(0),
+        };
+        view.update(left.1, right.1);
+        view
+    }
+
+    /// Whether the buffer at `idx` is one of the compared ones.
+    pub fn shows(&self, idx: usize) -> bool {
+        idx == self.left || idx == self.right
+    }
+
+    /// Whether either text changed since the rows were computed.
+    pub fn is_stale(&self, left: &TextArea, right: &TextArea) -> bool {
+        self.versions != (left.version(), right.version())
+    }
+
+    pub fn update(&mut self, left: &TextArea, right: &TextArea) {
+        self.rows = diff::rows(&left.lines, &right.lines);
+        self.versions = (left.version(), right.version());
+    }
+
+    pub fn hunk_count(&self) -> usize {
+        diff::hunks(&self.rows).len()
+    }
+
+    /// The line of one side to go to for the next or previous run of differences from `line`, if there is one.
+    pub fn hunk_line(&self, line: usize, right: bool, forward: bool) -> Option<usize> {
+        let side = |row: &Row| if right { row.right } else { row.left };
+        let current = self.rows.iter().position(|row| side(row) == Some(line)).unwrap_or(0);
+        let hunks = diff::hunks(&self.rows);
+        let hunk = if forward {
+            hunks.into_iter().find(|&idx| idx > current)?
+        } else {
+            hunks.into_iter().rev().find(|&idx| idx < current)?
+        };
+
+        // lines only on the other side go to the line after them, which can be where the cursor already is
+        self.rows[hunk..]
+            .iter()
+            .find_map(side)
+            .or_else(|| self.rows.iter().rev().find_map(side))
+            .filter(|&target| target != line)
+    }
+
+    /// Rows as shown: as computed side by side, with changed lines split into a removed and an added one when
+    /// unified.
+    fn shown_rows(&self) -> Vec<Row> {
+        if !self.unified {
+            return self.rows.clone();
+        }
+        self.rows
+            .iter()
+            .flat_map(|&row| match row.kind {
+                Kind::Changed => vec![
+                    Row {
+                        right: None,
+                        kind: Kind::Removed,
+                        ..row
+                    },
+                    Row {
+                        left: None,
+                        kind: Kind::Added,
+                        ..row
+                    },
+                ],
+                _ => vec![row],
+            })
+            .collect()
+    }
+
+    /// Draws the comparison, scrolled to show the cursor of the side being edited, and returns where that cursor is.
+    pub fn render(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        left: &TextArea,
+        right: &TextArea,
+        focus_right: bool,
+    ) -> Position {
+        let side = |row: &Row| if focus_right { row.right } else { row.left };
+        let focus = if focus_right { right } else { left };
+        let cursor = focus.cursor();
+        let rows = self.shown_rows();
+        // the rows may be a few edits behind, then the nearest line above will do
+        let cursor_idx = rows
+            .iter()
+            .position(|row| side(row) == Some(cursor.row))
+            .or_else(|| {
+                rows.iter()
+                    .rposition(|row| side(row).is_some_and(|line| line < cursor.row))
+            })
+            .unwrap_or(0);
+
+        let height = usize::from(area.height);
+        let top = self
+            .top
+            .get()
+            .clamp((cursor_idx + 1).saturating_sub(height), cursor_idx);
+        self.top.set(top);
+        let shown = &rows[cmp::min(top, rows.len())..cmp::min(top + height, rows.len())];
+
+        let gutter = usize::from(num_digits(cmp::max(left.lines.len(), right.lines.len()))) + 1;
+        let (pane, prefix) = if self.unified {
+            render_pane(area, buf, shown, gutter, |row| {
+                let (text, line) = match (row.left, row.right) {
+                    (Some(_), Some(_)) if focus_right => (right, row.right),
+                    (Some(line), _) => (left, Some(line)),
+                    (None, line) => (right, line),
+                };
+                let marker = match row.kind {
+                    Kind::Removed => "-",
+                    Kind::Added => "+",
+                    _ => " ",
+                };
+                (text, line, marker)
+            });
+            (area, gutter + 1)
+        } else {
+            let width = area.width.saturating_sub(1) / 2;
+            let left_area = Rect { width, ..area };
+            let right_area = Rect {
+                x: area.x + width + 1,
+                width: area.width - width - 1,
+                ..area
+            };
+            render_pane(left_area, buf, shown, gutter, |row| (left, row.left, ""));
+            render_pane(right_area, buf, shown, gutter, |row| (right, row.right, ""));
+            for y in area.top()..area.bottom() {
+                buf[(area.x + width, y)].set_symbol("│").set_style(FILLER);
+            }
+            (if focus_right { right_area } else { left_area }, gutter)
+        };
+
+        let col = focus
+            .lines
+            .get(cursor.row)
+            .map_or(0, |line| line.display_col(cursor.col, focus.indent.spaces().len()));
+        let x = cmp::min(prefix + col, usize::from(pane.width.saturating_sub(1)));
+        Position::new(
+            pane.x + u16::try_from(x).unwrap(),
+            area.y + u16::try_from(cursor_idx - top).unwrap(),
+        )
+    }
+}
+
+/// Draws one column of `rows`, getting the text, line and diff marker of each from `line_of`.
+fn render_pane<'t>(
+    area: Rect,
+    buf: &mut Buffer,
+    rows: &[Row],
+    gutter: usize,
+    line_of: impl Fn(&Row) -> (&'t TextArea, Option<usize>, &'static str),
+) {
+    let lines = rows.iter().map(|row| {
+        let (text, line, marker) = line_of(row);
+        let Some(content) = line.and_then(|line| text.lines.get(line)) else {
+            return Line::styled("╱".repeat(usize::from(area.width)), FILLER);
+        };
+        let style = match row.kind {
+            Kind::Same => Style::default(),
+            Kind::Changed => CHANGED,
+            Kind::Removed => REMOVED,
+            Kind::Added => ADDED,
+        };
+        Line::from_iter([
+            Span::styled(format!("{:>width$} ", line.unwrap() + 1, width = gutter - 1), FILLER),
+            Span::styled(
+                format!("{marker}{}", content.replace('\t', text.indent.spaces())),
+                style,
+            ),
+        ])
+    });
+    Paragraph::new(lines.collect::<Vec<_>>()).render(area, buf);
+}
+
+fn num_digits(n: usize) -> u8 {
+    u8::try_from(n.max(1).ilog10() + 1).unwrap()
+}
+
+#[test]
+fn test() {
+    let text = |lines: &[&str]| {
+        let mut text = TextArea::default();
+        text.lines = lines.iter().map(|line| line.to_string()).collect();
+        text
+    };
+    let (left, mut right) = (text(&["a", "b", "c", "d"]), text(&["a", "B", "c", "d", "e"]));
+    let mut view = DiffView::new((0, &left), (1, &right));
+    assert_eq!(view.hunk_count(), 2);
+    assert_eq!(view.hunk_line(0, true, true), Some(1));
+    assert_eq!(view.hunk_line(1, true, true), Some(4));
+    assert_eq!(view.hunk_line(4, true, true), None);
+    // the added line has nothing on the left, so the last line is as close as it gets
+    assert_eq!(view.hunk_line(1, false, true), Some(3));
+    assert_eq!(view.hunk_line(3, false, true), None);
+    assert_eq!(view.hunk_line(3, false, false), Some(1));
+
+    let screen = |view: &DiffView, right: &TextArea| {
+        let area = Rect::new(0, 0, 21, 5);
+        let mut buf = Buffer::empty(area);
+        let cursor = view.render(area, &mut buf, &left, right, true);
+        let rows = (0..area.height)
+            .map(|y| (0..area.width).map(|x| buf[(x, y)].symbol()).collect::<String>())
+            .collect::<Vec<_>>();
+        (rows, cursor)
+    };
+    let (rows, cursor) = screen(&view, &right);
+    assert_eq!(
+        rows,
+        [
+            "1 a       │1 a       ",
+            "2 b       │2 B       ",
+            "3 c       │3 c       ",
+            "4 d       │4 d       ",
+            "╱╱╱╱╱╱╱╱╱╱│5 e       ",
+        ]
+    );
+    assert_eq!(cursor, Position::new(13, 0));
+
+    view.unified = true;
+    right.set_cursor(crate::textarea::CursorPosition { row: 1, col: 1 }, false);
+    let (rows, cursor) = screen(&view, &right);
+    assert_eq!(
+        rows[1..4],
+        [
+            "2 -b                 ",
+            "2 +B                 ",
+            "3  c                 "
+        ]
+    );
+    assert_eq!(cursor, Position::new(4, 2));
+
+    right.input(crate::input::Input {
+        key: crate::input::Key::Char('x'),
+        ..Default::default()
+    });
+    assert!(view.is_stale(&left, &right));
+    view.update(&left, &right);
+    assert!(!view.is_stale(&left, &right));
+}
@@ -1,73 +1,159 @@
 use anyhow::Result;
 
+use std::cmp;
+use std::ops::RangeInclusive;
+
 use crate::input::{Input, Key};
-use crate::textarea::{BytePosition, CursorPosition, HistoryAction, Indent, TextArea};
+use crate::loader::LineReader;
+use crate::textarea::{BytePosition, CursorPosition, HistoryAction, Indent, TextArea, matching_open};
+
+/// Opening characters that insert their closing counterpart when typed.
+pub const AUTO_PAIRS: &[char] = &['(', '[', '{', '\'', '"'];
 
-#[derive(Default)]
 pub struct Editor {
     pub textarea: TextArea,
+    /// Used instead of the detected indent, for file types that require a particular one.
+    pub indent_override: Option<Indent>,
+    /// Which of [`AUTO_PAIRS`] are paired, none turns auto-pairing off.
+    pub auto_pairs: Vec<char>,
+    /// Set by the input that auto-paired, so a Backspace right after removes the closing char too.
+    auto_paired: Option<AutoPaired>,
+    auto_indent: Option<AutoIndent>,
 }
 
-impl Editor {
-    pub fn new_from_file(file: &std::fs::File) -> Result<Self> {
-        use std::io::BufRead;
-
-        let mut file_reader = std::io::BufReader::new(file);
-
-        let mut buf = String::new();
-        let mut lines = Vec::new();
-        let mut indent = None;
-        let mut ends_in_newline = false;
-        loop {
-            buf.clear();
-            match file_reader.read_line(&mut buf)? {
-                0 => break,
-                _ => {
-                    if indent.is_none() {
-                        if buf.starts_with('\t') {
-                            indent = Some(Indent::Tabs);
-                        } else if buf.starts_with(' ') {
-                            let mut spaces = 1;
-                            for char in buf.chars().skip(1) {
-                                if char == ' ' {
-                                    spaces += 1;
-                                } else {
-                                    break;
-                                }
-                            }
-                            indent = Some(spaces.into());
-                        }
-                    }
+/// A line Ctrl+Enter opened with the indent of the line it was opened from. The indent is taken off again if the
+/// cursor leaves the line before anything else is typed on it, so it doesn't stay behind as trailing whitespace.
+struct AutoIndent {
+    row: usize,
+    undo_len: usize,
+}
 
-                    ends_in_newline = buf.ends_with('\n');
-                    if ends_in_newline {
-                        buf.pop();
-                        if buf.ends_with('\r') {
-                            buf.pop();
-                        }
-                    }
-                    lines.push(buf.clone());
-                }
-            };
-        }
+impl AutoIndent {
+    /// Whether the line still holds just the indent, with nothing done since.
+    fn is_current(&self, textarea: &TextArea) -> bool {
+        textarea.undo_len() == self.undo_len
+            && textarea
+                .lines
+                .get(self.row)
+                .is_some_and(|line| !line.is_empty() && line.trim().is_empty())
+    }
+}
 
-        if ends_in_newline {
-            lines.push(String::new());
+/// The state right after typing an opening char inserted its closing one.
+struct AutoPaired {
+    /// Between the pair.
+    cursor: CursorPosition,
+    undo_len: usize,
+    pair: [char; 2],
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            textarea: TextArea::default(),
+            indent_override: None,
+            auto_pairs: AUTO_PAIRS.to_vec(),
+            auto_paired: None,
+            auto_indent: None,
         }
+    }
+}
+
+/// The lines `reader` reads, with an empty last one if it ends in a newline.
+fn read_lines(reader: impl std::io::Read) -> Result<Vec<String>> {
+    let mut reader = LineReader::new(std::io::BufReader::new(reader));
+    let (mut lines, _) = reader.read_lines(usize::MAX)?;
+    if reader.ends_in_newline() {
+        lines.push(String::new());
+    }
+    Ok(lines)
+}
+
+impl Editor {
+    pub fn new_from_file(file: impl std::io::Read, indent_override: Option<Indent>) -> Result<Self> {
+        let lines = read_lines(file)?;
+        let mut editor = Self {
+            indent_override,
+            ..Default::default()
+        };
+        editor.append_lines(lines, true);
+        Ok(editor)
+    }
 
+    /// Replaces the text with what `reader` reads, as one undo step, returning whether it differed.
+    pub fn reload(&mut self, reader: impl std::io::Read) -> Result<bool> {
+        let mut lines = read_lines(reader)?;
         if lines.is_empty() {
             lines.push(String::new());
         }
+        Ok(self.textarea.replace_lines(lines))
+    }
+
+    /// Adds lines read from a file, replacing the initial empty line and detecting the indent if `first`.
+    pub fn append_lines(&mut self, lines: Vec<String>, first: bool) {
+        if !first {
+            self.textarea.extend_lines(lines);
+            return;
+        }
 
-        let mut textarea = TextArea::default();
-        textarea.lines = lines;
-        textarea.indent = indent.unwrap_or_default();
+        self.textarea.indent = self
+            .indent_override
+            .clone()
+            .or_else(|| detect_indent(&lines))
+            .unwrap_or_default();
+        if !lines.is_empty() {
+            self.textarea.set_lines(lines);
+        }
+    }
 
-        Ok(Self { textarea })
+    /// Handles `input` `count` times, as a single undo step.
+    pub fn input_repeat(&mut self, input: Input, count: usize) -> bool {
+        let undo_len = self.textarea.undo_len();
+        let mut modified = false;
+        for _ in 0..count {
+            modified |= self.input(input.clone());
+        }
+        self.textarea.chain_undo_since(undo_len);
+        modified
     }
 
     pub fn input(&mut self, input: Input) -> bool {
-        match input {
+        // anything but an immediate Backspace leaves the pair alone
+        let auto_paired = self.auto_paired.take().filter(|auto_paired| {
+            let textarea = &self.textarea;
+            auto_paired.cursor == textarea.cursor()
+                && auto_paired.undo_len == textarea.undo_len()
+                && textarea.selection().is_none()
+                && (!textarea.overwrite || textarea.overwrite_backspace_deletes)
+        });
+        // a cursor moved by something else, like a search or a click, is on a line this doesn't know about
+        let auto_indent = self.auto_indent.take().filter(|auto_indent| {
+            auto_indent.is_current(&self.textarea) && auto_indent.row == self.textarea.cursor().row
+        });
+        let undo_len = self.textarea.undo_len();
+        let (auto_indent, stripped) = match auto_indent {
+            // Enter leaves the line, which is tidied first, but a line opened from it still gets the indent
+            Some(AutoIndent { row, .. }) if input.key == Key::Enter => (None, Some(self.strip_auto_indent(row))),
+            auto_indent => (auto_indent, None),
+        };
+
+        let modified = match input {
+            Input {
+                key: Key::Backspace,
+                ctrl: false,
+                alt: false,
+                shift: false,
+            } if let Some(AutoPaired { cursor, pair, .. }) = auto_paired => {
+                let start = CursorPosition { col: cursor.col - 1, ..cursor };
+                let cursor = self.textarea.do_action(HistoryAction::RemoveLines {
+                    lines: vec![String::from_iter(pair)],
+                    position: BytePosition::from_line(start, &self.textarea.lines[cursor.row]),
+                    cursor: (cursor, start),
+                });
+                self.textarea.set_cursor(cursor, false);
+
+                true
+            }
             Input {
                 key: Key::Enter,
                 ctrl: false,
@@ -85,6 +171,73 @@ impl Editor {
 
                 true
             }
+            Input {
+                key: Key::Enter,
+                ctrl,
+                alt,
+                shift,
+            } if ctrl || alt => {
+                let lines = &self.textarea.lines;
+                let cursor = self.textarea.cursor();
+
+                let line = &lines[cursor.row];
+                let indent = stripped
+                    .clone()
+                    .unwrap_or_else(|| line[..line.len() - line.trim_start().len()].to_string());
+
+                // Ctrl+Enter opens a line below, Ctrl+Shift+Enter above; Alt is accepted as Ctrl is often not reported
+                let (position, row) = if shift {
+                    (BytePosition { row: cursor.row, col: 0 }, cursor.row)
+                } else {
+                    (
+                        BytePosition {
+                            row: cursor.row,
+                            col: line.len(),
+                        },
+                        cursor.row + 1,
+                    )
+                };
+
+                let cursor = self.textarea.do_action(HistoryAction::InsertLinebreak {
+                    position,
+                    cursor: (cursor, CursorPosition { row, col: 0 }),
+                });
+                let cursor = if indent.is_empty() {
+                    cursor
+                } else {
+                    let cursor = self.textarea.do_action_chain(HistoryAction::InsertLines {
+                        position: BytePosition { row, col: 0 },
+                        cursor: (
+                            cursor,
+                            CursorPosition {
+                                row,
+                                col: indent.chars().count(),
+                            },
+                        ),
+                        lines: vec![indent],
+                    });
+                    self.auto_indent = Some(AutoIndent {
+                        row,
+                        undo_len: self.textarea.undo_len(),
+                    });
+                    cursor
+                };
+                self.textarea.set_cursor(cursor, false);
+
+                true
+            }
+            // a literal tab, even when indenting with spaces
+            Input {
+                key: Key::Tab,
+                ctrl: false,
+                alt: true,
+                shift: false,
+            } => self.textarea.input(Input {
+                key: Key::Char('\t'),
+                ctrl: false,
+                alt: false,
+                shift: false,
+            }),
             Input {
                 key: Key::Tab,
                 ctrl: false,
@@ -92,144 +245,14 @@ impl Editor {
                 ..
             } => {
                 let cursor = self.textarea.cursor();
-                let selection = self.textarea.selection();
-
-                match selection {
-                    Some(selection) if cursor.row != selection.row => {
-                        let selection_range = if cursor < selection {
-                            cursor.row + 1..selection.row
-                        } else {
-                            selection.row + 1..cursor.row
-                        };
-
-                        let action = if cursor < selection {
-                            match &self.textarea.indent {
-                                Indent::Tabs => HistoryAction::InsertChar {
-                                    char: '\t',
-                                    position: BytePosition { row: cursor.row, col: 0 },
-                                    cursor: (cursor, CursorPosition { col: cursor.col + 1, ..cursor }),
-                                },
-                                Indent::Spaces(spaces) => HistoryAction::InsertLines {
-                                    lines: vec![spaces.clone()],
-                                    position: BytePosition { row: cursor.row, col: 0 },
-                                    cursor: (
-                                        cursor,
-                                        CursorPosition {
-                                            col: cursor.col + spaces.len(),
-                                            ..cursor
-                                        },
-                                    ),
-                                },
-                            }
-                        } else {
-                            match &self.textarea.indent {
-                                Indent::Tabs => HistoryAction::InsertChar {
-                                    char: '\t',
-                                    position: BytePosition { row: selection.row, col: 0 },
-                                    cursor: (cursor, cursor),
-                                },
-                                Indent::Spaces(spaces) => HistoryAction::InsertLines {
-                                    lines: vec![spaces.clone()],
-                                    position: BytePosition { row: selection.row, col: 0 },
-                                    cursor: (cursor, cursor),
-                                },
-                            }
-                        };
-                        let cursor = self.textarea.do_action(action);
-                        self.textarea.set_cursor(cursor, false);
-
-                        let mut cursor = cursor;
-                        for row in selection_range {
-                            let action = match &self.textarea.indent {
-                                Indent::Tabs => HistoryAction::InsertChar {
-                                    char: '\t',
-                                    position: BytePosition { row, col: 0 },
-                                    cursor: (cursor, cursor),
-                                },
-                                Indent::Spaces(spaces) => HistoryAction::InsertLines {
-                                    lines: vec![spaces.clone()],
-                                    position: BytePosition { row, col: 0 },
-                                    cursor: (cursor, cursor),
-                                },
-                            };
-                            cursor = self.textarea.do_action_chain(action);
-                        }
-
-                        let action = if cursor < selection {
-                            match &self.textarea.indent {
-                                Indent::Tabs => HistoryAction::InsertChar {
-                                    char: '\t',
-                                    position: BytePosition { row: selection.row, col: 0 },
-                                    cursor: (cursor, cursor),
-                                },
-                                Indent::Spaces(spaces) => HistoryAction::InsertLines {
-                                    lines: vec![spaces.clone()],
-                                    position: BytePosition { row: selection.row, col: 0 },
-                                    cursor: (cursor, cursor),
-                                },
-                            }
-                        } else {
-                            match &self.textarea.indent {
-                                Indent::Tabs => HistoryAction::InsertChar {
-                                    char: '\t',
-                                    position: BytePosition { row: cursor.row, col: 0 },
-                                    cursor: (cursor, CursorPosition { col: cursor.col + 1, ..cursor }),
-                                },
-                                Indent::Spaces(spaces) => HistoryAction::InsertLines {
-                                    lines: vec![spaces.clone()],
-                                    position: BytePosition { row: cursor.row, col: 0 },
-                                    cursor: (
-                                        cursor,
-                                        CursorPosition {
-                                            col: cursor.col + spaces.len(),
-                                            ..cursor
-                                        },
-                                    ),
-                                },
-                            }
-                        };
-                        let cursor = self.textarea.do_action_chain(action);
-                        self.textarea.set_cursor(cursor, false);
-
-                        let selection_increment = match &self.textarea.indent {
-                            Indent::Tabs => 1,
-                            Indent::Spaces(spaces) => spaces.len(),
-                        };
-                        self.textarea.set_selection(Some(CursorPosition {
-                            col: selection.col + selection_increment,
-                            ..selection
-                        }));
-                    }
+                match self.textarea.selection() {
+                    // a selection indents every line it touches, whichever side of it the cursor is on, and a
+                    // selection within one line indents it even if it is blank, as that line was asked for
                     Some(selection) => {
-                        let action = match &self.textarea.indent {
-                            Indent::Tabs => HistoryAction::InsertChar {
-                                char: '\t',
-                                position: BytePosition { row: cursor.row, col: 0 },
-                                cursor: (cursor, CursorPosition { col: cursor.col + 1, ..cursor }),
-                            },
-                            Indent::Spaces(spaces) => HistoryAction::InsertLines {
-                                lines: vec![spaces.clone()],
-                                position: BytePosition::from_line(cursor, &self.textarea.lines[cursor.row]),
-                                cursor: (
-                                    cursor,
-                                    CursorPosition {
-                                        col: cursor.col + spaces.len(),
-                                        ..cursor
-                                    },
-                                ),
-                            },
-                        };
-                        let selection_increment = match &self.textarea.indent {
-                            Indent::Tabs => 1,
-                            Indent::Spaces(spaces) => spaces.len(),
-                        };
-
-                        let cursor = self.textarea.do_action(action);
-                        self.textarea.set_cursor(cursor, false);
-                        self.textarea.set_selection(Some(CursorPosition {
-                            col: selection.col + selection_increment,
-                            ..selection
-                        }));
+                        let blank = cursor.row == selection.row;
+                        let rows = cmp::min(cursor.row, selection.row)..=cmp::max(cursor.row, selection.row);
+                        let actions = rows.filter_map(|row| self.indent_line(row, blank)).collect();
+                        self.reindent(actions);
                     }
                     None => {
                         let action = match &self.textarea.indent {
@@ -265,294 +288,12 @@ impl Editor {
                 ..
             } => {
                 let cursor = self.textarea.cursor();
-                let selection = self.textarea.selection();
-
-                match selection {
-                    Some(selection) if cursor.row != selection.row => {
-                        let selection_range = if cursor < selection {
-                            cursor.row + 1..selection.row
-                        } else {
-                            selection.row + 1..cursor.row
-                        };
-
-                        let mut first_action = true;
-                        let action = if cursor < selection {
-                            match &self.textarea.indent {
-                                Indent::Tabs => {
-                                    if self.textarea.lines[cursor.row].starts_with('\t') {
-                                        Some(HistoryAction::RemoveChar {
-                                            char: '\t',
-                                            position: BytePosition { row: cursor.row, col: 0 },
-                                            cursor: (
-                                                cursor,
-                                                CursorPosition {
-                                                    col: cursor.col.saturating_sub(1),
-                                                    ..cursor
-                                                },
-                                            ),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                                Indent::Spaces(spaces) => {
-                                    if self.textarea.lines[cursor.row].starts_with('\t')
-                                        || self.textarea.lines[cursor.row].starts_with(spaces)
-                                    {
-                                        Some(HistoryAction::RemoveLines {
-                                            lines: vec![spaces.clone()],
-                                            position: BytePosition { row: cursor.row, col: 0 },
-                                            cursor: (
-                                                cursor,
-                                                CursorPosition {
-                                                    col: cursor.col.saturating_sub(spaces.len()),
-                                                    ..cursor
-                                                },
-                                            ),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                            }
-                        } else {
-                            match &self.textarea.indent {
-                                Indent::Tabs => {
-                                    if self.textarea.lines[selection.row].starts_with('\t') {
-                                        Some(HistoryAction::RemoveChar {
-                                            char: '\t',
-                                            position: BytePosition { row: selection.row, col: 0 },
-                                            cursor: (cursor, cursor),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                                Indent::Spaces(spaces) => {
-                                    if self.textarea.lines[selection.row].starts_with('\t')
-                                        || self.textarea.lines[selection.row].starts_with(spaces)
-                                    {
-                                        Some(HistoryAction::RemoveLines {
-                                            lines: vec![spaces.clone()],
-                                            position: BytePosition { row: selection.row, col: 0 },
-                                            cursor: (cursor, cursor),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                            }
-                        };
-
-                        let cursor = match action {
-                            Some(action) => {
-                                first_action = false;
-                                self.textarea.do_action(action)
-                            }
-                            None => cursor,
-                        };
-
-                        let mut cursor = cursor;
-                        for row in selection_range {
-                            let action = match &self.textarea.indent {
-                                Indent::Tabs => {
-                                    if self.textarea.lines[row].starts_with('\t') {
-                                        Some(HistoryAction::RemoveChar {
-                                            char: '\t',
-                                            position: BytePosition { row, col: 0 },
-                                            cursor: (cursor, cursor),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                                Indent::Spaces(spaces) => {
-                                    if self.textarea.lines[row].starts_with('\t')
-                                        || self.textarea.lines[row].starts_with(spaces)
-                                    {
-                                        Some(HistoryAction::RemoveLines {
-                                            lines: vec![spaces.clone()],
-                                            position: BytePosition { row, col: 0 },
-                                            cursor: (cursor, cursor),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                            };
-
-                            cursor = match action {
-                                Some(action) => {
-                                    if first_action {
-                                        first_action = false;
-                                        self.textarea.do_action(action)
-                                    } else {
-                                        first_action = false;
-                                        self.textarea.do_action_chain(action)
-                                    }
-                                }
-                                None => cursor,
-                            };
-                        }
-
-                        let action = if cursor < selection {
-                            match &self.textarea.indent {
-                                Indent::Tabs => {
-                                    if self.textarea.lines[selection.row].starts_with('\t') {
-                                        Some(HistoryAction::RemoveChar {
-                                            char: '\t',
-                                            position: BytePosition { row: selection.row, col: 0 },
-                                            cursor: (cursor, cursor),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                                Indent::Spaces(spaces) => {
-                                    if self.textarea.lines[selection.row].starts_with('\t')
-                                        || self.textarea.lines[selection.row].starts_with(spaces)
-                                    {
-                                        Some(HistoryAction::RemoveLines {
-                                            lines: vec![spaces.clone()],
-                                            position: BytePosition { row: selection.row, col: 0 },
-                                            cursor: (cursor, cursor),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                            }
-                        } else {
-                            match &self.textarea.indent {
-                                Indent::Tabs => {
-                                    if self.textarea.lines[cursor.row].starts_with('\t') {
-                                        Some(HistoryAction::RemoveChar {
-                                            char: '\t',
-                                            position: BytePosition { row: cursor.row, col: 0 },
-                                            cursor: (
-                                                cursor,
-                                                CursorPosition {
-                                                    col: cursor.col.saturating_sub(1),
-                                                    ..cursor
-                                                },
-                                            ),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                                Indent::Spaces(spaces) => {
-                                    if self.textarea.lines[cursor.row].starts_with('\t')
-                                        || self.textarea.lines[cursor.row].starts_with(spaces)
-                                    {
-                                        Some(HistoryAction::RemoveLines {
-                                            lines: vec![spaces.clone()],
-                                            position: BytePosition { row: cursor.row, col: 0 },
-                                            cursor: (
-                                                cursor,
-                                                CursorPosition {
-                                                    col: cursor.col.saturating_sub(spaces.len()),
-                                                    ..cursor
-                                                },
-                                            ),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                }
-                            }
-                        };
-
-                        let selection_increment = if action.is_some() {
-                            match &self.textarea.indent {
-                                Indent::Tabs => 1,
-                                Indent::Spaces(spaces) => spaces.len(),
-                            }
-                        } else {
-                            0
-                        };
-
-                        let cursor = match action {
-                            Some(action) => {
-                                if first_action {
-                                    first_action = false;
-                                    self.textarea.do_action(action)
-                                } else {
-                                    first_action = false;
-                                    self.textarea.do_action_chain(action)
-                                }
-                            }
-                            None => cursor,
-                        };
-
-                        self.textarea.set_cursor(cursor, false);
-                        self.textarea.set_selection(Some(CursorPosition {
-                            col: selection.col + selection_increment,
-                            ..selection
-                        }));
-
-                        !first_action
-                    }
-                    _ => {
-                        let action = match &self.textarea.indent {
-                            Indent::Tabs => {
-                                if self.textarea.lines[cursor.row].starts_with('\t') {
-                                    Some(HistoryAction::RemoveChar {
-                                        char: '\t',
-                                        position: BytePosition { row: cursor.row, col: 0 },
-                                        cursor: (
-                                            cursor,
-                                            CursorPosition {
-                                                col: cursor.col.saturating_sub(1),
-                                                ..cursor
-                                            },
-                                        ),
-                                    })
-                                } else {
-                                    None
-                                }
-                            }
-                            Indent::Spaces(spaces) => {
-                                if self.textarea.lines[cursor.row].starts_with('\t')
-                                    || self.textarea.lines[cursor.row].starts_with(spaces)
-                                {
-                                    Some(HistoryAction::RemoveLines {
-                                        lines: vec![spaces.clone()],
-                                        position: BytePosition { row: cursor.row, col: 0 },
-                                        cursor: (
-                                            cursor,
-                                            CursorPosition {
-                                                col: cursor.col.saturating_sub(spaces.len()),
-                                                ..cursor
-                                            },
-                                        ),
-                                    })
-                                } else {
-                                    None
-                                }
-                            }
-                        };
-
-                        match action {
-                            Some(action) => {
-                                let cursor = self.textarea.do_action(action);
-                                self.textarea.set_cursor(cursor, false);
-
-                                let selection_increment = match &self.textarea.indent {
-                                    Indent::Tabs => 1,
-                                    Indent::Spaces(spaces) => spaces.len(),
-                                };
-                                self.textarea.set_selection(selection.map(|selection| CursorPosition {
-                                    col: selection.col - selection_increment,
-                                    ..selection
-                                }));
-
-                                true
-                            }
-                            None => false,
-                        }
-                    }
-                }
+                let rows = match self.textarea.selection() {
+                    Some(selection) => cmp::min(cursor.row, selection.row)..=cmp::max(cursor.row, selection.row),
+                    None => cursor.row..=cursor.row,
+                };
+                let actions = rows.filter_map(|row| self.dedent_line(row)).collect();
+                self.reindent(actions)
             }
             Input {
                 key: key @ (Key::Up | Key::Down),
@@ -563,6 +304,7 @@ impl Editor {
                 let cursor = self.textarea.cursor();
                 let selection = self.textarea.selection();
 
+                // the whole moved block is kept in view, not just the cursor at one end of it
                 let (start, end) = match selection {
                     Some(selection) if cursor < selection => (cursor, selection),
                     Some(selection) if cursor > selection => (selection, cursor),
@@ -570,43 +312,49 @@ impl Editor {
                 };
 
                 if key == Key::Up && start.row > 0 {
-                    let mut cursor = self.textarea.do_action(HistoryAction::SwapLines {
+                    let mut textarea = self.textarea.begin_chain();
+                    let mut cursor = textarea.do_action(HistoryAction::SwapLines {
                         lines: (start.row, start.row - 1),
                         cursor: (cursor, CursorPosition { row: cursor.row - 1, ..cursor }),
                     });
 
                     for row in (start.row..=end.row).skip(1) {
-                        cursor = self.textarea.do_action_chain(HistoryAction::SwapLines {
+                        cursor = textarea.do_action(HistoryAction::SwapLines {
                             lines: (row, row - 1),
                             cursor: (cursor, cursor),
                         });
                     }
 
-                    self.textarea.set_cursor(cursor, false);
-                    self.textarea.set_selection(selection.map(|selection| CursorPosition {
+                    textarea.set_cursor(cursor, false);
+                    textarea.set_selection(selection.map(|selection| CursorPosition {
                         row: selection.row - 1,
                         ..selection
                     }));
+                    textarea.show_rows(start.row - 1..=end.row - 1);
+                    textarea.commit();
 
                     true
                 } else if key == Key::Down && end.row < self.textarea.lines.len() - 1 {
-                    let mut cursor = self.textarea.do_action(HistoryAction::SwapLines {
+                    let mut textarea = self.textarea.begin_chain();
+                    let mut cursor = textarea.do_action(HistoryAction::SwapLines {
                         lines: (end.row, end.row + 1),
                         cursor: (cursor, CursorPosition { row: cursor.row + 1, ..cursor }),
                     });
 
                     for row in (start.row..end.row).rev() {
-                        cursor = self.textarea.do_action_chain(HistoryAction::SwapLines {
+                        cursor = textarea.do_action(HistoryAction::SwapLines {
                             lines: (row, row + 1),
                             cursor: (cursor, cursor),
                         });
                     }
 
-                    self.textarea.set_cursor(cursor, false);
-                    self.textarea.set_selection(selection.map(|selection| CursorPosition {
+                    textarea.set_cursor(cursor, false);
+                    textarea.set_selection(selection.map(|selection| CursorPosition {
                         row: selection.row + 1,
                         ..selection
                     }));
+                    textarea.show_rows(start.row + 1..=end.row + 1);
+                    textarea.commit();
 
                     true
                 } else {
@@ -620,11 +368,16 @@ impl Editor {
                 shift: false,
             } => {
                 let cursor = self.textarea.cursor();
+                let line = self.textarea.lines[cursor.row].clone();
+                let below = CursorPosition {
+                    row: cursor.row + 1,
+                    col: cmp::min(cursor.col, line.chars().count()),
+                };
 
-                let cursor = self.textarea.do_action(HistoryAction::InsertLines {
-                    lines: vec![self.textarea.lines[cursor.row].clone(), "".to_string()],
-                    position: BytePosition { row: cursor.row, col: 0 },
-                    cursor: (cursor, CursorPosition { row: cursor.row + 1, ..cursor }),
+                let cursor = self.textarea.do_action(HistoryAction::InsertFullLines {
+                    row: below.row,
+                    lines: vec![line],
+                    cursor: (cursor, below),
                 });
                 self.textarea.set_cursor(cursor, false);
 
@@ -633,7 +386,9 @@ impl Editor {
             Input {
                 key: Key::Char(char @ ('(' | '[' | '{' | '\'' | '"')),
                 ..
-            } if !(char == '\'' && self.textarea.selection().is_none()) => {
+            } if self.auto_pairs.contains(&char)
+                && (self.textarea.selection().is_some() || self.pairs_without_selection(char)) =>
+            {
                 let cursor = self.textarea.cursor();
                 let selection = self.textarea.selection();
 
@@ -705,11 +460,63 @@ impl Editor {
                             cursor: (cursor, cursor),
                         });
                         self.textarea.set_cursor(cursor, false);
+                        self.auto_paired = Some(AutoPaired {
+                            cursor,
+                            undo_len: self.textarea.undo_len(),
+                            pair: [char, closing_char],
+                        });
                     }
                 }
 
                 true
             }
+            Input {
+                key: Key::Char(char @ ('}' | ')' | ']')),
+                ctrl: false,
+                alt: false,
+                ..
+            } if let Some(indent) = self.closing_indent(char) => {
+                let cursor = self.textarea.cursor();
+                let start = CursorPosition { row: cursor.row, col: 0 };
+                let end = CursorPosition {
+                    col: indent.chars().count(),
+                    ..start
+                };
+                let typed = self.textarea.lines[cursor.row][..cursor.col].to_string();
+
+                // the indent is put right before the char, so both are undone together
+                let mut actions = Vec::new();
+                if !typed.is_empty() {
+                    actions.push(HistoryAction::RemoveLines {
+                        lines: vec![typed],
+                        position: BytePosition { row: cursor.row, col: 0 },
+                        cursor: (cursor, start),
+                    });
+                }
+                if !indent.is_empty() {
+                    actions.push(HistoryAction::InsertLines {
+                        lines: vec![indent.clone()],
+                        position: BytePosition { row: cursor.row, col: 0 },
+                        cursor: (start, end),
+                    });
+                }
+                actions.push(HistoryAction::InsertChar {
+                    char,
+                    position: BytePosition {
+                        row: cursor.row,
+                        col: indent.len(),
+                    },
+                    cursor: (end, CursorPosition { col: end.col + 1, ..end }),
+                });
+                let mut actions = actions.into_iter();
+                let mut cursor = self.textarea.do_action(actions.next().unwrap());
+                for action in actions {
+                    cursor = self.textarea.do_action_chain(action);
+                }
+                self.textarea.set_cursor(cursor, false);
+
+                true
+            }
             Input {
                 key: Key::Char('K'),
                 ctrl: true,
@@ -751,41 +558,783 @@ impl Editor {
 
                 true
             }
-            Input {
-                key: Key::Char('x'),
-                ctrl: true,
-                alt: false,
-                shift: false,
-            } => {
-                if self.textarea.selection().is_none() {
-                    let cursor = self.textarea.cursor();
-                    let lines = &self.textarea.lines;
-
-                    let line = lines[cursor.row].clone();
-                    _ = self.textarea.clipboard.set_text(&line);
-
-                    let cursor = if cursor.row == lines.len() {
-                        self.textarea.do_action(HistoryAction::RemoveLines {
-                            lines: vec![line],
-                            position: BytePosition { row: cursor.row, col: 0 },
-                            cursor: (cursor, CursorPosition { row: cursor.row, col: 0 }),
-                        })
-                    } else {
-                        self.textarea.do_action(HistoryAction::RemoveLines {
-                            lines: vec![line, String::new()],
-                            position: BytePosition { row: cursor.row, col: 0 },
-                            cursor: (cursor, CursorPosition { col: 0, ..cursor }),
-                        })
-                    };
-                    self.textarea.set_cursor(cursor, false);
 
-                    true
+            input => self.textarea.input(input),
+        };
+
+        if stripped.is_some() {
+            self.textarea.chain_undo_since(undo_len);
+            return true;
+        }
+        if let Some(auto_indent) = auto_indent.filter(|auto_indent| auto_indent.is_current(&self.textarea)) {
+            if self.textarea.cursor().row == auto_indent.row {
+                self.auto_indent = Some(auto_indent);
+            } else {
+                self.strip_auto_indent(auto_indent.row);
+                return true;
+            }
+        }
+        modified
+    }
+
+    /// Takes the indent Ctrl+Enter put on line `row` back off, returning it.
+    fn strip_auto_indent(&mut self, row: usize) -> String {
+        let indent = self.textarea.lines[row].clone();
+        let cursor = self.textarea.cursor();
+        let after = if cursor.row == row {
+            CursorPosition { row, col: 0 }
+        } else {
+            cursor
+        };
+        let cursor = self.textarea.do_action(HistoryAction::RemoveLines {
+            lines: vec![indent.clone()],
+            position: BytePosition { row, col: 0 },
+            cursor: (cursor, after),
+        });
+        if cursor.row == row {
+            self.textarea.set_cursor(cursor, false);
+        }
+        indent
+    }
+
+    /// The indent to insert at the start of line `row`, if it has text on it. Blank lines are left alone, indenting
+    /// them would only leave trailing whitespace, unless `blank` asks for them too. Where the cursor ends up is filled
+    /// in by [`Editor::reindent`].
+    fn indent_line(&self, row: usize, blank: bool) -> Option<HistoryAction> {
+        if !blank && self.textarea.lines[row].trim().is_empty() {
+            return None;
+        }
+        let indent = match &self.textarea.indent {
+            Indent::Tabs => "\t".to_string(),
+            Indent::Spaces(spaces) => spaces.clone(),
+        };
+        let cursor = self.textarea.cursor();
+        Some(HistoryAction::InsertLines {
+            lines: vec![indent],
+            position: BytePosition { row, col: 0 },
+            cursor: (cursor, cursor),
+        })
+    }
+
+    /// The indent to take off the start of line `row`: a tab, or up to one level of spaces, so a line indented by less
+    /// loses what it has. `None` if it isn't indented.
+    fn dedent_line(&self, row: usize) -> Option<HistoryAction> {
+        let line = &self.textarea.lines[row];
+        let indent = if line.starts_with('\t') {
+            "\t"
+        } else {
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            &line[..cmp::min(spaces, self.textarea.indent.spaces().len())]
+        };
+        let cursor = self.textarea.cursor();
+        (!indent.is_empty()).then(|| HistoryAction::RemoveLines {
+            lines: vec![indent.to_string()],
+            position: BytePosition { row, col: 0 },
+            cursor: (cursor, cursor),
+        })
+    }
+
+    /// Indents, or dedents, the lines in `rows` as one undo step, returning how many changed.
+    pub fn indent_rows(&mut self, rows: RangeInclusive<usize>, dedent: bool) -> usize {
+        let actions = rows
+            .filter_map(|row| {
+                if dedent {
+                    self.dedent_line(row)
                 } else {
-                    false
+                    self.indent_line(row, false)
                 }
+            })
+            .collect::<Vec<_>>();
+        let count = actions.len();
+        self.reindent(actions);
+        count
+    }
+
+    /// Does the indent changes from [`Editor::indent_line`] and [`Editor::dedent_line`] as one undo step, moving the
+    /// cursor and the selection anchor by exactly what changed on their own lines. Returns whether there was anything
+    /// to do.
+    fn reindent(&mut self, actions: Vec<HistoryAction>) -> bool {
+        if actions.is_empty() {
+            return false;
+        }
+
+        let shift = |position: CursorPosition| {
+            let col = actions.iter().fold(position.col, |col, action| match action {
+                HistoryAction::InsertLines { lines, position: at, .. } if at.row == position.row => {
+                    col + lines[0].chars().count()
+                }
+                HistoryAction::RemoveLines { lines, position: at, .. } if at.row == position.row => {
+                    col.saturating_sub(lines[0].chars().count())
+                }
+                _ => col,
+            });
+            CursorPosition { col, ..position }
+        };
+        let (moved_cursor, moved_selection) = (shift(self.textarea.cursor()), self.textarea.selection().map(shift));
+
+        let mut textarea = self.textarea.begin_chain();
+        for mut action in actions {
+            if let HistoryAction::InsertLines { cursor, .. } | HistoryAction::RemoveLines { cursor, .. } = &mut action {
+                cursor.1 = moved_cursor;
             }
+            textarea.do_action(action);
+        }
+        textarea.set_cursor(moved_cursor, false);
+        textarea.set_selection(moved_selection);
+        textarea.commit();
+        true
+    }
 
-            input => self.textarea.input(input),
+    /// The indent of the line with the bracket that typing the closing bracket `char` would close, when the cursor has
+    /// only whitespace before it that differs from that indent, so the two brackets line up. `None` leaves the line as
+    /// it is, also when there is no such bracket.
+    fn closing_indent(&self, char: char) -> Option<String> {
+        let textarea = &self.textarea;
+        if textarea.selection().is_some() || textarea.overwrite {
+            return None;
+        }
+        let cursor = textarea.cursor();
+        let line = &textarea.lines[cursor.row];
+        let before = line.chars().take(cursor.col).collect::<String>();
+        if !before.chars().all(|c| c == ' ' || c == '\t') {
+            return None;
+        }
+
+        let open = matching_open(&textarea.lines, cursor, char)?;
+        let open_line = &textarea.lines[open.row];
+        let indent = &open_line[..open_line.len() - open_line.trim_start_matches([' ', '\t']).len()];
+        (indent != before).then(|| indent.to_string())
+    }
+
+    /// Whether typing `char` with no selection should insert its closing counterpart too. Single quotes never do, being
+    /// apostrophes as often as not. Double quotes don't right before a word, after a backslash, or when they end a
+    /// string already open on the line.
+    fn pairs_without_selection(&self, char: char) -> bool {
+        if char == '\'' {
+            return false;
+        }
+        if char != '"' {
+            return true;
+        }
+
+        let cursor = self.textarea.cursor();
+        let chars = self.textarea.lines[cursor.row].chars().collect::<Vec<_>>();
+        let before = &chars[..cursor.col];
+        if chars.get(cursor.col).is_some_and(|c| c.is_alphanumeric()) || before.last() == Some(&'\\') {
+            return false;
+        }
+
+        let mut in_string = false;
+        let mut escaped = false;
+        for &c in before {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == char {
+                in_string = !in_string;
+            }
+        }
+        !in_string
+    }
+}
+
+/// Lines looked at when detecting the indent, to keep opening huge files fast.
+const INDENT_SAMPLE_LINES: usize = 10_000;
+
+/// The indent style most of `lines` use, if any are indented.
+///
+/// Tabs win if more lines start with a tab than with a space. Otherwise the width is the most common step between
+/// the indents of consecutive lines, so a few odd lines (a doc comment's ` * `, a wrapped argument list) don't
+/// decide it. Steps of one space don't count at all, being alignment like that ` * ` far more often than an indent, so
+/// with no wider step there is no indent to go by.
+fn detect_indent(lines: &[String]) -> Option<Indent> {
+    let (mut tabs, mut spaces) = (0, 0);
+    let mut steps = [0usize; 9];
+    let mut prev = 0;
+    for line in lines.iter().take(INDENT_SAMPLE_LINES) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let width = line.chars().take_while(|&c| c == ' ').count();
+        if line.starts_with('\t') {
+            tabs += 1;
+        } else if width > 0 {
+            spaces += 1;
+        }
+
+        if !line.starts_with('\t') {
+            if let Some(count) = steps.get_mut(width.saturating_sub(prev)) {
+                *count += 1;
+            }
+            prev = width;
+        }
+    }
+
+    if tabs == 0 && spaces == 0 {
+        None
+    } else if tabs > spaces {
+        Some(Indent::Tabs)
+    } else {
+        // the first step with the highest count wins, so ties go to the narrower indent
+        (2..steps.len())
+            .rev()
+            .max_by_key(|&step| steps[step])
+            .filter(|&step| steps[step] > 0)
+            .map(Indent::from)
+    }
+}
+
+#[test]
+fn open_line() {
+    let mut editor = Editor::default();
+    editor.textarea.lines = vec!["    foo(bar)".to_string()].into();
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 8 }, false);
+
+    editor.input(Input {
+        key: Key::Enter,
+        ctrl: true,
+        ..Default::default()
+    });
+    assert_eq!(editor.textarea.lines, ["    foo(bar)", "    "]);
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 1, col: 4 });
+
+    editor.input(Input {
+        key: Key::Enter,
+        alt: true,
+        shift: true,
+        ..Default::default()
+    });
+    // the line left holding just the indent gives it up
+    assert_eq!(editor.textarea.lines, ["    foo(bar)", "    ", ""]);
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 1, col: 4 });
+
+    editor.input(Input {
+        key: Key::Char('z'),
+        ctrl: true,
+        ..Default::default()
+    });
+    editor.input(Input {
+        key: Key::Char('z'),
+        ctrl: true,
+        ..Default::default()
+    });
+    assert_eq!(editor.textarea.lines, ["    foo(bar)"]);
+}
+
+#[test]
+fn indent_selection_within_line() {
+    let mut editor = Editor::default();
+    editor.textarea.indent = Indent::Spaces("  ".into());
+    editor.textarea.lines = vec!["foo bar".to_string()].into();
+    let tab = |shift| Input {
+        key: if shift { Key::BackTab } else { Key::Tab },
+        shift,
+        ..Default::default()
+    };
+    let ends = |editor: &Editor| (editor.textarea.selection().unwrap().col, editor.textarea.cursor().col);
+
+    // anchor left of the cursor
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 4 }, false);
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 7 }, true);
+    assert!(editor.input(tab(false)));
+    assert_eq!(editor.textarea.lines, ["  foo bar"]);
+    assert_eq!(ends(&editor), (6, 9));
+    assert!(editor.input(tab(true)));
+    assert_eq!(editor.textarea.lines, ["foo bar"]);
+    assert_eq!(ends(&editor), (4, 7));
+
+    // anchor right of the cursor, starting at the line start, which can't move further left
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 3 }, false);
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 0 }, true);
+    assert!(editor.input(tab(false)));
+    assert_eq!(editor.textarea.lines, ["  foo bar"]);
+    assert_eq!(ends(&editor), (5, 2));
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 0 }, true);
+    assert!(editor.input(tab(true)));
+    assert_eq!(editor.textarea.lines, ["foo bar"]);
+    assert_eq!(ends(&editor), (3, 0));
+
+    // one undo step each
+    editor.input(tab(false));
+    editor.input(Input {
+        key: Key::Char('z'),
+        ctrl: true,
+        ..Default::default()
+    });
+    assert_eq!(editor.textarea.lines, ["foo bar"]);
+
+    // an anchor inside the removed indent stops at the line start
+    editor.textarea.lines = vec!["  foo".to_string()].into();
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 4 }, true);
+    assert!(editor.input(tab(true)));
+    assert_eq!(editor.textarea.lines, ["foo"]);
+    assert_eq!(ends(&editor), (0, 2));
+}
+
+#[test]
+fn indent_lines_with_blank_lines() {
+    let tab = |shift| Input {
+        key: if shift { Key::BackTab } else { Key::Tab },
+        shift,
+        ..Default::default()
+    };
+    let undo = Input {
+        key: Key::Char('z'),
+        ctrl: true,
+        ..Default::default()
+    };
+    let editor = |lines: &[&str], indent: Indent, selection: (usize, usize), cursor: (usize, usize)| {
+        let mut editor = Editor::default();
+        editor.textarea.indent = indent;
+        editor.textarea.lines = lines.iter().map(|line| line.to_string()).collect();
+        editor.textarea.set_cursor(
+            CursorPosition {
+                row: selection.0,
+                col: selection.1,
+            },
+            false,
+        );
+        editor
+            .textarea
+            .set_cursor(CursorPosition { row: cursor.0, col: cursor.1 }, true);
+        editor
+    };
+    let ends = |editor: &Editor| {
+        let (selection, cursor) = (editor.textarea.selection().unwrap(), editor.textarea.cursor());
+        ((selection.row, selection.col), (cursor.row, cursor.col))
+    };
+    let spaces = || Indent::Spaces("    ".into());
+
+    // empty and whitespace-only lines get no indent, including the one the cursor is on
+    let lines = ["a", "", "  ", "b", ""];
+    let mut e = editor(&lines, spaces(), (0, 1), (4, 0));
+    assert!(e.input(tab(false)));
+    assert_eq!(e.textarea.lines, ["    a", "", "  ", "    b", ""]);
+    assert_eq!(ends(&e), ((0, 5), (4, 0)));
+    assert!(e.input(tab(true)));
+    assert_eq!(e.textarea.lines, ["a", "", "", "b", ""]);
+    assert_eq!(ends(&e), ((0, 1), (4, 0)));
+    // both were a single undo step
+    e.input(undo.clone());
+    assert_eq!(e.textarea.lines, ["    a", "", "  ", "    b", ""]);
+    e.input(undo.clone());
+    assert_eq!(e.textarea.lines, lines);
+
+    // selected upwards, the anchor on a blank line stays put
+    let mut e = editor(&["", "a", "b"], Indent::Tabs, (0, 0), (2, 1));
+    let mut e_up = editor(&["a", "b", ""], Indent::Tabs, (2, 0), (0, 1));
+    assert!(e.input(tab(false)));
+    assert!(e_up.input(tab(false)));
+    assert_eq!(e.textarea.lines, ["", "\ta", "\tb"]);
+    assert_eq!(ends(&e), ((0, 0), (2, 2)));
+    assert_eq!(e_up.textarea.lines, ["\ta", "\tb", ""]);
+    assert_eq!(ends(&e_up), ((2, 0), (0, 2)));
+
+    // a selection within a whitespace-only line indents it like any other line
+    let mut e = editor(&["a", "  "], spaces(), (1, 0), (1, 2));
+    assert!(e.input(tab(false)));
+    assert_eq!(e.textarea.lines, ["a", "      "]);
+    assert_eq!(ends(&e), ((1, 4), (1, 6)));
+    e.input(undo.clone());
+    assert_eq!(e.textarea.lines, ["a", "  "]);
+
+    // partial indent comes off as far as it goes, each end moving by what its own line lost
+    let mut e = editor(&["      a", "  b", "c", "\td"], spaces(), (0, 7), (3, 2));
+    assert!(e.input(tab(true)));
+    assert_eq!(e.textarea.lines, ["  a", "b", "c", "d"]);
+    assert_eq!(ends(&e), ((0, 3), (3, 1)));
+    assert!(e.input(tab(true)));
+    assert_eq!(e.textarea.lines, ["a", "b", "c", "d"]);
+    assert_eq!(ends(&e), ((0, 1), (3, 1)));
+    // nothing left to take off
+    assert!(!e.input(tab(true)));
+    assert_eq!(ends(&e), ((0, 1), (3, 1)));
+
+    // with tabs, leading spaces come off up to a tab's width
+    let mut e = editor(&["\t\ta", "      b"], Indent::Tabs, (1, 7), (0, 0));
+    assert!(e.input(tab(true)));
+    assert_eq!(e.textarea.lines, ["\ta", "  b"]);
+    assert_eq!(ends(&e), ((1, 3), (0, 0)));
+}
+
+#[test]
+fn indent_and_dedent_line() {
+    let mut editor = Editor::default();
+    editor.textarea.indent = Indent::Spaces("    ".into());
+    editor.textarea.lines = ["a", " \t", "  b", "\t  c", "      d"].map(String::from).into();
+    let text = |action: Option<HistoryAction>| match action {
+        Some(HistoryAction::InsertLines { lines, .. } | HistoryAction::RemoveLines { lines, .. }) => {
+            Some(lines[0].clone())
         }
+        _ => None,
+    };
+
+    assert_eq!(text(editor.indent_line(0, false)).as_deref(), Some("    "));
+    assert_eq!(text(editor.indent_line(1, false)), None);
+    assert_eq!(text(editor.indent_line(1, true)).as_deref(), Some("    "));
+    assert_eq!(text(editor.dedent_line(0)), None);
+    assert_eq!(text(editor.dedent_line(1)).as_deref(), Some(" "));
+    assert_eq!(text(editor.dedent_line(2)).as_deref(), Some("  "));
+    assert_eq!(text(editor.dedent_line(3)).as_deref(), Some("\t"));
+    assert_eq!(text(editor.dedent_line(4)).as_deref(), Some("    "));
+
+    editor.textarea.indent = Indent::Tabs;
+    assert_eq!(text(editor.indent_line(2, false)).as_deref(), Some("\t"));
+    assert_eq!(text(editor.dedent_line(4)).as_deref(), Some("    "));
+}
+
+#[test]
+fn literal_tab() {
+    let mut editor = Editor::default();
+    editor.textarea.lines = vec!["ab".to_string()].into();
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+
+    let tab = Input {
+        key: Key::Tab,
+        ctrl: false,
+        alt: true,
+        shift: false,
+    };
+    assert!(editor.input(tab));
+    assert_eq!(editor.textarea.lines, ["a\tb"]);
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 0, col: 2 });
+}
+
+#[cfg(test)]
+fn indent_of(text: &str) -> Option<String> {
+    let lines = text.lines().map(String::from).collect::<Vec<_>>();
+    detect_indent(&lines).map(|indent| indent.to_string())
+}
+
+#[test]
+fn indent_detection() {
+    let js = "/**\n * Sums.\n */\nfunction sum(xs) {\n  let total = 0;\n  for (const x of xs) {\n    total += x;\n  }\n  return total;\n}\n";
+    assert_eq!(indent_of(js).as_deref(), Some("2 spaces"));
+
+    let python = "class A:\n    def f(self,\n          x):\n        if x:\n            return 1\n        return 2\n\n    def g(self):\n        pass\n";
+    assert_eq!(indent_of(python).as_deref(), Some("4 spaces"));
+
+    let go = "func main() {\n\tif x {\n\t\tfmt.Println(\"x\")\n\t}\n}\n";
+    assert_eq!(indent_of(go).as_deref(), Some("tabs"));
+
+    // mostly tabs, with a space-aligned continuation line
+    let mixed = "fn f() {\n\tlet x = g(a,\n\t          b);\n\th(x);\n   // odd\n\treturn x;\n}\n";
+    assert_eq!(indent_of(mixed).as_deref(), Some("tabs"));
+
+    assert_eq!(indent_of("a\nb\n\nc\n"), None);
+    // only a doc comment's one space
+    assert_eq!(indent_of("/**\n * Sums.\n */\nfn sum() {}\n"), None);
+}
+
+#[test]
+fn auto_pairs() {
+    let typed = |line: &str, col, char| {
+        let mut editor = Editor::default();
+        editor.textarea.lines = vec![line.to_string()].into();
+        editor.textarea.set_cursor(CursorPosition { row: 0, col }, false);
+        editor.input(Input {
+            key: Key::Char(char),
+            ..Default::default()
+        });
+        editor.textarea.lines.remove(0)
+    };
+
+    assert_eq!(typed("x = ", 4, '"'), "x = \"\"");
+    assert_eq!(typed("f()", 2, '('), "f(())");
+    // not right before a word
+    assert_eq!(typed("say word", 4, '"'), "say \"word");
+    // not after a backslash
+    assert_eq!(typed("\"a\\", 3, '"'), "\"a\\\"");
+    // not when closing a string
+    assert_eq!(typed("x = \"abc", 8, '"'), "x = \"abc\"");
+    // but after a closed one, and an escaped quote doesn't close it
+    assert_eq!(typed("f(\"a\", ", 7, '"'), "f(\"a\", \"\"");
+    assert_eq!(typed("\"a\\\" b", 6, '"'), "\"a\\\" b\"");
+    // apostrophes are left alone
+    assert_eq!(typed("it", 2, '\''), "it'");
+
+    let mut editor = Editor {
+        auto_pairs: vec!['('],
+        ..Default::default()
+    };
+    for char in "[\"(".chars() {
+        editor.input(Input {
+            key: Key::Char(char),
+            ..Default::default()
+        });
+    }
+    assert_eq!(editor.textarea.lines, ["[\"()"]);
+}
+
+#[test]
+fn backspace_after_auto_pair() {
+    let mut editor = Editor::default();
+    editor.textarea.lines = vec!["f".to_string()].into();
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    let press = |editor: &mut Editor, key, ctrl| {
+        editor.input(Input {
+            key,
+            ctrl,
+            ..Default::default()
+        })
+    };
+
+    press(&mut editor, Key::Char('('), false);
+    assert_eq!(editor.textarea.lines, ["f()"]);
+    press(&mut editor, Key::Backspace, false);
+    assert_eq!(editor.textarea.lines, ["f"]);
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 0, col: 1 });
+    // and back in one step
+    press(&mut editor, Key::Char('z'), true);
+    assert_eq!(editor.textarea.lines, ["f()"]);
+
+    // undoing the pair removes both chars
+    press(&mut editor, Key::Char('z'), true);
+    assert_eq!(editor.textarea.lines, ["f"]);
+
+    // only right after pairing
+    press(&mut editor, Key::Char('['), false);
+    press(&mut editor, Key::Char('x'), false);
+    press(&mut editor, Key::Backspace, false);
+    assert_eq!(editor.textarea.lines, ["f[]"]);
+    press(&mut editor, Key::Backspace, false);
+    assert_eq!(editor.textarea.lines, ["f]"]);
+
+    press(&mut editor, Key::Char('{'), false);
+    press(&mut editor, Key::Right, false);
+    press(&mut editor, Key::Left, false);
+    press(&mut editor, Key::Backspace, false);
+    assert_eq!(editor.textarea.lines, ["f}]"]);
+
+    // typing the closing char by hand ends it too
+    press(&mut editor, Key::Char('"'), false);
+    press(&mut editor, Key::Char('"'), false);
+    press(&mut editor, Key::Backspace, false);
+    assert_eq!(editor.textarea.lines, ["f\"\"}]"]);
+}
+
+#[test]
+fn move_lines_stay_in_view() {
+    let alt = |key| Input {
+        key,
+        alt: true,
+        ..Default::default()
+    };
+    let mut editor = Editor::default();
+    editor.textarea.lines = (0..20).map(|n| format!("line {n}")).collect();
+
+    // three selected lines, with the cursor on the first, walk down a 5 line view
+    editor.textarea.set_cursor(CursorPosition { row: 3, col: 2 }, false);
+    editor.textarea.set_cursor(CursorPosition { row: 1, col: 0 }, true);
+    editor.textarea.update_size(20, 5);
+    for top in [0, 1, 2, 3, 4] {
+        editor.input(alt(Key::Down));
+        let (top_left, bottom_right) = editor.textarea.update_size(20, 5);
+        assert_eq!(top_left.row, top);
+        let (start, end) = editor.textarea.selection_range().unwrap();
+        assert!(top_left.row <= start.row && end.row < bottom_right.row);
+    }
+    assert_eq!(editor.textarea.lines[6..9], ["line 1", "line 2", "line 3"]);
+
+    // and back up, with the cursor on the last line
+    editor.textarea.set_cursor(CursorPosition { row: 6, col: 0 }, false);
+    editor.textarea.set_cursor(CursorPosition { row: 8, col: 0 }, true);
+    editor.textarea.set_view_row(7);
+    editor.textarea.update_size(20, 5);
+    editor.input(alt(Key::Up));
+    assert_eq!(editor.textarea.update_size(20, 5).0.row, 5);
+
+    // a block taller than the view is centered as far as the cursor stays in view, so the view starts at the cursor
+    editor.textarea.set_cursor(CursorPosition { row: 8, col: 0 }, false);
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 0 }, true);
+    editor.textarea.update_size(20, 5);
+    editor.input(alt(Key::Down));
+    assert_eq!(editor.textarea.update_size(20, 5).0.row, 1);
+}
+
+#[test]
+fn duplicate_line() {
+    let ctrl_d = Input {
+        key: Key::Char('d'),
+        ctrl: true,
+        ..Default::default()
+    };
+    let undo = Input {
+        key: Key::Char('z'),
+        ctrl: true,
+        ..Default::default()
+    };
+    let at = |row, col| CursorPosition { row, col };
+
+    for (lines, cursor, duplicated) in [
+        (&["ab", "cd"][..], at(0, 1), &["ab", "ab", "cd"][..]),
+        (&["ab", "cd"], at(1, 2), &["ab", "cd", "cd"]),
+        (&["ab", ""], at(1, 0), &["ab", "", ""]),
+        (&["ab"], at(0, 2), &["ab", "ab"]),
+        (&[""], at(0, 0), &["", ""]),
+    ] {
+        let mut editor = Editor::default();
+        editor.textarea.lines = lines.iter().map(|line| line.to_string()).collect();
+        editor.textarea.set_cursor(cursor, false);
+
+        assert!(editor.input(ctrl_d.clone()));
+        assert_eq!(editor.textarea.lines, duplicated, "{lines:?}");
+        assert_eq!(editor.textarea.cursor(), at(cursor.row + 1, cursor.col));
+
+        // undo takes back just the added line
+        editor.input(undo.clone());
+        assert_eq!(editor.textarea.lines, lines, "{lines:?}");
+        assert_eq!(editor.textarea.cursor(), cursor);
+    }
+}
+
+#[test]
+fn undo_keeps_selection() {
+    let press = |editor: &mut Editor, key, ctrl, alt| {
+        editor.input(Input {
+            key,
+            ctrl,
+            alt,
+            ..Default::default()
+        })
+    };
+    let at = |row, col| CursorPosition { row, col };
+    let mut editor = Editor::default();
+    editor.textarea.indent = Indent::Spaces("  ".into());
+    editor.textarea.lines = ["a", "b", "c"].map(String::from).into();
+    editor.textarea.set_cursor(at(0, 1), false);
+    editor.textarea.set_cursor(at(1, 1), true);
+    let ends = |editor: &Editor| (editor.textarea.cursor(), editor.textarea.selection());
+
+    // undone, the indent leaves the selection as it was, ready to indent again
+    press(&mut editor, Key::Tab, false, false);
+    assert_eq!(editor.textarea.lines, ["  a", "  b", "c"]);
+    assert_eq!(ends(&editor), (at(1, 3), Some(at(0, 3))));
+    press(&mut editor, Key::Char('z'), true, false);
+    assert_eq!(editor.textarea.lines, ["a", "b", "c"]);
+    assert_eq!(ends(&editor), (at(1, 1), Some(at(0, 1))));
+    press(&mut editor, Key::Char('y'), true, false);
+    assert_eq!(editor.textarea.lines, ["  a", "  b", "c"]);
+    assert_eq!(ends(&editor), (at(1, 3), Some(at(0, 3))));
+
+    // the same for moved lines
+    press(&mut editor, Key::Down, false, true);
+    assert_eq!(editor.textarea.lines, ["c", "  a", "  b"]);
+    press(&mut editor, Key::Char('z'), true, false);
+    assert_eq!(ends(&editor), (at(1, 3), Some(at(0, 3))));
+    press(&mut editor, Key::Char('y'), true, false);
+    assert_eq!(ends(&editor), (at(2, 3), Some(at(1, 3))));
+
+    // steps made otherwise still drop the selection
+    press(&mut editor, Key::Char('x'), false, false);
+    press(&mut editor, Key::Char('z'), true, false);
+    assert_eq!(editor.textarea.selection(), None);
+}
+
+#[test]
+fn strip_unused_auto_indent() {
+    let press = |editor: &mut Editor, key, ctrl| {
+        editor.input(Input {
+            key,
+            ctrl,
+            ..Default::default()
+        })
+    };
+    let mut editor = Editor::default();
+    editor.textarea.lines = vec!["    foo".to_string()].into();
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 7 }, false);
+
+    // opening a line, then another, and typing on the second
+    press(&mut editor, Key::Enter, true);
+    press(&mut editor, Key::Enter, true);
+    press(&mut editor, Key::Char('x'), false);
+    assert_eq!(editor.textarea.lines, ["    foo", "", "    x"]);
+
+    // undo puts the indent back along with the line opened after it
+    press(&mut editor, Key::Char('z'), true);
+    press(&mut editor, Key::Char('z'), true);
+    assert_eq!(editor.textarea.lines, ["    foo", "    "]);
+
+    press(&mut editor, Key::Char('z'), true);
+    assert_eq!(editor.textarea.lines, ["    foo"]);
+
+    // moving off the line does it too, moving along it doesn't
+    press(&mut editor, Key::Enter, true);
+    press(&mut editor, Key::Left, false);
+    press(&mut editor, Key::Up, false);
+    assert_eq!(editor.textarea.lines, ["    foo", ""]);
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 0, col: 3 });
+    press(&mut editor, Key::Char('z'), true);
+    assert_eq!(editor.textarea.lines, ["    foo", "    "]);
+
+    // whitespace typed by hand stays
+    press(&mut editor, Key::Char('z'), true);
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 7 }, false);
+    press(&mut editor, Key::Enter, true);
+    press(&mut editor, Key::Char(' '), false);
+    press(&mut editor, Key::Up, false);
+    assert_eq!(editor.textarea.lines, ["    foo", "     "]);
+
+    // the cursor moved elsewhere without a key, then a line opened there gets the indent of its own line
+    let mut editor = Editor::default();
+    editor.textarea.lines = vec!["x".to_string(), "        foo".to_string()].into();
+    editor.textarea.set_cursor(CursorPosition { row: 1, col: 11 }, false);
+    press(&mut editor, Key::Enter, true);
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 0 }, false);
+    press(&mut editor, Key::Enter, true);
+    assert_eq!(editor.textarea.lines, ["x", "", "        foo", "        "]);
+}
+
+#[test]
+fn align_closing_brackets() {
+    let press = |editor: &mut Editor, key, ctrl| {
+        editor.input(Input {
+            key,
+            ctrl,
+            ..Default::default()
+        })
+    };
+    let editor_at = |lines: &[&str]| {
+        let mut editor = Editor::default();
+        editor.textarea.lines = lines.iter().map(|line| line.to_string()).collect();
+        let row = lines.len() - 1;
+        let col = lines[row].chars().count();
+        editor.textarea.set_cursor(CursorPosition { row, col }, false);
+        editor
+    };
+
+    // nested blocks, each closing brace lines up with the line it was opened on
+    let mut editor = editor_at(&["fn f() {", "    if x {", "        g();", "        "]);
+    press(&mut editor, Key::Char('}'), false);
+    assert_eq!(editor.textarea.lines[3], "    }");
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 3, col: 5 });
+    press(&mut editor, Key::Enter, true);
+    press(&mut editor, Key::Char('}'), false);
+    assert_eq!(editor.textarea.lines[4], "}");
+
+    // one undo step takes the char and the changed indent back
+    press(&mut editor, Key::Char('z'), true);
+    assert_eq!(editor.textarea.lines[4], "    ");
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 4, col: 4 });
+
+    // the indent is copied from the line of the opening bracket, tabs and all
+    let mut editor = editor_at(&["\tcall(", "\t\targ,", "  "]);
+    press(&mut editor, Key::Char(')'), false);
+    assert_eq!(editor.textarea.lines[2], "\t)");
+    let mut editor = editor_at(&["  [", "\t\t"]);
+    press(&mut editor, Key::Char(']'), false);
+    assert_eq!(editor.textarea.lines[1], "  ]");
+
+    // left alone without a bracket to close, with text before the cursor, or when it lines up already
+    for lines in [&["    "][..], &["{", "  x"], &["  {", "  "]] {
+        let mut editor = editor_at(lines);
+        press(&mut editor, Key::Char('}'), false);
+        assert_eq!(
+            editor.textarea.lines.last().unwrap(),
+            &format!("{}}}", lines[lines.len() - 1])
+        );
     }
 }
@@ -0,0 +1,286 @@
+use std::cell::Cell;
+use std::cmp;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Position, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Paragraph, Widget};
+
+use crate::input::{Input, Key};
+
+/// Files up to this size can be shown as hex, which keeps all their bytes in memory.
+pub const MAX_SIZE: u64 = 4 * 1024 * 1024;
+const ROW_BYTES: usize = 16;
+/// Width of the offset column and the gap after it.
+const OFFSET_WIDTH: usize = 10;
+/// Where the ASCII column starts: after the hex digits, the extra gap in their middle and a `|`.
+const ASCII_X: usize = OFFSET_WIDTH + ROW_BYTES * 3 + 2;
+
+/// Bytes shown as rows of an offset, hex digits and ASCII, edited by overwriting them.
+pub struct Hex {
+    pub bytes: Vec<u8>,
+    /// Byte under the cursor, 0 when there are none.
+    cursor: usize,
+    /// Whether the cursor is on the second hex digit of the byte.
+    low_nibble: bool,
+    /// Typing replaces bytes with ASCII chars instead of hex digits, switched with Tab.
+    ascii: bool,
+    undo_history: Vec<Overwrite>,
+    redo_history: Vec<Overwrite>,
+    /// First row shown, scrolled on render to keep the cursor in view.
+    top: Cell<usize>,
+    /// Rows that fit, from the last render.
+    height: Cell<usize>,
+}
+
+/// A byte replaced by typing over it.
+#[derive(Debug, Clone, Copy)]
+struct Overwrite {
+    offset: usize,
+    old: u8,
+    new: u8,
+}
+
+impl Hex {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes,
+            cursor: 0,
+            low_nibble: false,
+            ascii: false,
+            undo_history: Vec::new(),
+            redo_history: Vec::new(),
+            top: Cell::new(0),
+            height: Cell::new(1),
+        }
+    }
+
+    /// The offset of the byte under the cursor.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Whether any byte was overwritten, and not undone since.
+    pub fn is_edited(&self) -> bool {
+        !self.undo_history.is_empty()
+    }
+
+    /// Handles `input`, returning whether the bytes changed.
+    pub fn input(&mut self, input: Input) -> bool {
+        let last = self.bytes.len().saturating_sub(1);
+        let page = ROW_BYTES * cmp::max(self.height.get(), 1);
+        match input {
+            Input { key: Key::Left, .. } if !self.ascii && self.low_nibble => self.low_nibble = false,
+            Input { key: Key::Left, .. } if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.low_nibble = !self.ascii;
+            }
+            Input { key: Key::Right, .. } => self.advance(),
+            Input { key: Key::Up, .. } => self.move_to(self.cursor.saturating_sub(ROW_BYTES)),
+            Input { key: Key::Down, .. } if self.cursor + ROW_BYTES <= last => self.move_to(self.cursor + ROW_BYTES),
+            Input { key: Key::PageUp, .. } => self.move_to(self.cursor.saturating_sub(page)),
+            Input { key: Key::PageDown, .. } => self.move_to(cmp::min(self.cursor + page, last)),
+            Input { key: Key::Home, ctrl: true, .. } => self.move_to(0),
+            Input { key: Key::End, ctrl: true, .. } => self.move_to(last),
+            Input { key: Key::Home, .. } => self.move_to(self.cursor - self.cursor % ROW_BYTES),
+            Input { key: Key::End, .. } => {
+                self.move_to(cmp::min(self.cursor - self.cursor % ROW_BYTES + ROW_BYTES - 1, last))
+            }
+            Input { key: Key::Tab, .. } => {
+                self.ascii = !self.ascii;
+                self.low_nibble = false;
+            }
+            Input {
+                key: Key::Char('z'),
+                ctrl: true,
+                alt: false,
+                ..
+            } => return self.undo(),
+            Input {
+                key: Key::Char('y'),
+                ctrl: true,
+                alt: false,
+                ..
+            } => return self.redo(),
+            Input {
+                key: Key::Char(c),
+                ctrl: false,
+                alt: false,
+                ..
+            } if self.cursor < self.bytes.len() => {
+                let old = self.bytes[self.cursor];
+                let new = if self.ascii {
+                    if !c.is_ascii() || c.is_ascii_control() {
+                        return false;
+                    }
+                    c as u8
+                } else {
+                    let Some(digit) = c.to_digit(16) else {
+                        return false;
+                    };
+                    let digit = u8::try_from(digit).unwrap();
+                    if self.low_nibble {
+                        old & 0xf0 | digit
+                    } else {
+                        old & 0x0f | digit << 4
+                    }
+                };
+                self.overwrite(Overwrite { offset: self.cursor, old, new });
+                self.redo_history.clear();
+                self.advance();
+                return true;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn move_to(&mut self, offset: usize) {
+        self.cursor = offset;
+        self.low_nibble = false;
+    }
+
+    /// Moves to the next hex digit, or the next byte in the ASCII column, stopping at the last one.
+    fn advance(&mut self) {
+        if !self.ascii && !self.low_nibble && self.cursor < self.bytes.len() {
+            self.low_nibble = true;
+        } else if self.cursor + 1 < self.bytes.len() {
+            self.move_to(self.cursor + 1);
+        }
+    }
+
+    fn overwrite(&mut self, overwrite: Overwrite) {
+        self.bytes[overwrite.offset] = overwrite.new;
+        self.undo_history.push(overwrite);
+    }
+
+    fn undo(&mut self) -> bool {
+        let Some(overwrite) = self.undo_history.pop() else {
+            return false;
+        };
+        self.bytes[overwrite.offset] = overwrite.old;
+        self.redo_history.push(overwrite);
+        self.move_to(overwrite.offset);
+        true
+    }
+
+    fn redo(&mut self) -> bool {
+        let Some(overwrite) = self.redo_history.pop() else {
+            return false;
+        };
+        self.overwrite(overwrite);
+        self.move_to(overwrite.offset);
+        true
+    }
+
+    /// Where the cursor is drawn, relative to the area last rendered to.
+    pub fn terminal_cursor_position(&self) -> Position {
+        let col = self.cursor % ROW_BYTES;
+        let x = if self.ascii {
+            ASCII_X + col
+        } else {
+            OFFSET_WIDTH + col * 3 + usize::from(col >= ROW_BYTES / 2) + usize::from(self.low_nibble)
+        };
+        let y = (self.cursor / ROW_BYTES).saturating_sub(self.top.get());
+        Position::new(u16::try_from(x).unwrap(), u16::try_from(y).unwrap_or(u16::MAX))
+    }
+}
+
+impl Widget for &Hex {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let height = usize::from(area.height);
+        let row = self.cursor / ROW_BYTES;
+        self.height.set(height);
+        self.top
+            .set(self.top.get().clamp((row + 1).saturating_sub(height), row));
+
+        // the column typing doesn't go to shows the cursor too
+        let marked = Style::default().add_modifier(Modifier::REVERSED);
+        let lines = self
+            .bytes
+            .chunks(ROW_BYTES)
+            .enumerate()
+            .skip(self.top.get())
+            .take(height)
+            .map(|(row, chunk)| {
+                let start = row * ROW_BYTES;
+                let mut spans = vec![Span::from(format!("{start:08x}  "))];
+                for (col, byte) in chunk.iter().enumerate() {
+                    let style = if start + col == self.cursor && self.ascii {
+                        marked
+                    } else {
+                        Style::default()
+                    };
+                    spans.push(Span::styled(format!("{byte:02x}"), style));
+                    spans.push(Span::from(if col == ROW_BYTES / 2 - 1 { "  " } else { " " }));
+                }
+                let padding = (ROW_BYTES - chunk.len()) * 3 + usize::from(chunk.len() < ROW_BYTES / 2);
+                spans.push(Span::from(format!("{}|", " ".repeat(padding))));
+                for (col, &byte) in chunk.iter().enumerate() {
+                    let c = if byte.is_ascii_graphic() || byte == b' ' {
+                        char::from(byte)
+                    } else {
+                        '.'
+                    };
+                    let style = if start + col == self.cursor && !self.ascii {
+                        marked
+                    } else {
+                        Style::default()
+                    };
+                    spans.push(Span::styled(c.to_string(), style));
+                }
+                spans.push(Span::from("|"));
+                Line::from(spans)
+            });
+        Paragraph::new(lines.collect::<Vec<_>>()).render(area, buf);
+    }
+}
+
+#[test]
+fn test() {
+    let input = |key| Input { key, ..Default::default() };
+    let mut hex = Hex::new(b"hello, world!\n\x00\xff-".to_vec());
+
+    assert!(hex.input(input(Key::Char('4'))));
+    assert!(hex.input(input(Key::Char('A'))));
+    assert!(!hex.input(input(Key::Char('g'))));
+    assert_eq!(&hex.bytes[..2], b"Je");
+    assert_eq!(hex.cursor(), 1);
+
+    hex.input(input(Key::Down));
+    assert_eq!(hex.cursor(), 1);
+    hex.input(input(Key::End));
+    assert_eq!(hex.cursor(), 15);
+    hex.input(input(Key::Right));
+    hex.input(input(Key::Right));
+    assert_eq!(hex.cursor(), 16);
+    // the cursor stays on the last byte
+    hex.input(input(Key::Right));
+    hex.input(input(Key::Right));
+    assert_eq!(hex.cursor(), 16);
+
+    hex.input(input(Key::Home));
+    hex.input(input(Key::Tab));
+    hex.input(input(Key::Char('X')));
+    assert!(!hex.input(input(Key::Char('é'))));
+    assert_eq!(hex.bytes[16], b'X');
+
+    let undo = Input {
+        ctrl: true,
+        ..input(Key::Char('z'))
+    };
+    let redo = Input {
+        ctrl: true,
+        ..input(Key::Char('y'))
+    };
+    assert!(hex.input(undo.clone()));
+    assert_eq!(hex.bytes[16], b'-');
+    assert!(hex.input(redo));
+    assert_eq!(hex.bytes[16], b'X');
+    for _ in 0..3 {
+        hex.input(undo.clone());
+    }
+    assert!(!hex.is_edited());
+    assert_eq!(hex.bytes, b"hello, world!\n\x00\xff-");
+}
@@ -35,6 +35,14 @@ pub enum Key {
     PageDown,
     /// Escape key
     Esc,
+    /// Insert key
+    Insert,
+    /// Dedicated copy key
+    Copy,
+    /// Dedicated cut key
+    Cut,
+    /// Dedicated paste key
+    Paste,
     /// An invalid key input (this key is always ignored by [`TextArea`](crate::TextArea))
     #[default]
     Null,
@@ -81,6 +89,7 @@ impl From<KeyCode> for Key {
             KeyCode::PageUp => Key::PageUp,
             KeyCode::PageDown => Key::PageDown,
             KeyCode::Esc => Key::Esc,
+            KeyCode::Insert => Key::Insert,
             KeyCode::F(x) => Key::F(x),
             _ => Key::Null,
         }
@@ -96,11 +105,101 @@ impl From<KeyEvent> for Input {
             return Self::default();
         }
 
-        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
-        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        let mut ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let mut alt = key.modifiers.contains(KeyModifiers::ALT);
         let shift = key.modifiers.contains(KeyModifiers::SHIFT);
-        let key = Key::from(key.code);
+        let key = match Key::from(key.code) {
+            // AltGr arrives as Ctrl+Alt with the character it typed, which is then typed like any other. Ctrl+Alt with a
+            // letter or digit is still a shortcut.
+            Key::Char(c) if ctrl && alt && !c.is_ascii_alphanumeric() && !c.is_control() => {
+                (ctrl, alt) = (false, false);
+                Key::Char(c)
+            }
+            // with keyboard enhancement, Shift+letter arrives as the lowercase letter
+            Key::Char(c) if shift => Key::Char(c.to_ascii_uppercase()),
+            key => key,
+        };
 
         Self { key, ctrl, alt, shift }
     }
 }
+
+#[test]
+fn from_key_event() {
+    let input = |code, modifiers| Input::from(KeyEvent::new(code, modifiers));
+    assert_eq!(
+        input(KeyCode::Char('u'), KeyModifiers::CONTROL | KeyModifiers::SHIFT),
+        Input {
+            key: Key::Char('U'),
+            ctrl: true,
+            alt: false,
+            shift: true,
+        }
+    );
+    assert_eq!(input(KeyCode::Char('U'), KeyModifiers::SHIFT).key, Key::Char('U'));
+    assert_eq!(input(KeyCode::Esc, KeyModifiers::NONE).key, Key::Esc);
+
+    let release = KeyEvent::new_with_kind(KeyCode::Char('a'), KeyModifiers::NONE, KeyEventKind::Release);
+    assert_eq!(Input::from(release), Input::default());
+}
+
+#[test]
+fn altgr_characters() {
+    let altgr = |c| {
+        Input::from(KeyEvent::new(
+            KeyCode::Char(c),
+            KeyModifiers::CONTROL | KeyModifiers::ALT,
+        ))
+    };
+    assert_eq!(
+        altgr('@'),
+        Input {
+            key: Key::Char('@'),
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        altgr('€'),
+        Input {
+            key: Key::Char('€'),
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        altgr('{'),
+        Input {
+            key: Key::Char('{'),
+            ..Default::default()
+        }
+    );
+    assert!(matches!(
+        altgr('2'),
+        Input {
+            key: Key::Char('2'),
+            ctrl: true,
+            alt: true,
+            ..
+        }
+    ));
+    assert!(matches!(
+        altgr('a'),
+        Input {
+            key: Key::Char('a'),
+            ctrl: true,
+            alt: true,
+            ..
+        }
+    ));
+
+    // only plain Alt+digit switches buffers
+    let alt = Input::from(KeyEvent::new(KeyCode::Char('2'), KeyModifiers::ALT));
+    assert!(matches!(
+        alt,
+        Input {
+            key: Key::Char('2'),
+            alt: true,
+            ctrl: false,
+            shift: false
+        }
+    ));
+}
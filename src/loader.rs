@@ -0,0 +1,211 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+/// Files at least this big are loaded in the background.
+pub const ASYNC_LOAD_SIZE: u64 = 16 * 1024 * 1024;
+const CHUNK_LINES: usize = 16 * 1024;
+
+/// Splits a reader into lines, dropping `\n` and `\r\n` line endings.
+pub struct LineReader<R> {
+    reader: R,
+    buf: String,
+    ends_in_newline: bool,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: String::new(),
+            ends_in_newline: false,
+        }
+    }
+
+    /// Reads up to `max_lines` lines, returning them with the number of bytes consumed.
+    pub fn read_lines(&mut self, max_lines: usize) -> io::Result<(Vec<String>, usize)> {
+        let mut lines = Vec::new();
+        let mut bytes = 0;
+        while lines.len() < max_lines {
+            self.buf.clear();
+            match self.reader.read_line(&mut self.buf)? {
+                0 => break,
+                n => bytes += n,
+            }
+
+            self.ends_in_newline = self.buf.ends_with('\n');
+            if self.ends_in_newline {
+                self.buf.pop();
+                if self.buf.ends_with('\r') {
+                    self.buf.pop();
+                }
+            }
+            lines.push(self.buf.clone());
+        }
+
+        Ok((lines, bytes))
+    }
+
+    /// Whether the last line read ended in a newline, meaning the text ends with an empty line.
+    pub fn ends_in_newline(&self) -> bool {
+        self.ends_in_newline
+    }
+}
+
+struct Chunk {
+    lines: Vec<String>,
+    bytes: usize,
+    done: bool,
+}
+
+/// Reads a file into lines on a background thread. Dropping it stops the thread.
+pub struct Loader {
+    receiver: Receiver<io::Result<Chunk>>,
+    cancel: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    len: u64,
+    read: u64,
+    done: bool,
+    error: Option<String>,
+}
+
+impl Loader {
+    pub fn new(file: File) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let thread = thread::spawn({
+            let cancel = Arc::clone(&cancel);
+            move || {
+                let mut reader = LineReader::new(BufReader::new(file));
+                while !cancel.load(Ordering::Relaxed) {
+                    let chunk = reader.read_lines(CHUNK_LINES).map(|(mut lines, bytes)| {
+                        let done = lines.is_empty();
+                        if done && reader.ends_in_newline() {
+                            lines.push(String::new());
+                        }
+                        Chunk { lines, bytes, done }
+                    });
+
+                    let stop = chunk.as_ref().map_or(true, |chunk| chunk.done);
+                    // the receiver is gone if the buffer was closed
+                    if sender.send(chunk).is_err() || stop {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            receiver,
+            cancel,
+            thread: Some(thread),
+            len,
+            read: 0,
+            done: false,
+            error: None,
+        })
+    }
+
+    /// Returns the lines read since the last call.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        while !self.done {
+            match self.receiver.try_recv() {
+                Ok(Ok(chunk)) => {
+                    lines.extend(chunk.lines);
+                    self.read += chunk.bytes as u64;
+                    self.done = chunk.done;
+                }
+                Ok(Err(err)) => {
+                    self.error = Some(err.to_string());
+                    self.done = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.error = Some("reader thread stopped".to_string());
+                    self.done = true;
+                }
+            }
+        }
+        lines
+    }
+
+    /// Whether the whole file was read, or reading failed.
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.read
+    }
+
+    pub fn percent(&self) -> u64 {
+        (self.read * 100).checked_div(self.len).unwrap_or(100).min(100)
+    }
+}
+
+impl Drop for Loader {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+fn temp_file(name: &str, contents: &str) -> File {
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, contents).unwrap();
+    File::open(path).unwrap()
+}
+
+#[test]
+fn line_reader() {
+    let mut reader = LineReader::new("a\r\nb\n\nc".as_bytes());
+    assert_eq!(
+        reader.read_lines(2).unwrap(),
+        (vec!["a".to_string(), "b".to_string()], 5)
+    );
+    assert_eq!(
+        reader.read_lines(10).unwrap(),
+        (vec![String::new(), "c".to_string()], 2)
+    );
+    assert!(!reader.ends_in_newline());
+    assert_eq!(reader.read_lines(10).unwrap(), (vec![], 0));
+}
+
+#[test]
+fn load() {
+    let contents = (0..CHUNK_LINES * 3).map(|i| format!("line {i}\n")).collect::<String>();
+    let mut loader = Loader::new(temp_file("ded-test-load", &contents)).unwrap();
+
+    let mut lines = Vec::new();
+    while !loader.is_done() {
+        lines.extend(loader.poll());
+        thread::yield_now();
+    }
+
+    assert_eq!(loader.error(), None);
+    assert_eq!(loader.percent(), 100);
+    assert_eq!(lines.len(), CHUNK_LINES * 3 + 1);
+    assert_eq!(lines[CHUNK_LINES + 1], format!("line {}", CHUNK_LINES + 1));
+    assert_eq!(lines.last().map(String::as_str), Some(""));
+}
+
+#[test]
+fn cancel_load() {
+    let contents = "line\n".repeat(CHUNK_LINES * 20);
+    let loader = Loader::new(temp_file("ded-test-cancel", &contents)).unwrap();
+    // must not hang waiting for the thread
+    drop(loader);
+}
@@ -1,62 +1,574 @@
 use anyhow::Result;
 
-use crossterm::event::Event;
-use ratatui::DefaultTerminal;
-use ratatui::layout::{Constraint, Direction, Layout, Position};
+use crossterm::cursor::SetCursorStyle;
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyModifiers, KeyboardEnhancementFlags, MouseButton,
+    MouseEvent, MouseEventKind, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::Terminal;
+use ratatui::backend::{Backend, CrosstermBackend};
+use ratatui::layout::{Constraint, Direction, Layout, Position, Rect};
 use ratatui::style::{Modifier, Style};
-use ratatui::widgets::Paragraph;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use regex::Regex;
+use unicode_width::UnicodeWidthStr;
 
 use std::borrow::Cow;
-use std::io::{self, Write};
-use std::path::PathBuf;
+use std::cell::OnceCell;
+use std::cmp;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+use std::iter;
+use std::mem;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::rc::Rc;
+use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 use std::{env, fs};
 
+use crate::browser::Browser;
+use crate::charbox::CharBox;
+use crate::cli::{Batch, CliOptions, FileArg, HELP};
+use crate::diffview::DiffView;
 use crate::editor::Editor;
+use crate::hex::Hex;
 use crate::input::{Input, Key};
+use crate::loader::{ASYNC_LOAD_SIZE, Loader};
+use crate::path::{
+    canonical_path, complete_path, dir_prefix, display_path, expand_home, is_makefile, is_url, split_location,
+    token_at, truncate_path,
+};
+use crate::picker::Picker;
+use crate::promptbox::{PromptBox, PromptEvent};
+use crate::replacebox::ReplaceBox;
+use crate::runner::{Runner, location, shell};
 use crate::searchbox::SearchBox;
+use crate::settings::{BufferSettings, CursorShape, FileSettings, Fullscreen, PROJECT_FILE, Settings};
+use crate::tags::{Address, Tag};
+use crate::textarea::{Clip, CursorPosition, Delimiters, Dictionary, Indent, LineOrder, Side, Stats, TextArea};
+use crate::vim::{Mode, Vim};
 
+mod batch;
+mod browser;
+mod charbox;
+mod cli;
+mod diff;
+mod diffview;
 mod editor;
+mod hex;
 mod input;
+mod loader;
+mod match_counter;
+mod path;
+mod picker;
+mod positions;
+mod promptbox;
+mod replacebox;
+mod runner;
 mod searchbox;
+mod settings;
+mod tags;
 mod textarea;
+mod theme;
+mod trash;
+mod vim;
+
+/// Set with `--pipe`, which draws on stderr so that stdout only gets the edited text.
+static PIPE: AtomicBool = AtomicBool::new(false);
+
+type Tui = Terminal<CrosstermBackend<Box<dyn Write>>>;
 
 fn main() -> Result<()> {
-    let term = ratatui::init();
-    let result = (|| App::new(env::args_os().skip(1))?.run(term))();
-    ratatui::restore();
+    let options = CliOptions::parse(env::args_os().skip(1))?;
+    if options.help {
+        print!("{HELP}");
+        return Ok(());
+    }
+    if options.version {
+        println!("ded {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+    if let Some(batch) = &options.batch {
+        return run_batch(&batch_script(batch)?, options.paths());
+    }
+    PIPE.store(options.pipe, Ordering::Relaxed);
+
+    let term = init_terminal()?;
+    let result = (|| {
+        let mut app = App::new(options)?;
+        if app.settings.mouse {
+            crossterm::execute!(ui_output(), EnableMouseCapture)?;
+        }
+        // without a /dev/tty the query for it is written to stdout, which is where the text goes with --pipe
+        let enhanced = app.settings.keyboard_enhancement && !app.pipe && enable_keyboard_enhancement();
+        let result = app.run(term);
+        if enhanced {
+            _ = crossterm::execute!(ui_output(), PopKeyboardEnhancementFlags);
+        }
+        result?;
+        if app.aborted {
+            anyhow::bail!("Aborted, nothing was written");
+        }
+        Ok(app.pipe_output())
+    })();
+    restore_terminal();
+
+    if let Some(output) = result? {
+        io::stdout().write_all(&output)?;
+    }
+    Ok(())
+}
+
+/// The script given with `--batch SCRIPT`, or read from `--batch-file PATH`.
+fn batch_script(batch: &Batch) -> Result<String> {
+    match batch {
+        Batch::Script(script) => Ok(script.clone()),
+        Batch::File(path) => {
+            fs::read_to_string(path).map_err(|err| anyhow::anyhow!("Can't read {}: {err}", path.display()))
+        }
+    }
+}
+
+/// Runs a `--batch` script on each of `paths` and saves them, printing what it did. Stops at the first error, or at a
+/// file with `\r\n` line endings that the script changed.
+fn run_batch(script: &str, paths: Vec<PathBuf>) -> Result<()> {
+    let steps = batch::parse(script).map_err(|err| anyhow::anyhow!("Script error at {err}"))?;
+    let settings = Settings {
+        positions_file: None,
+        ..Settings::default()
+    };
+
+    // every file is edited before any is written, so an error leaves them all as they were
+    let mut edited = Vec::new();
+    for path in paths {
+        let mut buffer = Buffer::new(path, &settings)?;
+        while let Some(loader) = &buffer.loader {
+            if let Some(err) = loader.error() {
+                anyhow::bail!("Can't read {}: {err}", display_path(&buffer.path));
+            }
+            if !buffer.poll_loader() {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        if let Content::Bytes(_) = buffer.content {
+            anyhow::bail!("{} isn't text", display_path(&buffer.path));
+        }
+
+        let version = buffer.editor.textarea.version();
+        let done = batch::run(&mut buffer.editor, &steps)
+            .map_err(|err| anyhow::anyhow!("{}: script error at {err}", display_path(&buffer.path)))?;
+        let text = if buffer.editor.textarea.version() != version {
+            Some(batch_text(&buffer)?)
+        } else {
+            None
+        };
+        edited.push((buffer.path, done, text));
+    }
+
+    for (path, done, text) in edited {
+        let written = match text {
+            Some(text) => {
+                // writing over the file keeps its permissions and writes through symlinks
+                fs::write(&path, &text)?;
+                format!("wrote {}", format_size(text.len() as u64))
+            }
+            None => "unchanged".to_string(),
+        };
+        let done = done.iter().map(|done| format!("{done}, ")).collect::<String>();
+        println!("{}: {done}{written}", display_path(&path));
+    }
+    Ok(())
+}
+
+/// The text of `buffer` as `--batch` writes it, with the line endings of its file, and a newline at the end only if
+/// the file had one.
+fn batch_text(buffer: &Buffer) -> Result<Vec<u8>> {
+    let original = fs::read(&buffer.path)?;
+    let crlf = original.windows(2).filter(|pair| *pair == b"\r\n").count();
+    let lf = original.iter().filter(|&&byte| byte == b'\n').count();
+    if crlf > 0 && crlf < lf {
+        anyhow::bail!(
+            "{} has mixed line endings, which --batch can't keep",
+            display_path(&buffer.path)
+        );
+    }
+
+    let mut text = Vec::new();
+    buffer.write_text(&mut text)?;
+    if !original.is_empty() && !original.ends_with(b"\n") && text.ends_with(b"\n") {
+        text.pop();
+    }
+    if crlf == 0 {
+        return Ok(text);
+    }
+    let mut crlf_text = Vec::with_capacity(text.len() + crlf);
+    for byte in text {
+        if byte == b'\n' {
+            crlf_text.push(b'\r');
+        }
+        crlf_text.push(byte);
+    }
+    Ok(crlf_text)
+}
+
+/// Where the user interface is drawn: stdout, or stderr with [`PIPE`].
+fn ui_output() -> Box<dyn Write> {
+    if PIPE.load(Ordering::Relaxed) {
+        Box::new(io::stderr())
+    } else {
+        Box::new(io::stdout())
+    }
+}
+
+/// Like `ratatui::init`, but drawing on [`ui_output`].
+fn init_terminal() -> Result<Tui> {
+    terminal::enable_raw_mode()?;
+    crossterm::execute!(ui_output(), EnterAlternateScreen)?;
+
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        hook(info);
+    }));
+    Ok(Terminal::new(CrosstermBackend::new(ui_output()))?)
+}
+
+fn restore_terminal() {
+    _ = terminal::disable_raw_mode();
+    // harmless if it was never enabled
+    _ = crossterm::execute!(ui_output(), DisableMouseCapture, LeaveAlternateScreen);
+}
+
+/// Turns on the kitty keyboard protocol if the terminal supports it, returning whether it did.
+fn enable_keyboard_enhancement() -> bool {
+    if !terminal::supports_keyboard_enhancement().unwrap_or(false)
+        || crossterm::execute!(
+            ui_output(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )
+        .is_err()
+    {
+        return false;
+    }
 
-    result
+    // runs before the hook of init_terminal, which restores the rest of the terminal
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        _ = crossterm::execute!(ui_output(), PopKeyboardEnhancementFlags);
+        hook(info);
+    }));
+    true
 }
 
 struct App<'a> {
     buffers: Vec<Buffer<'a>>,
     current: usize,
     message: Option<Cow<'static, str>>,
+    message_time: Instant,
+    /// Whether the message is an error, shown in red.
+    message_error: bool,
+    /// When the files of the buffers were last checked for changes made by other programs.
+    disk_checked: Instant,
+    settings: Settings,
+    charbox: CharBox<'a>,
+    replacebox: ReplaceBox<'a>,
+    alignbox: PromptBox<'a>,
+    runbox: PromptBox<'a>,
+    surroundbox: PromptBox<'a>,
+    changebox: PromptBox<'a>,
+    gotobox: PromptBox<'a>,
+    /// Asks for the name of the file to move to the trash, so it can't be done with a stray key.
+    trashbox: PromptBox<'a>,
+    /// Asks for the name of a file to make in the directory listed.
+    newfilebox: PromptBox<'a>,
+    /// The command last run with F5, writing into the output buffer while it runs.
+    runner: Option<Runner>,
+    last_command: Option<String>,
+    /// Definitions to choose from, when the tags file has several for an identifier.
+    tag_picker: Option<(Picker, Vec<Tag>)>,
+    /// Open buffers to switch to, opened with Alt+0 for the ones past the ninth.
+    buffer_picker: Option<Picker>,
+    /// Positions jumped away from, for going back with Ctrl+O.
+    jumps: Vec<(PathBuf, CursorPosition)>,
+    /// Loaded when a prose file is first shown, `None` inside if there is no word list on the system.
+    dictionary: OnceCell<Option<Rc<Dictionary>>>,
+    /// Set once Down found no more matches, so pressing it again continues in the next buffer.
+    search_other_buffers: bool,
+    /// Reordering asked for without a selection, done on the whole buffer if asked for again right away.
+    confirm_reorder: Option<LineOrder>,
+    /// Title and items of a list shown over the editor until the next key.
+    overlay: Option<(String, Vec<String>)>,
+    /// Whether the user was told that Alt+Tab inserted a tab character.
+    literal_tab_hint_shown: bool,
+    /// Whether the key being handled went to the editor, or was F4, rather than running a command that F4 can't
+    /// repeat.
+    edit_key: bool,
+    tab_bar: bool,
+    /// Repeat count being typed after Alt+-, for the next command.
+    count: Option<usize>,
+    /// Named clipboards for keeping several texts around, shared by all buffers.
+    registers: HashMap<char, Clip>,
+    /// Set by Ctrl+R until the letter of a register is typed.
+    choosing_register: bool,
+    /// The register the next copy, cut or paste uses instead of the clipboard.
+    register: Option<char>,
+    /// Set with `--pipe`: the text from stdin is written to stdout when quitting with Ctrl+Q.
+    pipe: bool,
+    /// Ctrl+C or Esc pressed once in pipe mode, quitting without output if pressed again right away.
+    abort_pending: Option<Key>,
+    aborted: bool,
+    /// The two buffers compared with `--diff`, until one of them is closed.
+    diff: Option<DiffView>,
+    /// Whether the last input changed anything on screen.
+    redraw: bool,
+    /// Where the tab bar and the buffer slot were last rendered, for mouse clicks.
+    tab_bar_area: Rect,
+    slot_area: Rect,
+    /// Where the text was last rendered, for clicks on line numbers.
+    editor_area: Rect,
+    /// The line a drag along the line numbers started on.
+    gutter_anchor: Option<usize>,
+    /// The cursor shape the last render asked for, and the one the terminal was last told to use.
+    cursor_shape: Option<CursorShape>,
+    terminal_cursor_shape: Option<CursorShape>,
+    /// When the status line was inverted for a rejected key, it is put back after [`INVALID_FLASH`].
+    invalid_flash: Option<Instant>,
+    /// Rings the terminal bell after the next render.
+    bell: bool,
 }
 
-impl<'a> App<'a> {
-    fn new<I>(paths: I) -> Result<Self>
-    where
-        I: Iterator,
-        I::Item: Into<PathBuf>,
-    {
-        let buffers = paths.map(|p| Buffer::new(p.into())).collect::<Result<Vec<_>>>()?;
-        if buffers.is_empty() {
-            anyhow::bail!("USAGE: ded FILE1 [FILE2...]");
-        }
+/// How long to wait for input before calling [`App::on_tick`].
+const TICK: Duration = Duration::from_millis(250);
+const MAX_EVENT_BATCH: usize = 256;
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the status line stays inverted for a rejected key.
+const INVALID_FLASH: Duration = Duration::from_millis(100);
+/// How often to look for files changed by other programs.
+const DISK_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// Keeps a mistyped count from hanging the editor.
+const MAX_COUNT: usize = 10_000;
+/// How long the format command may run on save before it is stopped, as the editor waits for it.
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(10);
 
-        Ok(Self {
-            buffers,
+/// Where ctags writes its index of definitions by default.
+const TAGS_FILE: &str = "tags";
+
+/// Name of the buffer showing what the command run with F5 printed.
+const OUTPUT_NAME: &str = "[output]";
+/// Name of the buffer with what was piped to ded, read when `-` is given as a file.
+const STDIN_NAME: &str = "[stdin]";
+
+/// Closes the tab it is drawn in when clicked.
+const CLOSE_GLYPH: &str = "×";
+
+impl<'a> App<'a> {
+    fn new(options: CliOptions) -> Result<Self> {
+        let mut app = Self {
+            buffers: Vec::new(),
             current: 0,
             message: None,
-        })
+            message_time: Instant::now(),
+            message_error: false,
+            disk_checked: Instant::now(),
+            settings: Settings::default(),
+            charbox: CharBox::default(),
+            replacebox: ReplaceBox::default(),
+            alignbox: PromptBox::new(" Align on: ").with_history(),
+            runbox: PromptBox::new(" Run: ").with_history(),
+            surroundbox: PromptBox::new(" Surround with: ").with_history(),
+            changebox: PromptBox::new(" Change surrounding to: ").with_history(),
+            gotobox: PromptBox::new(" Go to line[:col] or @byte: ")
+                .with_validation(|text| GoTo::parse(text).map(drop))
+                .with_history(),
+            trashbox: PromptBox::new(" Type the file name to move it to the trash: "),
+            newfilebox: PromptBox::new(" New file: ").with_completion(complete_path),
+            runner: None,
+            last_command: None,
+            tag_picker: None,
+            buffer_picker: None,
+            jumps: Vec::new(),
+            dictionary: OnceCell::new(),
+            search_other_buffers: false,
+            overlay: None,
+            confirm_reorder: None,
+            literal_tab_hint_shown: false,
+            edit_key: false,
+            tab_bar: false,
+            count: None,
+            registers: HashMap::new(),
+            choosing_register: false,
+            register: None,
+            pipe: false,
+            abort_pending: None,
+            aborted: false,
+            diff: None,
+            redraw: false,
+            tab_bar_area: Rect::default(),
+            slot_area: Rect::default(),
+            editor_area: Rect::default(),
+            gutter_anchor: None,
+            cursor_shape: None,
+            terminal_cursor_shape: None,
+            invalid_flash: None,
+            bell: false,
+        };
+        let config_given = options.config.is_some();
+        if let Some(config) = options.config {
+            app.settings.config_file = Some(config);
+        }
+        if let Some(path) = &app.settings.config_file {
+            match FileSettings::load(path) {
+                Ok(None) if config_given => {
+                    app.set_error(format!("{} doesn't exist, using the default settings", path.display()));
+                }
+                Ok(global) => {
+                    app.settings.global = global.unwrap_or_default();
+                    app.settings.theme = app.settings.global.pick_theme();
+                    if let Some(invalid_key) = app.settings.global.invalid_key {
+                        app.settings.invalid_key = invalid_key;
+                    }
+                }
+                Err(err) => app.set_error(format!("Ignoring {}: {err}", path.display())),
+            }
+        }
+        app.settings.vim = options.vim;
+        app.settings.readonly = options.readonly;
+        app.settings.mouse = options.mouse;
+        app.pipe = options.pipe;
+
+        for file in options.files {
+            match file {
+                FileArg::Stdin => {
+                    if !app.buffers.iter().any(|buffer| buffer.stdin) {
+                        app.buffers.push(Buffer::from_stdin(&app.settings)?);
+                    }
+                }
+                FileArg::Path(path, position) => {
+                    app.open(path)?;
+                    if let Some(CursorPosition { row, col }) = position {
+                        app.buffers[app.current].editor.textarea.goto(row, col);
+                    }
+                }
+            }
+        }
+
+        if app.pipe && !app.buffers.iter().any(|buffer| buffer.stdin) {
+            anyhow::bail!("--pipe edits what comes from stdin, pass - to read it");
+        }
+        if options.diff {
+            let [left, right] = &app.buffers[..] else {
+                anyhow::bail!("--diff compares two files");
+            };
+            app.diff = Some(DiffView::new((0, &left.editor.textarea), (1, &right.editor.textarea)));
+        }
+
+        app.current = 0;
+        Ok(app)
+    }
+
+    /// Opens `path` in a new buffer and switches to it, or just switches if it is already open.
+    fn open(&mut self, path: PathBuf) -> Result<()> {
+        let canonical_path = canonical_path(&path);
+        self.current = match self.buffers.iter().position(|b| b.canonical_path == canonical_path) {
+            Some(idx) => idx,
+            None => {
+                self.buffers.push(Buffer::new(path, &self.settings)?);
+                self.buffers.len() - 1
+            }
+        };
+        if let Some(err) = self.buffers[self.current].settings_error.take() {
+            self.set_error(err);
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self, mut terminal: Tui) -> Result<()> {
+        let result = self.event_loop(&mut terminal);
+        self.remember_positions(&self.buffers);
+        if self.terminal_cursor_shape.is_some() {
+            _ = crossterm::execute!(ui_output(), SetCursorStyle::DefaultUserShape);
+        }
+        result
     }
 
-    fn run(&mut self, mut terminal: DefaultTerminal) -> Result<()> {
-        self.render(&mut terminal)?;
+    fn event_loop(&mut self, terminal: &mut Tui) -> Result<()> {
+        self.render(terminal)?;
+        self.apply_cursor_shape()?;
 
+        let mut last_tick = Instant::now();
         loop {
-            match crossterm::event::read()? {
+            let timeout = (last_tick + self.tick_interval()).saturating_duration_since(Instant::now());
+            if event::poll(timeout)? {
+                // drain everything that is already queued, so a burst of keys is drawn only once
+                let mut events = vec![event::read()?];
+                while events.len() < MAX_EVENT_BATCH && event::poll(Duration::ZERO)? {
+                    events.push(event::read()?);
+                }
+
+                if self.process_events(events)? == Status::Stop {
+                    break;
+                }
+            }
+            // also while events keep coming, like from a held key or a mouse drag, which would otherwise hold off
+            // loading, messages timing out and everything else done on ticks
+            let now = Instant::now();
+            if now >= last_tick + self.tick_interval() {
+                self.on_tick(now);
+                last_tick = now;
+            }
+
+            if mem::take(&mut self.redraw) {
+                self.render(terminal)?;
+                self.apply_cursor_shape()?;
+            }
+            if mem::take(&mut self.bell) {
+                let mut output = ui_output();
+                output.write_all(b"\x07")?;
+                output.flush()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// How often [`App::on_tick`] is called: sooner while waiting to search for what was typed, or for the number of
+    /// matches.
+    fn tick_interval(&self) -> Duration {
+        let searching = self.buffers.iter().any(|buffer| {
+            let searchbox = &buffer.searchbox;
+            searchbox.is_pending() || searchbox.is_counting()
+        });
+        if self.invalid_flash.is_some() {
+            INVALID_FLASH
+        } else if searching {
+            searchbox::DEBOUNCE
+        } else {
+            TICK
+        }
+    }
+
+    /// Tells the terminal about a change of [`App::cursor_shape`].
+    fn apply_cursor_shape(&mut self) -> Result<()> {
+        if self.cursor_shape != self.terminal_cursor_shape
+            && let Some(shape) = self.cursor_shape
+        {
+            crossterm::execute!(ui_output(), shape.style())?;
+            self.terminal_cursor_shape = Some(shape);
+        }
+        Ok(())
+    }
+
+    fn process_events(&mut self, events: impl IntoIterator<Item = Event>) -> Result<Status> {
+        for event in events {
+            match event {
                 Event::Key(event) => {
                     let event = event.into();
                     // ignore Key::Null so we don't rerender unnecessarily
@@ -66,57 +578,469 @@ impl<'a> App<'a> {
 
                     // process input / change state
                     if self.process_input(event)? == Status::Stop {
-                        break;
+                        return Ok(Status::Stop);
                     }
-
-                    self.render(&mut terminal)?;
                 }
-                Event::Resize(_, _) => self.render(&mut terminal)?,
+                Event::Mouse(event) => self.redraw |= self.process_mouse(event),
+                Event::Resize(_, _) => self.redraw = true,
                 _ => {}
             }
         }
 
-        Ok(())
+        Ok(Status::Continue)
+    }
+
+    /// Called every [`App::tick_interval`], whether or not input arrived, for anything that changes on its own.
+    fn on_tick(&mut self, now: Instant) {
+        if self
+            .invalid_flash
+            .take_if(|flashed| now.duration_since(*flashed) >= INVALID_FLASH)
+            .is_some()
+        {
+            self.redraw = true;
+        }
+        for buffer in &mut self.buffers {
+            self.redraw |= buffer.poll_loader();
+            // the lines undo changed stay highlighted until a pause
+            self.redraw |= buffer.editor.textarea.clear_flash();
+            self.redraw |= buffer.searchbox.poll_match_count();
+        }
+        for idx in 0..self.buffers.len() {
+            if self.buffers[idx].searchbox.take_due(now) {
+                self.apply_search_pattern(idx);
+            }
+        }
+        self.poll_runner();
+        // comparing after each key would slow typing down, so it waits for a pause
+        if let Some(diff) = &mut self.diff {
+            let (left, right) = (
+                &self.buffers[diff.left].editor.textarea,
+                &self.buffers[diff.right].editor.textarea,
+            );
+            if diff.is_stale(left, right) {
+                diff.update(left, right);
+                self.redraw = true;
+            }
+        }
+        if now.duration_since(self.disk_checked) >= DISK_CHECK_INTERVAL {
+            self.disk_checked = now;
+            self.reload_changed_files();
+        }
+
+        if self.message.is_some() && now.duration_since(self.message_time) >= MESSAGE_TIMEOUT {
+            self.message = None;
+            self.redraw = true;
+        }
+    }
+
+    /// Reloads the buffers whose file another program changed, unless they have unsaved changes, in which case the
+    /// status line warns about it instead.
+    fn reload_changed_files(&mut self) {
+        let mut reloaded = Vec::new();
+        let mut failed = None;
+        for buffer in &mut self.buffers {
+            let disk_state = buffer.disk_state;
+            match buffer.reload_if_changed() {
+                Ok(true) => reloaded.push(buffer.name()),
+                Ok(false) => {}
+                Err(err) => failed = Some(format!("Can't reload {}: {err}", buffer.name())),
+            }
+            self.redraw |= buffer.disk_state != disk_state;
+        }
+        if let Some(message) = failed {
+            self.set_message(message);
+        } else if !reloaded.is_empty() {
+            self.set_message(format!("{} reloaded from disk", reloaded.join(", ")));
+        }
+    }
+
+    fn set_message(&mut self, message: impl Into<Cow<'static, str>>) {
+        self.message = Some(message.into());
+        self.message_time = Instant::now();
+        self.message_error = false;
+        self.redraw = true;
+    }
+
+    fn set_error(&mut self, message: impl Into<Cow<'static, str>>) {
+        self.set_message(message);
+        self.message_error = true;
+    }
+
+    /// Signals a key that had nothing to act on, the way [`Settings::invalid_key`] asks.
+    fn notify_invalid(&mut self) {
+        let invalid_key = self.settings.invalid_key;
+        if invalid_key.flashes() {
+            self.invalid_flash = Some(Instant::now());
+            self.redraw = true;
+        }
+        self.bell |= invalid_key.rings();
+    }
+
+    /// Moves the file of the current buffer to the trash and closes it, if `name` is the file's name. A symlink is
+    /// moved itself, not the file it points to. The last buffer is left open, empty, for the file to be written again
+    /// if that was a mistake.
+    fn trash_current(&mut self, name: &str) {
+        let buffer = &self.buffers[self.current];
+        if buffer.path.file_name() != Some(name.as_ref()) {
+            self.set_message("That isn't the file's name, it was kept");
+            return;
+        }
+
+        let path = buffer.path.clone();
+        match trash::trash(&path, self.settings.trash_dir.as_deref()) {
+            Ok(to) => {
+                if self.buffers.len() == 1 {
+                    // failing that, the text stays in the buffer, which is no worse
+                    if let Ok(buffer) = Buffer::new(path.clone(), &self.settings) {
+                        self.buffers[0] = buffer;
+                    }
+                } else {
+                    self.remove(self.current);
+                }
+                self.set_message(format!("Moved {} to {}", display_path(&path), display_path(&to)));
+            }
+            Err(err) => self.set_error(format!("Can't move {} to the trash: {err}", display_path(&path))),
+        }
+    }
+
+    /// Closes the buffer at `idx`, unless it has unsaved changes or is the last one.
+    fn close(&mut self, idx: usize) {
+        if self.buffers[idx].modified {
+            self.set_message("Unsaved changes, save before closing");
+            return;
+        }
+        if self.buffers.len() == 1 {
+            self.set_message("Can't close the last buffer");
+            return;
+        }
+        if self.pipe && self.buffers[idx].stdin {
+            self.set_message("Can't close the text from stdin, Ctrl+Q writes it to stdout");
+            return;
+        }
+
+        self.remember_positions(slice::from_ref(&self.buffers[idx]));
+        self.remove(idx);
+    }
+
+    /// Drops the buffer at `idx`, which mustn't be the last one.
+    fn remove(&mut self, idx: usize) {
+        self.buffers.remove(idx);
+        if let Some(diff) = &mut self.diff {
+            if diff.left == idx || diff.right == idx {
+                self.diff = None;
+            } else {
+                diff.left -= usize::from(diff.left > idx);
+                diff.right -= usize::from(diff.right > idx);
+            }
+        }
+        if self.current > idx || self.current == self.buffers.len() {
+            self.current -= 1;
+        }
+    }
+
+    /// What to write to stdout on quitting in pipe mode: the text from stdin, as edited.
+    fn pipe_output(&self) -> Option<Vec<u8>> {
+        let buffer = self.buffers.iter().find(|buffer| self.pipe && buffer.stdin)?;
+        let mut output = Vec::new();
+        buffer
+            .write_contents(&mut output)
+            .expect("writing to memory can't fail");
+        Some(output)
+    }
+
+    /// Saves where the cursor is in `buffers`, for opening their files there next time. Failing to is not worth
+    /// bothering about.
+    fn remember_positions(&self, buffers: &[Buffer]) {
+        let Some(path) = &self.settings.positions_file else {
+            return;
+        };
+        let records = buffers
+            .iter()
+            .filter(|buffer| buffer.view_only_reason().is_none() && buffer.path.is_file())
+            .map(|buffer| (buffer.canonical_path.clone(), buffer.editor.textarea.cursor()))
+            .collect::<Vec<_>>();
+        if !records.is_empty() {
+            _ = positions::save(path, &records);
+        }
+    }
+
+    fn tab_labels(&self) -> impl Iterator<Item = String> {
+        self.buffers.iter().map(|buffer| {
+            let name = buffer.name();
+            let modified = if buffer.modified { "*" } else { "" };
+            format!(" {name}{modified} {CLOSE_GLYPH} ")
+        })
+    }
+
+    /// Returns the buffer whose tab is at column `x`, and whether the click hit its close glyph.
+    fn tab_at(&self, x: u16) -> Option<(usize, bool)> {
+        let x = usize::from(x.checked_sub(self.tab_bar_area.x)?);
+        let mut start = 0;
+        for (idx, label) in self.tab_labels().enumerate() {
+            let end = start + label.width();
+            if x < end {
+                return Some((idx, x == end - 1 - CLOSE_GLYPH.width()));
+            }
+            start = end;
+        }
+        None
+    }
+
+    /// Handles clicks on the tab bar and the buffer slot, returning whether anything changed.
+    fn process_mouse(&mut self, event: MouseEvent) -> bool {
+        let position = Position::new(event.column, event.row);
+        if let MouseEventKind::Drag(MouseButton::Left) = event.kind
+            && let Some(anchor) = self.gutter_anchor
+        {
+            let textarea = &mut self.buffers[self.current].editor.textarea;
+            // dragging past the top or bottom keeps selecting the lines shown there
+            let y = cmp::min(event.row, self.editor_area.bottom().saturating_sub(1));
+            textarea.select_lines(anchor, textarea.line_at(y.saturating_sub(self.editor_area.y)));
+            return textarea.take_redraw();
+        }
+        if event.kind != MouseEventKind::Down(MouseButton::Left) {
+            if event.kind == MouseEventKind::Up(MouseButton::Left) {
+                self.gutter_anchor = None;
+            }
+            return false;
+        }
+
+        if self.slot_area.contains(position) {
+            self.message = None;
+            self.current = (self.current + 1) % self.buffers.len();
+            return true;
+        }
+
+        if self.tab_bar && self.tab_bar_area.contains(position) {
+            self.message = None;
+            match self.tab_at(event.column) {
+                Some((idx, true)) => self.close(idx),
+                Some((idx, false)) => self.current = idx,
+                None => return false,
+            }
+            return true;
+        }
+
+        let buffer = &mut self.buffers[self.current];
+        let textarea = &mut buffer.editor.textarea;
+        let gutter = Rect {
+            width: cmp::min(textarea.gutter_width(), self.editor_area.width),
+            ..self.editor_area
+        };
+        let diff_shown = self.diff.as_ref().is_some_and(|diff| diff.shows(self.current));
+        if gutter.contains(position) && matches!(buffer.content, Content::Text) && !diff_shown {
+            let row = textarea.line_at(event.row - gutter.y);
+            let anchor = if event.modifiers.contains(KeyModifiers::SHIFT) {
+                textarea.line_selection_anchor()
+            } else {
+                row
+            };
+            textarea.select_lines(anchor, row);
+            self.gutter_anchor = Some(anchor);
+            self.message = None;
+            return true;
+        }
+
+        false
     }
 
-    fn render(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+    fn render<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
         terminal.draw(|f| {
             let num_buffers = self.buffers.len();
+            let tab_labels: Vec<_> = if self.tab_bar {
+                self.tab_labels().collect()
+            } else {
+                Vec::new()
+            };
+            let prompt_open = self.prompt_open();
+            let dictionary = self.dictionary_for(&self.buffers[self.current].path);
             let buffer = &mut self.buffers[self.current];
 
             let chunks = Layout::default()
                 .direction(Direction::Vertical)
                 .constraints([
-                    Constraint::Length(if buffer.searchbox.is_open() { 3 } else { 0 }),
+                    Constraint::Length(if self.tab_bar { 1 } else { 0 }),
+                    Constraint::Length(if prompt_open || buffer.searchbox.is_open() {
+                        3
+                    } else {
+                        0
+                    }),
                     Constraint::Min(1),
-                    Constraint::Length(1),
+                    Constraint::Length(if self.settings.fullscreen == Fullscreen::Off {
+                        1
+                    } else {
+                        0
+                    }),
                 ])
                 .split(f.area());
+            let theme = self.settings.theme;
+            let mut status_style = theme.status_style;
+            if self.invalid_flash.is_some() {
+                status_style = if status_style.add_modifier.contains(Modifier::REVERSED) {
+                    status_style.remove_modifier(Modifier::REVERSED)
+                } else {
+                    status_style.add_modifier(Modifier::REVERSED)
+                };
+            }
 
-            if buffer.searchbox.is_open() {
-                f.render_widget(&buffer.searchbox, chunks[0]);
+            self.tab_bar_area = chunks[0];
+            if self.tab_bar {
+                let tabs = tab_labels.into_iter().enumerate().map(|(idx, label)| {
+                    let style = if idx == self.current {
+                        Style::default().add_modifier(Modifier::BOLD)
+                    } else {
+                        status_style
+                    };
+                    Span::styled(label, style)
+                });
+                f.render_widget(
+                    Paragraph::new(Line::from_iter(tabs)).style(status_style),
+                    self.tab_bar_area,
+                );
             }
 
-            f.render_widget(
-                &buffer.editor.textarea,
-                Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([Constraint::Min(1), Constraint::Length(1)])
-                    .split(chunks[1])[0],
-            );
+            let mut editor_area = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Min(1), Constraint::Length(1)])
+                .split(chunks[2])[0];
+            let full = self.settings.fullscreen == Fullscreen::Full;
+            if full {
+                let width = cmp::min(editor_area.width, Fullscreen::TEXT_WIDTH);
+                editor_area.x += (editor_area.width - width) / 2;
+                editor_area.width = width;
+            }
+            buffer.editor.textarea.line_numbers = !full;
+            buffer.editor.textarea.rulers.clone_from(&buffer.settings.rulers);
+            buffer.editor.textarea.indent_guides = self.settings.indent_guides;
+            buffer.editor.textarea.trailing_whitespace = self.settings.trailing_whitespace;
+            buffer.editor.textarea.theme = theme;
+            buffer.editor.textarea.overwrite_backspace_deletes = self.settings.overwrite_backspace_deletes;
+            buffer.editor.textarea.dictionary = dictionary;
+            buffer.editor.auto_pairs.clone_from(&self.settings.auto_pairs);
+            let buffer = &self.buffers[self.current];
+
+            if self.charbox.is_open() {
+                f.render_widget(&self.charbox, chunks[1]);
+            } else if self.replacebox.is_open() {
+                f.render_widget(&self.replacebox, chunks[1]);
+            } else if let Some(prompt) = self.open_prompt() {
+                f.render_widget(prompt, chunks[1]);
+            } else if buffer.searchbox.is_open() {
+                f.render_widget(&buffer.searchbox, chunks[1]);
+            }
+
+            let diff = self
+                .diff
+                .as_ref()
+                .filter(|diff| diff.shows(self.current) && matches!(buffer.content, Content::Text));
+            self.editor_area = editor_area;
+            let diff_cursor = match (&buffer.content, diff) {
+                (_, Some(diff)) => Some(diff.render(
+                    editor_area,
+                    f.buffer_mut(),
+                    &self.buffers[diff.left].editor.textarea,
+                    &self.buffers[diff.right].editor.textarea,
+                    self.current == diff.right,
+                )),
+                (Content::Bytes(hex), None) => {
+                    f.render_widget(hex, editor_area);
+                    None
+                }
+                (Content::Text, None) => {
+                    f.render_widget(&buffer.editor.textarea, editor_area);
+                    None
+                }
+            };
+            let buffer = &self.buffers[self.current];
+            if let Some((title, items)) = &self.overlay {
+                render_overlay(f, editor_area, title, items);
+            }
+            if let Some((picker, _)) = &self.tag_picker {
+                f.render_widget(picker, editor_area);
+            }
+            if let Some(picker) = &self.buffer_picker {
+                f.render_widget(picker, editor_area);
+            }
+            if let Some(prompt) = self.open_prompt() {
+                prompt.render_completions(editor_area, f.buffer_mut());
+            }
 
             // Render status line
-            let modified = if buffer.modified { " [modified]" } else { "" };
+            let modified = match &buffer.loader {
+                Some(loader) if loader.error().is_some() => " [loading failed]".into(),
+                Some(loader) => format!(" [loading {}%]", loader.percent()).into(),
+                None => match (buffer.modified, buffer.disk_state) {
+                    (true, DiskState::Changed) => " [modified, changed on disk]".into(),
+                    (true, DiskState::Deleted) => " [modified, deleted on disk]".into(),
+                    (false, DiskState::Changed) => " [changed on disk]".into(),
+                    (false, DiskState::Deleted) => " [deleted on disk]".into(),
+                    (true, DiskState::Same) => " [modified]".into(),
+                    (false, DiskState::Same) => Cow::Borrowed(""),
+                },
+            };
             let slot = format!("[{}/{}]", self.current + 1, num_buffers);
-            let path = format!(" {}{} ", buffer.path.display(), modified);
-            let cursor = buffer.editor.textarea.cursor();
-            let cursor = match buffer.editor.textarea.selection() {
-                Some(selection) => format!(
-                    "({},{}) - ({},{})",
-                    selection.row, selection.col, cursor.row, cursor.col
-                ),
-                None => format!("({},{})", cursor.row, cursor.col),
+            // rows and columns are shown 1-based, like in every other editor
+            let textarea = &buffer.editor.textarea;
+            let cursor = textarea.cursor();
+            let cursor = match (textarea.selection(), textarea.selection_range()) {
+                (Some(selection), Some((start, end))) => {
+                    let stats = textarea.selection_stats().unwrap_or_default();
+                    let span = if start.row == end.row {
+                        let chars = end.col - start.col;
+                        format!("{chars} char{} ({}W)", if chars == 1 { "" } else { "s" }, stats.words)
+                    } else {
+                        format!("{} lines ({}W, {}C)", end.row - start.row + 1, stats.words, stats.chars)
+                    };
+                    format!(
+                        "({},{}) - ({},{}) {span}",
+                        selection.row + 1,
+                        selection.col + 1,
+                        cursor.row + 1,
+                        cursor.col + 1
+                    )
+                }
+                _ => format!("({},{})", cursor.row + 1, cursor.col + 1),
+            };
+            let mut cursor = match &buffer.content {
+                Content::Bytes(hex) => format!("HEX  {:#x}/{:#x}", hex.cursor(), hex.bytes.len()),
+                Content::Text => format!("{}  {cursor}", textarea.indent),
             };
+            if textarea.overwrite {
+                cursor = format!("OVR  {cursor}");
+            }
+            if buffer.settings.project.is_some() {
+                cursor = format!("{PROJECT_FILE}  {cursor}");
+            }
+            // counted once the whole file is there, not again for every chunk that comes in
+            if buffer.loader.is_none() && matches!(buffer.content, Content::Text) {
+                let mixed = textarea.mixed_indent_lines();
+                if mixed > 0 {
+                    cursor = format!(
+                        "[mixed indent: {mixed} line{}]  {cursor}",
+                        if mixed == 1 { "" } else { "s" }
+                    );
+                }
+            }
+            if let Some(diff) = diff {
+                let hunks = diff.hunk_count();
+                cursor = format!("{hunks} difference{}  {cursor}", if hunks == 1 { "" } else { "s" });
+            }
+            if self.choosing_register {
+                cursor = format!("Register: ?  {cursor}");
+            } else if let Some(name) = self.register {
+                cursor = format!("Register: {name}  {cursor}");
+            }
+            if let Some(count) = self.count {
+                let count = if count == 0 { String::new() } else { count.to_string() };
+                cursor = format!("Repeat: {count}  {cursor}");
+            }
+            if let Some(runner) = &self.runner {
+                cursor = format!("{} running  {cursor}", runner.spinner());
+            }
+            if let Some(vim) = &buffer.vim {
+                let mode = format!("{} {}", vim.mode(), vim.pending());
+                cursor = format!("{}  {cursor}", mode.trim_end());
+            }
             let status_chunks = Layout::default()
                 .direction(Direction::Horizontal)
                 .constraints(
@@ -127,26 +1051,111 @@ impl<'a> App<'a> {
                     ]
                     .as_ref(),
                 )
-                .split(chunks[2]);
-            let status_style = Style::default().add_modifier(Modifier::REVERSED);
+                .split(chunks[3]);
+            self.slot_area = status_chunks[0];
+            let path = match &self.message {
+                Some(message) => format!(" {message} "),
+                None => {
+                    let width = usize::from(status_chunks[1].width).saturating_sub(modified.len() + 2);
+                    let path = display_path(&buffer.path);
+                    format!(" {}{modified} ", truncate_path(&path, width))
+                }
+            };
             f.render_widget(Paragraph::new(slot).style(status_style), status_chunks[0]);
-            f.render_widget(Paragraph::new(path).style(status_style), status_chunks[1]);
+            let path_style = match self.message {
+                Some(_) if self.message_error => status_style.fg(theme.message_error),
+                _ => status_style,
+            };
+            f.render_widget(Paragraph::new(path).style(path_style), status_chunks[1]);
             f.render_widget(Paragraph::new(cursor).style(status_style), status_chunks[2]);
 
-            if buffer.searchbox.is_open() {
+            let shape = if self.charbox.is_open() {
                 f.set_cursor_position(Position::new(
-                    buffer.searchbox.textarea.terminal_cursor_position().x + 1,
-                    1,
+                    self.charbox.textarea.terminal_cursor_position().x + 1,
+                    chunks[1].y + 1,
                 ));
+                CursorShape::Underline
+            } else if self.replacebox.is_open() {
+                f.set_cursor_position(Position::new(
+                    self.replacebox.textarea.terminal_cursor_position().x + 1,
+                    chunks[1].y + 1,
+                ));
+                CursorShape::Underline
+            } else if let Some(prompt) = self
+                .open_prompt()
+                .or(buffer.searchbox.is_open().then(|| buffer.searchbox.prompt()))
+            {
+                f.set_cursor_position(prompt.cursor_position(chunks[1]));
+                CursorShape::Underline
+            } else if let Content::Bytes(hex) = &buffer.content {
+                let Position { x, y } = hex.terminal_cursor_position();
+                f.set_cursor_position(Position::new(editor_area.x + x, editor_area.y + y));
+                CursorShape::Block
             } else {
-                f.set_cursor_position(buffer.editor.textarea.terminal_cursor_position());
-            }
+                let Position { x, y } = buffer.editor.textarea.terminal_cursor_position();
+                f.set_cursor_position(diff_cursor.unwrap_or(Position::new(editor_area.x + x, editor_area.y + y)));
+                let vim_command = buffer.vim.as_ref().is_some_and(|vim| vim.mode() != Mode::Insert);
+                if buffer.editor.textarea.overwrite || vim_command {
+                    CursorShape::Block
+                } else {
+                    CursorShape::Bar
+                }
+            };
+            self.cursor_shape = self.settings.cursor_shapes.then_some(shape);
         })?;
 
         Ok(())
     }
 
     fn process_input(&mut self, event: Input) -> Result<Status> {
+        self.redraw |= self.message.take().is_some();
+        self.redraw |= self.overlay.take().is_some();
+        let confirm_reorder = self.confirm_reorder.take();
+
+        let mut repeat = 1;
+        if let Some(count) = self.count.take() {
+            self.redraw = true;
+            match event {
+                Input {
+                    key: Key::Char(digit @ '0'..='9'),
+                    ctrl: false,
+                    alt: false,
+                    ..
+                } => {
+                    let digit = digit.to_digit(10).unwrap() as usize;
+                    self.count = Some(cmp::min(count * 10 + digit, MAX_COUNT));
+                    return Ok(Status::Continue);
+                }
+                Input { key: Key::Esc, .. } => return Ok(Status::Continue),
+                _ => repeat = cmp::max(count, 1),
+            }
+        }
+
+        if mem::take(&mut self.choosing_register) {
+            self.redraw = true;
+            if let Input {
+                key: Key::Char(name @ 'a'..='z'),
+                ctrl: false,
+                alt: false,
+                ..
+            } = event
+            {
+                self.register = Some(name);
+            }
+            return Ok(Status::Continue);
+        }
+        // only ever for the key right after it
+        let register = self.register.take();
+        self.redraw |= register.is_some();
+
+        if self.pipe && self.abort_requested(&event) {
+            self.aborted = true;
+            return Ok(Status::Stop);
+        }
+
+        self.edit_key = false;
+        let current = self.current;
+        let prompt_open = self.prompt_open();
         let buffer = &mut self.buffers[self.current];
 
         match event {
@@ -162,64 +1171,624 @@ impl<'a> App<'a> {
                 ctrl: false,
                 shift: false,
             } if char.is_ascii_digit() => {
-                let buf_idx = char.to_digit(10).unwrap().saturating_sub(1).try_into().unwrap();
-                if buf_idx < self.buffers.len() {
-                    self.current = buf_idx;
+                // Alt+0 picks from all buffers, so the ones past the ninth can be reached too
+                if char == '0' {
+                    let items = self.buffers.iter().map(|buffer| display_path(&buffer.path)).collect();
+                    self.buffer_picker = Some(Picker::new(" Buffers: ", items));
+                    self.redraw = true;
+                } else {
+                    let buf_idx = char.to_digit(10).unwrap().saturating_sub(1).try_into().unwrap();
+                    if buf_idx < self.buffers.len() && buf_idx != self.current {
+                        self.current = buf_idx;
+                        self.redraw = true;
+                    }
                 }
             }
             Input {
-                key: Key::Char('s'),
+                key: Key::Char('-'),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                self.count = Some(0);
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char('r'),
                 ctrl: true,
-                ..
+                alt: false,
+                shift: false,
             } => {
-                buffer.save()?;
-                self.message = Some("Saved!".into());
+                self.choosing_register = true;
+                let mut items: Vec<_> = self
+                    .registers
+                    .iter()
+                    .map(|(name, clip)| format!("{name}: {}", register_preview(&clip.text)))
+                    .collect();
+                if !items.is_empty() {
+                    items.sort();
+                    self.overlay = Some((" Registers ".to_string(), items));
+                }
+                self.redraw = true;
+            }
+            Input { key: Key::F(11), .. } => {
+                self.settings.fullscreen = self.settings.fullscreen.toggle();
+                self.redraw = true;
+            }
+            Input { key: Key::F(12), .. } => {
+                let theme = self.settings.theme.next();
+                self.settings.theme = theme.with_colors(&self.settings.global.theme_colors);
+                self.set_message(format!("Theme: {}", theme.name));
+            }
+            Input {
+                key: Key::Char('x'),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                if let Err(err) = buffer.toggle_hex() {
+                    self.set_message(err);
+                }
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char('g'),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            } => {
+                self.gotobox.open("");
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char('g'),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                self.settings.indent_guides = !self.settings.indent_guides;
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char('e'),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                self.settings.trailing_whitespace = !self.settings.trailing_whitespace;
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char('t'),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                self.tab_bar = !self.tab_bar;
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char(c @ ('n' | 'p')),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } if let Some(diff) = self.diff.as_ref().filter(|diff| diff.shows(self.current)) => {
+                let textarea = &mut buffer.editor.textarea;
+                match diff.hunk_line(textarea.cursor().row, self.current == diff.right, c == 'n') {
+                    Some(row) => textarea.goto(row, 0),
+                    None => self.set_message("No more differences"),
+                }
+                self.redraw = true;
+            }
+            Input { key: Key::F(9), .. } if let Some(diff) = &mut self.diff => {
+                diff.unified = !diff.unified;
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char(c @ ('n' | 'p')),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                if !buffer.editor.textarea.jump_to_conflict(c == 'n') {
+                    self.set_message("No more conflicts");
+                }
+            }
+            // not while typing in a prompt, where they would change the text behind it
+            Input {
+                key: Key::Char(c @ ('o' | 'r' | 'b')),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } if buffer.editable() && !prompt_open && !buffer.searchbox.is_open() => {
+                let side = match c {
+                    'o' => Side::Ours,
+                    'r' => Side::Theirs,
+                    _ => Side::Both,
+                };
+                match buffer.editor.textarea.resolve_conflict(side) {
+                    Ok(()) => buffer.modified = true,
+                    Err(err) => self.set_message(err),
+                }
+            }
+            Input {
+                key: Key::Char('K'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } => {
+                if buffer.stdin || buffer.output || !buffer.path.is_file() {
+                    self.set_message("There is no file to move to the trash");
+                } else if buffer.readonly {
+                    self.set_message("Opened with --readonly, files aren't moved to the trash");
+                } else if buffer.modified {
+                    self.set_message("Unsaved changes, save or undo them before moving the file to the trash");
+                } else {
+                    self.trashbox.open("");
+                    self.redraw = true;
+                }
+            }
+            Input {
+                key: Key::Char('N'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } => {
+                if !buffer.editor.textarea.jump_to_mixed_indent() {
+                    self.set_message("No mixed indentation");
+                }
+            }
+            Input {
+                key: Key::Char('F'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } if buffer.editable() => {
+                // the lines are rewritten in the style they are indented in already
+                let textarea = &mut buffer.editor.textarea;
+                let fixed = textarea.convert_indent(matches!(textarea.indent, Indent::Tabs));
+                buffer.modified |= fixed > 0;
+                self.set_message(format!(
+                    "Fixed the indentation of {fixed} line{}",
+                    if fixed == 1 { "" } else { "s" }
+                ));
+            }
+            Input {
+                key: Key::Char(c @ ('S' | 'T')),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } if buffer.editable() => {
+                let tabs = c == 'T';
+                let converted = buffer.editor.textarea.convert_indent(tabs);
+                buffer.modified |= converted > 0;
+                self.set_message(format!(
+                    "Converted {converted} line{} to {}",
+                    if converted == 1 { "" } else { "s" },
+                    if tabs { "tabs" } else { "spaces" }
+                ));
+            }
+            Input {
+                key: Key::Char(c @ ('V' | 'D' | 'A' | 'X')),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } if buffer.editable() => {
+                let order = match c {
+                    'V' => LineOrder::Reverse,
+                    'D' => LineOrder::DedupConsecutive,
+                    'A' => LineOrder::DedupAll,
+                    _ => LineOrder::Shuffle,
+                };
+                let textarea = &mut buffer.editor.textarea;
+                if textarea.selection_range().is_none() && confirm_reorder != Some(order) {
+                    self.confirm_reorder = Some(order);
+                    self.set_message(format!("No selection, press again to {order} all lines"));
+                    return Ok(Status::Continue);
+                }
+
+                let undo_len = textarea.undo_len();
+                let removed = textarea.reorder_lines(order);
+                buffer.modified |= textarea.undo_len() != undo_len;
+                if matches!(order, LineOrder::DedupConsecutive | LineOrder::DedupAll) {
+                    self.set_message(format!("Removed {removed} line{}", if removed == 1 { "" } else { "s" }));
+                }
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char('I'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } => {
+                // only future indenting changes, the text is left alone
+                let textarea = &mut buffer.editor.textarea;
+                textarea.indent = textarea.indent.cycle();
+                let message = format!("Indenting with {}", textarea.indent);
+                self.set_message(message);
+            }
+            Input {
+                key: Key::Char('w'),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                let textarea = &buffer.editor.textarea;
+                let (stats, of) = match textarea.selection_stats() {
+                    Some(stats) => (stats, "selection"),
+                    None => (textarea.stats(), "buffer"),
+                };
+                let Stats { lines, words, chars, bytes } = stats;
+                let offset = textarea.byte_offset();
+                self.set_message(format!(
+                    "{of}: {lines} lines, {words} words, {chars} chars, {bytes} bytes, cursor at byte {offset}"
+                ));
+            }
+            Input {
+                key: Key::Char(c @ ('c' | 'C')),
+                alt: true,
+                ctrl: false,
+                ..
+            } => self.count_matches(c == 'C'),
+            Input {
+                key: Key::Char('i'),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                let path = buffer.canonical_path.display().to_string();
+                self.set_message(path);
+            }
+            Input {
+                key: Key::Char('s'),
+                ctrl: true,
+                ..
+            } => {
+                if let Some(loader) = &buffer.loader {
+                    let message = format!("Can't save: {}", loader.error().unwrap_or("still loading"));
+                    self.set_message(message);
+                    return Ok(Status::Continue);
+                }
+                if buffer.output {
+                    self.set_message("Can't save the command output");
+                    return Ok(Status::Continue);
+                }
+                if buffer.browser.is_some() {
+                    self.set_message("Can't save a directory listing");
+                    return Ok(Status::Continue);
+                }
+                if buffer.readonly {
+                    self.set_message("Opened with --readonly, saving is off");
+                    return Ok(Status::Continue);
+                }
+                if buffer.stdin {
+                    self.set_message(if self.pipe {
+                        "Ctrl+Q writes the text from stdin to stdout"
+                    } else {
+                        "The text from stdin has no file to save to"
+                    });
+                    return Ok(Status::Continue);
+                }
+
+                let tidy_error = buffer.tidy(FORMAT_TIMEOUT).err();
+                match buffer.save(self.settings.replace_symlinks) {
+                    Ok(Some(bytes)) if let Some(err) = tidy_error => {
+                        self.set_error(format!("Wrote {}, but {err}", format_size(bytes)));
+                    }
+                    Ok(Some(bytes)) => {
+                        let lines = &buffer.editor.textarea.lines;
+                        let message = match buffer.content {
+                            Content::Bytes(_) => format!("Wrote {}", format_size(bytes)),
+                            Content::Text => {
+                                let count = lines.len() - usize::from(lines.last().is_some_and(String::is_empty));
+                                let s = if count == 1 { "" } else { "s" };
+                                format!("Wrote {count} line{s}, {}", format_size(bytes))
+                            }
+                        };
+                        self.set_message(message);
+                    }
+                    Ok(None) => self.set_message("No changes to save"),
+                    Err(err) => self.set_error(format!("Can't save: {err}")),
+                }
+            }
+            // terminals without the kitty keyboard protocol send Ctrl+] as Ctrl+5
+            Input {
+                key: Key::Char(']' | '5'),
+                ctrl: true,
+                alt: false,
+                ..
+            } => self.go_to_definition(),
+            Input {
+                key: Key::Char('o'),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            } => self.jump_back(),
+            Input { key: Key::F(4), .. } if buffer.editable() => {
+                self.edit_key = true;
+                if let Err(err) = buffer.repeat_edit() {
+                    self.set_message(err);
+                }
+            }
+            Input { key: Key::F(5), .. } => {
+                let command = self.last_command.clone().or_else(|| {
+                    let dir = env::current_dir().ok()?;
+                    self.settings.run_command(&dir).map(str::to_string)
+                });
+                self.runbox.open(command.as_deref().unwrap_or_default());
+                self.redraw = true;
+            }
+            Input {
+                key: Key::F(8),
+                ctrl: false,
+                alt: false,
+                shift,
+            } => self.jump_to_error(!shift),
+            Input {
+                key: Key::Char('O'),
+                ctrl: true,
+                alt: false,
+                shift: true,
+            } => self.open_under_cursor(),
+            Input {
+                key: Key::F(7),
+                ctrl,
+                alt: false,
+                shift,
+            } => self.spell_check(ctrl, shift),
+            Input {
+                key: Key::Char('U'),
+                ctrl: true,
+                alt: false,
+                shift: true,
+            } => {
+                self.charbox.open();
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char(c @ ('J' | 'M')),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } if buffer.editable() => {
+                let textarea = &mut buffer.editor.textarea;
+                let undo_len = textarea.undo_len();
+                let result = textarea.format_json(c == 'J');
+                buffer.modified |= textarea.undo_len() != undo_len;
+                if let Err(err) = result {
+                    self.set_message(err);
+                }
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char('L'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } if buffer.editable() => {
+                if buffer.editor.textarea.selection_range().is_some() {
+                    self.alignbox.open("=");
+                    self.redraw = true;
+                } else {
+                    self.set_message("Select the lines to align first");
+                }
+            }
+            Input {
+                key: Key::Char('q'),
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } if buffer.editable() => {
+                let textarea = &mut buffer.editor.textarea;
+                let undo_len = textarea.undo_len();
+                if textarea.reflow(
+                    self.settings.wrap_width,
+                    self.settings.has_prose_extension(&buffer.path),
+                ) {
+                    buffer.modified |= textarea.undo_len() != undo_len;
+                } else {
+                    self.set_message("No paragraph at the cursor");
+                }
+            }
+            Input {
+                key: Key::Char('P'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } => {
+                if !buffer.editor.textarea.select_paragraph() {
+                    self.set_message("No paragraph at the cursor");
+                }
+            }
+            Input {
+                key: Key::Char('W'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } if buffer.editable() => {
+                if buffer.editor.textarea.selection_range().is_some() {
+                    self.surroundbox.open("");
+                    self.redraw = true;
+                } else {
+                    self.set_message("Select the text to surround first");
+                }
+            }
+            Input {
+                key: Key::Char('H'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } if buffer.editable() => {
+                self.changebox.open("");
+                self.redraw = true;
+            }
+            Input {
+                key: Key::Char('U'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } if buffer.editable() => match buffer.editor.textarea.delete_surrounding() {
+                Ok(()) => buffer.modified = true,
+                Err(err) => self.set_message(err),
+            },
+            Input {
+                key: Key::Char('R'),
+                alt: true,
+                ctrl: false,
+                shift: true,
+            } => {
+                self.replacebox.open();
+                self.redraw = true;
             }
             event => {
-                if buffer.searchbox.is_open() {
+                if let Some(picker) = &mut self.buffer_picker {
+                    if let Some(choice) = picker.input(event) {
+                        self.buffer_picker = None;
+                        if let Some(idx) = choice.filter(|&idx| idx < self.buffers.len()) {
+                            self.current = idx;
+                        }
+                    }
+                    self.redraw = true;
+                } else if let Some((picker, _)) = &mut self.tag_picker {
+                    if let Some(choice) = picker.input(event) {
+                        let (_, tags) = self.tag_picker.take().unwrap();
+                        if let Some(tag) = choice.and_then(|idx| tags.get(idx)) {
+                            self.jump_to_tag(tag);
+                        }
+                    }
+                    self.redraw = true;
+                } else if self.charbox.is_open() {
+                    if let Some(char) = self.charbox.input(event) {
+                        self.insert_char(char);
+                    }
+                    self.redraw = true;
+                } else if self.replacebox.is_open() {
+                    if let Some((pattern, replacement)) = self.replacebox.input(event) {
+                        self.replace_in_all_buffers(&pattern, &replacement);
+                    }
+                    self.redraw = true;
+                } else if self.runbox.is_open() {
+                    if let PromptEvent::Accepted(command) = self.runbox.input(event) {
+                        self.run_command(command);
+                    }
+                    self.redraw = true;
+                } else if self.alignbox.is_open() {
+                    if let PromptEvent::Accepted(needle) = self.alignbox.input(event) {
+                        let buffer = &mut self.buffers[self.current];
+                        let aligned = buffer.editor.textarea.align(&needle);
+                        buffer.modified |= aligned > 0;
+                        self.set_message(format!("Aligned {aligned} line{}", if aligned == 1 { "" } else { "s" }));
+                    }
+                    self.redraw = true;
+                } else if self.surroundbox.is_open() {
+                    if let PromptEvent::Accepted(text) = self.surroundbox.input(event) {
+                        let buffer = &mut self.buffers[self.current];
+                        buffer.modified |= buffer.editor.textarea.surround(&Delimiters::parse(&text));
+                    }
+                    self.redraw = true;
+                } else if self.changebox.is_open() {
+                    if let PromptEvent::Accepted(text) = self.changebox.input(event) {
+                        let buffer = &mut self.buffers[self.current];
+                        match buffer.editor.textarea.change_surrounding(&Delimiters::parse(&text)) {
+                            Ok(()) => buffer.modified = true,
+                            Err(err) => self.set_message(err),
+                        }
+                    }
+                    self.redraw = true;
+                } else if self.gotobox.is_open() {
+                    if let PromptEvent::Accepted(text) = self.gotobox.input(event) {
+                        self.go_to(&text);
+                    }
+                    self.redraw = true;
+                } else if self.trashbox.is_open() {
+                    if let PromptEvent::Accepted(name) = self.trashbox.input(event) {
+                        self.trash_current(&name);
+                    }
+                    self.redraw = true;
+                } else if self.newfilebox.is_open() {
+                    if let PromptEvent::Accepted(name) = self.newfilebox.input(event) {
+                        self.new_file(&name);
+                    }
+                    self.redraw = true;
+                } else if buffer.searchbox.is_open() {
                     self.process_searchbox_input(event);
+                    self.redraw = true;
+                } else if let Some(name) = register
+                    && matches!(buffer.content, Content::Text)
+                    && is_clipboard_key(&event)
+                {
+                    self.process_register_input(name, event, repeat);
                 } else {
-                    self.process_textarea_input(event);
+                    self.process_textarea_input(event, repeat);
                 }
             }
         };
 
+        if !self.edit_key
+            && let Some(last) = self
+                .buffers
+                .get_mut(current)
+                .and_then(|buffer| buffer.last_edit.as_mut())
+        {
+            last.superseded = true;
+        }
+        let textarea = &mut self.buffers[self.current].editor.textarea;
+        self.redraw |= textarea.take_redraw();
+        if textarea.take_rejected() {
+            self.notify_invalid();
+        }
         Ok(Status::Continue)
     }
 
     fn process_searchbox_input(&mut self, event: Input) {
+        // what was typed last is searched for before moving to a match
+        if matches!(event.key, Key::Down | Key::Up | Key::Enter) && self.buffers[self.current].searchbox.take_pending()
+        {
+            self.apply_search_pattern(self.current);
+        }
+
+        let search_other_buffers = std::mem::take(&mut self.search_other_buffers);
+        let other_buffers = self.buffers.len() > 1;
         let buffer = &mut self.buffers[self.current];
 
+        let mut not_found = false;
         match event {
             Input { key: Key::Down, .. } => {
-                if !buffer.searchbox.textarea.lines[0].is_empty() {
-                    if let Some((cursor, selection)) = buffer.editor.textarea.search_forward() {
+                if !buffer.searchbox.text().is_empty() {
+                    if let Some(found) = buffer.editor.textarea.search_forward() {
                         buffer.searchbox.set_error_message(None::<&str>);
-                        buffer.editor.textarea.set_cursor(cursor, false);
-                        buffer.editor.textarea.set_selection(Some(selection));
+                        buffer.editor.textarea.select_match(found);
+                    } else if search_other_buffers {
+                        self.search_next_buffers();
+                    } else if other_buffers {
+                        buffer
+                            .searchbox
+                            .set_error_message(Some("no more matches, Down for other buffers"));
+                        self.search_other_buffers = true;
                     } else {
                         buffer.searchbox.set_error_message(Some("not found"));
+                        not_found = true;
                     }
                 }
             }
             Input { key: Key::Up, .. } => {
-                if !buffer.searchbox.textarea.lines[0].is_empty() {
-                    if let Some((cursor, selection)) = buffer.editor.textarea.search_backward() {
+                if !buffer.searchbox.text().is_empty() {
+                    if let Some(found) = buffer.editor.textarea.search_backward() {
                         buffer.searchbox.set_error_message(None::<&str>);
-                        buffer.editor.textarea.set_cursor(cursor, false);
-                        buffer.editor.textarea.set_selection(Some(selection));
+                        buffer.editor.textarea.select_match(found);
                     } else {
                         buffer.searchbox.set_error_message(Some("not found"));
+                        not_found = true;
                     }
                 }
             }
             Input { key: Key::Enter, .. } => {
-                if !buffer.searchbox.textarea.lines[0].is_empty() && buffer.editor.textarea.selection().is_none() {
-                    if let Some((cursor_start, cursor_end)) = buffer.editor.textarea.search_forward() {
-                        buffer.editor.textarea.set_cursor(cursor_start, false);
-                        buffer.editor.textarea.set_selection(Some(cursor_end));
+                if !buffer.searchbox.text().is_empty() && buffer.editor.textarea.selection().is_none() {
+                    if let Some(found) = buffer.editor.textarea.search_forward() {
+                        buffer.editor.textarea.select_match(found);
                     } else {
                         buffer.searchbox.set_error_message(Some("not found"));
+                        not_found = true;
                     }
                 }
 
@@ -231,96 +1800,3505 @@ impl<'a> App<'a> {
                 buffer.editor.textarea.set_search_pattern("").unwrap();
             }
             input => {
-                if let Some(query) = buffer.searchbox.input(input) {
-                    let maybe_err = buffer.editor.textarea.set_search_pattern(query).err();
-                    buffer.searchbox.set_error_message(maybe_err);
-                }
+                buffer.searchbox.input(input, Instant::now());
             }
         }
+        if not_found {
+            self.notify_invalid();
+        }
     }
 
-    fn process_textarea_input(&mut self, event: Input) {
-        let buffer = &mut self.buffers[self.current];
-
-        match event {
-            Input {
-                key: Key::Char('f'),
-                ctrl: true,
-                alt: false,
-                shift: false,
-            } => {
-                let search_pattern = {
-                    let prev_search_pattern = buffer.searchbox.open();
-                    buffer
-                        .editor
-                        .textarea
-                        .selected_text_single_line()
-                        .unwrap_or(prev_search_pattern)
-                        .to_owned()
-                };
-
-                buffer.searchbox.set_text(&search_pattern);
-                let maybe_err = buffer.editor.textarea.set_search_pattern(&search_pattern).err();
-                buffer.searchbox.set_error_message(maybe_err);
+    /// Searches buffer `idx` for the pattern in its searchbox, and starts counting the matches.
+    fn apply_search_pattern(&mut self, idx: usize) {
+        let buffer = &mut self.buffers[idx];
+        let pattern = buffer.searchbox.pattern().into_owned();
+        let textarea = &mut buffer.editor.textarea;
+        match textarea.set_search_pattern(&pattern) {
+            Ok(()) => {
+                buffer.searchbox.set_error_message(None::<&str>);
+                buffer.searchbox.count_matches(textarea.search_pattern(), textarea);
             }
-            input => {
-                let buffer = &mut self.buffers[self.current];
-                buffer.modified |= buffer.editor.input(input);
+            Err(err) => {
+                buffer.searchbox.set_error_message(Some(err));
+                buffer.searchbox.count_matches(None, textarea);
             }
         }
+        self.redraw = true;
     }
-}
 
-#[derive(PartialEq, Eq)]
-enum Status {
-    Continue,
-    Stop,
-}
+    /// Continues the search in the next buffer with a match, wrapping around to the start of the current one. The
+    /// searchbox moves along with it.
+    fn search_next_buffers(&mut self) {
+        let current = self.current;
+        let searchbox = &self.buffers[current].searchbox;
+        let (text, literal, pattern) = (
+            searchbox.text().to_string(),
+            searchbox.literal(),
+            searchbox.pattern().into_owned(),
+        );
 
-#[derive(Default)]
-struct Buffer<'a> {
-    path: PathBuf,
-    searchbox: SearchBox<'a>,
-    editor: Editor,
-    modified: bool,
-}
+        let len = self.buffers.len();
+        for idx in (1..=len).map(|i| (current + i) % len) {
+            let textarea = &mut self.buffers[idx].editor.textarea;
+            if textarea.set_search_pattern(&pattern).is_err() {
+                break;
+            }
+            let Some(found) = textarea.search_first() else {
+                if idx != current {
+                    textarea.set_search_pattern("").unwrap();
+                }
+                continue;
+            };
+            textarea.select_match(found);
 
-impl<'a> Buffer<'a> {
-    fn new(path: PathBuf) -> Result<Self> {
-        let textarea = if path.exists() {
-            Editor::new_from_file(&fs::File::open(&path)?)?
-        } else {
-            Editor::default()
-        };
+            if idx != current {
+                let buffer = &mut self.buffers[current];
+                buffer.searchbox.close();
+                buffer.editor.textarea.set_search_pattern("").unwrap();
 
-        Ok(Self {
-            editor: textarea,
-            path,
-            ..Default::default()
-        })
+                let buffer = &mut self.buffers[idx];
+                buffer.searchbox.open();
+                buffer.searchbox.set_text(&text);
+                buffer.searchbox.set_literal(literal);
+                let textarea = &buffer.editor.textarea;
+                buffer.searchbox.count_matches(textarea.search_pattern(), textarea);
+                self.current = idx;
+                let name = self.buffers[idx].name();
+                self.set_message(format!("Match in other buffer: {name}"));
+            } else {
+                self.buffers[idx].searchbox.set_error_message(None::<&str>);
+                self.set_message("Search wrapped around all buffers");
+            }
+            return;
+        }
+
+        self.buffers[current].searchbox.set_error_message(Some("not found"));
+        self.notify_invalid();
     }
 
-    fn save(&mut self) -> Result<()> {
-        if !self.modified {
-            return Ok(());
+    /// Counts the matches of the search pattern, or else of the word at the cursor. With `list`, the lines they are on
+    /// are shown too.
+    fn count_matches(&mut self, list: bool) {
+        if self.buffers[self.current].searchbox.take_pending() {
+            self.apply_search_pattern(self.current);
         }
+        let textarea = &self.buffers[self.current].editor.textarea;
+        let pattern = match (textarea.search_pattern(), textarea.word_at_cursor()) {
+            (Some(pattern), _) => pattern.clone(),
+            (None, Some(word)) => Regex::new(&format!(r"\b{}\b", regex::escape(word))).unwrap(),
+            (None, None) => {
+                self.set_message("Nothing to count, search for something or move to a word");
+                return;
+            }
+        };
 
-        let mut f = io::BufWriter::new(fs::File::create(&self.path)?);
-
-        let lines = &self.editor.textarea.lines;
-        for line in lines.iter().take(lines.len() - 1) {
-            f.write_all(line.as_bytes())?;
-            f.write_all(b"\n")?;
+        let (count, rows) = textarea.count_matches(&pattern);
+        let message = format!(
+            "{count} match{} in {} line{}",
+            if count == 1 { "" } else { "es" },
+            rows.len(),
+            if rows.len() == 1 { "" } else { "s" }
+        );
+        if list && !rows.is_empty() {
+            let items = rows.iter().map(|row| (row + 1).to_string()).collect();
+            self.overlay = Some((format!(" Lines matching {pattern} "), items));
         }
+        self.set_message(message);
+    }
 
-        if let Some(last_line) = lines.last() {
-            f.write_all(last_line.as_bytes())?;
-            if !last_line.is_empty() {
-                f.write_all(b"\n")?;
+    /// Opens the file whose path is under the cursor, relative to the buffer's directory and at the line and column
+    /// after it if any. URLs are handed to the system's opener instead.
+    fn open_under_cursor(&mut self) {
+        let buffer = &self.buffers[self.current];
+        let textarea = &buffer.editor.textarea;
+        let cursor = textarea.cursor();
+        let Some(token) = token_at(&textarea.lines[cursor.row], cursor.col) else {
+            self.set_message("No path or URL under the cursor");
+            return;
+        };
+        let token = token.to_string();
+
+        if is_url(&token) {
+            match open_url(&token) {
+                Ok(()) => self.set_message(format!("Opened {token}")),
+                Err(err) => self.set_message(format!("Can't open {token}: {err}")),
             }
+            return;
         }
 
-        self.modified = false;
-        Ok(())
+        let dir = buffer.canonical_path.parent().unwrap_or(Path::new(""));
+        let joined = dir.join(&token).to_string_lossy().into_owned();
+        let (path, position) = split_location(&joined);
+        if !Path::new(path).is_file() {
+            self.set_message(format!("No such file: {token}"));
+            return;
+        }
+
+        if let Err(err) = self.open(path.into()) {
+            self.set_message(format!("Can't open {token}: {err}"));
+            return;
+        }
+        if let Some(CursorPosition { row, col }) = position {
+            self.buffers[self.current].editor.textarea.goto(row, col);
+        }
+        self.redraw = true;
+    }
+
+    /// Runs `command` in the working directory, replacing the output buffer with what it prints.
+    fn run_command(&mut self, command: String) {
+        self.runner = None;
+        let runner = match Runner::new(&command) {
+            Ok(runner) => runner,
+            Err(err) => {
+                self.set_message(format!("Can't run {command}: {err}"));
+                return;
+            }
+        };
+
+        let idx = match self.buffers.iter().position(|buffer| buffer.output) {
+            Some(idx) => idx,
+            None => {
+                self.buffers.push(Buffer {
+                    path: PathBuf::from(OUTPUT_NAME),
+                    canonical_path: canonical_path(Path::new(OUTPUT_NAME)),
+                    output: true,
+                    settings: self.settings.for_dir(None).0,
+                    ..Default::default()
+                });
+                self.buffers.len() - 1
+            }
+        };
+        let textarea = &mut self.buffers[idx].editor.textarea;
+        textarea.set_lines(vec![format!("$ {command}")]);
+        textarea.set_cursor(CursorPosition::default(), false);
+
+        self.current = idx;
+        self.runner = Some(runner);
+        self.last_command = Some(command);
+        self.redraw = true;
+    }
+
+    /// Appends what the running command printed to the output buffer, and reports its exit status once it is done.
+    fn poll_runner(&mut self) {
+        let Some(runner) = &mut self.runner else {
+            return;
+        };
+        // closing the output buffer stops the command
+        let Some(buffer) = self.buffers.iter_mut().find(|buffer| buffer.output) else {
+            self.runner = None;
+            return;
+        };
+
+        let lines = runner.poll();
+        buffer.editor.textarea.extend_lines(lines);
+        // the spinner turns while it runs
+        self.redraw = true;
+
+        let Some(status) = runner.status() else {
+            return;
+        };
+        let status = match status.code() {
+            Some(code) => format!("exit status {code}"),
+            None => "killed".to_string(),
+        };
+        buffer.editor.textarea.extend_lines(vec![format!("[{status}]")]);
+        self.runner = None;
+        self.set_message(format!("Command finished with {status}"));
+    }
+
+    /// Opens the file at the location on `row` of the output buffer, relative to the working directory the command
+    /// ran in. Returns whether there was one.
+    fn jump_to_location(&mut self, row: usize) -> bool {
+        let Some(buffer) = self.buffers.iter().find(|buffer| buffer.output) else {
+            return false;
+        };
+        let Some((path, CursorPosition { row, col })) = location(&buffer.editor.textarea.lines[row]) else {
+            return false;
+        };
+        if !Path::new(path).is_file() {
+            return false;
+        }
+
+        let path = PathBuf::from(path);
+        if let Err(err) = self.open(path) {
+            self.set_message(format!("Can't open: {err}"));
+        } else {
+            self.buffers[self.current].editor.textarea.goto(row, col);
+        }
+        self.redraw = true;
+        true
+    }
+
+    /// Moves to the next or previous line in the output buffer with a location, and opens it.
+    fn jump_to_error(&mut self, forward: bool) {
+        let Some(idx) = self.buffers.iter().position(|buffer| buffer.output) else {
+            self.set_message("No command output, run one with F5");
+            return;
+        };
+
+        let textarea = &self.buffers[idx].editor.textarea;
+        let cursor = textarea.cursor().row;
+        let rows: Box<dyn Iterator<Item = usize>> = if forward {
+            Box::new(cursor + 1..textarea.lines.len())
+        } else {
+            Box::new((0..cursor).rev())
+        };
+        for row in rows {
+            if self.jump_to_location(row) {
+                let textarea = &mut self.buffers[idx].editor.textarea;
+                textarea.set_cursor(CursorPosition { row, col: 0 }, false);
+                return;
+            }
+        }
+        self.set_message("No more locations in the command output");
+    }
+
+    /// Jumps to the definition of the identifier at the cursor, as listed in the tags file in the working directory. With
+    /// several definitions, they are offered to pick from.
+    fn go_to_definition(&mut self) {
+        let textarea = &self.buffers[self.current].editor.textarea;
+        let Some(name) = textarea.word_at_cursor().map(str::to_string) else {
+            self.set_message("No identifier under the cursor");
+            return;
+        };
+        let path = Path::new(TAGS_FILE);
+        if !path.is_file() {
+            self.set_message("No tags file in the working directory, create one with ctags -R");
+            return;
+        }
+
+        let mut tags = match tags::lookup(path, &name) {
+            Ok(tags) => tags,
+            Err(err) => {
+                self.set_message(format!("Can't read {TAGS_FILE}: {err}"));
+                return;
+            }
+        };
+        match tags.len() {
+            0 => self.set_message(format!("No definition of {name} in {TAGS_FILE}")),
+            1 => self.jump_to_tag(&tags.remove(0)),
+            _ => {
+                let items = tags
+                    .iter()
+                    .map(|tag| match &tag.address {
+                        Address::Line(row) => format!("{}:{}", tag.file, row + 1),
+                        Address::Pattern { text, .. } => format!("{}  {}", tag.file, text.trim()),
+                    })
+                    .collect();
+                let picker = Picker::new(format!(" Definitions of {name}: "), items);
+                self.tag_picker = Some((picker, tags));
+                self.redraw = true;
+            }
+        }
+    }
+
+    /// Opens the file of `tag` at the line it points to, remembering where to go back to.
+    fn jump_to_tag(&mut self, tag: &Tag) {
+        let path = PathBuf::from(&tag.file);
+        if !path.is_file() {
+            self.set_message(format!("No such file: {}", tag.file));
+            return;
+        }
+
+        let buffer = &self.buffers[self.current];
+        let from = (buffer.path.clone(), buffer.editor.textarea.cursor());
+        if let Err(err) = self.open(path) {
+            self.set_message(format!("Can't open {}: {err}", tag.file));
+            return;
+        }
+        self.jumps.push(from);
+
+        let textarea = &mut self.buffers[self.current].editor.textarea;
+        let row = tag.find(&textarea.lines).unwrap_or(0);
+        let col = textarea.lines.get(row).and_then(|line| {
+            let idx = line.find(&tag.name)?;
+            Some(line[..idx].chars().count())
+        });
+        textarea.goto(row, col.unwrap_or(0));
+        self.redraw = true;
+    }
+
+    /// Goes back to where the last jump to a definition came from.
+    fn jump_back(&mut self) {
+        let Some((path, CursorPosition { row, col })) = self.jumps.pop() else {
+            self.set_message("No earlier position to go back to");
+            return;
+        };
+        if let Err(err) = self.open(path) {
+            self.set_message(format!("Can't go back: {err}"));
+            return;
+        }
+        self.buffers[self.current].editor.textarea.goto(row, col);
+        self.redraw = true;
+    }
+
+    /// The dictionary to check `path` with, if it is prose and there is one.
+    fn dictionary_for(&self, path: &Path) -> Option<Rc<Dictionary>> {
+        if !self.settings.is_prose(path) {
+            return None;
+        }
+        self.dictionary
+            .get_or_init(|| Dictionary::load(self.settings.personal_dictionary.clone()).map(Rc::new))
+            .clone()
+    }
+
+    /// F7 goes to the next misspelled word, Shift+F7 cycles through suggestions for the one at the cursor, and Ctrl+F7
+    /// adds it to the personal dictionary.
+    fn spell_check(&mut self, add: bool, suggest: bool) {
+        let buffer = &self.buffers[self.current];
+        if !self.settings.is_prose(&buffer.path) {
+            self.set_message("Spell checking is only done in text and Markdown files");
+            return;
+        }
+        let Some(dictionary) = self.dictionary_for(&buffer.path) else {
+            self.set_message("No dictionary found, install a word list like /usr/share/dict/words");
+            return;
+        };
+
+        let buffer = &mut self.buffers[self.current];
+        let textarea = &mut buffer.editor.textarea;
+        if add {
+            let Some(word) = textarea.word_at_cursor().map(str::to_string) else {
+                self.set_message("No word at the cursor");
+                return;
+            };
+            let message = match (dictionary.add(&word), dictionary.personal_path()) {
+                (Ok(()), Some(path)) => format!("Added {word} to {}", display_path(path)),
+                (Ok(()), None) => format!("Added {word} for this session"),
+                (Err(err), _) => format!("Can't add {word} to the personal dictionary: {err}"),
+            };
+            self.set_message(message);
+        } else if suggest {
+            if let Some(reason) = buffer.read_only_reason() {
+                self.set_message(reason);
+                return;
+            }
+            let textarea = &mut buffer.editor.textarea;
+            let undo_len = textarea.undo_len();
+            let result = textarea.cycle_suggestion(&dictionary);
+            buffer.modified |= textarea.undo_len() != undo_len;
+            match result {
+                Ok((word, 0, _)) => self.set_message(format!("Back to {word}")),
+                Ok((word, idx, count)) => self.set_message(format!("{word} ({idx}/{count}), Shift+F7 for the next")),
+                Err(err) => self.set_message(err),
+            }
+        } else if !textarea.next_misspelling(&dictionary) {
+            self.set_message("No misspelled words");
+        }
+        self.redraw = true;
+    }
+
+    /// Whether `input` is Ctrl+C or Esc pressed a second time in a row, to quit pipe mode without output. The first
+    /// press still does what it usually does.
+    fn abort_requested(&mut self, input: &Input) -> bool {
+        let buffer = &self.buffers[self.current];
+        let abort_key = match input {
+            Input {
+                key: Key::Char('c'),
+                ctrl: true,
+                alt: false,
+                ..
+            } => true,
+            // only once nothing is left for Esc to close, and not in vim, where Esc is pressed all the time
+            Input { key: Key::Esc, .. } => {
+                !self.prompt_open()
+                    && !buffer.searchbox.is_open()
+                    && self.tag_picker.is_none()
+                    && self.buffer_picker.is_none()
+                    && buffer.vim.is_none()
+            }
+            _ => false,
+        };
+
+        let pending = self.abort_pending.take();
+        if !abort_key {
+            return false;
+        }
+        if pending == Some(input.key) {
+            return true;
+        }
+        self.abort_pending = Some(input.key);
+        let key = if input.key == Key::Esc { "Esc" } else { "Ctrl+C" };
+        self.set_message(format!(
+            "Press {key} again to quit without output, Ctrl+Q writes the text"
+        ));
+        false
+    }
+
+    /// Whether one of the prompts shared by all buffers is open.
+    fn prompt_open(&self) -> bool {
+        self.charbox.is_open() || self.replacebox.is_open() || self.open_prompt().is_some()
+    }
+
+    /// The single line prompt shared by all buffers that is open, if one is.
+    fn open_prompt(&self) -> Option<&PromptBox<'a>> {
+        [
+            &self.alignbox,
+            &self.runbox,
+            &self.surroundbox,
+            &self.changebox,
+            &self.gotobox,
+            &self.trashbox,
+            &self.newfilebox,
+        ]
+        .into_iter()
+        .find(|prompt| prompt.is_open())
+    }
+
+    /// Moves the cursor to what was typed in the go to prompt: a 1-based `line[:col]`, or `@offset` in bytes.
+    fn go_to(&mut self, text: &str) {
+        let textarea = &mut self.buffers[self.current].editor.textarea;
+        match GoTo::parse(text) {
+            Ok(GoTo::Byte(offset)) => textarea.goto_byte_offset(offset),
+            Ok(GoTo::Line(line, col)) => textarea.goto(line.saturating_sub(1), col.saturating_sub(1)),
+            Err(err) => self.set_message(err),
+        }
+    }
+
+    /// Replaces `pattern` in every buffer that can be edited, each as its own undo step.
+    fn replace_in_all_buffers(&mut self, pattern: &str, replacement: &str) {
+        let pattern = match Regex::new(pattern) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                self.set_message(format!("Invalid pattern: {err}"));
+                return;
+            }
+        };
+
+        let (mut replaced, mut touched, mut loading, mut read_only) = (0, 0, 0, 0);
+        for buffer in &mut self.buffers {
+            // command output and directory listings aren't the text of a file
+            if buffer.output || buffer.browser.is_some() {
+                continue;
+            }
+            // still loading, so not editable yet
+            if buffer.loader.is_some() {
+                loading += 1;
+                continue;
+            }
+            // opened with --readonly or in hex mode
+            if !buffer.editable() {
+                read_only += 1;
+                continue;
+            }
+
+            let count = buffer.editor.textarea.replace_all(&pattern, replacement, 0);
+            if count > 0 {
+                replaced += count;
+                touched += 1;
+                buffer.modified = true;
+            }
+        }
+
+        let mut message = format!(
+            "Replaced {replaced} occurrence{} in {touched} buffer{}",
+            if replaced == 1 { "" } else { "s" },
+            if touched == 1 { "" } else { "s" }
+        );
+        if loading > 0 {
+            message.push_str(&format!(", skipped {loading} still loading"));
+        }
+        if read_only > 0 {
+            let s = if read_only == 1 { "" } else { "s" };
+            message.push_str(&format!(", {read_only} read-only buffer{s} skipped"));
+        }
+        self.set_message(message);
+    }
+
+    /// Inserts `char` at the cursor, also in vim's normal mode.
+    fn insert_char(&mut self, char: char) {
+        let buffer = &mut self.buffers[self.current];
+        if let Some(reason) = buffer.read_only_reason() {
+            self.set_message(reason);
+            return;
+        }
+
+        let input = Input {
+            key: Key::Char(char),
+            ctrl: false,
+            alt: false,
+            shift: false,
+        };
+        buffer.modified |= buffer.editor.input(input);
+    }
+
+    /// Handles the keys of a directory listing, returning whether `event` was one: Enter opens the file or directory
+    /// on the cursor line, Backspace goes up, Alt+. shows or hides hidden files and Ctrl+N makes a new file.
+    fn process_browser_input(&mut self, event: &Input) -> bool {
+        let buffer = &mut self.buffers[self.current];
+        let Some(browser) = &buffer.browser else {
+            return false;
+        };
+        let dir = browser.dir().to_path_buf();
+        let show_hidden = browser.show_hidden();
+        let entry = browser.entry(buffer.editor.textarea.cursor().row);
+
+        let listed = match event {
+            Input {
+                key: Key::Enter,
+                ctrl: false,
+                alt: false,
+                ..
+            } => match entry {
+                Some((path, true)) => {
+                    // going up puts the cursor on the directory just left
+                    let select = dir.file_name().filter(|_| dir.parent() == Some(path.as_path()));
+                    buffer.browse(&path, show_hidden, select)
+                }
+                Some((path, false)) => {
+                    if let Err(err) = self.open(path) {
+                        self.set_message(format!("Can't open: {err}"));
+                    }
+                    Ok(())
+                }
+                None => Ok(()),
+            },
+            Input {
+                key: Key::Backspace,
+                ctrl: false,
+                alt: false,
+                ..
+            } => match dir.parent() {
+                Some(parent) => buffer.browse(parent, show_hidden, dir.file_name()),
+                None => Ok(()),
+            },
+            Input {
+                key: Key::Char('.'),
+                ctrl: false,
+                alt: true,
+                ..
+            } => {
+                let select = entry.as_ref().and_then(|(path, _)| path.file_name());
+                let listed = buffer.browse(&dir, !show_hidden, select);
+                self.set_message(if show_hidden {
+                    "Hiding hidden files"
+                } else {
+                    "Showing hidden files"
+                });
+                listed
+            }
+            Input {
+                key: Key::Char('n'),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            } => {
+                self.newfilebox.open_to_append(&dir_prefix(&dir));
+                Ok(())
+            }
+            _ => return false,
+        };
+
+        if let Err(err) = listed {
+            self.set_error(format!("Can't list the directory: {err}"));
+        }
+        self.redraw = true;
+        true
+    }
+
+    /// Opens a buffer for the file `name`, which saving it creates if it doesn't exist.
+    fn new_file(&mut self, name: &str) {
+        let path = PathBuf::from(expand_home(name).as_ref());
+        let exists = path.exists();
+        match self.open(path) {
+            Ok(()) if !exists => self.set_message("New file, saving creates it"),
+            Ok(()) => {}
+            Err(err) => self.set_message(format!("Can't open: {err}")),
+        }
+    }
+
+    /// Handles the copy, cut or paste `event` with register `name` standing in for the clipboard.
+    fn process_register_input(&mut self, name: char, event: Input, repeat: usize) {
+        let clip = self.registers.get(&name).cloned();
+        if clip.is_none() && matches!(event.key, Key::Char('v') | Key::Paste) {
+            self.set_message(format!("Register {name} is empty"));
+            return;
+        }
+
+        self.buffers[self.current].editor.textarea.clipboard.use_register(clip);
+        self.process_textarea_input(event, repeat);
+        if let Some(clip) = self.buffers[self.current].editor.textarea.clipboard.take_register() {
+            self.registers.insert(name, clip);
+        }
+    }
+
+    /// Handles `event` `repeat` times, as one undo step.
+    fn process_textarea_input(&mut self, event: Input, repeat: usize) {
+        let buffer = &self.buffers[self.current];
+        if buffer.output && event.key == Key::Enter {
+            if !self.jump_to_location(buffer.editor.textarea.cursor().row) {
+                self.set_message("No location of an existing file on this line");
+            }
+            return;
+        }
+        if self.process_browser_input(&event) {
+            return;
+        }
+        let buffer = &self.buffers[self.current];
+        if let Some(reason) = buffer.read_only_reason()
+            && !is_navigation(&event)
+            && (matches!(buffer.content, Content::Text) || buffer.readonly)
+        {
+            self.set_message(reason);
+            return;
+        }
+
+        let buffer = &mut self.buffers[self.current];
+        if let Content::Bytes(hex) = &mut buffer.content {
+            buffer.modified |= hex.input(event);
+            self.redraw = true;
+            return;
+        }
+
+        match event {
+            Input {
+                key: Key::Char('f'),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            } => {
+                let selection = buffer.editor.textarea.selected_text_single_line().map(str::to_owned);
+                buffer.searchbox.open();
+                // a selection is searched for as is, the box shows it unescaped for editing
+                match selection {
+                    Some(selection) => {
+                        buffer.searchbox.set_text(&selection);
+                        buffer.searchbox.set_literal(true);
+                    }
+                    // without one it is a regex search again, for the same text
+                    None if buffer.searchbox.literal() => {
+                        let pattern = buffer.searchbox.pattern().into_owned();
+                        buffer.searchbox.set_text(&pattern);
+                        buffer.searchbox.set_literal(false);
+                    }
+                    None => {}
+                }
+
+                self.apply_search_pattern(self.current);
+            }
+            Input {
+                key: Key::Char(key @ ('z' | 'y')),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            } => {
+                let (verb, step): (_, fn(&mut TextArea) -> _) = match key {
+                    'z' => ("Undo", TextArea::undo),
+                    _ => ("Redo", TextArea::redo),
+                };
+                let textarea = &mut buffer.editor.textarea;
+                let steps = (0..repeat).map_while(|_| step(textarea)).collect::<Vec<_>>();
+                let Some(last) = steps.last() else {
+                    self.set_message(format!("Nothing to {}", verb.to_lowercase()));
+                    return;
+                };
+
+                buffer.modified = true;
+                let actions = steps.iter().map(|step| step.actions).sum::<usize>();
+                let remaining = last.remaining;
+                self.set_message(if actions == 1 {
+                    format!("{verb} ({remaining} remaining)")
+                } else {
+                    format!("{verb} {actions} changes at once ({remaining} remaining)")
+                });
+            }
+            input => {
+                let literal_tab = input
+                    == Input {
+                        key: Key::Tab,
+                        ctrl: false,
+                        alt: true,
+                        shift: false,
+                    };
+                buffer.modified |= match &mut buffer.vim {
+                    Some(vim) => {
+                        let undo_len = buffer.editor.textarea.undo_len();
+                        let mut modified = false;
+                        for _ in 0..repeat {
+                            modified |= vim.input(&mut buffer.editor, input.clone());
+                        }
+                        buffer.editor.textarea.chain_undo_since(undo_len);
+                        modified
+                    }
+                    None => {
+                        let before = (buffer.editor.textarea.version(), buffer.editor.textarea.cursor());
+                        let modified = buffer.editor.input_repeat(input.clone(), repeat);
+                        buffer.record_edit(input, repeat, before);
+                        modified
+                    }
+                };
+                self.edit_key = true;
+
+                if literal_tab && !mem::replace(&mut self.literal_tab_hint_shown, true) {
+                    self.set_message("Inserted a literal tab character (Alt+Tab)");
+                }
+            }
+        }
+    }
+}
+
+/// Whether `input` copies, cuts or pastes, which a register can be used for.
+fn is_clipboard_key(input: &Input) -> bool {
+    matches!(
+        input,
+        Input {
+            key: Key::Char('c' | 'x' | 'v'),
+            ctrl: true,
+            alt: false,
+            shift: false,
+        } | Input {
+            key: Key::Copy | Key::Cut | Key::Paste,
+            ..
+        }
+    )
+}
+
+/// The first line of `text`, cut short to fit a list of registers.
+fn register_preview(text: &str) -> String {
+    const MAX_CHARS: usize = 20;
+
+    let line = text.lines().next().unwrap_or_default();
+    match line.char_indices().nth(MAX_CHARS) {
+        Some((idx, _)) => format!("{}…", &line[..idx]),
+        None => line.to_string(),
+    }
+}
+
+/// Opens `url` in the browser without waiting for it, so the terminal stays with the editor.
+fn open_url(url: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        // not `cmd /C start`, which would read `&` and the like in the URL as its own syntax
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Draws `items` comma-separated in a box along the bottom of `area`, cut off with the number of items left out if they
+/// don't fit.
+fn render_overlay(f: &mut ratatui::Frame, area: Rect, title: &str, items: &[String]) {
+    let width = usize::from(area.width.saturating_sub(2));
+    let max_rows = usize::from(area.height / 2).saturating_sub(2).max(1);
+
+    let mut rows: Vec<String> = vec![String::new()];
+    for (idx, item) in items.iter().enumerate() {
+        let full = rows.len() == max_rows;
+        let row = rows.last_mut().unwrap();
+        let sep = if row.is_empty() { "" } else { ", " };
+        if !row.is_empty() && row.len() + sep.len() + item.len() + 1 > width {
+            if full {
+                let rest = format!(" … {} more", items.len() - idx);
+                while !row.is_empty() && row.len() + rest.len() > width {
+                    row.pop();
+                }
+                row.push_str(&rest);
+                break;
+            }
+            row.push(',');
+            rows.push(item.clone());
+        } else {
+            row.push_str(sep);
+            row.push_str(item);
+        }
+    }
+
+    let height = u16::try_from(rows.len() + 2).unwrap_or(u16::MAX).min(area.height);
+    let area = Rect {
+        y: area.bottom() - height,
+        height,
+        ..area
+    };
+    let lines: Vec<Line> = rows.into_iter().map(Line::from).collect();
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+        area,
+    );
+}
+
+/// `bytes` as bytes, or in KB or MB with one decimal once that reads better.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 2] = ["KB", "MB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = UNITS[0];
+    if size >= 1024.0 {
+        size /= 1024.0;
+        unit = UNITS[1];
+    }
+    format!("{size:.1} {unit}")
+}
+
+/// Whether `input` only moves the cursor around or searches, for buffers that can't be edited.
+fn is_navigation(input: &Input) -> bool {
+    match input {
+        // swaps lines
+        Input {
+            key: Key::Up | Key::Down,
+            alt: true,
+            ctrl: false,
+            ..
+        } => false,
+        Input {
+            key:
+                Key::Up | Key::Down | Key::Left | Key::Right | Key::Home | Key::End | Key::PageUp | Key::PageDown | Key::Esc,
+            ..
+        } => true,
+        // copy and search
+        Input {
+            key: Key::Char('c' | 'f'),
+            ctrl: true,
+            alt: false,
+            shift: false,
+        }
+        | Input { key: Key::Copy, .. } => true,
+        _ => false,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Status {
+    Continue,
+    Stop,
+}
+
+/// Where the go to prompt moves the cursor.
+#[derive(Debug, PartialEq, Eq)]
+enum GoTo {
+    /// A 1-based line and column.
+    Line(usize, usize),
+    Byte(usize),
+}
+
+impl GoTo {
+    /// Reads `line[:col]` or `@offset`.
+    fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        if let Some(offset) = text.strip_prefix('@') {
+            return offset
+                .parse()
+                .map(Self::Byte)
+                .map_err(|_| format!("Not a byte offset: {offset}"));
+        }
+
+        let (line, col) = text.split_once(':').unwrap_or((text, "1"));
+        match (line.parse(), col.parse()) {
+            (Ok(line), Ok(col)) => Ok(Self::Line(line, col)),
+            _ => Err(format!("Not a line[:col] or @byte: {text}")),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Buffer<'a> {
+    /// The path as given by the user, for display.
+    path: PathBuf,
+    /// The path used to tell if two buffers are the same file.
+    canonical_path: PathBuf,
+    searchbox: SearchBox<'a>,
+    editor: Editor,
+    modified: bool,
+    /// The file as it was when last read or written, to notice other programs changing it.
+    disk_stamp: Option<DiskStamp>,
+    disk_state: DiskState,
+    /// Reads big files in the background, editing and saving is disabled until it is done.
+    loader: Option<Loader>,
+    /// Shows what the command run with F5 printed, which is never edited or saved.
+    output: bool,
+    /// Holds what was piped to ded, which has no file to save to.
+    stdin: bool,
+    /// Lists the entries of a directory, opened in place of a file.
+    browser: Option<Browser>,
+    /// Opened with `--readonly`, nothing in it can be changed or saved.
+    readonly: bool,
+    vim: Option<Vim>,
+    content: Content,
+    settings: BufferSettings,
+    /// Why the project settings couldn't be read, shown once when the file is opened.
+    settings_error: Option<String>,
+    last_edit: Option<LastEdit>,
+}
+
+/// The last edit made with a key, which F4 makes again at the cursor.
+#[derive(Clone)]
+struct LastEdit {
+    edit: Edit,
+    /// The text's version right after it. Any change made otherwise, like undo, moves on from it and leaves nothing
+    /// to repeat.
+    version: usize,
+    /// Where it left the cursor, so chars typed right there go into the same insert.
+    cursor: CursorPosition,
+    /// Set by a command run after it, like saving or searching, which F4 doesn't skip over to repeat the edit before.
+    superseded: bool,
+}
+
+#[derive(Clone)]
+enum Edit {
+    /// Chars typed one after another, repeated as one insert.
+    Insert(String),
+    /// Any other key that changed the text, and how many times it was pressed at once.
+    Key(Input, usize),
+}
+
+/// What a buffer shows, edits and saves.
+#[derive(Default)]
+enum Content {
+    /// The lines of [`Buffer::editor`].
+    #[default]
+    Text,
+    /// The bytes of the file, in hex mode, toggled with Alt+X. The text stays in [`Buffer::editor`] meanwhile, with
+    /// its undo history, for going back to it. A file that isn't UTF-8 only has bytes.
+    Bytes(Hex),
+}
+
+/// What identifies a version of a file without reading it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DiskStamp {
+    modified: SystemTime,
+    len: u64,
+    /// Tells a file replaced by another apart, like `git checkout` does, even if the time and size match.
+    inode: u64,
+}
+
+impl DiskStamp {
+    /// The stamp of the file at `path`, or `None` if there is no file there.
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(path).ok().filter(fs::Metadata::is_file)?;
+        #[cfg(unix)]
+        let inode = std::os::unix::fs::MetadataExt::ino(&metadata);
+        #[cfg(not(unix))]
+        let inode = 0;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+            inode,
+        })
     }
 }
+
+/// How the file relates to the buffer, as far as other programs are concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum DiskState {
+    #[default]
+    Same,
+    /// Changed while the buffer had unsaved changes, so it wasn't reloaded.
+    Changed,
+    /// Deleted, renamed or replaced by something that isn't a file.
+    Deleted,
+}
+
+/// Passes what is written on to `W`, counting the bytes, so a save can say how much it wrote without asking the file
+/// system again.
+struct CountingWriter<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W> CountingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, written: 0 }
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a> Buffer<'a> {
+    fn new(path: PathBuf, settings: &Settings) -> Result<Self> {
+        if path.is_dir() {
+            let mut buffer = Self {
+                vim: settings.vim.then(Vim::default),
+                settings: settings.for_dir(None).0,
+                ..Default::default()
+            };
+            buffer.browse(&path, false, None)?;
+            return Ok(buffer);
+        }
+
+        let mut loader = None;
+        let mut content = Content::Text;
+        let canonical = canonical_path(&path);
+        let (buffer_settings, settings_error) = settings.for_dir(canonical.parent());
+        let indent_override = settings
+            .indent_override(&path)
+            .or_else(|| buffer_settings.indent.clone());
+        let mut editor = if path.exists() {
+            let file = fs::File::open(&path)?;
+            if file.metadata()?.len() >= ASYNC_LOAD_SIZE {
+                loader = Some(Loader::new(file)?);
+                let mut editor = Editor::default();
+                editor.indent_override = indent_override;
+                editor
+            } else {
+                let mut editor = match Editor::new_from_file(&file, indent_override.clone()) {
+                    Ok(editor) => editor,
+                    // not text, but small enough to show as hex
+                    Err(err)
+                        if file.metadata()?.len() <= hex::MAX_SIZE
+                            && err
+                                .downcast_ref::<io::Error>()
+                                .is_some_and(|err| err.kind() == io::ErrorKind::InvalidData) =>
+                    {
+                        content = Content::Bytes(Hex::new(fs::read(&path)?));
+                        Editor::default()
+                    }
+                    Err(err) => return Err(err),
+                };
+                let restored = settings
+                    .positions_file
+                    .as_deref()
+                    .and_then(|state| positions::lookup(state, &canonical));
+                if let Some(CursorPosition { row, col }) = restored {
+                    editor.textarea.goto(row, col);
+                }
+                editor
+            }
+        } else {
+            let mut editor = Editor::default();
+            editor.textarea.indent = indent_override.unwrap_or_default();
+            editor
+        };
+        editor.textarea.makefile = is_makefile(&path);
+
+        Ok(Self {
+            editor,
+            disk_stamp: DiskStamp::of(&path),
+            canonical_path: canonical,
+            path,
+            loader,
+            content,
+            readonly: settings.readonly,
+            vim: settings.vim.then(Vim::default),
+            settings: buffer_settings,
+            settings_error,
+            ..Default::default()
+        })
+    }
+
+    fn from_stdin(settings: &Settings) -> Result<Self> {
+        Ok(Self {
+            editor: Editor::new_from_file(io::stdin().lock(), None)?,
+            path: PathBuf::from(STDIN_NAME),
+            canonical_path: canonical_path(Path::new(STDIN_NAME)),
+            stdin: true,
+            readonly: settings.readonly,
+            vim: settings.vim.then(Vim::default),
+            settings: settings.for_dir(None).0,
+            ..Default::default()
+        })
+    }
+
+    /// Lists `dir` in place of the text, with the cursor on the entry `select` if it is listed.
+    fn browse(&mut self, dir: &Path, show_hidden: bool, select: Option<&OsStr>) -> io::Result<()> {
+        let canonical = canonical_path(dir);
+        let browser = Browser::new(canonical.clone(), show_hidden)?;
+        let textarea = &mut self.editor.textarea;
+        textarea.set_lines(browser.lines());
+        textarea.set_cursor(
+            CursorPosition {
+                row: browser.row_of(select),
+                col: 0,
+            },
+            false,
+        );
+        self.path = dir.to_path_buf();
+        self.canonical_path = canonical;
+        self.browser = Some(browser);
+        Ok(())
+    }
+
+    /// Why the text can't be edited, if it can't.
+    fn read_only_reason(&self) -> Option<&'static str> {
+        if self.readonly && self.browser.is_none() {
+            Some("Opened with --readonly, nothing can be changed")
+        } else {
+            self.view_only_reason()
+        }
+    }
+
+    /// Like [`Buffer::read_only_reason`], leaving out `--readonly`, which still has the text follow the file.
+    fn view_only_reason(&self) -> Option<&'static str> {
+        if self.browser.is_some() {
+            Some("A directory listing can't be edited, Enter opens what is on the line")
+        } else if self.loader.is_some() {
+            Some("Editing is disabled until the file is loaded")
+        } else if self.output {
+            Some("The command output can't be edited, Enter opens the location on a line")
+        } else if let Content::Bytes(_) = self.content {
+            Some("Only bytes can be overwritten in hex mode, Alt+X goes back to text")
+        } else {
+            None
+        }
+    }
+
+    fn editable(&self) -> bool {
+        self.read_only_reason().is_none()
+    }
+
+    /// Remembers `input`, pressed `repeat` times with the text at `before`, for F4 if it changed the text. A char
+    /// typed right where the insert before it ended adds to that insert.
+    fn record_edit(&mut self, input: Input, repeat: usize, before: (usize, CursorPosition)) {
+        let textarea = &self.editor.textarea;
+        if textarea.version() == before.0 {
+            return;
+        }
+        let edit = match (input, self.last_edit.take()) {
+            (
+                Input {
+                    key: Key::Char(char),
+                    ctrl: false,
+                    alt: false,
+                    ..
+                },
+                last,
+            ) => {
+                let mut text = match last {
+                    Some(LastEdit {
+                        edit: Edit::Insert(text),
+                        version,
+                        cursor,
+                        superseded: false,
+                    }) if (version, cursor) == before => text,
+                    _ => String::new(),
+                };
+                text.extend(iter::repeat_n(char, repeat));
+                Edit::Insert(text)
+            }
+            (input, _) => Edit::Key(input, repeat),
+        };
+        self.last_edit = Some(LastEdit {
+            edit,
+            version: textarea.version(),
+            cursor: textarea.cursor(),
+            superseded: false,
+        });
+    }
+
+    /// Makes the last edit made with a key again at the cursor, as one undo step.
+    fn repeat_edit(&mut self) -> Result<(), &'static str> {
+        let Some(last) = self.last_edit.clone() else {
+            return Err("Nothing to repeat yet");
+        };
+        let textarea = &self.editor.textarea;
+        if last.version != textarea.version() {
+            return Err("The last change wasn't made with a key, so it can't be repeated");
+        }
+        if last.superseded {
+            return Err("The last command can't be repeated, only edits made with a key");
+        }
+
+        let undo_len = textarea.undo_len();
+        let (inputs, count) = match &last.edit {
+            Edit::Insert(text) => (
+                text.chars()
+                    .map(|char| Input {
+                        key: Key::Char(char),
+                        ..Default::default()
+                    })
+                    .collect(),
+                1,
+            ),
+            Edit::Key(input, count) => (vec![input.clone()], *count),
+        };
+        for input in inputs {
+            self.modified |= self.editor.input_repeat(input, count);
+        }
+        let textarea = &mut self.editor.textarea;
+        textarea.chain_undo_since(undo_len);
+        self.last_edit = Some(LastEdit {
+            version: textarea.version(),
+            cursor: textarea.cursor(),
+            ..last
+        });
+        Ok(())
+    }
+
+    /// The file name, or the whole path if it has none.
+    fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map_or_else(|| display_path(&self.path), |name| name.to_string_lossy().into_owned())
+    }
+
+    /// Appends what the loader read so far, returning whether anything changed.
+    fn poll_loader(&mut self) -> bool {
+        let Some(loader) = &mut self.loader else {
+            return false;
+        };
+
+        let first = loader.bytes_read() == 0;
+        let lines = loader.poll();
+        if !lines.is_empty() {
+            self.editor.append_lines(lines, first);
+        }
+
+        // a failed loader is kept around, so the partial text can't be saved over the file
+        if loader.is_done() && loader.error().is_none() {
+            self.loader = None;
+        }
+        true
+    }
+
+    /// Reloads the file if another program changed it and there are no unsaved changes, as an undoable edit,
+    /// returning whether it did. Otherwise the change is only recorded in [`Buffer::disk_state`]. Bytes shown in hex
+    /// mode are never reloaded, only checked, so saving them doesn't go over a change unannounced.
+    fn reload_if_changed(&mut self) -> Result<bool> {
+        let bytes = matches!(self.content, Content::Bytes(_));
+        if (self.view_only_reason().is_some() && !bytes) || self.disk_stamp.is_none() {
+            return Ok(false);
+        }
+        let stamp = DiskStamp::of(&self.path);
+        if stamp == self.disk_stamp {
+            // renamed back
+            if self.disk_state == DiskState::Deleted {
+                self.disk_state = DiskState::Same;
+            }
+            return Ok(false);
+        }
+        let Some(stamp) = stamp else {
+            self.disk_state = DiskState::Deleted;
+            return Ok(false);
+        };
+        // too big to read without freezing the editor, like when opening it
+        if self.modified || bytes || stamp.len >= ASYNC_LOAD_SIZE {
+            self.disk_state = DiskState::Changed;
+            return Ok(false);
+        }
+
+        self.disk_stamp = Some(stamp);
+        self.disk_state = DiskState::Same;
+        self.editor.reload(&fs::File::open(&self.path)?)
+    }
+
+    /// Writes the buffer to its file if it has unsaved changes or the file is gone, returning how many bytes were
+    /// written, or `None` when there was nothing to write. A deleted file is created again, along with its directory.
+    /// A symlink is written through to the file it finally points to, unless `replace_symlinks` is set, in which case a
+    /// regular file is written next to the link and renamed over it, so the link is only gone once the text is safe.
+    fn save(&mut self, replace_symlinks: bool) -> Result<Option<u64>> {
+        if !self.modified && self.disk_state != DiskState::Deleted {
+            return Ok(None);
+        }
+        if self.path.is_dir() {
+            anyhow::bail!("{} is a directory", display_path(&self.path));
+        }
+        let replace = replace_symlinks && self.path.is_symlink();
+        let target = if replace {
+            let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+            name.push(".ded-save");
+            self.path.with_file_name(name)
+        } else if !self.path.is_symlink() {
+            self.path.clone()
+        } else {
+            fs::canonicalize(&self.path)
+                .map_err(|_| anyhow::anyhow!("{} is a link to a missing file", display_path(&self.path)))?
+        };
+        if self.disk_state == DiskState::Deleted
+            && let Some(dir) = target.parent().filter(|dir| !dir.as_os_str().is_empty())
+        {
+            fs::create_dir_all(dir)?;
+        }
+
+        // writing over the file keeps its permissions and any other links to it
+        let write = || -> Result<u64> {
+            let mut f = CountingWriter::new(io::BufWriter::new(fs::File::create(&target)?));
+            self.write_contents(&mut f)?;
+            f.flush()?;
+            if replace {
+                fs::rename(&target, &self.path)?;
+            }
+            Ok(f.written)
+        };
+        let written = write().inspect_err(|_| {
+            if replace {
+                _ = fs::remove_file(&target);
+            }
+        })?;
+        self.modified = false;
+        self.disk_stamp = DiskStamp::of(&self.path);
+        self.disk_state = DiskState::Same;
+        Ok(Some(written))
+    }
+
+    /// Trims trailing whitespace and pipes the text through the format command before saving, as the buffer's settings
+    /// ask, each as one undo step. The text is left as it was when the command fails or runs for longer than `timeout`.
+    fn tidy(&mut self, timeout: Duration) -> Result<(), String> {
+        if !self.modified || matches!(self.content, Content::Bytes(_)) {
+            return Ok(());
+        }
+        if self.settings.trim_on_save {
+            let textarea = &mut self.editor.textarea;
+            let lines = textarea.lines.iter().map(|line| line.trim_end().to_string()).collect();
+            textarea.replace_lines(lines);
+        }
+        let Some(command) = &self.settings.format_command else {
+            return Ok(());
+        };
+
+        let mut text = Vec::new();
+        self.write_text(&mut text).expect("writing to memory can't fail");
+        let empty = text.is_empty();
+        let mut formatter = shell(command);
+        if let Some(dir) = self.canonical_path.parent() {
+            formatter.current_dir(dir);
+        }
+        let mut child = formatter
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("can't run {command}: {err}"))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        // written and read from other threads, so a formatter that prints as it reads can't fill a pipe and stall
+        let writer = thread::spawn(move || stdin.write_all(&text));
+        let read = |mut pipe: Box<dyn Read + Send>| {
+            thread::spawn(move || {
+                let mut output = Vec::new();
+                pipe.read_to_end(&mut output).map(|_| output)
+            })
+        };
+        let stdout = read(Box::new(child.stdout.take().expect("stdout is piped")));
+        let stderr = read(Box::new(child.stderr.take().expect("stderr is piped")));
+
+        let deadline = Instant::now() + timeout;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                Ok(None) => {
+                    _ = child.kill();
+                    _ = child.wait();
+                    return Err(format!(
+                        "{command} took over {}s and was stopped",
+                        timeout.as_secs_f32()
+                    ));
+                }
+                Err(err) => return Err(format!("can't run {command}: {err}")),
+            }
+        };
+        let output = |reader: thread::JoinHandle<io::Result<Vec<u8>>>| {
+            reader
+                .join()
+                .expect("reading from the formatter panicked")
+                .map_err(|err| format!("can't read what {command} printed: {err}"))
+        };
+        let (stdout, stderr) = (output(stdout)?, output(stderr)?);
+        let written = writer.join().expect("writing to the formatter panicked");
+
+        // a command that exits early fails to be written to, which says less than how it failed
+        if !status.success() {
+            let stderr = String::from_utf8_lossy(&stderr);
+            let reason = stderr.lines().map(str::trim).find(|line| !line.is_empty());
+            return Err(format!(
+                "{command} failed: {}",
+                reason.map_or(status.to_string(), str::to_string)
+            ));
+        }
+        written.map_err(|err| format!("{command} didn't read all of the text: {err}"))?;
+        // more likely a command that doesn't read stdin than a formatter that wants the text gone
+        if stdout.is_empty() && !empty {
+            return Err(format!("{command} printed nothing"));
+        }
+        self.editor
+            .reload(stdout.as_slice())
+            .map_err(|err| format!("{command} printed what isn't text: {err}"))?;
+        Ok(())
+    }
+
+    /// Writes what saving writes: the bytes in hex mode, otherwise the text.
+    fn write_contents(&self, out: &mut impl Write) -> io::Result<()> {
+        match &self.content {
+            Content::Bytes(hex) => out.write_all(&hex.bytes),
+            Content::Text => self.write_text(out),
+        }
+    }
+
+    /// Writes the lines as they are saved, each ending in a newline except for an empty last one.
+    fn write_text(&self, out: &mut impl Write) -> io::Result<()> {
+        let lines = &self.editor.textarea.lines;
+        for line in lines.iter().take(lines.len() - 1) {
+            out.write_all(line.as_bytes())?;
+            out.write_all(b"\n")?;
+        }
+
+        if let Some(last_line) = lines.last() {
+            out.write_all(last_line.as_bytes())?;
+            if !last_line.is_empty() {
+                out.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Switches between editing the text and the bytes it is saved as. Bytes overwritten in hex mode become part of
+    /// the text as one undo step.
+    fn toggle_hex(&mut self) -> Result<(), &'static str> {
+        let Content::Bytes(hex) = mem::take(&mut self.content) else {
+            if let Some(reason) = self.read_only_reason() {
+                return Err(reason);
+            }
+            let mut bytes = Vec::new();
+            self.write_text(&mut bytes).expect("writing to memory can't fail");
+            if bytes.len() as u64 > hex::MAX_SIZE {
+                return Err("Too big for hex mode");
+            }
+            self.content = Content::Bytes(Hex::new(bytes));
+            return Ok(());
+        };
+
+        if str::from_utf8(&hex.bytes).is_err() {
+            self.content = Content::Bytes(hex);
+            return Err("Not valid UTF-8, so it can only be shown as hex");
+        }
+        if hex.is_edited() {
+            self.editor.reload(&hex.bytes[..]).expect("valid UTF-8 reads as text");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn test_app(lines: usize) -> App<'static> {
+    // nothing is read from or written to the home directory of whoever runs the tests
+    let home = env::temp_dir().join("ded-test-home");
+    fs::create_dir_all(&home).unwrap();
+    let config = home.join("config.toml");
+    fs::write(&config, "").unwrap();
+    let options = CliOptions {
+        config: Some(config),
+        ..CliOptions::parse(["ded-test-a", "ded-test-b"]).unwrap()
+    };
+    let mut app = App::new(options).unwrap();
+    app.settings.positions_file = None;
+    app.settings.personal_dictionary = Some(home.join("words"));
+    app.settings.trash_dir = Some(home.join("Trash"));
+    for buffer in &mut app.buffers {
+        buffer.editor.textarea.lines = (0..lines).map(|i| format!("line {i}")).collect();
+    }
+    app
+}
+
+#[cfg(test)]
+fn test_render(app: &mut App, width: u16, height: u16) -> Vec<String> {
+    let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(width, height)).unwrap();
+    app.render(&mut terminal).unwrap();
+
+    let buffer = terminal.backend().buffer();
+    (0..height)
+        .map(|y| (0..width).map(|x| buffer[(x, y)].symbol()).collect())
+        .collect()
+}
+
+#[cfg(test)]
+fn key(key: Key, ctrl: bool, alt: bool) -> Input {
+    Input { key, ctrl, alt, shift: false }
+}
+
+#[test]
+fn view_preserved_across_buffer_switches() {
+    let mut app = test_app(200);
+
+    // scroll down and a bit further, so the cursor is in the middle of the view
+    test_render(&mut app, 40, 11);
+    for _ in 0..3 {
+        app.process_input(key(Key::PageDown, false, false)).unwrap();
+        test_render(&mut app, 40, 11);
+    }
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    let before = test_render(&mut app, 40, 11);
+    assert!(before[0].starts_with(" 24 line 24"), "{before:?}");
+
+    app.process_input(key(Key::Char('2'), false, true)).unwrap();
+    assert!(test_render(&mut app, 40, 11)[0].starts_with("  0 line 0"));
+    app.process_input(key(Key::Char('1'), false, true)).unwrap();
+    assert_eq!(test_render(&mut app, 40, 11), before);
+}
+
+#[test]
+fn searchbox_preserved_across_buffer_switches() {
+    let mut app = test_app(3);
+
+    app.process_input(key(Key::Char('2'), false, true)).unwrap();
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    app.process_input(key(Key::Char('1'), false, false)).unwrap();
+
+    app.process_input(key(Key::Char('1'), false, true)).unwrap();
+    assert!(!app.buffers[0].searchbox.is_open());
+    assert!(test_render(&mut app, 40, 6)[0].starts_with("0 line 0"));
+
+    app.process_input(key(Key::Char('2'), false, true)).unwrap();
+    assert!(app.buffers[1].searchbox.is_open());
+    assert_eq!(app.buffers[1].searchbox.text(), "1");
+    assert!(test_render(&mut app, 40, 6)[0].starts_with("┌ Search:"));
+}
+
+#[test]
+fn same_file_opened_once() {
+    let absolute = env::current_dir().unwrap().join("Cargo.toml");
+    let app =
+        App::new(CliOptions::parse(["Cargo.toml".into(), absolute, "src/../Cargo.toml".into()]).unwrap()).unwrap();
+    assert_eq!(app.buffers.len(), 1);
+    assert_eq!(app.buffers[0].path, std::path::Path::new("Cargo.toml"));
+
+    let mut app =
+        App::new(CliOptions::parse(["ded-test-new", "./x/../ded-test-new", "ded-test-other"]).unwrap()).unwrap();
+    assert_eq!(app.buffers.len(), 2);
+    assert_eq!(app.current, 0);
+
+    app.open("ded-test-other".into()).unwrap();
+    assert_eq!(app.current, 1);
+    app.open(env::current_dir().unwrap().join("ded-test-new")).unwrap();
+    assert_eq!((app.buffers.len(), app.current), (2, 0));
+}
+
+#[test]
+fn open_at_location() {
+    let app =
+        App::new(CliOptions::parse(["src/main.rs:3:5", "+2", "Cargo.toml", "src/path.rs:100000"]).unwrap()).unwrap();
+    let cursors: Vec<_> = app.buffers.iter().map(|b| b.editor.textarea.cursor()).collect();
+    let rows = app.buffers[2].editor.textarea.lines.len() - 1;
+    assert_eq!(
+        cursors,
+        [
+            CursorPosition { row: 2, col: 4 },
+            CursorPosition { row: 1, col: 0 },
+            CursorPosition { row: rows, col: 0 },
+        ]
+    );
+
+    // the target line starts out in the middle of the view
+    let mut app = test_app(100);
+    app.buffers[0].editor.textarea.goto(50, 0);
+    assert!(test_render(&mut app, 40, 11)[0].starts_with(" 45 line 45"));
+}
+
+#[test]
+fn tab_bar() {
+    let mut app = test_app(3);
+    app.buffers[1].modified = true;
+    app.process_input(key(Key::Char('t'), false, true)).unwrap();
+    let screen = test_render(&mut app, 40, 6);
+    assert_eq!(screen[0].trim_end(), " ded-test-a ×  ded-test-b* ×");
+    assert!(screen[1].starts_with("0 line 0"));
+
+    let click = |column| MouseEvent {
+        kind: MouseEventKind::Down(MouseButton::Left),
+        column,
+        row: 0,
+        modifiers: crossterm::event::KeyModifiers::NONE,
+    };
+    assert!(app.process_mouse(click(16)));
+    assert_eq!(app.current, 1);
+
+    // modified buffers are kept, unmodified ones closed
+    assert!(app.process_mouse(click(26)));
+    assert_eq!((app.buffers.len(), app.current), (2, 1));
+    assert!(app.process_mouse(click(12)));
+    assert_eq!((app.buffers.len(), app.current), (1, 0));
+    assert!(!app.process_mouse(click(30)));
+
+    // the slot in the status line cycles through buffers
+    app.open("ded-test-c".into()).unwrap();
+    test_render(&mut app, 40, 6);
+    let slot = MouseEvent { row: 5, column: 1, ..click(0) };
+    assert!(app.process_mouse(slot));
+    assert_eq!(app.current, 0);
+}
+
+#[test]
+fn redraw_only_on_change() {
+    let mut app = test_app(3);
+    let redraw = |app: &mut App, input| {
+        app.process_input(input).unwrap();
+        mem::take(&mut app.redraw)
+    };
+
+    assert!(redraw(&mut app, key(Key::Down, false, false)));
+    assert!(redraw(&mut app, key(Key::Char('x'), false, false)));
+    assert!(redraw(&mut app, key(Key::Char('2'), false, true)));
+    assert!(!redraw(&mut app, key(Key::Up, false, false)));
+    assert!(!redraw(&mut app, key(Key::F(6), false, false)));
+    assert!(!redraw(&mut app, key(Key::Char('2'), false, true)));
+    assert!(redraw(&mut app, key(Key::Char('i'), false, true)));
+    // clearing the message needs a redraw too
+    assert!(redraw(&mut app, key(Key::F(6), false, false)));
+    assert!(redraw(&mut app, key(Key::Char('f'), true, false)));
+}
+
+#[test]
+fn events_coalesced() {
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+    let mut app = test_app(3);
+    let char = |c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE));
+    let ctrl = |c| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL));
+
+    let status = app
+        .process_events([
+            char('a'),
+            char('b'),
+            Event::FocusGained,
+            char('c'),
+            Event::Key(KeyCode::F(5).into()),
+        ])
+        .unwrap();
+    assert_eq!(status, Status::Continue);
+    assert_eq!(app.buffers[0].editor.textarea.lines[0], "abcline 0");
+    assert!(mem::take(&mut app.redraw));
+
+    assert!(app.process_events([Event::Resize(10, 10)]).is_ok());
+    assert!(mem::take(&mut app.redraw));
+
+    // nothing after quitting is processed
+    let status = app.process_events([ctrl('q'), char('d')]).unwrap();
+    assert_eq!(status, Status::Stop);
+    assert_eq!(app.buffers[0].editor.textarea.lines[0], "abcline 0");
+}
+
+#[test]
+fn message_timeout() {
+    let mut app = test_app(3);
+    app.on_tick(Instant::now());
+    assert!(!app.redraw);
+
+    app.set_message("hello");
+    mem::take(&mut app.redraw);
+    app.on_tick(app.message_time + MESSAGE_TIMEOUT / 2);
+    assert!(app.message.is_some() && !app.redraw);
+    app.on_tick(app.message_time + MESSAGE_TIMEOUT);
+    assert!(app.message.is_none() && app.redraw);
+}
+
+#[test]
+fn registers() {
+    let mut app = test_app(4);
+    let lines = |app: &App| app.buffers[0].editor.textarea.lines.clone();
+
+    // the line under the cursor into a, the next one cut into b
+    app.process_input(key(Key::Char('r'), true, false)).unwrap();
+    let screen = test_render(&mut app, 40, 6);
+    assert!(screen[5].contains("Register: ?"), "{screen:?}");
+    app.process_input(key(Key::Char('a'), false, false)).unwrap();
+    assert!(test_render(&mut app, 40, 6)[5].contains("Register: a"));
+    app.process_input(key(Key::Char('c'), true, false)).unwrap();
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::Char('r'), true, false)).unwrap();
+    app.process_input(key(Key::Char('b'), false, false)).unwrap();
+    app.process_input(key(Key::Char('x'), true, false)).unwrap();
+    assert_eq!(lines(&app), ["line 0", "line 2", "line 3"]);
+    assert!(!test_render(&mut app, 40, 6)[5].contains("Register"));
+
+    // the clipboard is left alone, and a register is only used for the next key
+    assert_eq!(app.buffers[0].editor.textarea.clipboard.history_len(), 0);
+    app.process_input(key(Key::Char('r'), true, false)).unwrap();
+    app.process_input(key(Key::Char('a'), false, false)).unwrap();
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::Char('v'), true, false)).unwrap();
+    assert_eq!(lines(&app), ["line 0", "line 2", "line 3"]);
+
+    // shared by all buffers, pasted as lines like they were copied
+    app.process_input(key(Key::Char('2'), false, true)).unwrap();
+    for name in ['b', 'a'] {
+        app.process_input(key(Key::Char('r'), true, false)).unwrap();
+        app.process_input(key(Key::Char(name), false, false)).unwrap();
+        app.process_input(key(Key::Char('v'), true, false)).unwrap();
+    }
+    assert_eq!(
+        app.buffers[1].editor.textarea.lines[..4],
+        ["line 1", "line 0", "line 0", "line 1"]
+    );
+
+    app.process_input(key(Key::Char('r'), true, false)).unwrap();
+    assert_eq!(
+        app.overlay,
+        Some((
+            " Registers ".to_string(),
+            vec!["a: line 0".to_string(), "b: line 1".to_string()]
+        ))
+    );
+    app.process_input(key(Key::Char('z'), false, false)).unwrap();
+    assert!(app.overlay.is_none());
+    app.process_input(key(Key::Char('v'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Register z is empty"));
+
+    // anything but a letter cancels
+    app.process_input(key(Key::Char('r'), true, false)).unwrap();
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    assert!(!app.choosing_register && app.register.is_none());
+
+    assert_eq!(register_preview("short\nsecond line"), "short");
+    assert_eq!(register_preview(&"é".repeat(30)), format!("{}…", "é".repeat(20)));
+}
+
+#[test]
+fn undo_messages() {
+    let mut app = test_app(3);
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Nothing to undo"));
+
+    app.process_input(key(Key::Char('a'), false, false)).unwrap();
+    let undo_len = app.buffers[0].editor.textarea.undo_len();
+    app.process_input(key(Key::Char('b'), false, false)).unwrap();
+    app.process_input(key(Key::Char('c'), false, false)).unwrap();
+    app.buffers[0].editor.textarea.chain_undo_since(undo_len);
+
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Undo 2 changes at once (1 remaining)"));
+    assert_eq!(app.buffers[0].editor.textarea.lines[0], "aline 0");
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Undo (0 remaining)"));
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Nothing to undo"));
+
+    app.process_input(key(Key::Char('y'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Redo (1 remaining)"));
+    app.process_input(key(Key::Char('y'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Redo 2 changes at once (0 remaining)"));
+    assert_eq!(app.buffers[0].editor.textarea.lines[0], "abcline 0");
+    app.process_input(key(Key::Char('y'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Nothing to redo"));
+    assert!(app.buffers[0].modified);
+}
+
+#[test]
+fn undo_flash_cleared_on_tick() {
+    let mut app = test_app(3);
+    app.process_input(key(Key::Char('a'), false, false)).unwrap();
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    test_render(&mut app, 40, 6);
+    mem::take(&mut app.redraw);
+
+    app.on_tick(Instant::now());
+    assert!(app.redraw);
+    assert!(!app.buffers[0].editor.textarea.clear_flash());
+}
+
+#[test]
+fn load_in_background() {
+    let path = env::temp_dir().join("ded-test-big");
+    let line = "  ".to_string() + &"x".repeat(1023) + "\n";
+    fs::write(&path, line.repeat(usize::try_from(ASYNC_LOAD_SIZE).unwrap() / 1024)).unwrap();
+
+    let mut app = App::new(CliOptions::parse([path]).unwrap()).unwrap();
+    assert!(app.buffers[0].loader.is_some());
+
+    app.process_input(key(Key::Char('a'), false, false)).unwrap();
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert!(!app.buffers[0].modified);
+    assert!(app.message.as_deref().unwrap().starts_with("Can't save"));
+
+    while app.buffers[0].loader.is_some() {
+        app.on_tick(Instant::now());
+    }
+    let textarea = &app.buffers[0].editor.textarea;
+    assert_eq!(
+        textarea.lines.len(),
+        usize::try_from(ASYNC_LOAD_SIZE).unwrap() / 1024 + 1
+    );
+    assert_eq!(textarea.lines[0], line.trim_end());
+    assert_eq!(textarea.indent.spaces(), "  ");
+
+    app.process_input(key(Key::Char('a'), false, false)).unwrap();
+    assert!(app.buffers[0].modified);
+}
+
+#[test]
+fn stats() {
+    let mut app = test_app(3);
+    app.process_input(key(Key::Char('w'), false, true)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("buffer: 3 lines, 6 words, 20 chars, 20 bytes, cursor at byte 0")
+    );
+
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Down, false, false)
+    })
+    .unwrap();
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Right, false, false)
+    })
+    .unwrap();
+    assert!(test_render(&mut app, 60, 5)[4].ends_with("(1,1) - (2,2) 2 lines (3W, 8C)"));
+    app.process_input(key(Key::Char('w'), false, true)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("selection: 2 lines, 3 words, 8 chars, 8 bytes, cursor at byte 8")
+    );
+
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Up, false, false)
+    })
+    .unwrap();
+    assert!(test_render(&mut app, 60, 5)[4].ends_with("(1,1) - (1,2) 1 char (1W)"));
+
+    // counted again for text that is set without an edit
+    let textarea = &mut app.buffers[0].editor.textarea;
+    textarea.set_lines(vec![" ine 0".into()]);
+    assert_eq!(textarea.selection_stats().map(|stats| stats.words), Some(0));
+}
+
+#[test]
+fn fullscreen() {
+    let mut app = test_app(3);
+    assert!(test_render(&mut app, 100, 5)[4].starts_with("[1/2]"));
+
+    app.process_input(key(Key::F(11), false, false)).unwrap();
+    let screen = test_render(&mut app, 100, 5);
+    assert!(screen[0].starts_with("0 line 0") && screen[4].trim().is_empty());
+
+    app.process_input(key(Key::F(11), false, false)).unwrap();
+    let screen = test_render(&mut app, 100, 5);
+    assert!(screen[0].starts_with(&format!("{}line 0", " ".repeat(9))));
+
+    // the searchbox still shows up
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    assert!(test_render(&mut app, 100, 6)[0].starts_with("┌ Search:"));
+
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    app.process_input(key(Key::F(11), false, false)).unwrap();
+    assert_eq!(app.settings.fullscreen, Fullscreen::Off);
+    assert!(test_render(&mut app, 100, 5)[0].starts_with("0 line 0"));
+}
+
+#[test]
+fn conflict_keys_redraw() {
+    let mut app = test_app(3);
+    app.buffers[0].editor.textarea.lines = ["<<<<<<<", "a", "=======", "b", ">>>>>>>"].map(String::from).into();
+
+    app.process_input(key(Key::Char('n'), false, true)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No more conflicts"));
+    app.process_input(key(Key::Char('r'), false, true)).unwrap();
+    assert!(mem::take(&mut app.redraw));
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["b"]);
+    assert!(app.buffers[0].modified);
+
+    // a prompt gets them instead
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_lines(["<<<<<<<", "a", "=======", "b", ">>>>>>>"].map(String::from).to_vec());
+    app.process_input(key(Key::Char('g'), true, false)).unwrap();
+    app.process_input(key(Key::Char('o'), false, true)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines.len(), 5);
+    assert!(app.gotobox.is_open());
+}
+
+#[test]
+fn insert_unicode() {
+    let mut app = test_app(1);
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Char('U'), true, false)
+    })
+    .unwrap();
+    assert!(test_render(&mut app, 40, 6)[0].starts_with("┌ Unicode"));
+
+    for c in "2192".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    assert!(test_render(&mut app, 40, 6)[0].starts_with("┌ Unicode: → U+2192"));
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert!(!app.charbox.is_open());
+    assert_eq!(app.buffers[0].editor.textarea.lines[0], "→line 0");
+    assert!(app.buffers[0].modified);
+}
+
+#[test]
+fn convert_indent() {
+    let mut app = test_app(1);
+    app.buffers[0].editor.textarea.lines = vec!["a".to_string(), "        b".to_string()].into();
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Char('T'), false, true)
+    })
+    .unwrap();
+    assert_eq!(app.message.as_deref(), Some("Converted 1 line to tabs"));
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["a", "\t\tb"]);
+    assert!(app.buffers[0].modified);
+    assert!(test_render(&mut app, 40, 3)[2].contains("tabs"));
+}
+
+#[test]
+fn mixed_indent() {
+    let mut app = test_app(1);
+    app.buffers[0].editor.textarea.lines = ["a", "\tb", "    c", "\t  d"].map(String::from).into();
+    assert!(test_render(&mut app, 60, 3)[2].contains("[mixed indent: 2 lines]  4 spaces"));
+
+    let alt_shift = |c| Input {
+        shift: true,
+        ..key(Key::Char(c), false, true)
+    };
+    app.process_input(alt_shift('N')).unwrap();
+    app.process_input(alt_shift('N')).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.cursor().row, 3);
+    app.process_input(alt_shift('N')).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.cursor().row, 1);
+
+    app.process_input(alt_shift('F')).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Fixed the indentation of 2 lines"));
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["a", "    b", "    c", "      d"]);
+    assert!(!test_render(&mut app, 60, 3)[2].contains("mixed"));
+    app.process_input(alt_shift('N')).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No mixed indentation"));
+
+    // text set without an edit is counted again, even with as many lines
+    let lines = ["a", "    b", "\tc", "      d"].map(String::from).to_vec();
+    app.buffers[0].editor.textarea.set_lines(lines);
+    assert!(test_render(&mut app, 60, 3)[2].contains("[mixed indent: 1 line]"));
+}
+
+#[test]
+fn cycle_indent() {
+    let mut app = test_app(1);
+    let cycle = Input {
+        shift: true,
+        ..key(Key::Char('I'), false, true)
+    };
+    for expected in ["8 spaces", "tabs", "2 spaces"] {
+        app.process_input(cycle.clone()).unwrap();
+        assert!(mem::take(&mut app.redraw));
+        assert_eq!(
+            app.message.as_deref(),
+            Some(format!("Indenting with {expected}").as_str())
+        );
+    }
+    assert!(test_render(&mut app, 40, 3)[2].contains("2 spaces"));
+}
+
+#[test]
+fn vim_mode() {
+    let app = test_app(2);
+    assert!(!app.settings.vim);
+    assert!(app.buffers[0].vim.is_none());
+
+    let mut app = App::new(CliOptions::parse(["--vim", "ded-test-a"]).unwrap()).unwrap();
+    app.buffers[0].editor.textarea.lines = vec!["one".to_string(), "two".to_string()].into();
+    assert!(test_render(&mut app, 40, 3)[2].ends_with("NORMAL  4 spaces  (1,1)"));
+
+    app.process_input(key(Key::Char('d'), false, false)).unwrap();
+    assert!(test_render(&mut app, 40, 3)[2].ends_with("NORMAL d  4 spaces  (1,1)"));
+    app.process_input(key(Key::Char('d'), false, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["two"]);
+    assert!(app.buffers[0].modified);
+
+    app.process_input(key(Key::Char('i'), false, false)).unwrap();
+    app.process_input(key(Key::Char('j'), false, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["jtwo"]);
+    assert!(test_render(&mut app, 40, 3)[2].ends_with("INSERT  4 spaces  (1,2)"));
+}
+
+#[test]
+fn overwrite_mode() {
+    let mut app = test_app(1);
+    app.process_input(key(Key::Insert, false, false)).unwrap();
+    assert!(mem::take(&mut app.redraw));
+    assert!(test_render(&mut app, 40, 3)[2].ends_with("OVR  4 spaces  (1,1)"));
+
+    app.process_input(key(Key::Char('L'), false, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["Line 0"]);
+}
+
+#[test]
+fn cursor_shape() {
+    let mut app = test_app(1);
+    test_render(&mut app, 40, 6);
+    assert_eq!(app.cursor_shape, Some(CursorShape::Bar));
+
+    app.process_input(key(Key::Insert, false, false)).unwrap();
+    test_render(&mut app, 40, 6);
+    assert_eq!(app.cursor_shape, Some(CursorShape::Block));
+
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    test_render(&mut app, 40, 6);
+    assert_eq!(app.cursor_shape, Some(CursorShape::Underline));
+
+    app.settings.cursor_shapes = false;
+    test_render(&mut app, 40, 6);
+    assert_eq!(app.cursor_shape, None);
+}
+
+#[test]
+fn repeat_count() {
+    let mut app = test_app(10);
+    for input in [key(Key::Char('-'), false, true), key(Key::Char('1'), false, false)] {
+        app.process_input(input).unwrap();
+    }
+    assert!(test_render(&mut app, 60, 6)[5].ends_with("Repeat: 1  4 spaces  (1,1)"));
+    app.process_input(key(Key::Char('2'), false, false)).unwrap();
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    assert_eq!(
+        app.buffers[0].editor.textarea.cursor(),
+        CursorPosition { row: 9, col: 0 }
+    );
+    assert_eq!(app.count, None);
+
+    // edits repeat as one undo step
+    for input in [
+        key(Key::Char('-'), false, true),
+        key(Key::Char('3'), false, false),
+        key(Key::Char('x'), true, false),
+    ] {
+        app.process_input(input).unwrap();
+    }
+    assert_eq!(app.buffers[0].editor.textarea.lines.len(), 7);
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines.len(), 10);
+
+    // Esc cancels the count
+    for input in [
+        key(Key::Char('-'), false, true),
+        key(Key::Char('3'), false, false),
+        key(Key::Esc, false, false),
+        key(Key::Up, false, false),
+    ] {
+        app.process_input(input).unwrap();
+    }
+    assert_eq!(app.buffers[0].editor.textarea.cursor().row, 8);
+}
+
+#[test]
+fn escape_key() {
+    let mut app = test_app(2);
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Down, false, false)
+    })
+    .unwrap();
+    app.set_message("hello");
+
+    // the message goes with any key, the selection only with Esc
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    assert_eq!(app.message, None);
+    assert_eq!(app.buffers[0].editor.textarea.selection(), None);
+
+    // the search box still closes on Esc, without touching the editor
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    assert!(!app.buffers[0].searchbox.is_open());
+}
+
+#[test]
+fn search_selection_literally() {
+    let text = r".*[](){}+?|\ x";
+    let mut app = test_app(1);
+    app.buffers[0].editor.textarea.lines = vec![String::new(), text.to_string()].into();
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_cursor(CursorPosition { row: 1, col: 0 }, false);
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_cursor(CursorPosition { row: 1, col: 13 }, true);
+
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    assert_eq!(app.buffers[0].searchbox.text(), r".*[](){}+?|\ ");
+    assert!(test_render(&mut app, 40, 8)[0].contains("Search (literal)"));
+
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_cursor(CursorPosition { row: 0, col: 0 }, false);
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    assert_eq!(
+        app.buffers[0].editor.textarea.cursor(),
+        CursorPosition { row: 1, col: 0 }
+    );
+    assert_eq!(
+        app.buffers[0].editor.textarea.selection(),
+        Some(CursorPosition { row: 1, col: 13 })
+    );
+
+    // searching again without a selection takes a regex
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    assert!(app.buffers[0].editor.textarea.selection().is_none());
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    assert!(!app.buffers[0].searchbox.literal());
+    assert_eq!(app.buffers[0].searchbox.text(), r"\.\*\[\]\(\)\{\}\+\?\|\\ ");
+    assert!(test_render(&mut app, 40, 8)[0].starts_with("┌ Search: "));
+}
+
+#[test]
+fn replace_in_all_buffers() {
+    let mut app = test_app(3);
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Char('R'), false, true)
+    })
+    .unwrap();
+    for c in "line (\\d)".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    for c in "L$1".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+
+    assert_eq!(app.message.as_deref(), Some("Replaced 6 occurrences in 2 buffers"));
+    for buffer in &app.buffers {
+        assert_eq!(buffer.editor.textarea.lines, ["L0", "L1", "L2"]);
+        assert!(buffer.modified);
+    }
+
+    // each buffer undoes on its own
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["line 0", "line 1", "line 2"]);
+    assert_eq!(app.buffers[1].editor.textarea.lines, ["L0", "L1", "L2"]);
+
+    // hex mode only edits bytes
+    app.buffers[1].toggle_hex().unwrap();
+    app.replace_in_all_buffers("L", "l");
+    assert_eq!(
+        app.message.as_deref(),
+        Some("Replaced 0 occurrences in 0 buffers, 1 read-only buffer skipped")
+    );
+    assert_eq!(app.buffers[1].editor.textarea.lines, ["L0", "L1", "L2"]);
+}
+
+#[test]
+fn search_debounced() {
+    let mut app = test_app(1);
+    app.buffers[0].editor.textarea.lines = (0..2000)
+        .map(|i| format!("{}end{i}", "word, and more ".repeat(100)))
+        .collect();
+    let pattern = r"(?:\w+\W+){1,10}end\d*5$";
+
+    // typing a slow pattern doesn't compile or run it for each key, ticks before the pause don't either
+    let start = Instant::now();
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    for c in pattern.chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+        test_render(&mut app, 80, 20);
+        app.on_tick(start);
+        assert!(app.buffers[0].editor.textarea.search_pattern().is_none(), "{c}");
+    }
+    assert!(app.buffers[0].searchbox.is_pending());
+
+    // searched for once typing pauses, with the matches counted in the background
+    app.on_tick(Instant::now() + searchbox::DEBOUNCE);
+    assert_eq!(
+        app.buffers[0].editor.textarea.search_pattern().map(Regex::as_str),
+        Some(pattern)
+    );
+    for _ in 0..1000 {
+        if !app.buffers[0].searchbox.is_counting() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(5));
+        app.on_tick(Instant::now());
+    }
+    assert!(test_render(&mut app, 80, 20)[0].starts_with("┌ Search: 200 matches ─"));
+
+    // a pattern too big to run fast is refused
+    app.process_input(key(Key::Char('a'), true, false)).unwrap();
+    app.process_input(key(Key::Backspace, false, false)).unwrap();
+    for c in r"\w{1,60}\s\w{1,60}".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    app.on_tick(Instant::now() + searchbox::DEBOUNCE);
+    assert!(test_render(&mut app, 120, 20)[0].contains("exceeds size limit"));
+    assert!(!app.buffers[0].searchbox.is_counting());
+}
+
+#[test]
+fn search_other_buffers() {
+    let mut app = test_app(3);
+    app.buffers[1].editor.textarea.lines[2] = "needle".to_string();
+    app.buffers[0].editor.textarea.lines[0] = "needle".to_string();
+
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    for c in "needle".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    assert_eq!(app.current, 0);
+    assert!(test_render(&mut app, 60, 6)[0].contains("no more matches"));
+
+    // the second Down moves on, taking the searchbox along
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    assert_eq!(app.current, 1);
+    assert_eq!(app.message.as_deref(), Some("Match in other buffer: ded-test-b"));
+    assert!(!app.buffers[0].searchbox.is_open());
+    assert!(app.buffers[1].searchbox.is_open());
+    assert_eq!(app.buffers[1].searchbox.text(), "needle");
+    assert_eq!(
+        app.buffers[1].editor.textarea.cursor(),
+        CursorPosition { row: 2, col: 0 }
+    );
+
+    // and wraps around to the first buffer
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    assert_eq!(app.current, 0);
+    assert_eq!(
+        app.buffers[0].editor.textarea.cursor(),
+        CursorPosition { row: 0, col: 0 }
+    );
+}
+
+#[test]
+fn count_matches() {
+    let mut app = test_app(12);
+    app.buffers[0].editor.textarea.lines[11] = "line 1 line".to_string();
+
+    // the word at the cursor
+    app.process_input(key(Key::Char('c'), false, true)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("13 matches in 12 lines"));
+    assert_eq!(
+        app.buffers[0].editor.textarea.cursor(),
+        CursorPosition { row: 0, col: 0 }
+    );
+
+    // the search pattern, with the lines listed
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    app.process_input(key(Key::Char('1'), false, false)).unwrap();
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Char('C'), false, true)
+    })
+    .unwrap();
+    assert_eq!(app.message.as_deref(), Some("3 matches in 3 lines"));
+    let lines = test_render(&mut app, 30, 14);
+    assert_eq!(lines[10], "┌ Lines matching 1 ─────────┐ ");
+    assert_eq!(lines[11], "│2, 11, 12                  │ ");
+
+    // gone with the next key
+    app.process_input(key(Key::Right, false, false)).unwrap();
+    assert!(!test_render(&mut app, 30, 14)[10].starts_with("┌ Lines"));
+}
+
+#[test]
+fn reorder_lines() {
+    let mut app = test_app(3);
+    let reverse = Input {
+        shift: true,
+        ..key(Key::Char('V'), false, true)
+    };
+
+    // the whole buffer only once confirmed
+    app.process_input(reverse.clone()).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("No selection, press again to reverse all lines")
+    );
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["line 0", "line 1", "line 2"]);
+    app.process_input(reverse.clone()).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["line 2", "line 1", "line 0"]);
+    assert!(app.buffers[0].modified);
+
+    // any other key in between asks again
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    app.process_input(reverse.clone()).unwrap();
+    app.process_input(key(Key::Right, false, false)).unwrap();
+    app.process_input(reverse).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["line 2", "line 1", "line 0"]);
+}
+
+#[test]
+fn align() {
+    let mut app = test_app(3);
+    app.buffers[0].editor.textarea.lines = ["a = 1", "bbb = 2"].map(String::from).into();
+    let align = Input {
+        shift: true,
+        ..key(Key::Char('L'), false, true)
+    };
+
+    app.process_input(align.clone()).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Select the lines to align first"));
+
+    app.process_input(key(Key::Char('a'), true, false)).unwrap();
+    app.process_input(align).unwrap();
+    assert!(test_render(&mut app, 40, 6)[0].starts_with("┌ Align on:"));
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["a   = 1", "bbb = 2"]);
+    assert_eq!(app.message.as_deref(), Some("Aligned 1 line"));
+    assert!(app.buffers[0].modified);
+}
+
+#[test]
+fn open_under_cursor() {
+    let mut app = test_app(3);
+    let open = Input {
+        shift: true,
+        ..key(Key::Char('O'), true, false)
+    };
+    app.buffers[0].editor.textarea.lines[0] = "see src/missing.rs, or (src/path.rs:3:5).".to_string();
+
+    app.process_input(open.clone()).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No such file: see"));
+
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_cursor(CursorPosition { row: 0, col: 8 }, false);
+    app.process_input(open.clone()).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No such file: src/missing.rs"));
+    assert_eq!(app.buffers.len(), 2);
+
+    // relative to the buffer's directory, which is the working directory here
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_cursor(CursorPosition { row: 0, col: 30 }, false);
+    app.process_input(open).unwrap();
+    assert_eq!(app.buffers.len(), 3);
+    assert_eq!(app.current, 2);
+    assert!(app.buffers[2].path.ends_with("src/path.rs"));
+    assert_eq!(
+        app.buffers[2].editor.textarea.cursor(),
+        CursorPosition { row: 2, col: 4 }
+    );
+}
+
+#[test]
+fn run_command() {
+    let mut app = test_app(3);
+    app.process_input(key(Key::F(5), false, false)).unwrap();
+    // offered for the project in the working directory
+    assert_eq!(app.runbox.textarea.lines, ["cargo build"]);
+    for c in "echo src/path.rs:3:5: note; echo README.md; exit 2".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert_eq!(app.current, 2);
+    assert!(test_render(&mut app, 60, 6)[5].contains("running"));
+
+    while app.runner.is_some() {
+        app.on_tick(Instant::now());
+        thread::yield_now();
+    }
+    let output = &app.buffers[2];
+    assert_eq!(
+        output.editor.textarea.lines,
+        [
+            "$ echo src/path.rs:3:5: note; echo README.md; exit 2",
+            "src/path.rs:3:5: note",
+            "README.md",
+            "[exit status 2]"
+        ]
+    );
+    assert_eq!(app.message.as_deref(), Some("Command finished with exit status 2"));
+
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("The command output can't be edited, Enter opens the location on a line")
+    );
+
+    // from any buffer
+    app.current = 0;
+    app.process_input(key(Key::F(8), false, false)).unwrap();
+    assert!(app.buffers[app.current].path.ends_with("src/path.rs"));
+    assert_eq!(
+        app.buffers[app.current].editor.textarea.cursor(),
+        CursorPosition { row: 2, col: 4 }
+    );
+    app.process_input(key(Key::F(8), false, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No more locations in the command output"));
+
+    // the last command is offered again
+    app.process_input(key(Key::F(5), false, false)).unwrap();
+    assert_eq!(
+        app.runbox.textarea.lines[0],
+        "echo src/path.rs:3:5: note; echo README.md; exit 2"
+    );
+}
+
+#[test]
+fn go_to_definition() {
+    let mut app = test_app(3);
+    app.buffers[0].editor.textarea.lines[0] = "canonical_path(path)".to_string();
+    app.process_input(key(Key::Char(']'), true, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("No tags file in the working directory, create one with ctags -R")
+    );
+
+    let tag = |address| Tag {
+        name: "canonical_path".to_string(),
+        file: "src/path.rs".to_string(),
+        address,
+        line: Some(16),
+    };
+    let items = vec!["src/path.rs:1".to_string(), "src/path.rs  pub fn".to_string()];
+    let tags = vec![
+        tag(Address::Line(0)),
+        tag(Address::Pattern {
+            text: "pub fn canonical_path(renamed".to_string(),
+            whole_line: false,
+        }),
+    ];
+    app.tag_picker = Some((Picker::new(" Definitions: ", items), tags));
+    // the pattern isn't in the file anymore, so the line field is used
+    app.process_input(key(Key::Char('2'), false, false)).unwrap();
+    assert!(app.tag_picker.is_none());
+    assert!(app.buffers[app.current].path.ends_with("src/path.rs"));
+    assert_eq!(
+        app.buffers[app.current].editor.textarea.cursor(),
+        CursorPosition { row: 16, col: 7 }
+    );
+
+    app.process_input(key(Key::Char('o'), true, false)).unwrap();
+    assert_eq!(app.current, 0);
+    assert_eq!(
+        app.buffers[0].editor.textarea.cursor(),
+        CursorPosition { row: 0, col: 0 }
+    );
+    app.process_input(key(Key::Char('o'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No earlier position to go back to"));
+}
+
+#[test]
+fn spell_check() {
+    let mut app = test_app(1);
+    let f7 = |ctrl, shift| Input {
+        key: Key::F(7),
+        ctrl,
+        alt: false,
+        shift,
+    };
+    app.buffers[0].editor.textarea.lines = vec!["Teh ded editor".to_string()].into();
+    app.process_input(f7(false, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("Spell checking is only done in text and Markdown files")
+    );
+
+    app.buffers[0].path = PathBuf::from("ded-test.md");
+    app.dictionary = OnceCell::from(Some(Rc::new(Dictionary::new(["the", "editor"].map(String::from)))));
+    app.process_input(f7(false, true)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("The (1/1), Shift+F7 for the next"));
+    assert!(app.buffers[0].modified);
+
+    app.process_input(f7(false, false)).unwrap();
+    assert_eq!(
+        app.buffers[0].editor.textarea.cursor(),
+        CursorPosition { row: 0, col: 4 }
+    );
+    app.process_input(f7(true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Added ded for this session"));
+    app.process_input(f7(false, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No misspelled words"));
+}
+
+#[test]
+fn surround() {
+    let mut app = test_app(1);
+    let alt_shift = |c| Input {
+        shift: true,
+        ..key(Key::Char(c), false, true)
+    };
+    app.buffers[0].editor.textarea.lines = vec!["say hi".to_string()].into();
+
+    app.process_input(alt_shift('W')).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Select the text to surround first"));
+
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_cursor(CursorPosition { row: 0, col: 4 }, false);
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_cursor(CursorPosition { row: 0, col: 6 }, true);
+    // nothing to surround with changes nothing
+    app.process_input(alt_shift('W')).unwrap();
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["say hi"]);
+    assert!(!app.buffers[0].modified);
+
+    app.process_input(alt_shift('W')).unwrap();
+    for c in "<em>\n".chars() {
+        let pressed = if c == '\n' { Key::Enter } else { Key::Char(c) };
+        app.process_input(key(pressed, false, false)).unwrap();
+    }
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["say <em>hi</em>"]);
+    assert!(app.buffers[0].modified);
+
+    app.process_input(alt_shift('U')).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No quotes or brackets around the cursor"));
+    app.buffers[0].editor.textarea.lines = vec!["say (hi)".to_string()].into();
+    app.buffers[0]
+        .editor
+        .textarea
+        .set_cursor(CursorPosition { row: 0, col: 6 }, false);
+    app.process_input(alt_shift('H')).unwrap();
+    for c in "'\n".chars() {
+        let pressed = if c == '\n' { Key::Enter } else { Key::Char(c) };
+        app.process_input(key(pressed, false, false)).unwrap();
+    }
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["say 'hi'"]);
+    app.process_input(alt_shift('U')).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["say hi"]);
+}
+
+#[test]
+fn reflow() {
+    let mut app = test_app(1);
+    app.settings.wrap_width = 20;
+    app.buffers[0].editor.textarea.lines = ["// one two three four five", "// six", "", "next"]
+        .map(String::from)
+        .into();
+
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Char('P'), false, true)
+    })
+    .unwrap();
+    assert_eq!(
+        app.buffers[0].editor.textarea.selection_range(),
+        Some((CursorPosition { row: 0, col: 0 }, CursorPosition { row: 1, col: 6 }))
+    );
+
+    app.process_input(key(Key::Char('q'), false, true)).unwrap();
+    assert_eq!(
+        app.buffers[0].editor.textarea.lines,
+        ["// one two three", "// four five six", "", "next"]
+    );
+    assert_eq!(
+        app.buffers[0].editor.textarea.cursor(),
+        CursorPosition { row: 1, col: 16 }
+    );
+    assert!(app.buffers[0].modified);
+
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::Char('q'), false, true)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No paragraph at the cursor"));
+
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(
+        app.buffers[0].editor.textarea.lines[..2],
+        ["// one two three four five", "// six"]
+    );
+}
+
+#[test]
+fn restore_positions() {
+    let dir = env::temp_dir();
+    let state = dir.join("ded-test-restore-state");
+    let file = dir.join("ded-test-restore.txt");
+    _ = fs::remove_file(&state);
+    fs::write(&file, "a\nb\nc\nd\n").unwrap();
+    let canonical = canonical_path(&file);
+    positions::save(&state, &[(canonical.clone(), CursorPosition { row: 2, col: 1 })]).unwrap();
+
+    let mut app = test_app(1);
+    app.settings.positions_file = Some(state.clone());
+    app.open(file.clone()).unwrap();
+    assert_eq!(
+        app.buffers[2].editor.textarea.cursor(),
+        CursorPosition { row: 2, col: 1 }
+    );
+
+    app.buffers[2].editor.textarea.goto(3, 0);
+    app.close(2);
+    assert_eq!(
+        positions::lookup(&state, &canonical),
+        Some(CursorPosition { row: 3, col: 0 })
+    );
+
+    // clamped to the text
+    fs::write(&file, "a\n").unwrap();
+    app.open(file.clone()).unwrap();
+    assert_eq!(
+        app.buffers[2].editor.textarea.cursor(),
+        CursorPosition { row: 1, col: 0 }
+    );
+
+    // garbage is ignored
+    fs::write(&state, "\u{0}\u{1}").unwrap();
+    app.close(2);
+    app.open(file).unwrap();
+    assert_eq!(
+        app.buffers[2].editor.textarea.cursor(),
+        CursorPosition { row: 1, col: 0 }
+    );
+}
+
+#[test]
+fn reload_changed_files() {
+    let file = env::temp_dir().join("ded-test-reload.txt");
+    fs::write(&file, "a\nb\nc\nd\n").unwrap();
+    let mut app = test_app(1);
+    app.open(file.clone()).unwrap();
+    app.buffers[2].editor.textarea.goto(3, 1);
+
+    let later = Instant::now() + DISK_CHECK_INTERVAL;
+    fs::write(&file, "a\nB\n").unwrap();
+    app.on_tick(later);
+    assert_eq!(app.buffers[2].editor.textarea.lines, ["a", "B", ""]);
+    assert_eq!(
+        app.buffers[2].editor.textarea.cursor(),
+        CursorPosition { row: 2, col: 0 }
+    );
+    assert!(!app.buffers[2].modified);
+    assert_eq!(app.message.as_deref(), Some("ded-test-reload.txt reloaded from disk"));
+
+    // an accidental reload can be undone
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.buffers[2].editor.textarea.lines, ["a", "b", "c", "d", ""]);
+    assert!(app.buffers[2].modified);
+
+    // unsaved changes are kept
+    fs::write(&file, "x\n").unwrap();
+    app.on_tick(later + DISK_CHECK_INTERVAL);
+    assert_eq!(app.buffers[2].editor.textarea.lines, ["a", "b", "c", "d", ""]);
+    app.message = None;
+    let screen = test_render(&mut app, 80, 6);
+    assert!(
+        screen.iter().any(|line| line.contains("[modified, changed on disk]")),
+        "{screen:?}"
+    );
+
+    app.buffers[2].save(false).unwrap();
+    assert_eq!(app.buffers[2].disk_state, DiskState::Same);
+    app.on_tick(later + DISK_CHECK_INTERVAL * 2);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "a\nb\nc\nd\n");
+}
+
+#[test]
+fn file_deleted_or_replaced_on_disk() {
+    let dir = env::temp_dir().join("ded-test-disk-state");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let file = dir.join("file.txt");
+    fs::write(&file, "one\n").unwrap();
+    let mut app = test_app(1);
+    app.open(file.clone()).unwrap();
+    let mut now = Instant::now();
+    let mut tick = |app: &mut App| {
+        now += DISK_CHECK_INTERVAL;
+        app.on_tick(now);
+    };
+    let status = |app: &mut App| test_render(app, 80, 6)[5].clone();
+
+    // replaced by another file with the same time and size, like git checkout does
+    let other = dir.join("other.txt");
+    fs::write(&other, "two\n").unwrap();
+    let modified = fs::metadata(&file).unwrap().modified().unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&other)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    fs::rename(&other, &file).unwrap();
+    tick(&mut app);
+    assert_eq!(app.buffers[2].editor.textarea.lines, ["two", ""]);
+
+    fs::rename(&file, &other).unwrap();
+    tick(&mut app);
+    app.message = None;
+    assert!(status(&mut app).contains("file.txt [deleted on disk]"));
+    // renamed back
+    fs::rename(&other, &file).unwrap();
+    tick(&mut app);
+    assert_eq!(app.buffers[2].disk_state, DiskState::Same);
+
+    // saving creates it again, even without changes
+    fs::remove_dir_all(&dir).unwrap();
+    tick(&mut app);
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Wrote 1 line, 4 B"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "two\n");
+    assert!(!status(&mut app).contains("on disk"));
+
+    fs::remove_file(&file).unwrap();
+    fs::create_dir(&file).unwrap();
+    tick(&mut app);
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some(format!("Can't save: {} is a directory", display_path(&file)).as_str())
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn save_through_symlinks() {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    let dir = env::temp_dir().join("ded-test-symlinks");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let file = dir.join("file");
+    fs::write(&file, "old\n").unwrap();
+    fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+    symlink(&file, dir.join("middle")).unwrap();
+    symlink("middle", dir.join("link")).unwrap();
+
+    let mut app = test_app(1);
+    app.open(dir.join("link")).unwrap();
+    let buffer = &mut app.buffers[2];
+    buffer.editor.textarea.lines = vec!["new".into(), String::new()].into();
+    buffer.modified = true;
+    buffer.save(false).unwrap();
+    assert!(dir.join("link").is_symlink());
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new\n");
+    assert_eq!(fs::metadata(&file).unwrap().permissions().mode() & 0o777, 0o640);
+
+    buffer.editor.textarea.lines = vec!["newer".into(), String::new()].into();
+    buffer.modified = true;
+    buffer.save(true).unwrap();
+    assert!(!dir.join("link").is_symlink());
+    assert_eq!(fs::read_to_string(dir.join("link")).unwrap(), "newer\n");
+    assert_eq!(fs::read_to_string(&file).unwrap(), "new\n");
+    assert!(!dir.join("link.ded-save").exists());
+
+    symlink("missing", dir.join("dangling")).unwrap();
+    let mut buffer = Buffer::new(dir.join("dangling"), &app.settings).unwrap();
+    buffer.modified = true;
+    let err = buffer.save(false).unwrap_err().to_string();
+    assert!(err.ends_with("dangling is a link to a missing file"), "{err}");
+    assert!(!dir.join("missing").exists());
+}
+
+#[test]
+fn go_to() {
+    let mut app = test_app(3);
+    let go_to = |app: &mut App, text: &str| {
+        app.process_input(key(Key::Char('g'), true, false)).unwrap();
+        for c in text.chars() {
+            app.process_input(key(Key::Char(c), false, false)).unwrap();
+        }
+        app.process_input(key(Key::Enter, false, false)).unwrap();
+        app.buffers[0].editor.textarea.cursor()
+    };
+
+    assert_eq!(go_to(&mut app, "2:3"), CursorPosition { row: 1, col: 2 });
+    assert_eq!(go_to(&mut app, "3"), CursorPosition { row: 2, col: 0 });
+    assert_eq!(go_to(&mut app, "@9"), CursorPosition { row: 1, col: 2 });
+    app.process_input(key(Key::Char('w'), false, true)).unwrap();
+    assert!(app.message.as_deref().unwrap().ends_with("cursor at byte 9"));
+
+    // a wrong one keeps the prompt open, saying what is wrong
+    go_to(&mut app, "@x");
+    assert!(app.gotobox.is_open());
+    assert_eq!(app.gotobox.error(), Some("Not a byte offset: x"));
+    assert!(test_render(&mut app, 60, 6)[0].starts_with("┌ Go to line[:col] or @byte: Not a byte offset: x ─"));
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    assert_eq!(go_to(&mut app, "1:x"), CursorPosition { row: 1, col: 2 });
+    assert_eq!(app.gotobox.error(), Some("Not a line[:col] or @byte: 1:x"));
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+
+    // Up brings back what was gone to before
+    app.process_input(key(Key::Char('g'), true, false)).unwrap();
+    app.process_input(key(Key::Up, false, false)).unwrap();
+    app.process_input(key(Key::Up, false, false)).unwrap();
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert_eq!(
+        app.buffers[0].editor.textarea.cursor(),
+        CursorPosition { row: 2, col: 0 }
+    );
+}
+
+#[test]
+fn hex_mode() {
+    let file = env::temp_dir().join("ded-test-hex.bin");
+    fs::write(&file, b"\x00\x01\xfe\xffrest\n").unwrap();
+    let mut app = test_app(1);
+    app.open(file.clone()).unwrap();
+    assert!(matches!(app.buffers[2].content, Content::Bytes(_)));
+    let screen = test_render(&mut app, 80, 4);
+    assert_eq!(
+        screen[0].trim_end(),
+        "00000000  00 01 fe ff 72 65 73 74  0a                      |....rest.|"
+    );
+    assert!(screen[3].ends_with("HEX  0x0/0x9"));
+
+    for c in "ab".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    app.process_input(key(Key::Tab, false, false)).unwrap();
+    app.process_input(key(Key::Char('!'), false, false)).unwrap();
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(fs::read(&file).unwrap(), b"\xab!\xfe\xffrest\n");
+
+    // a change by another program is noticed, though not reloaded
+    fs::write(&file, b"\xff\n").unwrap();
+    assert!(!app.buffers[2].reload_if_changed().unwrap());
+    assert_eq!(app.buffers[2].disk_state, DiskState::Changed);
+
+    app.process_input(key(Key::Char('x'), false, true)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("Not valid UTF-8, so it can only be shown as hex")
+    );
+
+    // text edited as bytes
+    let mut app = test_app(2);
+    app.process_input(key(Key::Char('x'), false, true)).unwrap();
+    // text commands leave the hidden text alone
+    app.process_input(key(Key::Char('q'), false, true)).unwrap();
+    for c in "4c".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    app.process_input(key(Key::Char('x'), false, true)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["Line 0", "line 1", ""]);
+    assert!(app.buffers[0].modified);
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["line 0", "line 1"]);
+}
+
+#[test]
+fn pipe() {
+    assert!(App::new(CliOptions::parse(["--pipe", "ded-test-a"]).unwrap()).is_err());
+
+    let pipe_app = || {
+        let mut app = test_app(2);
+        app.pipe = true;
+        app.buffers[0].stdin = true;
+        app
+    };
+    let stop = |app: &mut App, input| app.process_input(input).unwrap() == Status::Stop;
+    let ctrl_c = || key(Key::Char('c'), true, false);
+    let esc = || key(Key::Esc, false, false);
+
+    let mut app = pipe_app();
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("Ctrl+Q writes the text from stdin to stdout")
+    );
+    assert!(stop(&mut app, key(Key::Char('q'), true, false)));
+    assert!(!app.aborted);
+    assert_eq!(app.pipe_output().unwrap(), b"xline 0\nline 1\n");
+
+    let mut app = pipe_app();
+    assert!(!stop(&mut app, ctrl_c()));
+    assert!(app.message.as_deref().unwrap().starts_with("Press Ctrl+C again"));
+    // another key in between starts over
+    assert!(!stop(&mut app, esc()));
+    assert!(!stop(&mut app, ctrl_c()));
+    assert!(stop(&mut app, ctrl_c()));
+    assert!(app.aborted);
+
+    let mut app = pipe_app();
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    // the first one closes the search box
+    assert!(!stop(&mut app, esc()));
+    assert!(!stop(&mut app, esc()));
+    assert!(stop(&mut app, esc()));
+}
+
+#[test]
+fn diff_mode() {
+    assert!(App::new(CliOptions::parse(["--diff", "ded-test-a"]).unwrap()).is_err());
+    let app = App::new(CliOptions::parse(["--diff", "ded-test-a", "ded-test-b"]).unwrap()).unwrap();
+    assert!(app.diff.is_some());
+
+    let mut app = test_app(0);
+    app.buffers[0].editor.textarea.lines = ["a", "b", "c", "d", "e"].map(String::from).into();
+    app.buffers[1].editor.textarea.lines = ["a", "B", "c", "d", "e", "f"].map(String::from).into();
+    app.diff = Some(DiffView::new(
+        (0, &app.buffers[0].editor.textarea),
+        (1, &app.buffers[1].editor.textarea),
+    ));
+    let screen = test_render(&mut app, 41, 8);
+    assert_eq!(screen[1], "2 b                │2 B                  ");
+    assert_eq!(screen[5], "╱╱╱╱╱╱╱╱╱╱╱╱╱╱╱╱╱╱╱│6 f                  ");
+    assert!(screen[7].contains("2 differences"));
+
+    let next = || key(Key::Char('n'), false, true);
+    app.process_input(next()).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.cursor().row, 1);
+    app.process_input(next()).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.cursor().row, 4);
+    app.process_input(next()).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No more differences"));
+
+    // edits are compared again once typing pauses
+    app.current = 1;
+    app.buffers[1].editor.textarea.goto(3, 0);
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    assert!(test_render(&mut app, 41, 8)[7].contains("2 differences"));
+    app.on_tick(Instant::now());
+    assert!(test_render(&mut app, 41, 8)[7].contains("3 differences"));
+
+    app.process_input(key(Key::F(9), false, false)).unwrap();
+    let screen = test_render(&mut app, 41, 8);
+    assert_eq!(
+        screen[4..7],
+        [
+            "4 -d                                     ",
+            "4 +xd                                    ",
+            "5  e                                     "
+        ]
+    );
+
+    app.close(0);
+    assert!(app.diff.is_none());
+}
+
+#[test]
+fn gutter_selection() {
+    let mut app = test_app(20);
+    test_render(&mut app, 40, 10);
+    let mouse = |kind, column, row, modifiers| MouseEvent { kind, column, row, modifiers };
+    let click = |row| mouse(MouseEventKind::Down(MouseButton::Left), 1, row, KeyModifiers::NONE);
+    let drag = |row| mouse(MouseEventKind::Drag(MouseButton::Left), 30, row, KeyModifiers::NONE);
+    let selection = |app: &App| {
+        let textarea = &app.buffers[0].editor.textarea;
+        let (selection, cursor) = (textarea.selection().unwrap(), textarea.cursor());
+        ((selection.row, selection.col), (cursor.row, cursor.col))
+    };
+
+    assert!(app.process_mouse(click(2)));
+    assert_eq!(selection(&app), ((2, 0), (3, 0)));
+    assert!(app.process_mouse(drag(4)));
+    assert_eq!(selection(&app), ((2, 0), (5, 0)));
+    // upwards the clicked line stays selected
+    assert!(app.process_mouse(drag(0)));
+    assert_eq!(selection(&app), ((3, 0), (0, 0)));
+    app.process_mouse(mouse(MouseEventKind::Up(MouseButton::Left), 1, 0, KeyModifiers::NONE));
+    assert!(!app.process_mouse(drag(5)));
+
+    let shift_click = mouse(MouseEventKind::Down(MouseButton::Left), 0, 6, KeyModifiers::SHIFT);
+    assert!(app.process_mouse(shift_click));
+    assert_eq!(selection(&app), ((2, 0), (7, 0)));
+
+    // clicks below the text go to the last line, which has no next one to end on
+    app.buffers[0].editor.textarea.lines.truncate(5);
+    assert!(app.process_mouse(click(8)));
+    assert_eq!(selection(&app), ((4, 0), (4, 6)));
+
+    assert!(!app.process_mouse(mouse(
+        MouseEventKind::Down(MouseButton::Left),
+        10,
+        2,
+        KeyModifiers::NONE
+    )));
+}
+
+#[test]
+fn buffer_picker_and_altgr() {
+    let mut app = test_app(3);
+
+    // AltGr+Q on a German layout
+    let altgr = crossterm::event::KeyEvent::new(
+        crossterm::event::KeyCode::Char('@'),
+        KeyModifiers::CONTROL | KeyModifiers::ALT,
+    );
+    app.process_input(Input::from(altgr)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.lines[0], "@line 0");
+    assert_eq!(app.current, 0);
+
+    app.process_input(key(Key::Char('0'), false, true)).unwrap();
+    let screen = test_render(&mut app, 40, 10);
+    assert!(screen[5].starts_with("┌ Buffers: "), "{screen:?}");
+    assert!(screen[7].starts_with("│ 2 ded-test-b"), "{screen:?}");
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert_eq!(app.current, 1);
+    assert!(app.buffer_picker.is_none());
+    assert_eq!(app.buffers[1].editor.textarea.lines[0], "line 0");
+
+    app.process_input(key(Key::Char('0'), false, true)).unwrap();
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    assert!(app.buffer_picker.is_none());
+    assert_eq!(app.current, 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn project_settings() {
+    let dir = env::temp_dir().join("ded-test-project");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join(PROJECT_FILE),
+        "indent = 'tabs'\ntrim_on_save = true\nformat_command = 'touch ran'\nrulers = [12]\n",
+    )
+    .unwrap();
+    let file = dir.join("src").join("file.txt");
+    fs::write(&file, "one  \n  two\n").unwrap();
+
+    let mut app = test_app(1);
+    app.settings.global.format_command = Some("tr a-z A-Z".into());
+    app.open(file.clone()).unwrap();
+    let screen = test_render(&mut app, 40, 6);
+    assert!(screen[5].ends_with(".ded.toml  tabs  (1,1)"), "{screen:?}");
+    assert_eq!(app.buffers[2].editor.textarea.rulers, [12]);
+
+    // trimmed and formatted, as steps that can be undone
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Wrote 2 lines, 11 B"));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "XONE\n  TWO\n");
+    // only the global file can set a command to run
+    assert!(!dir.join("src").join("ran").exists());
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(app.buffers[2].editor.textarea.lines[0], "xone  ");
+
+    // saved anyway when the command fails
+    app.buffers[2].settings.format_command = Some("echo broken >&2; false".into());
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("Wrote 11 B, but echo broken >&2; false failed: broken")
+    );
+    assert!(app.message_error);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "xone\n  two\n");
+
+    // and stopped when it takes too long
+    app.buffers[2].settings.format_command = Some("sleep 5".into());
+    app.buffers[2].modified = true;
+    assert_eq!(
+        app.buffers[2].tidy(Duration::from_millis(100)),
+        Err("sleep 5 took over 0.1s and was stopped".into())
+    );
+
+    // a file that can't be read is reported, and the defaults are used
+    fs::write(dir.join(PROJECT_FILE), "rulers = 80\n").unwrap();
+    app.open(dir.join("new.txt")).unwrap();
+    assert!(
+        app.message
+            .as_deref()
+            .unwrap()
+            .ends_with(".ded.toml: line 1: rulers is a list of columns, like [80, 100]")
+    );
+    assert!(app.buffers[3].settings.project.is_none());
+    assert!(!test_render(&mut app, 40, 6)[5].contains(PROJECT_FILE));
+}
+
+#[test]
+fn invalid_keys() {
+    let mut app = test_app(1);
+    app.settings.invalid_key = settings::InvalidKey::Both;
+    let status_reversed = |app: &mut App| {
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(20, 3)).unwrap();
+        app.render(&mut terminal).unwrap();
+        terminal.backend().buffer()[(0, 2)]
+            .modifier
+            .contains(Modifier::REVERSED)
+    };
+    assert!(status_reversed(&mut app));
+
+    // Backspace at the start inverts the status line for a moment and rings the bell
+    app.process_input(key(Key::Backspace, false, false)).unwrap();
+    assert!(app.invalid_flash.is_some() && app.bell);
+    assert!(!status_reversed(&mut app));
+    app.on_tick(Instant::now());
+    assert!(!status_reversed(&mut app));
+    app.on_tick(Instant::now() + INVALID_FLASH);
+    assert!(app.invalid_flash.is_none());
+    assert!(status_reversed(&mut app));
+
+    // a search without matches
+    app.bell = false;
+    app.settings.invalid_key = settings::InvalidKey::Bell;
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    assert!(!app.bell);
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    assert!(app.bell && app.invalid_flash.is_none());
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+
+    // keys that just have nothing to change aren't signaled
+    app.bell = false;
+    app.process_input(key(Key::Left, false, false)).unwrap();
+    assert!(!app.bell);
+    app.settings.invalid_key = settings::InvalidKey::Silent;
+    app.process_input(key(Key::Backspace, false, false)).unwrap();
+    assert!(!app.bell && app.invalid_flash.is_none());
+}
+
+#[test]
+fn cycle_themes() {
+    let mut app = test_app(1);
+    app.settings.global = FileSettings {
+        theme_colors: vec![("status_style".into(), ratatui::style::Color::Green)],
+        ..FileSettings::default()
+    };
+    app.settings.theme = app.settings.global.pick_theme();
+    test_render(&mut app, 20, 3);
+    assert_eq!(app.buffers[0].editor.textarea.theme.name, "dark");
+
+    for name in ["light", "high-contrast", "dark"] {
+        app.process_input(key(Key::F(12), false, false)).unwrap();
+        assert_eq!(app.message.as_deref(), Some(format!("Theme: {name}").as_str()));
+        let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(20, 3)).unwrap();
+        app.render(&mut terminal).unwrap();
+        assert_eq!(app.buffers[0].editor.textarea.theme.name, name);
+        // the changed color stays
+        assert_eq!(terminal.backend().buffer()[(0, 2)].bg, ratatui::style::Color::Green);
+    }
+}
+
+#[test]
+fn repeat_edit() {
+    let mut app = test_app(3);
+    let lines = |app: &App| app.buffers[0].editor.textarea.lines.clone();
+    app.process_input(key(Key::F(4), false, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Nothing to repeat yet"));
+
+    // a typed run is one insert, made again wherever the cursor is
+    for char in "ab".chars() {
+        app.process_input(key(Key::Char(char), false, false)).unwrap();
+    }
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::F(4), false, false)).unwrap();
+    assert_eq!(lines(&app), ["abline 0", "liabne 1", "line 2"]);
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    assert_eq!(lines(&app), ["abline 0", "line 1", "line 2"]);
+
+    // as is any other key, with its count, after moving doesn't matter
+    app.process_input(key(Key::Char('d'), true, false)).unwrap();
+    app.process_input(key(Key::Up, false, false)).unwrap();
+    app.process_input(key(Key::Up, false, false)).unwrap();
+    app.process_input(key(Key::F(4), false, false)).unwrap();
+    assert_eq!(lines(&app), ["abline 0", "abline 0", "line 1", "line 1", "line 2"]);
+
+    // but not after a change that wasn't made with a key
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    app.process_input(key(Key::F(4), false, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("The last change wasn't made with a key, so it can't be repeated")
+    );
+    assert_eq!(lines(&app), ["abline 0", "line 1", "line 1", "line 2"]);
+
+    // nor after a command, like searching, until the next edit
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    app.process_input(key(Key::Char('f'), true, false)).unwrap();
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    app.process_input(key(Key::F(4), false, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("The last command can't be repeated, only edits made with a key")
+    );
+    assert_eq!(lines(&app), ["abxline 0", "line 1", "line 1", "line 2"]);
+    app.process_input(key(Key::Char('y'), false, false)).unwrap();
+    app.process_input(key(Key::F(4), false, false)).unwrap();
+    assert_eq!(lines(&app), ["abxyyline 0", "line 1", "line 1", "line 2"]);
+}
+
+#[test]
+fn browse_directory() {
+    let dir = env::temp_dir().join("ded-test-browse");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join("a.txt"), "a\n").unwrap();
+    fs::write(dir.join(".hidden"), "").unwrap();
+
+    let mut app = App::new(CliOptions::parse([&dir]).unwrap()).unwrap();
+    app.settings.positions_file = None;
+    let lines = |app: &App| app.buffers[app.current].editor.textarea.lines.to_vec();
+    let row = |app: &App| app.buffers[app.current].editor.textarea.cursor().row;
+    assert_eq!(lines(&app), ["../", "sub/", "a.txt"]);
+    assert_eq!(row(&app), 1);
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    assert!(
+        app.message
+            .as_deref()
+            .unwrap()
+            .starts_with("A directory listing can't be edited")
+    );
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Can't save a directory listing"));
+
+    // into a directory and back up, to where it was listed
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert_eq!(lines(&app), ["../"]);
+    assert_eq!(app.buffers[0].canonical_path, canonical_path(&dir.join("sub")));
+    app.process_input(key(Key::Backspace, false, false)).unwrap();
+    assert_eq!(
+        (lines(&app), row(&app)),
+        (vec!["../".into(), "sub/".into(), "a.txt".into()], 1)
+    );
+
+    app.process_input(key(Key::Char('.'), false, true)).unwrap();
+    assert_eq!(lines(&app), ["../", "sub/", ".hidden", "a.txt"]);
+    assert_eq!(row(&app), 1);
+
+    // files open in buffers of their own
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert_eq!(app.buffers.len(), 2);
+    assert_eq!(app.buffers[1].path, canonical_path(&dir.join("a.txt")));
+    assert_eq!(lines(&app), ["a", ""]);
+
+    app.current = 0;
+    app.process_input(key(Key::Char('n'), true, false)).unwrap();
+    let prefix = format!("{}/", canonical_path(&dir).display());
+    assert_eq!(app.newfilebox.text(), prefix);
+
+    // Tab lists what is in the directory under the prompt, and goes through it when pressed again
+    app.process_input(key(Key::Tab, false, false)).unwrap();
+    let screen = test_render(&mut app, 40, 10);
+    assert!(screen[4].starts_with("│a.txt│"));
+    assert!(screen[5].starts_with("│sub/ │"));
+    app.process_input(key(Key::Tab, false, false)).unwrap();
+    app.process_input(key(Key::Tab, false, false)).unwrap();
+    assert_eq!(app.newfilebox.text(), format!("{prefix}sub/"));
+    app.process_input(key(Key::Esc, false, false)).unwrap();
+    assert!(app.newfilebox.completions().is_none());
+
+    app.process_input(key(Key::Char('n'), true, false)).unwrap();
+    for c in "new.txt".chars() {
+        app.process_input(key(Key::Char(c), false, false)).unwrap();
+    }
+    app.process_input(key(Key::Enter, false, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("New file, saving creates it"));
+    assert_eq!(app.buffers[app.current].path, canonical_path(&dir.join("new.txt")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn trash_file() {
+    let dir = env::temp_dir().join("ded-test-trash-file");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let file = dir.join("scratch.txt");
+    fs::write(&file, "one\n").unwrap();
+
+    let mut app = test_app(1);
+    app.settings.trash_dir = Some(dir.join("Trash"));
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Char('K'), false, true)
+    })
+    .unwrap();
+    assert_eq!(app.message.as_deref(), Some("There is no file to move to the trash"));
+
+    let trash = |app: &mut App, name: &str| {
+        app.process_input(Input {
+            shift: true,
+            ..key(Key::Char('K'), false, true)
+        })
+        .unwrap();
+        assert!(app.trashbox.is_open());
+        for c in name.chars() {
+            app.process_input(key(Key::Char(c), false, false)).unwrap();
+        }
+        app.process_input(key(Key::Enter, false, false)).unwrap();
+    };
+    app.open(file.clone()).unwrap();
+    trash(&mut app, "scratch");
+    assert_eq!(app.message.as_deref(), Some("That isn't the file's name, it was kept"));
+    assert!(file.exists());
+
+    // not with unsaved changes, which would be lost
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Char('K'), false, true)
+    })
+    .unwrap();
+    assert!(!app.trashbox.is_open());
+    assert!(app.message.as_deref().unwrap().starts_with("Unsaved changes"));
+    app.process_input(key(Key::Char('z'), true, false)).unwrap();
+    app.buffers[app.current].modified = false;
+
+    trash(&mut app, "scratch.txt");
+    assert!(!file.exists());
+    assert_eq!(
+        fs::read_to_string(dir.join("Trash/files/scratch.txt")).unwrap(),
+        "one\n"
+    );
+    assert!(app.message.as_deref().unwrap().ends_with("/Trash/files/scratch.txt"));
+    assert_eq!(app.buffers.len(), 2);
+
+    // a symlink goes to the trash, not the file it points to
+    #[cfg(unix)]
+    {
+        let target = dir.join("target.txt");
+        fs::write(&target, "target\n").unwrap();
+        let link = dir.join("link.txt");
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        app.open(link.clone()).unwrap();
+        trash(&mut app, "link.txt");
+        assert!(fs::symlink_metadata(&link).is_err());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "target\n");
+        assert!(
+            fs::symlink_metadata(dir.join("Trash/files/link.txt"))
+                .unwrap()
+                .is_symlink()
+        );
+    }
+
+    // the last buffer is left open without its file
+    fs::write(&file, "two\n").unwrap();
+    let mut app = App::new(CliOptions::parse([&file]).unwrap()).unwrap();
+    app.settings.trash_dir = Some(dir.join("Trash"));
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["two", ""]);
+    trash(&mut app, "scratch.txt");
+    assert_eq!(app.buffers.len(), 1);
+    assert_eq!(app.buffers[0].path, file);
+    assert_eq!(app.buffers[0].editor.textarea.lines, [""]);
+    assert!(dir.join("Trash/files/scratch.txt.2").exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn save_messages() {
+    let dir = env::temp_dir().join("ded-test-save-messages");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let file = dir.join("file.txt");
+    fs::write(&file, "one\n").unwrap();
+
+    let mut app = test_app(1);
+    app.open(file.clone()).unwrap();
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("No changes to save"));
+
+    app.buffers[2].editor.textarea.lines = vec!["x".repeat(2047), "two".into(), String::new()].into();
+    app.buffers[2].modified = true;
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Wrote 2 lines, 2.0 KB"));
+    assert!(!app.message_error);
+
+    // the directory is gone and a file is in its way, which fails even where permissions don't
+    fs::remove_dir_all(&dir).unwrap();
+    fs::write(&dir, "").unwrap();
+    app.buffers[2].editor.textarea.lines = vec!["three".into()].into();
+    app.buffers[2].modified = true;
+    let status = app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert!(matches!(status, Status::Continue));
+    assert!(app.message.as_deref().unwrap().starts_with("Can't save: "));
+    assert!(app.message_error);
+    assert!(app.buffers[2].modified);
+
+    let mut terminal = Terminal::new(ratatui::backend::TestBackend::new(40, 6)).unwrap();
+    app.render(&mut terminal).unwrap();
+    assert_eq!(
+        terminal.backend().buffer()[(12, 5)].fg,
+        app.settings.theme.message_error
+    );
+
+    // the session goes on with every buffer
+    assert_eq!(app.buffers.len(), 3);
+    fs::remove_file(&dir).unwrap();
+    assert_eq!(format_size(1536), "1.5 KB");
+    assert_eq!(format_size(3 * 1024 * 1024), "3.0 MB");
+}
+
+#[test]
+fn batch_mode() {
+    let dir = env::temp_dir().join("ded-test-batch");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let (file, other, script) = (dir.join("a.txt"), dir.join("b.txt"), dir.join("script"));
+    fs::write(&file, "foo\n\tbar foo").unwrap();
+    fs::write(&other, "nothing\n").unwrap();
+    fs::write(&script, "replace:/foo/baz/g\ngoto:2; insert:new").unwrap();
+
+    let options = CliOptions::parse(["--batch-file".into(), script, file.clone(), other.clone()]).unwrap();
+    run_batch(&batch_script(options.batch.as_ref().unwrap()).unwrap(), options.paths()).unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "baz\nnew\n\tbar baz");
+    assert_eq!(fs::read_to_string(&other).unwrap(), "nothing\nnew\n");
+
+    // an error in one file leaves all of them unwritten
+    let long = dir.join("long.txt");
+    fs::write(&long, "1\n2\n3\n").unwrap();
+    let err = run_batch("goto:3; delete:3", vec![long.clone(), other.clone()])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.ends_with("b.txt: script error at 1:9: line 3 is past the end, there are 2"),
+        "{err}"
+    );
+    assert_eq!(fs::read_to_string(&long).unwrap(), "1\n2\n3\n");
+
+    // line endings are kept
+    let crlf = dir.join("crlf.txt");
+    fs::write(&crlf, "foo\r\nbar\r\n").unwrap();
+    run_batch("replace:/foo/baz/; goto:2; insert:new", vec![crlf.clone()]).unwrap();
+    assert_eq!(fs::read_to_string(&crlf).unwrap(), "baz\r\nnew\r\nbar\r\n");
+    let mixed = dir.join("mixed.txt");
+    fs::write(&mixed, "foo\r\nbar\n").unwrap();
+    run_batch("replace:/x/y/", vec![mixed.clone()]).unwrap();
+    let err = run_batch("replace:/foo/baz/", vec![mixed.clone()])
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.ends_with("mixed.txt has mixed line endings, which --batch can't keep"),
+        "{err}"
+    );
+    assert_eq!(fs::read_to_string(&mixed).unwrap(), "foo\r\nbar\n");
+    let err = run_batch("goto:3; frob", vec![other.clone()]).unwrap_err().to_string();
+    assert_eq!(err, "Script error at 1:9: unknown command frob");
+    let missing = Batch::File(dir.join("missing"));
+    assert!(
+        batch_script(&missing)
+            .unwrap_err()
+            .to_string()
+            .starts_with("Can't read ")
+    );
+}
+
+#[test]
+fn readonly() {
+    let path = env::temp_dir().join("ded-test-readonly");
+    fs::write(&path, "text\n").unwrap();
+    let config = env::temp_dir().join("ded-test-missing-config");
+    let options = CliOptions::parse(["--readonly".into(), "--config".into(), config.clone(), path.clone()]).unwrap();
+    let mut app = App::new(options).unwrap();
+    app.settings.positions_file = None;
+    assert_eq!(app.settings.config_file, Some(config));
+    assert!(
+        app.message
+            .as_deref()
+            .unwrap()
+            .ends_with("doesn't exist, using the default settings")
+    );
+
+    app.process_input(key(Key::Char('x'), false, false)).unwrap();
+    assert_eq!(
+        app.message.as_deref(),
+        Some("Opened with --readonly, nothing can be changed")
+    );
+    app.process_input(key(Key::Down, false, false)).unwrap();
+    assert_eq!(app.buffers[0].editor.textarea.cursor().row, 1);
+    app.process_input(key(Key::Char('s'), true, false)).unwrap();
+    assert_eq!(app.message.as_deref(), Some("Opened with --readonly, saving is off"));
+    app.replace_in_all_buffers("text", "other");
+    assert_eq!(
+        app.message.as_deref(),
+        Some("Replaced 0 occurrences in 0 buffers, 1 read-only buffer skipped")
+    );
+    assert_eq!(app.buffers[0].editor.textarea.lines, ["text", ""]);
+    app.process_input(Input {
+        shift: true,
+        ..key(Key::Char('K'), false, true)
+    })
+    .unwrap();
+    assert!(!app.trashbox.is_open());
+    assert!(!app.buffers[0].modified);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "text\n");
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,71 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use regex::Regex;
+
+/// Lines counted between checks whether the count is still wanted.
+const CHECK_LINES: usize = 1024;
+
+/// Counts the matches of a pattern in the text on a background thread, so a slow pattern on a big file
+/// doesn't hold up typing. Dropping it stops the count.
+pub struct MatchCounter {
+    receiver: Receiver<usize>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl MatchCounter {
+    pub fn new(pattern: Regex, lines: Arc<Vec<String>>) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        thread::spawn({
+            let cancel = Arc::clone(&cancel);
+            move || {
+                let mut count = 0;
+                for chunk in lines.chunks(CHECK_LINES) {
+                    if cancel.load(Ordering::Relaxed) {
+                        return;
+                    }
+                    count += chunk
+                        .iter()
+                        .map(|line| pattern.find_iter(line).filter(|m| !m.is_empty()).count())
+                        .sum::<usize>();
+                }
+                _ = sender.send(count);
+            }
+        });
+
+        Self { receiver, cancel }
+    }
+
+    /// The number of matches, once they are counted.
+    pub fn poll(&self) -> Option<usize> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for MatchCounter {
+    fn drop(&mut self) {
+        // not waited for, a line can take a while with a slow pattern
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test() {
+    let lines = Arc::new(vec!["a b a".to_string(); 3000]);
+    let counter = MatchCounter::new(Regex::new("a").unwrap(), Arc::clone(&lines));
+    let count = (0..1000).find_map(|_| {
+        thread::sleep(std::time::Duration::from_millis(5));
+        counter.poll()
+    });
+    assert_eq!(count, Some(6000));
+
+    // a dropped count stops before it's done
+    let counter = MatchCounter::new(Regex::new(r"\w{1,60}\s\w{1,60}5").unwrap(), lines);
+    let cancel = Arc::clone(&counter.cancel);
+    drop(counter);
+    assert!(cancel.load(Ordering::Relaxed));
+}
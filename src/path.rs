@@ -0,0 +1,262 @@
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::textarea::{ByteIndex, CursorPosition};
+
+/// Whether `path` is read by make, which needs recipe lines indented with tabs.
+pub fn is_makefile(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    matches!(name, "Makefile" | "makefile" | "GNUmakefile") || name.ends_with(".mk")
+}
+
+/// Resolves symlinks for existing files, otherwise makes the path absolute and removes `.` and `..` components.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    if let Ok(path) = fs::canonicalize(path) {
+        return path;
+    }
+
+    let path = env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.into());
+    let mut canonical_path = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                canonical_path.pop();
+            }
+            component => canonical_path.push(component),
+        }
+    }
+    canonical_path
+}
+
+/// Shortens absolute paths to be relative to the working directory, or failing that the home directory.
+pub fn display_path(path: &Path) -> String {
+    if path.is_absolute() {
+        if let Some(relative) = env::current_dir().ok().and_then(|dir| path.strip_prefix(dir).ok()) {
+            return relative.display().to_string();
+        }
+
+        if let Some(relative) = env::home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_owned)) {
+            return Path::new("~").join(relative).display().to_string();
+        }
+    }
+
+    path.display().to_string()
+}
+
+/// Splits a `:line` or `:line:col` suffix off `arg`, as printed by compilers and grep, if `arg` itself does not
+/// exist but the path before the suffix does. Returns the zero-based position.
+pub fn split_location(arg: &str) -> (&str, Option<CursorPosition>) {
+    let exists = |path: &str| !is_drive(path) && Path::new(path).exists();
+    let number = |s: &str| s.parse::<usize>().ok().map(|n| n.saturating_sub(1));
+
+    if Path::new(arg).exists() {
+        return (arg, None);
+    }
+
+    let Some((rest, last)) = arg.rsplit_once(':') else {
+        return (arg, None);
+    };
+    let Some(last) = number(last) else {
+        return (arg, None);
+    };
+    if exists(rest) {
+        return (rest, Some(CursorPosition { row: last, col: 0 }));
+    }
+
+    match rest.rsplit_once(':') {
+        Some((path, row)) if exists(path) => match number(row) {
+            Some(row) => (path, Some(CursorPosition { row, col: last })),
+            None => (arg, None),
+        },
+        _ => (arg, None),
+    }
+}
+
+/// Whether `path` is a bare Windows drive letter, which would otherwise make `C:10` look like line 10 of `C`.
+fn is_drive(path: &str) -> bool {
+    cfg!(windows) && path.len() == 1 && path.as_bytes()[0].is_ascii_alphabetic()
+}
+
+/// The path or URL around char column `col` of `line`, without the punctuation that would end a sentence after it.
+/// Unlike words these run through `/`, `.`, `:` and `-`, and only stop at whitespace, quotes and brackets.
+pub fn token_at(line: &str, col: usize) -> Option<&str> {
+    let is_delimiter = |c: char| c.is_whitespace() || "\"'`<>()[]{},;|".contains(c);
+    let cursor = line.byte_index(col);
+
+    let start = line[..cursor]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| is_delimiter(c))
+        .map_or(0, |(idx, c)| idx + c.len_utf8());
+    let end = line[cursor..].find(is_delimiter).map_or(line.len(), |idx| cursor + idx);
+    let token = line[start..end].trim_end_matches(['.', ':', '!', '?']);
+    (!token.is_empty()).then_some(token)
+}
+
+/// Whether `token` is a web address rather than a path.
+pub fn is_url(token: &str) -> bool {
+    token.starts_with("http://") || token.starts_with("https://")
+}
+
+/// Cuts the middle out of `path` so it fits into `width` columns, keeping the file name visible.
+pub fn truncate_path(path: &str, width: usize) -> Cow<'_, str> {
+    let char_width = |c: char| c.width().unwrap_or(0);
+    if path.chars().map(char_width).sum::<usize>() <= width {
+        return Cow::Borrowed(path);
+    }
+
+    let name = &path[path.rfind(['/', '\\']).unwrap_or(0)..];
+    let name_width = name.chars().map(char_width).sum::<usize>();
+
+    // one column goes to the ellipsis
+    let mut head_width = width.saturating_sub(name_width + 1);
+    let mut head_end = 0;
+    for (idx, c) in path.char_indices() {
+        let Some(rest) = head_width.checked_sub(char_width(c)) else {
+            break;
+        };
+        head_width = rest;
+        head_end = idx + c.len_utf8();
+    }
+
+    Cow::Owned(format!("{}…{name}", &path[..head_end]))
+}
+
+/// `dir` with a slash after it, to type a file name in it after. Relative to the working directory if it is in it.
+pub fn dir_prefix(dir: &Path) -> String {
+    let relative = env::current_dir()
+        .ok()
+        .and_then(|cwd| dir.strip_prefix(cwd).ok().map(Path::to_owned));
+    match relative {
+        Some(relative) if relative.as_os_str().is_empty() => String::new(),
+        Some(relative) => format!("{}/", relative.display()),
+        None => format!("{}/", dir.display().to_string().trim_end_matches('/')),
+    }
+}
+
+/// `path` with a `~` at its start replaced by the home directory.
+pub fn expand_home(path: &str) -> Cow<'_, str> {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => return Cow::Borrowed(path),
+    };
+    match env::home_dir() {
+        Some(home) => Cow::Owned(format!("{}{rest}", home.display())),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// The paths that `text` can be completed to, with a slash after directories and `~` expanded. Files starting with a
+/// dot are only offered once a dot is typed.
+pub fn complete_path(text: &str) -> Vec<String> {
+    let text = expand_home(text);
+    let (dir, name) = match text.rfind('/') {
+        Some(idx) => (&text[..=idx], &text[idx + 1..]),
+        None => ("", text.as_ref()),
+    };
+    let Ok(entries) = fs::read_dir(if dir.is_empty() { "." } else { dir }) else {
+        return Vec::new();
+    };
+    let mut completions = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name().into_string().ok()?;
+            let hidden = file_name.starts_with('.') && !name.starts_with('.');
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            (file_name.starts_with(name) && !hidden).then(|| format!("{dir}{file_name}{slash}"))
+        })
+        .collect::<Vec<_>>();
+    completions.sort();
+    completions
+}
+
+#[test]
+fn complete() {
+    let dir = env::temp_dir().join("ded-test-complete");
+    _ = fs::remove_dir_all(&dir);
+    for sub in ["alps", ".config", "beta"] {
+        fs::create_dir_all(dir.join(sub)).unwrap();
+    }
+    for file in ["alpha.txt", "Alpine", ".hidden", "beta/b.rs"] {
+        fs::write(dir.join(file), "").unwrap();
+    }
+    let complete = |text: &str| {
+        let prefix = format!("{}/", dir.display());
+        complete_path(&format!("{prefix}{text}"))
+            .into_iter()
+            .map(|completion| completion.strip_prefix(&prefix).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(complete("al"), ["alpha.txt", "alps/"]);
+    assert_eq!(complete("A"), ["Alpine"]);
+    assert_eq!(complete(""), ["Alpine", "alpha.txt", "alps/", "beta/"]);
+    // hidden files once a dot is typed
+    assert_eq!(complete("."), [".config/", ".hidden"]);
+    assert_eq!(complete("beta/"), ["beta/b.rs"]);
+    assert!(complete("gamma/").is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+
+    let home = env::home_dir().unwrap();
+    assert_eq!(expand_home("~/notes.txt"), format!("{}/notes.txt", home.display()));
+    assert_eq!(expand_home("~"), home.display().to_string());
+    assert_eq!(expand_home("~user/x"), "~user/x");
+    assert_eq!(expand_home("a/~"), "a/~");
+
+    assert_eq!(complete_path("src/mai"), ["src/main.rs"]);
+    assert_eq!(complete_path("src/textarea/dis"), ["src/textarea/display_width.rs"]);
+    assert_eq!(complete_path("sr"), ["src/"]);
+    assert_eq!(complete_path("src/c"), ["src/charbox.rs", "src/cli.rs"]);
+
+    let cwd = env::current_dir().unwrap();
+    assert_eq!(dir_prefix(&cwd), "");
+    assert_eq!(dir_prefix(&cwd.join("src")), "src/");
+    assert_eq!(dir_prefix(Path::new("/")), "/");
+}
+
+#[test]
+fn truncate() {
+    assert_eq!(truncate_path("src/main.rs", 11), "src/main.rs");
+    assert_eq!(truncate_path("src/textarea/textarea.rs", 20), "src/tex…/textarea.rs");
+    assert_eq!(truncate_path("src/textarea/textarea.rs", 17), "src/…/textarea.rs");
+    assert_eq!(truncate_path("src/textarea/textarea.rs", 12), "…/textarea.rs");
+    assert_eq!(truncate_path("src/textarea/textarea.rs", 4), "…/textarea.rs");
+    assert_eq!(truncate_path("日本語/テキスト/a.rs", 13), "日本語/…/a.rs");
+    // wide characters are never split
+    assert_eq!(truncate_path("日本語/テキスト/a.rs", 11), "日本…/a.rs");
+}
+
+#[test]
+fn location() {
+    let at = |row, col| Some(CursorPosition { row, col });
+    assert_eq!(split_location("src/main.rs"), ("src/main.rs", None));
+    assert_eq!(split_location("src/main.rs:42"), ("src/main.rs", at(41, 0)));
+    assert_eq!(split_location("src/main.rs:42:7"), ("src/main.rs", at(41, 6)));
+    assert_eq!(split_location("src/main.rs:0:0"), ("src/main.rs", at(0, 0)));
+    assert_eq!(split_location("src/main.rs:x:7"), ("src/main.rs:x:7", None));
+    assert_eq!(split_location("src/main.rs:42:"), ("src/main.rs:42:", None));
+    assert_eq!(split_location("ded-test-missing:42"), ("ded-test-missing:42", None));
+    assert_eq!(split_location(r"C:\foo.rs"), (r"C:\foo.rs", None));
+    assert_eq!(split_location(r"C:\foo.rs:10"), (r"C:\foo.rs:10", None));
+}
+
+#[test]
+fn token() {
+    let line = r#"see src/editor.rs:120, or "https://example.com/a?b=c#d". (foo.rs)"#;
+    assert_eq!(token_at(line, 0), Some("see"));
+    assert_eq!(token_at(line, 8), Some("src/editor.rs:120"));
+    assert_eq!(token_at(line, 30), Some("https://example.com/a?b=c#d"));
+    // right after the token still counts, the closing punctuation doesn't
+    assert_eq!(token_at(line, 21), Some("src/editor.rs:120"));
+    assert_eq!(token_at(line, 62), Some("foo.rs"));
+    assert_eq!(token_at(" ", 0), None);
+    assert!(is_url("https://example.com"));
+    assert!(!is_url("src/https.rs"));
+}
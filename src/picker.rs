@@ -0,0 +1,97 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Widget};
+
+use crate::input::{Input, Key};
+
+/// A list to choose one item from with Up, Down and Enter, drawn along the bottom of the editor.
+pub struct Picker {
+    title: String,
+    items: Vec<String>,
+    selected: usize,
+}
+
+impl Picker {
+    pub fn new(title: impl Into<String>, items: Vec<String>) -> Self {
+        Self {
+            title: title.into(),
+            items,
+            selected: 0,
+        }
+    }
+
+    /// Handles `input`, returning `Some` once done: with the index of the chosen item, or `None` if cancelled.
+    pub fn input(&mut self, input: Input) -> Option<Option<usize>> {
+        match input.key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => self.selected = (self.selected + 1).min(self.items.len().saturating_sub(1)),
+            Key::Enter => return Some(Some(self.selected)),
+            Key::Esc => return Some(None),
+            Key::Char(c @ '1'..='9') => {
+                let idx = c as usize - '1' as usize;
+                if idx < self.items.len() {
+                    return Some(Some(idx));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl Widget for &Picker {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let height = u16::try_from(self.items.len() + 2).unwrap_or(u16::MAX).min(area.height);
+        let area = Rect {
+            y: area.bottom() - height,
+            height,
+            ..area
+        };
+
+        // keep the selected item in view
+        let visible = usize::from(height.saturating_sub(2));
+        let scroll = (self.selected + 1).saturating_sub(visible);
+        let lines = self
+            .items
+            .iter()
+            .enumerate()
+            .skip(scroll)
+            .take(visible)
+            .map(|(idx, item)| {
+                let line = Line::from(format!("{:>2} {item}", idx + 1));
+                if idx == self.selected {
+                    line.style(Style::default().add_modifier(Modifier::REVERSED))
+                } else {
+                    line
+                }
+            });
+
+        Clear.render(area, buf);
+        Paragraph::new(lines.collect::<Vec<_>>())
+            .block(Block::default().borders(Borders::ALL).title(self.title.as_str()))
+            .render(area, buf);
+    }
+}
+
+#[test]
+fn test() {
+    let input = |key| Input { key, ..Default::default() };
+    let mut picker = Picker::new(" Pick: ", ["a", "b", "c"].map(String::from).to_vec());
+
+    assert_eq!(picker.input(input(Key::Up)), None);
+    picker.input(input(Key::Down));
+    picker.input(input(Key::Down));
+    picker.input(input(Key::Down));
+    assert_eq!(picker.input(input(Key::Enter)), Some(Some(2)));
+    assert_eq!(picker.input(input(Key::Char('2'))), Some(Some(1)));
+    assert_eq!(picker.input(input(Key::Char('4'))), None);
+    assert_eq!(picker.input(input(Key::Esc)), Some(None));
+
+    let mut buf = Buffer::empty(Rect::new(0, 0, 12, 6));
+    (&picker).render(buf.area, &mut buf);
+    let row = |y| (0..12).map(|x| buf[(x, y)].symbol()).collect::<String>();
+    assert_eq!(row(1), "┌ Pick: ───┐");
+    assert_eq!(row(4), "│ 3 c      │");
+}
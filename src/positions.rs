@@ -0,0 +1,85 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::textarea::CursorPosition;
+
+/// Files whose cursor position is remembered, the least recently closed ones are forgotten first.
+const MAX_RECORDS: usize = 500;
+
+/// Reads the `row<TAB>col<TAB>path` lines of the state file at `path`, oldest first. Lines that don't parse are
+/// skipped, and a missing or unreadable file has no records.
+fn load(path: &Path) -> Vec<(PathBuf, CursorPosition)> {
+    let Ok(text) = fs::read_to_string(path) else {
+        return Vec::new();
+    };
+
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let row = parts.next()?.parse().ok()?;
+            let col = parts.next()?.parse().ok()?;
+            let file = parts.next().filter(|file| !file.is_empty())?;
+            Some((PathBuf::from(file), CursorPosition { row, col }))
+        })
+        .collect()
+}
+
+/// Where the cursor was when `file`, a canonical path, was last closed.
+pub fn lookup(path: &Path, file: &Path) -> Option<CursorPosition> {
+    load(path)
+        .into_iter()
+        .rev()
+        .find_map(|(record, position)| (record == file).then_some(position))
+}
+
+/// Adds `records` to the state file at `path`, replacing older ones for the same files.
+pub fn save(path: &Path, records: &[(PathBuf, CursorPosition)]) -> io::Result<()> {
+    let mut all = load(path);
+    all.retain(|(file, _)| !records.iter().any(|(record, _)| record == file));
+    all.extend_from_slice(records);
+    let skip = all.len().saturating_sub(MAX_RECORDS);
+
+    let mut text = String::new();
+    for (file, CursorPosition { row, col }) in &all[skip..] {
+        // a line break would split the record
+        let Some(file) = file.to_str().filter(|file| !file.contains('\n')) else {
+            continue;
+        };
+        text.push_str(&format!("{row}\t{col}\t{file}\n"));
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // written next to it and renamed, so a crash can't leave half a file
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(tmp, path)
+}
+
+#[test]
+fn test() {
+    let path = std::env::temp_dir().join("ded-test-positions");
+    _ = fs::remove_file(&path);
+    let position = |row, col| CursorPosition { row, col };
+
+    assert_eq!(lookup(&path, Path::new("/a")), None);
+    save(&path, &[("/a".into(), position(1, 2)), ("/b".into(), position(3, 4))]).unwrap();
+    save(&path, &[("/a".into(), position(5, 6))]).unwrap();
+    assert_eq!(lookup(&path, Path::new("/a")), Some(position(5, 6)));
+    assert_eq!(lookup(&path, Path::new("/b")), Some(position(3, 4)));
+    assert_eq!(load(&path).len(), 2);
+
+    fs::write(&path, "garbage\n1\tx\t/c\n7\t8\t/d\n\u{0}").unwrap();
+    assert_eq!(lookup(&path, Path::new("/c")), None);
+    assert_eq!(lookup(&path, Path::new("/d")), Some(position(7, 8)));
+
+    let records = (0..MAX_RECORDS + 10)
+        .map(|i| (PathBuf::from(format!("/{i}")), position(i, 0)))
+        .collect::<Vec<_>>();
+    save(&path, &records).unwrap();
+    assert_eq!(load(&path).len(), MAX_RECORDS);
+    assert_eq!(lookup(&path, Path::new("/5")), None);
+    assert_eq!(lookup(&path, Path::new("/10")), Some(position(10, 0)));
+}
@@ -0,0 +1,499 @@
+use std::cmp;
+
+use ratatui::buffer::Buffer;
+use ratatui::layout::{Position, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Widget};
+use unicode_width::UnicodeWidthStr;
+
+use crate::input::{Input, Key};
+use crate::textarea::{CursorPosition, TextArea};
+
+/// Entries kept in a prompt's history.
+const MAX_HISTORY: usize = 100;
+/// Completions listed under the prompt at once, the list scrolls through the rest.
+const MAX_COMPLETIONS_SHOWN: usize = 8;
+
+/// Checks the text of a prompt, returning the error to show if it can't be accepted.
+pub type Validate = fn(&str) -> Result<(), String>;
+
+/// Prompt for a single line of text, like the text to align lines on or a command to run. The searchbox is one too,
+/// with its own Up, Down, Enter and Esc.
+pub struct PromptBox<'a> {
+    pub textarea: TextArea,
+    title: &'a str,
+    /// Shown after the title, like the number of matches.
+    status: Option<String>,
+    /// Shown after the title instead of the status, in red.
+    error: Option<String>,
+    open: bool,
+    /// Checks the text when Enter is pressed, which is kept open with the error in the title if it fails.
+    validate: Option<Validate>,
+    /// What Tab can complete the text to.
+    complete: Option<fn(&str) -> Vec<String>>,
+    /// The completions listed after Tab found several, and the one Tab went to since.
+    completions: Option<(Vec<String>, Option<usize>)>,
+    /// Accepted texts, oldest first, when Up and Down go through them.
+    history: Option<Vec<String>>,
+    /// The history entry shown, and the text typed before going to it.
+    recalled: Option<(usize, String)>,
+}
+
+/// What an input did to a [`PromptBox`].
+#[derive(Debug, PartialEq)]
+pub enum PromptEvent {
+    /// Still open.
+    Editing,
+    /// Closed with Enter on the text.
+    Accepted(String),
+    /// Closed with Esc.
+    Cancelled,
+}
+
+impl<'a> PromptBox<'a> {
+    pub fn new(title: &'a str) -> Self {
+        let mut textarea = TextArea::default();
+        textarea.line_numbers = false;
+        textarea.single_line = true;
+
+        Self {
+            textarea,
+            title,
+            status: None,
+            error: None,
+            open: false,
+            validate: None,
+            complete: None,
+            completions: None,
+            history: None,
+            recalled: None,
+        }
+    }
+
+    pub fn with_validation(mut self, validate: Validate) -> Self {
+        self.validate = Some(validate);
+        self
+    }
+
+    pub fn with_completion(mut self, complete: fn(&str) -> Vec<String>) -> Self {
+        self.complete = Some(complete);
+        self
+    }
+
+    pub fn with_history(mut self) -> Self {
+        self.history = Some(Vec::new());
+        self
+    }
+
+    /// Opens the prompt with `text` selected, so typing replaces it and Enter accepts it.
+    pub fn open(&mut self, text: &str) {
+        self.show();
+        self.set_text(text);
+        self.textarea.set_cursor(CursorPosition::default(), false);
+        let end = CursorPosition {
+            row: 0,
+            col: text.chars().count(),
+        };
+        self.textarea.set_cursor(end, !text.is_empty());
+    }
+
+    /// Opens the prompt with the cursor after `text`, so typing adds to it.
+    pub fn open_to_append(&mut self, text: &str) {
+        self.show();
+        self.set_text(text);
+    }
+
+    /// Opens the prompt on the text it had before.
+    pub fn show(&mut self) {
+        self.open = true;
+        self.recalled = None;
+        self.completions = None;
+        self.status = None;
+        self.error = None;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.completions = None;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn text(&self) -> &str {
+        &self.textarea.lines[0]
+    }
+
+    /// Replaces the text, with the cursor after it.
+    pub fn set_text(&mut self, text: &str) {
+        self.textarea.set_lines(vec![text.to_string()]);
+        self.textarea.set_cursor(
+            CursorPosition {
+                row: 0,
+                col: text.chars().count(),
+            },
+            false,
+        );
+    }
+
+    pub fn set_title(&mut self, title: &'a str) {
+        self.title = title;
+    }
+
+    pub fn set_status(&mut self, status: Option<String>) {
+        self.status = status;
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn set_error(&mut self, error: Option<String>) {
+        self.error = error;
+    }
+
+    /// Handles `input`, returning the text once Enter is pressed on some that is valid.
+    pub fn input(&mut self, input: Input) -> PromptEvent {
+        match input {
+            Input { key: Key::Enter, .. } => {
+                let text = self.text().to_string();
+                if text.is_empty() {
+                    return PromptEvent::Editing;
+                }
+                if let Some(Err(err)) = self.validate.map(|validate| validate(&text)) {
+                    self.error = Some(err);
+                    return PromptEvent::Editing;
+                }
+                self.remember(&text);
+                self.close();
+                PromptEvent::Accepted(text)
+            }
+            Input { key: Key::Esc, .. } => {
+                self.close();
+                PromptEvent::Cancelled
+            }
+            Input {
+                key: key @ (Key::Up | Key::Down),
+                ..
+            } if self.history.is_some() => {
+                self.recall(key == Key::Up);
+                PromptEvent::Editing
+            }
+            // the terminal sends Shift+Tab as BackTab
+            Input {
+                key: key @ (Key::Tab | Key::BackTab),
+                ctrl: false,
+                alt: false,
+                shift,
+            } if self.complete.is_some() => {
+                self.complete(!shift && key == Key::Tab);
+                PromptEvent::Editing
+            }
+            input => {
+                if self.textarea.input(input) {
+                    self.completions = None;
+                    self.status = None;
+                    self.error = None;
+                }
+                PromptEvent::Editing
+            }
+        }
+    }
+
+    /// Where the terminal cursor goes when the prompt is drawn in `area`.
+    pub fn cursor_position(&self, area: Rect) -> Position {
+        Position::new(self.textarea.terminal_cursor_position().x + 1, area.y + 1)
+    }
+
+    fn remember(&mut self, text: &str) {
+        self.recalled = None;
+        let Some(history) = &mut self.history else {
+            return;
+        };
+        history.retain(|entry| entry != text);
+        history.push(text.to_string());
+        if history.len() > MAX_HISTORY {
+            history.remove(0);
+        }
+    }
+
+    /// Shows the history entry before the one shown if `older`, or else the one after it, which after the newest is
+    /// the text typed before going through the history.
+    fn recall(&mut self, older: bool) {
+        self.completions = None;
+        let len = self.history.as_ref().map_or(0, Vec::len);
+        let recalled = match (self.recalled.take(), older) {
+            (None, true) if len > 0 => (len - 1, self.text().to_string()),
+            (None, _) => return,
+            (Some((idx, typed)), true) => (idx.saturating_sub(1), typed),
+            (Some((idx, typed)), false) if idx + 1 < len => (idx + 1, typed),
+            (Some((_, typed)), false) => {
+                self.set_text(&typed);
+                return;
+            }
+        };
+        let entry = self
+            .history
+            .as_ref()
+            .map(|history| history[recalled.0].clone())
+            .unwrap_or_default();
+        self.set_text(&entry);
+        self.recalled = Some(recalled);
+    }
+
+    /// Completes the text as far as all of its completions agree, listing them under the prompt if there are
+    /// several. Once they are listed, Tab goes to the next one and Shift+Tab to the one before.
+    fn complete(&mut self, forward: bool) {
+        if let Some((completions, selected)) = self.completions.take() {
+            let len = completions.len();
+            let selected = match selected {
+                None if forward => 0,
+                None => len - 1,
+                Some(idx) if forward => (idx + 1) % len,
+                Some(idx) => (idx + len - 1) % len,
+            };
+            self.set_text(&completions[selected]);
+            self.completions = Some((completions, Some(selected)));
+            return;
+        }
+
+        let Some(complete) = self.complete else {
+            return;
+        };
+        let text = self.text().to_string();
+        let completions = complete(&text);
+        let Some(first) = completions.first() else {
+            self.error = Some("nothing to complete".to_string());
+            return;
+        };
+
+        let common = completions.iter().fold(first.as_str(), |common, completion| {
+            let len = common
+                .char_indices()
+                .zip(completion.chars())
+                .find(|&((_, a), b)| a != b)
+                .map_or(common.len().min(completion.len()), |((idx, _), _)| idx);
+            &common[..len]
+        });
+        if common.len() > text.len() {
+            self.set_text(common);
+        }
+        self.error = None;
+        if completions.len() > 1 {
+            self.completions = Some((completions, None));
+        }
+    }
+
+    /// The completions Tab listed, and the one it went to since.
+    pub fn completions(&self) -> Option<(&[String], Option<usize>)> {
+        self.completions
+            .as_ref()
+            .map(|(completions, selected)| (completions.as_slice(), *selected))
+    }
+
+    /// Draws the completions Tab listed over the top of `area`, by the last part of their path.
+    pub fn render_completions(&self, area: Rect, buf: &mut Buffer) {
+        let Some((completions, selected)) = self.completions() else {
+            return;
+        };
+        let names = completions
+            .iter()
+            .map(|completion| {
+                let start = completion.trim_end_matches('/').rfind('/').map_or(0, |idx| idx + 1);
+                &completion[start..]
+            })
+            .collect::<Vec<_>>();
+        let visible = cmp::min(names.len(), MAX_COMPLETIONS_SHOWN);
+        let width = names.iter().map(|name| name.width()).max().unwrap_or(0) + 2;
+        let area = Rect {
+            width: u16::try_from(width).unwrap_or(u16::MAX).min(area.width),
+            height: u16::try_from(visible + 2).unwrap_or(u16::MAX).min(area.height),
+            ..area
+        };
+
+        // keep the selected completion in view
+        let scroll = selected.map_or(0, |selected| (selected + 1).saturating_sub(visible));
+        let lines = names.iter().enumerate().skip(scroll).take(visible).map(|(idx, name)| {
+            let line = Line::from(*name);
+            if Some(idx) == selected {
+                line.style(Style::default().add_modifier(Modifier::REVERSED))
+            } else {
+                line
+            }
+        });
+
+        Clear.render(area, buf);
+        Paragraph::new(lines.collect::<Vec<_>>())
+            .block(Block::default().borders(Borders::ALL))
+            .render(area, buf);
+    }
+}
+
+impl<'a> Widget for &PromptBox<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        if area.is_empty() {
+            return;
+        }
+
+        let title = self.title.trim_end();
+        let block = Block::default().borders(Borders::ALL);
+        let block = match (&self.error, &self.status) {
+            (Some(err), _) => block
+                .title(format!("{title} {err} "))
+                .style(Style::default().fg(Color::Red)),
+            (None, Some(status)) => block.title(format!("{title} {status} ")),
+            (None, None) => block.title(self.title),
+        };
+        (&block).render(area, buf);
+        self.textarea.render(block.inner(area), buf);
+    }
+}
+
+#[test]
+fn test() {
+    let input = |key| Input { key, ..Default::default() };
+    let mut promptbox = PromptBox::new(" Test: ");
+
+    promptbox.open("=");
+    assert_eq!(
+        promptbox.input(input(Key::Enter)),
+        PromptEvent::Accepted("=".to_string())
+    );
+    assert!(!promptbox.is_open());
+
+    // typing replaces the text it was opened with
+    promptbox.open("cargo build");
+    for c in "make".chars() {
+        promptbox.input(input(Key::Char(c)));
+    }
+    assert_eq!(
+        promptbox.input(input(Key::Enter)),
+        PromptEvent::Accepted("make".to_string())
+    );
+
+    promptbox.open("");
+    assert_eq!(promptbox.input(input(Key::Enter)), PromptEvent::Editing);
+    assert!(promptbox.is_open());
+    assert_eq!(promptbox.input(input(Key::Esc)), PromptEvent::Cancelled);
+    assert!(!promptbox.is_open());
+
+    promptbox.open_to_append("src/");
+    promptbox.input(input(Key::Char('a')));
+    assert_eq!(promptbox.text(), "src/a");
+}
+
+#[test]
+fn validation() {
+    let input = |key| Input { key, ..Default::default() };
+    let mut promptbox = PromptBox::new(" Number: ").with_validation(|text| match text.parse::<u32>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("not a number: {text}")),
+    });
+
+    promptbox.open("x");
+    assert_eq!(promptbox.input(input(Key::Enter)), PromptEvent::Editing);
+    assert!(promptbox.is_open());
+    assert_eq!(promptbox.error(), Some("not a number: x"));
+
+    // typing takes the error away, until Enter finds the text is still wrong
+    promptbox.input(input(Key::Char('y')));
+    assert_eq!(promptbox.error(), None);
+    promptbox.input(input(Key::Enter));
+    assert_eq!(promptbox.error(), Some("not a number: y"));
+
+    promptbox.set_text("42");
+    assert_eq!(
+        promptbox.input(input(Key::Enter)),
+        PromptEvent::Accepted("42".to_string())
+    );
+    promptbox.open("");
+    assert_eq!(promptbox.error(), None);
+}
+
+#[test]
+fn completion() {
+    let input = |key| Input { key, ..Default::default() };
+    let words = |text: &str| {
+        ["cargo build", "cargo check", "make"]
+            .into_iter()
+            .filter(|word| word.starts_with(text))
+            .map(String::from)
+            .collect()
+    };
+    let mut promptbox = PromptBox::new(" Run: ").with_completion(words);
+
+    promptbox.open_to_append("c");
+    promptbox.input(input(Key::Tab));
+    assert_eq!(promptbox.text(), "cargo ");
+    let listed = ["cargo build".to_string(), "cargo check".to_string()];
+    assert_eq!(promptbox.completions(), Some((&listed[..], None)));
+    promptbox.input(input(Key::Char('c')));
+    assert_eq!(promptbox.completions(), None);
+    promptbox.input(input(Key::Tab));
+    assert_eq!(promptbox.text(), "cargo check");
+    assert_eq!(promptbox.completions(), None);
+
+    // Tab again goes through the listed ones, wrapping around, and Shift+Tab goes back
+    promptbox.set_text("cargo");
+    let tab = |promptbox: &mut PromptBox, shift| {
+        promptbox.input(Input {
+            key: if shift { Key::BackTab } else { Key::Tab },
+            shift,
+            ..Default::default()
+        });
+        promptbox.text().to_string()
+    };
+    assert_eq!(tab(&mut promptbox, false), "cargo ");
+    assert_eq!(tab(&mut promptbox, false), "cargo build");
+    assert_eq!(tab(&mut promptbox, false), "cargo check");
+    assert_eq!(promptbox.completions(), Some((&listed[..], Some(1))));
+    assert_eq!(tab(&mut promptbox, false), "cargo build");
+    assert_eq!(tab(&mut promptbox, true), "cargo check");
+    promptbox.close();
+    assert_eq!(promptbox.completions(), None);
+
+    promptbox.set_text("x");
+    promptbox.input(input(Key::Tab));
+    assert_eq!(promptbox.text(), "x");
+    assert_eq!(promptbox.error(), Some("nothing to complete"));
+}
+
+#[test]
+fn history() {
+    let input = |key| Input { key, ..Default::default() };
+    let mut promptbox = PromptBox::new(" Run: ").with_history();
+    for command in ["make", "cargo test", "make"] {
+        promptbox.open(command);
+        promptbox.input(input(Key::Enter));
+    }
+
+    promptbox.open_to_append("car");
+    promptbox.input(input(Key::Up));
+    assert_eq!(promptbox.text(), "make");
+    promptbox.input(input(Key::Up));
+    assert_eq!(promptbox.text(), "cargo test");
+    // it stops at the oldest
+    promptbox.input(input(Key::Up));
+    assert_eq!(promptbox.text(), "cargo test");
+    promptbox.input(input(Key::Down));
+    promptbox.input(input(Key::Down));
+    assert_eq!(promptbox.text(), "car");
+    promptbox.input(input(Key::Down));
+    assert_eq!(promptbox.text(), "car");
+
+    promptbox.input(input(Key::Up));
+    promptbox.input(input(Key::Up));
+    assert_eq!(
+        promptbox.input(input(Key::Enter)),
+        PromptEvent::Accepted("cargo test".to_string())
+    );
+    assert_eq!(
+        promptbox.history,
+        Some(vec!["make".to_string(), "cargo test".to_string()])
+    );
+}
@@ -0,0 +1,139 @@
+use ratatui::buffer::Buffer;
+use ratatui::layout::Rect;
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Widget};
+use regex::Regex;
+
+use crate::input::{Input, Key};
+use crate::textarea::{CursorPosition, TextArea};
+
+/// Prompt for a pattern and then its replacement, for replacing in all buffers.
+pub struct ReplaceBox<'a> {
+    pub textarea: TextArea,
+    border_block: Block<'a>,
+    open: bool,
+    /// The pattern once it was entered, while the replacement is being typed.
+    pattern: Option<String>,
+}
+
+impl<'a> Default for ReplaceBox<'a> {
+    fn default() -> Self {
+        let mut textarea = TextArea::default();
+        textarea.line_numbers = false;
+        textarea.single_line = true;
+
+        Self {
+            textarea,
+            border_block: Self::block(None, None),
+            open: false,
+            pattern: None,
+        }
+    }
+}
+
+impl<'a> ReplaceBox<'a> {
+    pub fn open(&mut self) {
+        self.open = true;
+        self.pattern = None;
+        self.set_text("");
+        self.border_block = Self::block(None, None);
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Handles `input`, returning the pattern and the replacement once both were entered.
+    pub fn input(&mut self, input: Input) -> Option<(String, String)> {
+        match input {
+            Input { key: Key::Enter, .. } => {
+                let text = self.textarea.lines[0].clone();
+                match self.pattern.take() {
+                    Some(pattern) => {
+                        self.close();
+                        return Some((pattern, text));
+                    }
+                    None if text.is_empty() => {}
+                    None => match Regex::new(&text) {
+                        Ok(_) => {
+                            self.border_block = Self::block(Some(&text), None);
+                            self.pattern = Some(text);
+                            self.set_text("");
+                        }
+                        Err(err) => self.border_block = Self::block(None, Some(&err.to_string())),
+                    },
+                }
+            }
+            Input { key: Key::Esc, .. } => self.close(),
+            input => {
+                if self.textarea.input(input) && self.pattern.is_none() {
+                    self.border_block = Self::block(None, None);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn set_text(&mut self, text: &str) {
+        self.textarea.set_lines(vec![text.to_string()]);
+        self.textarea.set_cursor(
+            CursorPosition {
+                row: 0,
+                col: text.chars().count(),
+            },
+            false,
+        );
+    }
+
+    fn block(pattern: Option<&str>, error: Option<&str>) -> Block<'a> {
+        let block = Block::default().borders(Borders::ALL);
+        match (pattern, error) {
+            (_, Some(error)) => block
+                .title(format!(" Replace in all buffers: {error} "))
+                .style(Style::default().fg(Color::Red)),
+            (Some(pattern), None) => block.title(format!(" Replace {pattern} with: ")),
+            (None, None) => block.title(" Replace in all buffers: "),
+        }
+    }
+}
+
+impl<'a> Widget for &ReplaceBox<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        if area.is_empty() {
+            return;
+        }
+
+        (&self.border_block).render(area, buf);
+        self.textarea.render(self.border_block.inner(area), buf);
+    }
+}
+
+#[test]
+fn test() {
+    let input = |key| Input { key, ..Default::default() };
+    let mut replacebox = ReplaceBox::default();
+    replacebox.open();
+
+    for c in "a(".chars() {
+        replacebox.input(input(Key::Char(c)));
+    }
+    assert_eq!(replacebox.input(input(Key::Enter)), None);
+    assert!(replacebox.pattern.is_none());
+
+    replacebox.input(input(Key::Backspace));
+    assert_eq!(replacebox.input(input(Key::Enter)), None);
+    replacebox.input(input(Key::Char('b')));
+    assert_eq!(
+        replacebox.input(input(Key::Enter)),
+        Some(("a".to_string(), "b".to_string()))
+    );
+    assert!(!replacebox.is_open());
+}
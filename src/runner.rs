@@ -0,0 +1,186 @@
+use std::io::{self, BufRead, BufReader};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::LazyLock;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+
+use crate::textarea::CursorPosition;
+
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// How long output is still waited for after the command exited, for what it wrote just before that. Programs it
+/// started in the background may hold the pipe open for much longer, so its end isn't waited for.
+const OUTPUT_AFTER_EXIT: Duration = Duration::from_millis(100);
+
+/// `path:line:col` as printed by rustc, gcc and clang, or `path:line` as printed by grep.
+static LOCATION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[\s(\[])([^\s:()\[\]]+):(\d+)(?::(\d+))?").unwrap());
+
+/// Runs a shell command, collecting its stdout and stderr interleaved on a background thread. Dropping it kills the
+/// command.
+pub struct Runner {
+    receiver: Receiver<String>,
+    child: Child,
+    started: Instant,
+    output_done: bool,
+    /// When the command was seen to exit, and how.
+    exited: Option<(Instant, ExitStatus)>,
+    status: Option<ExitStatus>,
+}
+
+/// A command that runs `command` with the system shell.
+pub fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+impl Runner {
+    pub fn new(command: &str) -> io::Result<Self> {
+        let (reader, writer) = io::pipe()?;
+        let child = {
+            // dropped right away, so the pipe closes once the command is done with it
+            shell(command)
+                .stdin(Stdio::null())
+                .stdout(writer.try_clone()?)
+                .stderr(writer)
+                .spawn()?
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        // not joined, as programs the command started in the background may hold the pipe open
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            let mut buf = Vec::new();
+            while reader.read_until(b'\n', &mut buf).is_ok_and(|n| n > 0) {
+                let line = String::from_utf8_lossy(&buf);
+                let line = line.trim_end_matches(['\n', '\r']).to_string();
+                if sender.send(line).is_err() {
+                    break;
+                }
+                buf.clear();
+            }
+        });
+
+        Ok(Self {
+            receiver,
+            child,
+            started: Instant::now(),
+            output_done: false,
+            exited: None,
+            status: None,
+        })
+    }
+
+    /// Returns the lines output since the last call.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut lines = Vec::new();
+        while !self.output_done {
+            match self.receiver.try_recv() {
+                Ok(line) => lines.push(line),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => self.output_done = true,
+            }
+        }
+
+        if self.exited.is_none() {
+            self.exited = self
+                .child
+                .try_wait()
+                .ok()
+                .flatten()
+                .map(|status| (Instant::now(), status));
+        }
+        if let Some((at, status)) = self.exited
+            && (self.output_done || at.elapsed() >= OUTPUT_AFTER_EXIT)
+        {
+            self.status = Some(status);
+        }
+        lines
+    }
+
+    /// The exit status, once the command exited and the output it wrote before that was polled.
+    pub fn status(&self) -> Option<ExitStatus> {
+        self.status
+    }
+
+    /// A frame of a spinner turning while the command runs.
+    pub fn spinner(&self) -> char {
+        let frame = self.started.elapsed().as_millis() / 100;
+        SPINNER[frame as usize % SPINNER.len()]
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        if self.status.is_none() {
+            _ = self.child.kill();
+            _ = self.child.wait();
+        }
+    }
+}
+
+/// The path and position a line of compiler or grep output refers to.
+pub fn location(line: &str) -> Option<(&str, CursorPosition)> {
+    let captures = LOCATION.captures(line)?;
+    let number = |idx| {
+        captures
+            .get(idx)
+            .and_then(|m: regex::Match| m.as_str().parse::<usize>().ok())
+            .map_or(0, |n| n.saturating_sub(1))
+    };
+    let path = captures.get(1)?.as_str();
+    Some((path, CursorPosition { row: number(2), col: number(3) }))
+}
+
+#[test]
+fn locations() {
+    let at = |path, row, col| Some((path, CursorPosition { row, col }));
+    assert_eq!(location("  --> src/main.rs:10:5"), at("src/main.rs", 9, 4));
+    assert_eq!(location("src/a.c:3:14: error: expected ';'"), at("src/a.c", 2, 13));
+    assert_eq!(location("README.md:7:some text"), at("README.md", 6, 0));
+    assert_eq!(location("warning: unused variable: `x`"), None);
+    assert_eq!(location("   Compiling ded v0.1.0 (/root/crate)"), None);
+}
+
+#[test]
+fn run() {
+    let command = if cfg!(windows) {
+        "echo one && echo two 1>&2 && exit 3"
+    } else {
+        "echo one; echo two >&2; exit 3"
+    };
+    let mut runner = Runner::new(command).unwrap();
+
+    let mut lines = Vec::new();
+    while runner.status().is_none() {
+        lines.extend(runner.poll());
+        thread::yield_now();
+    }
+
+    assert_eq!(lines, ["one", "two"]);
+    assert_eq!(runner.status().and_then(|status| status.code()), Some(3));
+
+    // done when the command exits, though a program it left running still holds the output open
+    if cfg!(unix) {
+        let mut runner = Runner::new("echo one; sleep 10 & exit").unwrap();
+        let mut lines = Vec::new();
+        while runner.status().is_none() {
+            lines.extend(runner.poll());
+            thread::yield_now();
+        }
+        assert_eq!(lines, ["one"]);
+        assert!(runner.started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,69 +1,157 @@
+use std::borrow::Cow;
 use std::fmt::Display;
+use std::time::{Duration, Instant};
 
 use ratatui::buffer::Buffer;
 use ratatui::layout::Rect;
-use ratatui::style::{Color, Style};
-use ratatui::widgets::{Block, Borders, Widget};
+use ratatui::widgets::Widget;
+use regex::Regex;
 
 use crate::input::Input;
-use crate::textarea::{CursorPosition, TextArea};
+use crate::match_counter::MatchCounter;
+use crate::promptbox::PromptBox;
+use crate::textarea::TextArea;
+#[cfg(test)]
+use crate::{input::Key, textarea::CursorPosition};
+
+/// How long typing has to pause before the text is searched for, so a pattern isn't compiled and run for every key.
+pub const DEBOUNCE: Duration = Duration::from_millis(100);
 
 pub struct SearchBox<'a> {
-    pub textarea: TextArea,
-    border_block: Block<'a>,
-    open: bool,
+    prompt: PromptBox<'a>,
+    /// Searches for the text as is rather than as a regex, for searches started from a selection.
+    literal: bool,
+    /// When the text was last typed in, until it is searched for.
+    typed: Option<Instant>,
+    counter: Option<MatchCounter>,
 }
 
 impl<'a> Default for SearchBox<'a> {
     fn default() -> Self {
-        let mut textarea = TextArea::default();
-        textarea.line_numbers = false;
-
         Self {
-            textarea,
-            border_block: Block::default().borders(Borders::ALL).title(" Search: "),
-            open: false,
+            prompt: PromptBox::new(Self::title(false)),
+            literal: false,
+            typed: None,
+            counter: None,
         }
     }
 }
 
 impl<'a> SearchBox<'a> {
-    pub fn open(&mut self) -> &str {
-        self.open = true;
-        &self.textarea.lines[0]
+    /// Opens the searchbox on the text it had before.
+    pub fn open(&mut self) {
+        self.prompt.show();
     }
 
     pub fn close(&mut self) {
-        self.open = false;
-        self.border_block = Block::default().borders(Borders::ALL).title(" Search: ");
+        self.prompt.close();
+        self.typed = None;
+        self.prompt.set_error(None);
+        self.set_match_count(None);
     }
 
     pub fn is_open(&self) -> bool {
-        self.open
+        self.prompt.is_open()
+    }
+
+    pub fn prompt(&self) -> &PromptBox<'a> {
+        &self.prompt
     }
 
     pub fn text(&self) -> &str {
-        &self.textarea.lines[0]
+        self.prompt.text()
+    }
+
+    /// The regex to search for, which is the text escaped in literal mode.
+    pub fn pattern(&self) -> Cow<'_, str> {
+        if self.literal {
+            regex::escape(self.text()).into()
+        } else {
+            self.text().into()
+        }
+    }
+
+    pub fn literal(&self) -> bool {
+        self.literal
+    }
+
+    pub fn set_literal(&mut self, literal: bool) {
+        self.literal = literal;
+        self.prompt.set_title(Self::title(literal));
+        self.prompt.set_error(None);
     }
 
     pub fn set_text(&mut self, pattern: &str) {
-        self.textarea.lines[0] = pattern.to_string();
-        self.textarea
-            .set_cursor(CursorPosition { row: 0, col: pattern.len() }, false);
+        self.prompt.set_text(pattern);
     }
 
-    pub fn input(&mut self, input: Input) -> Option<&'_ str> {
-        self.textarea.input(input).then_some(self.text())
+    /// Handles `input` other than the keys that move between matches or close the searchbox, returning whether the
+    /// text changed. The new text is only searched for once typing pauses for [`DEBOUNCE`] after `now`.
+    pub fn input(&mut self, input: Input, now: Instant) -> bool {
+        let version = self.prompt.textarea.version();
+        self.prompt.input(input);
+        let changed = self.prompt.textarea.version() != version;
+        if changed {
+            self.typed = Some(now);
+        }
+        changed
+    }
+
+    /// Whether text was typed that wasn't searched for yet.
+    pub fn is_pending(&self) -> bool {
+        self.typed.is_some()
+    }
+
+    /// Whether typing paused long enough at `now` for the text to be searched for, which is then no longer pending.
+    pub fn take_due(&mut self, now: Instant) -> bool {
+        self.typed
+            .take_if(|typed| now.duration_since(*typed) >= DEBOUNCE)
+            .is_some()
+    }
+
+    /// Whether text was typed that wasn't searched for yet, which is then no longer pending. For keys that search
+    /// right away.
+    pub fn take_pending(&mut self) -> bool {
+        self.typed.take().is_some()
     }
 
     pub fn set_error_message(&mut self, error_message: Option<impl Display>) {
-        self.border_block = match error_message {
-            Some(err_msg) => Block::default()
-                .borders(Borders::ALL)
-                .title(format!(" Search: {err_msg} "))
-                .style(Style::default().fg(Color::Red)),
-            None => Block::default().borders(Borders::ALL).title(" Search: "),
+        self.prompt.set_error(error_message.map(|err| err.to_string()));
+    }
+
+    /// Counts the matches of the search pattern in the text of `textarea` in the background, for the title, or stops
+    /// counting when there is no pattern. The count holds on to the lines rather than a copy, they are only copied if
+    /// the text is edited before it is done.
+    pub fn count_matches(&mut self, pattern: Option<&Regex>, textarea: &TextArea) {
+        self.set_match_count(None);
+        let Some(pattern) = pattern else {
+            return;
         };
+        self.counter = Some(MatchCounter::new(pattern.clone(), textarea.lines.share()));
+    }
+
+    /// Whether matches are being counted.
+    pub fn is_counting(&self) -> bool {
+        self.counter.is_some()
+    }
+
+    /// Shows the number of matches once counted, returning whether it just was.
+    pub fn poll_match_count(&mut self) -> bool {
+        let Some(count) = self.counter.as_ref().and_then(MatchCounter::poll) else {
+            return false;
+        };
+        self.set_match_count(Some(count));
+        true
+    }
+
+    fn set_match_count(&mut self, match_count: Option<usize>) {
+        self.counter = None;
+        let status = match_count.map(|count| format!("{count} match{}", if count == 1 { "" } else { "es" }));
+        self.prompt.set_status(status);
+    }
+
+    fn title(literal: bool) -> &'static str {
+        if literal { " Search (literal): " } else { " Search: " }
     }
 }
 
@@ -72,11 +160,55 @@ impl<'a> Widget for &SearchBox<'a> {
     where
         Self: Sized,
     {
-        if area.is_empty() {
-            return;
-        }
-
-        (&self.border_block).render(area, buf);
-        self.textarea.render(self.border_block.inner(area), buf);
+        self.prompt.render(area, buf);
     }
 }
+
+#[test]
+fn set_text() {
+    let mut searchbox = SearchBox::default();
+    searchbox.set_text("äöü");
+    assert_eq!(searchbox.prompt.textarea.cursor(), CursorPosition { row: 0, col: 3 });
+}
+
+#[test]
+fn debounce() {
+    let mut searchbox = SearchBox::default();
+    let typed = Instant::now();
+    let key = |char| Input {
+        key: Key::Char(char),
+        ..Default::default()
+    };
+    assert!(searchbox.input(key('a'), typed));
+    assert!(!searchbox.take_due(typed + DEBOUNCE / 2));
+
+    // each key starts the wait over
+    assert!(searchbox.input(key('b'), typed + DEBOUNCE / 2));
+    assert!(!searchbox.take_due(typed + DEBOUNCE));
+    assert!(searchbox.take_due(typed + DEBOUNCE * 3 / 2));
+    assert!(!searchbox.is_pending());
+}
+
+#[test]
+fn count_matches() {
+    let mut textarea = TextArea::default();
+    textarea.set_lines(vec!["a b a".to_string(); 3]);
+    let mut searchbox = SearchBox::default();
+    let pattern = Regex::new("a").unwrap();
+    searchbox.open();
+    searchbox.count_matches(Some(&pattern), &textarea);
+
+    // counted in the text as it was when the count started, an edit meanwhile copies the lines it changes
+    textarea.lines[0].push('a');
+    assert!((0..1000).any(|_| {
+        std::thread::sleep(Duration::from_millis(5));
+        searchbox.poll_match_count()
+    }));
+    let mut buf = Buffer::empty(Rect::new(0, 0, 30, 3));
+    (&searchbox).render(buf.area, &mut buf);
+    let title = (0..30).map(|x| buf[(x, 0)].symbol()).collect::<String>();
+    assert!(title.contains("6 matches"), "{title}");
+
+    searchbox.count_matches(None, &textarea);
+    assert!(!searchbox.is_counting());
+}
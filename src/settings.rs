@@ -0,0 +1,595 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crossterm::cursor::SetCursorStyle;
+use ratatui::style::Color;
+
+use crate::editor::AUTO_PAIRS;
+use crate::textarea::Indent;
+use crate::theme::Theme;
+
+/// How much of the screen is given to the text, cycled with F11.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Fullscreen {
+    #[default]
+    Off,
+    /// Hides the status line.
+    Half,
+    /// Also hides line numbers and centers the text in a column of [`Fullscreen::TEXT_WIDTH`].
+    Full,
+}
+
+impl Fullscreen {
+    pub const TEXT_WIDTH: u16 = 80;
+
+    pub fn toggle(self) -> Self {
+        match self {
+            Fullscreen::Off => Fullscreen::Half,
+            Fullscreen::Half => Fullscreen::Full,
+            Fullscreen::Full => Fullscreen::Off,
+        }
+    }
+}
+
+/// Terminal cursor shape, telling what typing will do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    /// Typing inserts text.
+    Bar,
+    /// Typing replaces text, or runs commands in vim's normal mode.
+    Block,
+    /// Typing goes to a prompt.
+    Underline,
+}
+
+impl CursorShape {
+    pub fn style(self) -> SetCursorStyle {
+        match self {
+            CursorShape::Bar => SetCursorStyle::SteadyBar,
+            CursorShape::Block => SetCursorStyle::SteadyBlock,
+            CursorShape::Underline => SetCursorStyle::SteadyUnderScore,
+        }
+    }
+}
+
+/// How a key that has nothing to act on, like Backspace at the start of the text, is signaled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidKey {
+    Silent,
+    /// Inverts the status line for a moment.
+    #[default]
+    Flash,
+    /// Rings the terminal bell.
+    Bell,
+    Both,
+}
+
+impl InvalidKey {
+    const NAMES: [(&str, Self); 4] = [
+        ("silent", Self::Silent),
+        ("flash", Self::Flash),
+        ("bell", Self::Bell),
+        ("both", Self::Both),
+    ];
+
+    pub fn flashes(self) -> bool {
+        matches!(self, Self::Flash | Self::Both)
+    }
+
+    pub fn rings(self) -> bool {
+        matches!(self, Self::Bell | Self::Both)
+    }
+}
+
+/// Editor wide settings, shared by all buffers.
+#[derive(Debug)]
+pub struct Settings {
+    pub fullscreen: Fullscreen,
+    /// Columns to draw vertical guides at, so long lines stand out.
+    pub rulers: Vec<usize>,
+    /// Draws guides at each indent level, toggled with Alt+G.
+    pub indent_guides: bool,
+    /// Tints spaces and tabs at the end of lines, toggled with Alt+E.
+    pub trailing_whitespace: bool,
+    /// Indent styles forced by file name, checked before detecting the indent from the text. Patterns are either a
+    /// whole file name or `*` followed by a suffix.
+    pub indent_overrides: Vec<(String, Indent)>,
+    /// Starts buffers in vim's normal mode, set with `--vim`.
+    pub vim: bool,
+    /// Opens every buffer without allowing changes, set with `--readonly`.
+    pub readonly: bool,
+    /// Reports mouse clicks and drags to ded, set with `--mouse`. Off by default, as the terminal can't select and copy
+    /// text with the mouse meanwhile.
+    pub mouse: bool,
+    /// Whether Backspace deletes in overwrite mode, instead of only moving left like most editors do.
+    pub overwrite_backspace_deletes: bool,
+    /// Changes the terminal cursor shape with the mode, off for terminals that mangle the escape sequences.
+    pub cursor_shapes: bool,
+    /// Commands offered to run with F5, by a file that marks the kind of project in the working directory. The first
+    /// one whose file exists is used.
+    pub run_commands: Vec<(String, String)>,
+    /// Asks terminals that support the kitty keyboard protocol to report keys unambiguously, which makes bindings like
+    /// Ctrl+Shift+letter reachable.
+    pub keyboard_enhancement: bool,
+    /// Opening brackets and quotes that insert their closing counterpart when typed, or wrap the selection in it.
+    /// Leaving one out turns pairing off for it, an empty list turns it off entirely.
+    pub auto_pairs: Vec<char>,
+    /// Display columns Alt+Q wraps paragraphs at.
+    pub wrap_width: usize,
+    /// Underlines misspelled words in files with one of [`Settings::prose_extensions`].
+    pub spell_check: bool,
+    pub prose_extensions: Vec<String>,
+    /// Where words added with Ctrl+F7 are kept, one per line.
+    pub personal_dictionary: Option<PathBuf>,
+    /// Where the cursor position in closed files is kept, to open them there next time.
+    pub positions_file: Option<PathBuf>,
+    /// The freedesktop trash that Alt+Shift+K moves files into.
+    pub trash_dir: Option<PathBuf>,
+    /// Saving a symlink replaces it with a regular file, instead of writing to the file it points to.
+    pub replace_symlinks: bool,
+    /// Where the global [`FileSettings`] are read from when ded starts.
+    pub config_file: Option<PathBuf>,
+    pub global: FileSettings,
+    pub theme: Theme,
+    pub invalid_key: InvalidKey,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            fullscreen: Fullscreen::default(),
+            rulers: vec![80],
+            indent_guides: true,
+            trailing_whitespace: true,
+            // make only runs recipes indented with tabs, and gofmt always uses them
+            indent_overrides: ["Makefile", "makefile", "GNUmakefile", "*.mk", "go.mod", "*.go"]
+                .map(|pattern| (pattern.to_string(), Indent::Tabs))
+                .to_vec(),
+            vim: false,
+            readonly: false,
+            mouse: false,
+            overwrite_backspace_deletes: false,
+            cursor_shapes: true,
+            run_commands: [
+                ("Cargo.toml", "cargo build"),
+                ("go.mod", "go build ./..."),
+                ("package.json", "npm run build"),
+                ("Makefile", "make"),
+            ]
+            .map(|(file, command)| (file.to_string(), command.to_string()))
+            .to_vec(),
+            keyboard_enhancement: true,
+            auto_pairs: AUTO_PAIRS.to_vec(),
+            wrap_width: 80,
+            spell_check: true,
+            prose_extensions: ["md", "markdown", "txt", "text", "rst", "adoc", "org"]
+                .map(String::from)
+                .to_vec(),
+            personal_dictionary: user_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("ded").join("words")),
+            positions_file: user_dir("XDG_STATE_HOME", ".local/state").map(|dir| dir.join("ded").join("positions")),
+            trash_dir: user_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join("Trash")),
+            replace_symlinks: false,
+            config_file: user_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join("ded").join("config.toml")),
+            global: FileSettings::default(),
+            theme: Theme::default(),
+            invalid_key: InvalidKey::default(),
+        }
+    }
+}
+
+/// The directory named by the XDG environment variable `var`, or else `fallback` in the home directory.
+fn user_dir(var: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(fallback)))
+}
+
+impl Settings {
+    /// The indent style forced for `path` by [`Settings::indent_overrides`], if any.
+    pub fn indent_override(&self, path: &Path) -> Option<Indent> {
+        let name = path.file_name()?.to_str()?;
+        self.indent_overrides
+            .iter()
+            .find(|(pattern, _)| match pattern.strip_prefix('*') {
+                Some(suffix) => name.ends_with(suffix),
+                None => name == pattern,
+            })
+            .map(|(_, indent)| indent.clone())
+    }
+
+    /// Whether `path` is checked for spelling, being prose rather than code.
+    pub fn is_prose(&self, path: &Path) -> bool {
+        self.spell_check && self.has_prose_extension(path)
+    }
+
+    /// Whether `path` has one of [`Settings::prose_extensions`], whether or not spelling is checked.
+    pub fn has_prose_extension(&self, path: &Path) -> bool {
+        path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| {
+            self.prose_extensions
+                .iter()
+                .any(|prose| prose.eq_ignore_ascii_case(ext))
+        })
+    }
+
+    /// The settings for a file in `dir`: those of the nearest [`PROJECT_FILE`] over [`Settings::global`] over the
+    /// built-in defaults. Also returns why the project file couldn't be read, in which case it is left out.
+    pub fn for_dir(&self, dir: Option<&Path>) -> (BufferSettings, Option<String>) {
+        let mut merged = self.global.clone();
+        let mut project = None;
+        let mut error = None;
+        match dir.and_then(FileSettings::find) {
+            Some((path, Ok(settings))) => {
+                merged = merged.merge(settings);
+                project = Some(path);
+            }
+            Some((path, Err(err))) => error = Some(format!("Ignoring {}: {err}", path.display())),
+            None => {}
+        }
+
+        let settings = BufferSettings {
+            indent: merged.indent,
+            trim_on_save: merged.trim_on_save.unwrap_or(false),
+            format_command: merged.format_command,
+            rulers: merged.rulers.unwrap_or_else(|| self.rulers.clone()),
+            project,
+        };
+        (settings, error)
+    }
+
+    /// The command from [`Settings::run_commands`] for the project in `dir`, if any.
+    pub fn run_command(&self, dir: &Path) -> Option<&str> {
+        self.run_commands
+            .iter()
+            .find(|(file, _)| dir.join(file).exists())
+            .map(|(_, command)| command.as_str())
+    }
+}
+
+/// Name of the file with the settings of a project, looked for in the directory of each opened file and then in the
+/// directories above it.
+pub const PROJECT_FILE: &str = ".ded.toml";
+
+/// Settings that can differ between projects, read from a [`PROJECT_FILE`] or from the global `config.toml`. Both
+/// have a `key = value` per line, a subset of TOML:
+///
+/// ```toml
+/// indent = 4  # or "tabs"
+/// trim_on_save = true
+/// format_command = "rustfmt --edition 2024"
+/// rulers = [80, 100]
+/// ```
+///
+/// A key left out is taken from the layer below: the project file over the global file over the built-in defaults. A
+/// key that is set replaces the value below it whole, lists included. Only the nearest project file is read, so one
+/// in a subdirectory doesn't inherit from one further up.
+///
+/// The [`Theme`] and [`InvalidKey`] are the same for every file, so `theme`, `theme.<slot>` and `invalid_key` only count
+/// in the global file. So does `format_command`, as saving runs it: a project file comes with whatever was cloned or
+/// unpacked, and opening a file in there mustn't run commands from it.
+#[derive(Debug, Clone, Default)]
+pub struct FileSettings {
+    pub indent: Option<Indent>,
+    pub trim_on_save: Option<bool>,
+    pub format_command: Option<String>,
+    pub rulers: Option<Vec<usize>>,
+    pub theme: Option<Theme>,
+    /// Colors changed in the theme, by slot, kept to change them again in the next theme when cycling.
+    pub theme_colors: Vec<(String, Color)>,
+    pub invalid_key: Option<InvalidKey>,
+}
+
+impl FileSettings {
+    /// Reads the settings from `path`, or `None` if there is no file there.
+    pub fn load(path: &Path) -> Result<Option<Self>, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text).map(Some),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+
+    /// The [`PROJECT_FILE`] in `dir` or the nearest directory above it, with its settings.
+    fn find(dir: &Path) -> Option<(PathBuf, Result<Self, String>)> {
+        dir.ancestors().find_map(|dir| {
+            let path = dir.join(PROJECT_FILE);
+            Self::load(&path).transpose().map(|settings| (path, settings))
+        })
+    }
+
+    /// Unknown keys are errors rather than ignored, so a misspelled one doesn't go unnoticed.
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut settings = Self::default();
+        for (idx, line) in text.lines().enumerate() {
+            let error = |message: &str| format!("line {}: {message}", idx + 1);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') {
+                return Err(error("tables aren't supported"));
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| error("expected key = value"))?;
+            let value = Value::parse(value.trim()).map_err(|err| error(&err))?;
+            match (key.trim(), value) {
+                ("indent", Value::Integer(spaces)) if spaces > 0 => settings.indent = Some(Indent::from(spaces)),
+                ("indent", Value::String(tabs)) if tabs == "tabs" => settings.indent = Some(Indent::Tabs),
+                ("indent", _) => return Err(error("indent is a number of spaces or \"tabs\"")),
+                ("trim_on_save", Value::Bool(trim)) => settings.trim_on_save = Some(trim),
+                ("trim_on_save", _) => return Err(error("trim_on_save is true or false")),
+                ("format_command", Value::String(command)) => settings.format_command = Some(command),
+                ("format_command", _) => return Err(error("format_command is a string")),
+                ("rulers", Value::Integers(columns)) => settings.rulers = Some(columns),
+                ("rulers", _) => return Err(error("rulers is a list of columns, like [80, 100]")),
+                ("theme", Value::String(name)) => {
+                    let theme = Theme::named(&name).ok_or_else(|| {
+                        let names = Theme::BUILT_IN.map(|theme| theme.name).join(", ");
+                        error(&format!("no theme {name}, there are {names}"))
+                    })?;
+                    settings.theme = Some(theme);
+                }
+                ("theme", _) => return Err(error("theme is a name, like \"light\"")),
+                (key, Value::String(color)) if let Some(slot) = key.strip_prefix("theme.") => {
+                    let color = Color::from_str(&color).map_err(|_| error(&format!("no color {color}")))?;
+                    Theme::default().set(slot, color).map_err(|err| error(&err))?;
+                    settings.theme_colors.push((slot.to_string(), color));
+                }
+                ("invalid_key", Value::String(name))
+                    if let Some(&(_, invalid_key)) = InvalidKey::NAMES.iter().find(|(n, _)| *n == name) =>
+                {
+                    settings.invalid_key = Some(invalid_key);
+                }
+                ("invalid_key", _) => return Err(error("invalid_key is \"silent\", \"flash\", \"bell\" or \"both\"")),
+                (key, _) if key.starts_with("theme.") => {
+                    return Err(error("theme colors are names or #rrggbb, like \"lightblue\""));
+                }
+                (key, _) => return Err(error(&format!("unknown key {key}"))),
+            }
+        }
+        Ok(settings)
+    }
+
+    /// These settings with the project file's `over` replaced, except for the ones only the global file sets.
+    fn merge(self, over: Self) -> Self {
+        Self {
+            indent: over.indent.or(self.indent),
+            trim_on_save: over.trim_on_save.or(self.trim_on_save),
+            rulers: over.rulers.or(self.rulers),
+            ..self
+        }
+    }
+
+    /// The theme these settings pick, with their colors changed.
+    pub fn pick_theme(&self) -> Theme {
+        self.theme.unwrap_or_default().with_colors(&self.theme_colors)
+    }
+}
+
+/// A value in a settings file.
+enum Value {
+    String(String),
+    Integer(usize),
+    Bool(bool),
+    Integers(Vec<usize>),
+}
+
+impl Value {
+    /// Parses `text`, which may be followed by a comment.
+    fn parse(text: &str) -> Result<Self, String> {
+        let (value, rest) = if let Some(rest) = text.strip_prefix('"') {
+            let mut value = String::new();
+            let mut chars = rest.char_indices();
+            loop {
+                match chars.next() {
+                    None => return Err("unterminated string".into()),
+                    Some((idx, '"')) => break (Value::String(value), &rest[idx + 1..]),
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, '"')) => value.push('"'),
+                        Some((_, '\\')) => value.push('\\'),
+                        Some((_, 'n')) => value.push('\n'),
+                        Some((_, 't')) => value.push('\t'),
+                        _ => return Err("unsupported escape in string".into()),
+                    },
+                    Some((_, c)) => value.push(c),
+                }
+            }
+        } else if let Some(rest) = text.strip_prefix('\'') {
+            let (value, rest) = rest.split_once('\'').ok_or("unterminated string")?;
+            (Value::String(value.to_string()), rest)
+        } else if let Some(rest) = text.strip_prefix('[') {
+            let (items, rest) = rest.split_once(']').ok_or("unterminated list")?;
+            let numbers = items
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| item.parse().map_err(|_| format!("{item} isn't a number")))
+                .collect::<Result<_, _>>()?;
+            (Value::Integers(numbers), rest)
+        } else {
+            let (word, rest) = text.split_at(text.find('#').unwrap_or(text.len()));
+            let value = match word.trim() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                word => Value::Integer(word.parse().map_err(|_| format!("can't read {word}"))?),
+            };
+            (value, rest)
+        };
+
+        let rest = rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(format!("unexpected {rest}"));
+        }
+        Ok(value)
+    }
+}
+
+/// The settings of one buffer, with the layers of [`FileSettings`] merged.
+#[derive(Debug, Clone, Default)]
+pub struct BufferSettings {
+    /// Indent style forced on the file, after [`Settings::indent_overrides`] as those are for formats that need one.
+    pub indent: Option<Indent>,
+    /// Removes trailing whitespace from every line when saving.
+    pub trim_on_save: bool,
+    /// Shell command the text is piped through when saving, what it prints replacing the text.
+    pub format_command: Option<String>,
+    pub rulers: Vec<usize>,
+    /// The project file the settings came from, if there is one.
+    pub project: Option<PathBuf>,
+}
+
+#[test]
+fn indent_override() {
+    let settings = Settings::default();
+    assert!(matches!(
+        settings.indent_override(Path::new("src/Makefile")),
+        Some(Indent::Tabs)
+    ));
+    assert!(matches!(
+        settings.indent_override(Path::new("rules.mk")),
+        Some(Indent::Tabs)
+    ));
+    assert!(settings.indent_override(Path::new("Makefile.rs")).is_none());
+    assert!(settings.indent_override(Path::new("main.rs")).is_none());
+}
+
+#[test]
+fn run_command() {
+    let settings = Settings::default();
+    assert_eq!(
+        settings.run_command(Path::new(env!("CARGO_MANIFEST_DIR"))),
+        Some("cargo build")
+    );
+    assert_eq!(settings.run_command(Path::new("ded-test-missing")), None);
+}
+
+#[test]
+fn is_prose() {
+    let settings = Settings::default();
+    assert!(settings.is_prose(Path::new("README.md")));
+    assert!(settings.is_prose(Path::new("notes/TODO.TXT")));
+    assert!(!settings.is_prose(Path::new("src/main.rs")));
+    assert!(!settings.is_prose(Path::new("Makefile")));
+
+    let settings = Settings {
+        spell_check: false,
+        ..Settings::default()
+    };
+    assert!(!settings.is_prose(Path::new("README.md")));
+    assert!(settings.has_prose_extension(Path::new("README.md")));
+}
+
+#[test]
+fn parse_file_settings() {
+    let settings = FileSettings::parse(
+        "# comment\n\nindent = 2\ntrim_on_save = false  # for now\nformat_command = \"fmt \\\"#\\\"\" # quoted\n\
+         rulers = [72, 100,]\n",
+    )
+    .unwrap();
+    assert_eq!(
+        settings.indent.map(|indent| indent.to_string()).as_deref(),
+        Some("2 spaces")
+    );
+    assert_eq!(settings.trim_on_save, Some(false));
+    assert_eq!(settings.format_command.as_deref(), Some("fmt \"#\""));
+    assert_eq!(settings.rulers, Some(vec![72, 100]));
+    assert!(matches!(
+        FileSettings::parse("indent = 'tabs'").unwrap().indent,
+        Some(Indent::Tabs)
+    ));
+
+    let theme = FileSettings::parse("theme = 'light'\ntheme.match_bg = '#264f78'\ntheme.ruler = 'dark gray'").unwrap();
+    let picked = theme.pick_theme();
+    assert_eq!(picked.name, "light");
+    assert_eq!(picked.match_bg, Color::Rgb(0x26, 0x4f, 0x78));
+    assert_eq!(picked.ruler, Color::DarkGray);
+    assert_eq!(picked.selection_bg, Theme::LIGHT.selection_bg);
+    let bell = FileSettings::parse("invalid_key = \"bell\"").unwrap();
+    assert_eq!(bell.invalid_key, Some(InvalidKey::Bell));
+
+    for (text, error) in [
+        ("indent", "line 1: expected key = value"),
+        ("indent = 0", "line 1: indent is a number of spaces or \"tabs\""),
+        ("\n[editor]", "line 2: tables aren't supported"),
+        ("rulers = [80, wide]", "line 1: wide isn't a number"),
+        ("format_command = \"rustfmt", "line 1: unterminated string"),
+        ("format_command = 'a' 'b'", "line 1: unexpected 'b'"),
+        ("trim_on_save = yes", "line 1: can't read yes"),
+        ("tab_width = 4", "line 1: unknown key tab_width"),
+        (
+            "invalid_key = 'beep'",
+            "line 1: invalid_key is \"silent\", \"flash\", \"bell\" or \"both\"",
+        ),
+        (
+            "theme = 'solarized'",
+            "line 1: no theme solarized, there are dark, light, high-contrast",
+        ),
+        ("theme.cursor = 'red'", "line 1: unknown theme slot cursor"),
+        ("theme.ruler = 'reddish'", "line 1: no color reddish"),
+        (
+            "theme.ruler = 1",
+            "line 1: theme colors are names or #rrggbb, like \"lightblue\"",
+        ),
+    ] {
+        assert_eq!(FileSettings::parse(text).unwrap_err(), error, "{text}");
+    }
+}
+
+#[test]
+fn project_settings() {
+    let dir = env::temp_dir().join("ded-test-project-settings");
+    _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("inner").join("deeper")).unwrap();
+    fs::create_dir(dir.join("other")).unwrap();
+    fs::write(
+        dir.join(PROJECT_FILE),
+        "indent = \"tabs\"\nrulers = [100, 120]\nformat_command = 'cat'\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("inner").join(PROJECT_FILE),
+        "trim_on_save = true\nrulers = []\n",
+    )
+    .unwrap();
+    let settings = Settings {
+        global: FileSettings::parse("indent = 2\nformat_command = 'fmt'").unwrap(),
+        ..Settings::default()
+    };
+
+    // the project file over the global one over the defaults, except for the command it would run
+    let (other, error) = settings.for_dir(Some(&dir.join("other")));
+    assert!(error.is_none());
+    assert!(matches!(other.indent, Some(Indent::Tabs)));
+    assert!(!other.trim_on_save);
+    assert_eq!(other.format_command.as_deref(), Some("fmt"));
+    assert_eq!(other.rulers, [100, 120]);
+    assert_eq!(other.project, Some(dir.join(PROJECT_FILE)));
+
+    // only the nearest project file is read
+    let (deeper, _) = settings.for_dir(Some(&dir.join("inner").join("deeper")));
+    assert_eq!(
+        deeper.indent.map(|indent| indent.to_string()).as_deref(),
+        Some("2 spaces")
+    );
+    assert!(deeper.trim_on_save);
+    assert_eq!(deeper.format_command.as_deref(), Some("fmt"));
+    assert!(deeper.rulers.is_empty());
+    assert_eq!(deeper.project, Some(dir.join("inner").join(PROJECT_FILE)));
+
+    let (none, error) = Settings::default().for_dir(None);
+    assert!(error.is_none() && none.project.is_none());
+    assert!(none.indent.is_none() && !none.trim_on_save && none.format_command.is_none());
+    assert_eq!(none.rulers, [80]);
+
+    // a project file that can't be read is left out
+    fs::write(dir.join("inner").join(PROJECT_FILE), "trim_on_save = yes\n").unwrap();
+    let (inner, error) = settings.for_dir(Some(&dir.join("inner")));
+    let path = dir.join("inner").join(PROJECT_FILE);
+    assert_eq!(
+        error,
+        Some(format!("Ignoring {}: line 1: can't read yes", path.display()))
+    );
+    assert!(inner.project.is_none() && !inner.trim_on_save);
+    assert_eq!(inner.format_command.as_deref(), Some("fmt"));
+}
@@ -0,0 +1,202 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom};
+use std::path::Path;
+
+/// Where a tag points to in its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Address {
+    /// Zero-based.
+    Line(usize),
+    /// The text of the line, matched at its start, and up to its end too if `whole_line`.
+    Pattern { text: String, whole_line: bool },
+}
+
+/// A definition found in a `tags` file, as written by universal-ctags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    pub file: String,
+    pub address: Address,
+    /// Zero-based, from the `line:` field, to fall back on when the pattern isn't found anymore.
+    pub line: Option<usize>,
+}
+
+impl Tag {
+    /// Parses a `name<TAB>file<TAB>address;"<TAB>fields` line.
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(3, '\t');
+        let name = parts.next()?;
+        let file = parts.next()?;
+        let rest = parts.next()?;
+        let (address, fields) = rest.split_once(";\"").unwrap_or((rest, ""));
+
+        let address = if let Ok(n) = address.parse::<usize>() {
+            Address::Line(n.saturating_sub(1))
+        } else {
+            let delimiter = address.chars().next().filter(|&c| c == '/' || c == '?')?;
+            let text = address.strip_prefix(delimiter)?.strip_suffix(delimiter)?;
+            let text = text.strip_prefix('^').unwrap_or(text);
+            let (text, whole_line) = match text.strip_suffix('$') {
+                Some(text) if !text.ends_with('\\') => (text, true),
+                _ => (text, false),
+            };
+            Address::Pattern {
+                text: unescape(text, delimiter),
+                whole_line,
+            }
+        };
+        let line = fields
+            .split('\t')
+            .find_map(|field| field.strip_prefix("line:")?.parse::<usize>().ok())
+            .map(|n| n.saturating_sub(1));
+
+        Some(Self {
+            name: name.to_string(),
+            file: file.to_string(),
+            address,
+            line,
+        })
+    }
+
+    /// The row the tag points to in `lines`, falling back on the line number if the pattern isn't there.
+    pub fn find(&self, lines: &[String]) -> Option<usize> {
+        match &self.address {
+            Address::Line(row) => Some(*row),
+            Address::Pattern { text, whole_line } => lines
+                .iter()
+                .position(|line| {
+                    if *whole_line {
+                        line == text
+                    } else {
+                        line.starts_with(text.as_str())
+                    }
+                })
+                .or(self.line),
+        }
+    }
+}
+
+/// Undoes the escaping of `\`, and of the delimiter, in a ctags search pattern.
+fn unescape(text: &str, delimiter: char) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some(next)) if next == '\\' || next == delimiter => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+/// Looks up the tags called `name` in the sorted tags file at `path`. The file is binary searched, so only a few of
+/// its lines are read.
+pub fn lookup(path: &Path, name: &str) -> io::Result<Vec<Tag>> {
+    let file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+
+    // the first offset where the next line starting there isn't sorted before `name`
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        match line_from(&mut reader, mid)? {
+            Some(line) if tag_name(&line).cmp(name.as_bytes()) == Ordering::Less => lo = mid + 1,
+            _ => hi = mid,
+        }
+    }
+
+    let mut tags = Vec::new();
+    let mut line = line_from(&mut reader, lo)?;
+    while let Some(bytes) = line.take().filter(|line| tag_name(line) == name.as_bytes()) {
+        tags.extend(Tag::parse(&String::from_utf8_lossy(&bytes)));
+        line = next_line(&mut reader)?;
+    }
+    Ok(tags)
+}
+
+/// Reads the first line that starts at or after `offset`.
+fn line_from(reader: &mut BufReader<File>, offset: u64) -> io::Result<Option<Vec<u8>>> {
+    if offset > 0 {
+        // the byte before tells if a line starts right at `offset`
+        reader.seek(SeekFrom::Start(offset - 1))?;
+        reader.skip_until(b'\n')?;
+    } else {
+        reader.seek(SeekFrom::Start(0))?;
+    }
+    next_line(reader)
+}
+
+fn next_line(reader: &mut BufReader<File>) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    while line.last().is_some_and(|&b| b == b'\n' || b == b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+fn tag_name(line: &[u8]) -> &[u8] {
+    line.split(|&b| b == b'\t').next().unwrap_or_default()
+}
+
+#[test]
+fn parse() {
+    assert_eq!(
+        Tag::parse("main\tsrc/main.rs\t/^fn main() -> Result<()> {$/;\"\tf\tline:49"),
+        Some(Tag {
+            name: "main".to_string(),
+            file: "src/main.rs".to_string(),
+            address: Address::Pattern {
+                text: "fn main() -> Result<()> {".to_string(),
+                whole_line: true,
+            },
+            line: Some(48),
+        })
+    );
+    let tag = Tag::parse("a\tb.c\t/^int a(char \\/* c \\\\ *\\/);/;\"\tp").unwrap();
+    assert_eq!(
+        tag.address,
+        Address::Pattern {
+            text: r"int a(char /* c \ */);".to_string(),
+            whole_line: false,
+        }
+    );
+    assert_eq!(Tag::parse("N\tb.h\t12;\"\td").unwrap().address, Address::Line(11));
+
+    let lines = ["x", "int a(char /* c \\ */);", "int a;"].map(String::from);
+    assert_eq!(tag.find(&lines), Some(1));
+    assert_eq!(tag.find(&lines[2..]), None);
+}
+
+#[test]
+fn binary_search() {
+    let mut contents = "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/\n".to_string();
+    let names = (0..2000).map(|i| format!("name{i:04}")).collect::<Vec<_>>();
+    for name in &names {
+        contents.push_str(&format!("{name}\tsrc/{name}.rs\t/^fn {name}() {{$/;\"\tf\n"));
+        if name == "name0500" {
+            contents.push_str(&format!("{name}\tsrc/other.rs\t7;\"\tf\n"));
+        }
+    }
+    let path = std::env::temp_dir().join("ded-test-tags");
+    std::fs::write(&path, contents).unwrap();
+
+    for name in ["name0000", "name0999", "name1999"] {
+        let tags = lookup(&path, name).unwrap();
+        assert_eq!(tags.len(), 1);
+        assert_eq!(tags[0].file, format!("src/{name}.rs"));
+    }
+    let tags = lookup(&path, "name0500").unwrap();
+    assert_eq!(tags.len(), 2);
+    assert_eq!(tags[1].address, Address::Line(6));
+    assert!(lookup(&path, "name").unwrap().is_empty());
+    assert!(lookup(&path, "zzz").unwrap().is_empty());
+    assert!(lookup(&path, "!").unwrap().is_empty());
+}
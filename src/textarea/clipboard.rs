@@ -0,0 +1,133 @@
+use std::collections::VecDeque;
+
+const HISTORY_LEN: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clip {
+    pub text: String,
+    /// Whole lines copied without a selection, pasted above the cursor line instead of at the cursor.
+    pub linewise: bool,
+}
+
+/// System clipboard with a ring of the most recently copied texts.
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+    history: VecDeque<Clip>,
+    /// A named register copied to and pasted from instead, while one is in use.
+    register: Option<Option<Clip>>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self {
+            // tests must not touch the clipboard of whoever runs them
+            system: if cfg!(test) {
+                None
+            } else {
+                arboard::Clipboard::new().ok()
+            },
+            history: VecDeque::with_capacity(HISTORY_LEN),
+            register: None,
+        }
+    }
+}
+
+impl Clipboard {
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.set(Clip {
+            text: text.into(),
+            linewise: false,
+        });
+    }
+
+    pub fn set_line(&mut self, line: &str) {
+        self.set(Clip {
+            text: format!("{line}\n"),
+            linewise: true,
+        });
+    }
+
+    /// Makes copies and pastes use a named register holding `clip` until [`Clipboard::take_register`], leaving the
+    /// system clipboard and the history alone.
+    pub fn use_register(&mut self, clip: Option<Clip>) {
+        self.register = Some(clip);
+    }
+
+    /// Stops using the register and returns what it holds now.
+    pub fn take_register(&mut self) -> Option<Clip> {
+        self.register.take().flatten()
+    }
+
+    /// Returns the newest entry, merging in the system clipboard if it was changed by another program.
+    pub fn get(&mut self) -> Option<Clip> {
+        if let Some(register) = &self.register {
+            return register.clone();
+        }
+        if let Some(text) = self.system.as_mut().and_then(|system| system.get_text().ok())
+            && self.history.front().is_none_or(|clip| clip.text != text)
+        {
+            self.push(Clip { text, linewise: false });
+        }
+
+        self.history.front().cloned()
+    }
+
+    /// Returns the entry `idx` places back in the history, 0 being the newest.
+    pub fn history(&self, idx: usize) -> Option<&Clip> {
+        self.history.get(idx)
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    fn set(&mut self, clip: Clip) {
+        if let Some(register) = &mut self.register {
+            *register = Some(clip);
+            return;
+        }
+        if let Some(system) = &mut self.system {
+            _ = system.set_text(&clip.text);
+        }
+        self.push(clip);
+    }
+
+    fn push(&mut self, clip: Clip) {
+        if let Some(idx) = self.history.iter().position(|c| c.text == clip.text) {
+            self.history.remove(idx);
+        }
+        self.history.push_front(clip);
+        self.history.truncate(HISTORY_LEN);
+    }
+}
+
+#[test]
+fn test() {
+    let mut clipboard = Clipboard::default();
+    assert_eq!(clipboard.get(), None);
+
+    clipboard.set_text("a");
+    clipboard.set_line("b");
+    clipboard.set_text("a");
+    assert_eq!(clipboard.get().map(|clip| clip.text).as_deref(), Some("a"));
+    assert_eq!(
+        clipboard.history(1),
+        Some(&Clip {
+            text: "b\n".to_string(),
+            linewise: true
+        })
+    );
+    assert_eq!(clipboard.history_len(), 2);
+
+    (0..30).for_each(|i| clipboard.set_text(i.to_string()));
+    assert_eq!(clipboard.history_len(), HISTORY_LEN);
+    assert_eq!(clipboard.history(0).map(|clip| clip.text.as_str()), Some("29"));
+
+    // a register doesn't touch the history
+    clipboard.use_register(None);
+    assert_eq!(clipboard.get(), None);
+    clipboard.set_line("c");
+    assert_eq!(clipboard.get().map(|clip| clip.text).as_deref(), Some("c\n"));
+    assert!(clipboard.take_register().is_some_and(|clip| clip.linewise));
+    assert_eq!(clipboard.get().map(|clip| clip.text).as_deref(), Some("29"));
+}
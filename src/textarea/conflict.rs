@@ -0,0 +1,114 @@
+use std::ops::RangeInclusive;
+
+const START: &str = "<<<<<<<";
+const BASE: &str = "|||||||";
+const SEPARATOR: &str = "=======";
+const END: &str = ">>>>>>>";
+
+/// Which half of a conflict to keep when resolving it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Ours,
+    Theirs,
+    Both,
+}
+
+/// Rows of the marker lines of a merge conflict. `base` is only there in the diff3 style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Conflict {
+    pub start: usize,
+    pub base: Option<usize>,
+    pub separator: usize,
+    pub end: usize,
+}
+
+/// Whether `line` is one of the lines git puts around conflicts.
+pub fn is_marker(line: &str) -> bool {
+    [START, BASE, SEPARATOR, END]
+        .into_iter()
+        .any(|marker| is_a(line, marker))
+}
+
+pub fn is_start(line: &str) -> bool {
+    is_a(line, START)
+}
+
+fn is_a(line: &str, marker: &str) -> bool {
+    line.strip_prefix(marker)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+}
+
+impl Conflict {
+    /// Finds the conflict `row` is in, refusing nested or unterminated ones.
+    pub fn at(lines: &[String], row: usize) -> Result<Self, &'static str> {
+        let start = lines[..=row]
+            .iter()
+            .rposition(|line| is_start(line))
+            .filter(|&start| !lines[start..row].iter().any(|line| is_a(line, END)))
+            .ok_or("Not in a conflict")?;
+
+        let (mut base, mut separator) = (None, None);
+        for (idx, line) in lines.iter().enumerate().skip(start + 1) {
+            if is_start(line) {
+                return Err("Nested conflicts can't be resolved");
+            } else if is_a(line, BASE) && base.is_none() && separator.is_none() {
+                base = Some(idx);
+            } else if is_a(line, SEPARATOR) && separator.is_none() {
+                separator = Some(idx);
+            } else if is_a(line, END) {
+                let separator = separator.ok_or("Conflict has no ======= line")?;
+                return Ok(Self {
+                    start,
+                    base,
+                    separator,
+                    end: idx,
+                });
+            }
+        }
+
+        Err("Conflict has no end")
+    }
+
+    /// Rows to delete to keep `side`, bottom first so deleting one doesn't shift the others.
+    pub fn rows_to_remove(&self, side: Side) -> Vec<RangeInclusive<usize>> {
+        let ours_end = self.base.unwrap_or(self.separator);
+        match side {
+            Side::Ours => vec![ours_end..=self.end, self.start..=self.start],
+            Side::Theirs => vec![self.end..=self.end, self.start..=self.separator],
+            Side::Both => vec![self.end..=self.end, ours_end..=self.separator, self.start..=self.start],
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let lines = [
+        "a",
+        "<<<<<<< HEAD",
+        "ours",
+        "||||||| base",
+        "base",
+        "=======",
+        "theirs",
+        ">>>>>>> branch",
+        "b",
+    ]
+    .map(String::from);
+    let conflict = Conflict {
+        start: 1,
+        base: Some(3),
+        separator: 5,
+        end: 7,
+    };
+    assert_eq!(Conflict::at(&lines, 0), Err("Not in a conflict"));
+    assert_eq!(Conflict::at(&lines, 1), Ok(conflict));
+    assert_eq!(Conflict::at(&lines, 7), Ok(conflict));
+    assert_eq!(Conflict::at(&lines, 8), Err("Not in a conflict"));
+    assert_eq!(conflict.rows_to_remove(Side::Both), [7..=7, 3..=5, 1..=1]);
+    assert!(is_marker("=======") && !is_marker("========") && !is_marker(" <<<<<<<"));
+
+    let nested = ["<<<<<<<", "<<<<<<<", "=======", ">>>>>>>", "=======", ">>>>>>>"].map(String::from);
+    assert_eq!(Conflict::at(&nested, 0), Err("Nested conflicts can't be resolved"));
+    let unterminated = ["<<<<<<<", "a", "======="].map(String::from);
+    assert_eq!(Conflict::at(&unterminated, 1), Err("Conflict has no end"));
+}
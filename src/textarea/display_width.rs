@@ -0,0 +1,50 @@
+use std::ops::Range;
+
+use unicode_width::UnicodeWidthChar;
+
+pub trait DisplayWidth {
+    fn display_col(&self, char_idx: usize, tab_width: usize) -> usize;
+    fn display_slice(&self, range: Range<usize>) -> (usize, &str);
+}
+
+impl DisplayWidth for str {
+    /// Display column at which the char at `char_idx` starts, with tabs being `tab_width` wide.
+    fn display_col(&self, char_idx: usize, tab_width: usize) -> usize {
+        self.chars()
+            .take(char_idx)
+            .map(|c| if c == '\t' { tab_width } else { c.width().unwrap_or(0) })
+            .sum()
+    }
+
+    /// The chars lying entirely within the display columns in `range`, and the column the first of them starts at,
+    /// which is past `range.start` when a wide char is cut off by it. Tabs must already be expanded.
+    fn display_slice(&self, range: Range<usize>) -> (usize, &str) {
+        let mut start = None;
+        let mut col = 0;
+        for (idx, c) in self.char_indices() {
+            if start.is_none() && col >= range.start {
+                start = Some((idx, col));
+            }
+
+            let width = c.width().unwrap_or(0);
+            if col + width > range.end {
+                return start.map_or((range.start, ""), |(start, start_col)| (start_col, &self[start..idx]));
+            }
+            col += width;
+        }
+
+        start.map_or((range.start, ""), |(start, start_col)| (start_col, &self[start..]))
+    }
+}
+
+#[test]
+fn test() {
+    assert_eq!("\tab".display_col(1, 4), 4);
+    assert_eq!("日本語abc".display_col(2, 4), 4);
+    assert_eq!("日本語abc".display_col(4, 4), 7);
+
+    assert_eq!("日本語abc".display_slice(2..4), (2, "本"));
+    assert_eq!("日本語abc".display_slice(1..5), (2, "本"));
+    assert_eq!("日本語abc".display_slice(6..100), (6, "abc"));
+    assert_eq!("abc".display_slice(5..10), (5, ""));
+}
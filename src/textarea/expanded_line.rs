@@ -0,0 +1,125 @@
+use std::cmp;
+use std::ops::Range;
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use super::display_width::DisplayWidth;
+use super::textarea::{dots, spaces};
+
+/// Chars between two checkpoints of an [`ExpandedLine`].
+const CHECKPOINT_INTERVAL: usize = 1024;
+
+/// A line as it is drawn, with tabs expanded and trailing whitespace shown as dots. Kept between frames, and with
+/// checkpoints to start from, so a long line isn't gone through from its start every time the cursor moves.
+pub struct ExpandedLine {
+    /// The line it was made from, to tell when that changed.
+    line: String,
+    tab_width: usize,
+    pub text: String,
+    /// Display width of `text`.
+    pub width: usize,
+    /// Display column the trailing whitespace starts at, `width` if there is none.
+    pub trailing: usize,
+    /// For every [`CHECKPOINT_INTERVAL`]th char: its byte index in the line, its display column and its byte index in
+    /// `text`.
+    checkpoints: Vec<(usize, usize, usize)>,
+}
+
+impl ExpandedLine {
+    pub fn new(line: &str, tab_width: usize) -> Self {
+        let trailing = line.trim_end_matches([' ', '\t']).len();
+        let mut text = String::with_capacity(line.len());
+        let mut checkpoints = vec![(0, 0, 0)];
+        let mut col = 0;
+        let mut trailing_col = None;
+        for (idx, (byte_idx, c)) in line.char_indices().enumerate() {
+            if idx > 0 && idx % CHECKPOINT_INTERVAL == 0 {
+                checkpoints.push((byte_idx, col, text.len()));
+            }
+            if byte_idx == trailing {
+                trailing_col = Some(col);
+            }
+            let width = if c == '\t' { tab_width } else { c.width().unwrap_or(0) };
+            // a dot per column, so columns in the text and the line stay the same
+            match c {
+                _ if byte_idx >= trailing => text.push_str(&dots(width)),
+                '\t' => text.push_str(&spaces(tab_width)),
+                c => text.push(c),
+            }
+            col += width;
+        }
+
+        Self {
+            line: line.to_string(),
+            tab_width,
+            width: text.width(),
+            trailing: trailing_col.unwrap_or(col),
+            text,
+            checkpoints,
+        }
+    }
+
+    /// Whether this was made from `line` with tabs `tab_width` wide.
+    pub fn is_of(&self, line: &str, tab_width: usize) -> bool {
+        self.tab_width == tab_width && self.line == line
+    }
+
+    /// Like [`DisplayWidth::display_col`] on the line.
+    pub fn display_col(&self, char_idx: usize) -> usize {
+        let checkpoint = cmp::min(char_idx / CHECKPOINT_INTERVAL, self.checkpoints.len() - 1);
+        let (byte_idx, col, _) = self.checkpoints[checkpoint];
+        col + self.line[byte_idx..].display_col(char_idx - checkpoint * CHECKPOINT_INTERVAL, self.tab_width)
+    }
+
+    /// Like [`DisplayWidth::display_col`] on the line, for the char at byte `byte_idx`.
+    pub fn byte_display_col(&self, byte_idx: usize) -> usize {
+        let checkpoint = self.checkpoints.partition_point(|&(byte, _, _)| byte <= byte_idx) - 1;
+        let (byte, col, _) = self.checkpoints[checkpoint];
+        col + self.line[byte..byte_idx].display_col(usize::MAX, self.tab_width)
+    }
+
+    /// Like [`DisplayWidth::display_slice`] on `text`.
+    pub fn display_slice(&self, range: Range<usize>) -> (usize, &str) {
+        let checkpoint = self.checkpoints.partition_point(|&(_, col, _)| col <= range.start) - 1;
+        let (_, col, text_idx) = self.checkpoints[checkpoint];
+        let (start, slice) = self.text[text_idx..].display_slice(range.start - col..range.end.saturating_sub(col));
+        (col + start, slice)
+    }
+}
+
+#[test]
+fn test() {
+    let line = format!("{}\tab \t", "日本語x\t".repeat(1000));
+    let expanded = ExpandedLine::new(&line, 4);
+    assert_eq!(expanded.checkpoints.len(), 5);
+    assert!(expanded.text.ends_with("    ab·····"));
+    assert_eq!(expanded.width, 11 * 1000 + 11);
+    assert_eq!(expanded.trailing, 11 * 1000 + 6);
+    assert_eq!(ExpandedLine::new("ab", 4).trailing, 2);
+    assert_eq!(ExpandedLine::new(" \t", 4).trailing, 0);
+    // only spaces and tabs are trailing whitespace
+    assert_eq!(ExpandedLine::new("a\u{a0}", 4).trailing, 2);
+    assert_eq!(ExpandedLine::new("a\u{3000} ", 4).trailing, 3);
+
+    // the same as going through the whole line
+    let chars = line.chars().count();
+    for char_idx in [0, 3, 1023, 1024, 1025, 4000, chars - 1, chars, chars + 5] {
+        assert_eq!(
+            expanded.display_col(char_idx),
+            line.display_col(char_idx, 4),
+            "{char_idx}"
+        );
+    }
+    for range in [0..5, 1..6, 2000..2010, 5631..5700, 11_000..11_012, 11_010..usize::MAX] {
+        let whole = expanded.text.display_slice(range.clone());
+        assert_eq!(expanded.display_slice(range.clone()), whole, "{range:?}");
+    }
+    for (byte_idx, _) in line.char_indices().step_by(997).chain([(line.len(), ' ')]) {
+        let char_idx = line[..byte_idx].chars().count();
+        assert_eq!(expanded.byte_display_col(byte_idx), line.display_col(char_idx, 4));
+    }
+
+    assert!(expanded.is_of(&line, 4));
+    assert!(!expanded.is_of(&line, 8));
+    assert!(!expanded.is_of("", 4));
+}
@@ -1,3 +1,6 @@
+use std::cmp;
+use std::ops::{Range, RangeInclusive};
+
 use crate::textarea::{ByteIndex, CursorPosition};
 
 #[derive(Debug, Copy, Clone)]
@@ -49,6 +52,19 @@ pub enum HistoryAction {
         lines: (usize, usize),
         cursor: (CursorPosition, CursorPosition),
     },
+    /// Whole lines put in before line `row`, or after the last one when `row` is just past it.
+    InsertFullLines {
+        row: usize,
+        lines: Vec<String>,
+        cursor: (CursorPosition, CursorPosition),
+    },
+    /// The whole lines from line `row` on. Removing every line leaves an empty one, which the inverse doesn't take
+    /// back, so whole lines are replaced by inserting the new ones before removing the old.
+    RemoveFullLines {
+        row: usize,
+        lines: Vec<String>,
+        cursor: (CursorPosition, CursorPosition),
+    },
 }
 
 impl HistoryAction {
@@ -103,97 +119,380 @@ impl HistoryAction {
                 lines: (l2, l1),
                 cursor: (c2, c1),
             },
+            HistoryAction::InsertFullLines { row, lines, cursor: (c1, c2) } => {
+                HistoryAction::RemoveFullLines { row, lines, cursor: (c2, c1) }
+            }
+            HistoryAction::RemoveFullLines { row, lines, cursor: (c1, c2) } => {
+                HistoryAction::InsertFullLines { row, lines, cursor: (c2, c1) }
+            }
         }
     }
 
-    pub fn apply(&self, lines: &mut Vec<String>) -> CursorPosition {
+    /// Whether the action always makes the text longer by a line or more, which a single line text rejects.
+    pub fn adds_lines(&self) -> bool {
         match self {
+            HistoryAction::InsertLinebreak { .. } => true,
+            HistoryAction::InsertFullLines { lines, .. } => !lines.is_empty(),
+            _ => false,
+        }
+    }
+
+    /// The lines the action leaves changed, once done.
+    pub fn rows(&self) -> RangeInclusive<usize> {
+        match self {
+            HistoryAction::InsertChar { position, .. }
+            | HistoryAction::RemoveChar { position, .. }
+            | HistoryAction::RemoveLinebreak { position, .. }
+            | HistoryAction::RemoveLines { position, .. } => position.row..=position.row,
+            HistoryAction::InsertLinebreak { position, .. } => position.row..=position.row + 1,
+            HistoryAction::InsertLines { lines, position, .. } => {
+                // whole lines put in before a line leave that line as it was
+                let whole_lines = position.col == 0 && lines.len() > 1 && lines.last().is_some_and(String::is_empty);
+                position.row..=position.row + lines.len().saturating_sub(1 + usize::from(whole_lines))
+            }
+            HistoryAction::SwapLines { lines: (a, b), .. } => cmp::min(*a, *b)..=cmp::max(*a, *b),
+            HistoryAction::InsertFullLines { row, lines, .. } => *row..=row + lines.len().saturating_sub(1),
+            // the line that moved up into their place
+            HistoryAction::RemoveFullLines { row, .. } => *row..=*row,
+        }
+    }
+
+    /// The lines the action changes when done to `lines`, as they are before it, for an action that
+    /// [fits](HistoryAction::fits) them. The same rows, moved down by how many lines it adds, hold what it leaves
+    /// behind, and every line around them is kept as it was.
+    pub fn rows_before(&self, lines: &[String]) -> Range<usize> {
+        let len = lines.len();
+        match self {
+            HistoryAction::InsertChar { position, .. }
+            | HistoryAction::RemoveChar { position, .. }
+            | HistoryAction::InsertLinebreak { position, .. }
+            | HistoryAction::InsertLines { position, .. } => position.row..cmp::min(position.row + 1, len),
+            HistoryAction::RemoveLinebreak { position, .. } => position.row..position.row + 2,
+            HistoryAction::RemoveLines { lines: ls, position, .. } => {
+                position.row..cmp::min(position.row + cmp::max(ls.len(), 1), len)
+            }
+            HistoryAction::SwapLines { lines: (a, b), .. } => cmp::min(*a, *b)..cmp::max(*a, *b) + 1,
+            HistoryAction::InsertFullLines { row, .. } => *row..*row,
+            HistoryAction::RemoveFullLines { row, lines: ls, .. } => *row..row + ls.len(),
+        }
+    }
+
+    /// Whether the action can be done to `lines`: its positions are on char boundaries within them, and the text it
+    /// removes is there. One that doesn't fit is stale, made for some other text.
+    pub fn fits(&self, lines: &[String]) -> bool {
+        let rest = |position: &BytePosition| lines.get(position.row).and_then(|line| line.get(position.col..));
+        match self {
+            HistoryAction::InsertChar { position, .. } | HistoryAction::InsertLinebreak { position, .. } => {
+                rest(position).is_some()
+            }
+            HistoryAction::RemoveChar { char, position, .. } => {
+                rest(position).is_some_and(|rest| rest.starts_with(*char))
+            }
+            HistoryAction::RemoveLinebreak { position, .. } => {
+                position.row + 1 < lines.len() && rest(position) == Some("")
+            }
+            HistoryAction::InsertLines { lines: ls, position, .. } => {
+                // several lines may also go just past the last one, where they are appended whole
+                ls.is_empty()
+                    || rest(position).is_some()
+                    || (ls.len() > 1 && position.row == lines.len() && position.col == 0)
+            }
+            HistoryAction::RemoveLines { lines: ls, position, .. } => match ls.as_slice() {
+                [] => true,
+                [line] => rest(position).is_some_and(|rest| rest.starts_with(line.as_str())),
+                // lines that were appended past the last one
+                [appended @ .., last] if position.row + ls.len() == lines.len() + 1 => {
+                    position.col == 0 && last.is_empty() && lines[position.row..] == *appended
+                }
+                [first, middle @ .., last] => {
+                    let end = position.row + ls.len() - 1;
+                    rest(position) == Some(first.as_str())
+                        && lines.get(position.row + 1..end) == Some(middle)
+                        && lines.get(end).is_some_and(|line| line.starts_with(last.as_str()))
+                }
+            },
+            HistoryAction::SwapLines { lines: (a, b), .. } => *a < lines.len() && *b < lines.len(),
+            HistoryAction::InsertFullLines { row, .. } => *row <= lines.len(),
+            HistoryAction::RemoveFullLines { row, lines: ls, .. } => lines.get(*row..row + ls.len()) == Some(ls),
+        }
+    }
+
+    /// Does the action to `lines`, returning the cursor after it. Positions are in bytes. An action that doesn't
+    /// [fit](HistoryAction::fits) them is left undone and returns `None`.
+    pub fn apply(&self, lines: &mut Vec<String>) -> Option<CursorPosition> {
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        if !self.fits(lines) {
+            return None;
+        }
+
+        let cursor = match self {
             HistoryAction::InsertChar { char, position, cursor: (_, c) } => {
                 lines[position.row].insert(position.col, *char);
-                *c
+                c
             }
             HistoryAction::RemoveChar { position, cursor: (_, c), .. } => {
                 lines[position.row].remove(position.col);
-                *c
+                c
             }
             HistoryAction::InsertLinebreak { position, cursor: (_, c) } => {
-                lines.insert(position.row + 1, lines[position.row][position.col..].to_string());
-                lines[position.row].drain(position.col..);
-                *c
+                let rest = lines[position.row].split_off(position.col);
+                lines.insert(position.row + 1, rest);
+                c
             }
             HistoryAction::RemoveLinebreak { position, cursor: (_, c) } => {
-                let (a, b) = lines.split_at_mut(position.row + 1);
-                a.last_mut().unwrap().push_str(b.first().unwrap());
-                lines.remove(position.row + 1);
-                *c
+                let next = lines.remove(position.row + 1);
+                lines[position.row].push_str(&next);
+                c
             }
             HistoryAction::InsertLines {
                 lines: ls,
-                position,
+                position: BytePosition { row, col },
                 cursor: (_, c),
             } => {
                 match ls.len() {
                     0 => {}
-                    1 => {
-                        lines[position.row].insert_str(position.col, ls.first().unwrap());
-                    }
+                    1 => lines[*row].insert_str(*col, &ls[0]),
+                    // just past the last line, all but the empty line after the last linebreak are appended as whole
+                    // lines
+                    _ if *row == lines.len() => lines.extend(ls[..ls.len() - 1].iter().cloned()),
                     _ => {
-                        if let Some((l1, l2)) = lines.get(position.row).map(|l| l.split_at(position.col)) {
-                            let mut first_line = String::from(l1);
-                            first_line.push_str(ls.first().unwrap());
-
-                            let mut last_line = String::from(ls.last().unwrap());
-                            last_line.push_str(l2);
-
-                            lines[position.row] = first_line;
-                            lines.splice(
-                                position.row + 1..position.row + 1,
-                                ls.iter().skip(1).map(|l| l.to_string()),
-                            );
-                            lines[position.row + ls.len() - 1] = last_line;
-                        } else {
-                            lines.splice(
-                                position.row..position.row,
-                                ls.iter().take(ls.len() - 1).map(|l| l.to_string()),
-                            );
-                        }
+                        let mut last_line = ls.last().unwrap().clone();
+                        last_line.push_str(&lines[*row][*col..]);
+                        lines[*row].truncate(*col);
+                        lines[*row].push_str(&ls[0]);
+                        lines.splice(row + 1..row + 1, ls[1..ls.len() - 1].iter().cloned().chain([last_line]));
                     }
-                };
-
-                *c
+                }
+                c
             }
             HistoryAction::RemoveLines {
                 lines: ls,
-                position,
+                position: BytePosition { row, col },
                 cursor: (_, c),
             } => {
                 match ls.len() {
                     0 => {}
-                    1 => {
-                        lines[position.row].drain(position.col..position.col + ls.first().unwrap().len());
+                    1 => _ = lines[*row].drain(*col..col + ls[0].len()),
+                    // lines appended past the last one are taken back
+                    len if row + len > lines.len() => {
+                        lines.truncate(*row);
+                        if lines.is_empty() {
+                            lines.push(String::new());
+                        }
                     }
-                    _ => {
-                        let (a, b) = lines.split_at_mut(position.row + 1);
-                        a.last_mut().unwrap().replace_range(
-                            position.col..,
-                            b.get(ls.len() - 2)
-                                .map(|l| &l[ls.last().unwrap().len()..])
-                                .unwrap_or(""),
-                        );
-
-                        lines.drain((position.row + 1).min(lines.len())..(position.row + ls.len()).min(lines.len()));
+                    len => {
+                        let rest = lines[row + len - 1][ls.last().unwrap().len()..].to_string();
+                        lines[*row].truncate(*col);
+                        lines[*row].push_str(&rest);
+                        lines.drain(row + 1..row + len);
                     }
                 }
-
-                *c
+                c
             }
             HistoryAction::SwapLines {
                 lines: (l1, l2),
                 cursor: (_, c2),
             } => {
                 lines.swap(*l1, *l2);
-                *c2
+                c2
+            }
+            HistoryAction::InsertFullLines { row, lines: ls, cursor: (_, c) } => {
+                lines.splice(*row..*row, ls.iter().cloned());
+                c
             }
+            HistoryAction::RemoveFullLines { row, lines: ls, cursor: (_, c) } => {
+                lines.drain(*row..row + ls.len());
+                if lines.is_empty() {
+                    lines.push(String::new());
+                }
+                c
+            }
+        };
+        Some(*cursor)
+    }
+}
+
+#[test]
+fn apply_and_invert() {
+    // xorshift, so the sequences are random looking but the same on every run
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    let mut random = |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    };
+    let text = |random: &mut dyn FnMut(usize) -> usize| {
+        const CHARS: [char; 6] = ['a', 'é', '€', '😀', '\t', ' '];
+        (0..random(4)).map(|_| CHARS[random(CHARS.len())]).collect::<String>()
+    };
+    // a byte position on a char boundary
+    let position = |lines: &[String], random: &mut dyn FnMut(usize) -> usize| {
+        let row = random(lines.len());
+        let boundaries = lines[row]
+            .char_indices()
+            .map(|(idx, _)| idx)
+            .chain([lines[row].len()])
+            .collect::<Vec<_>>();
+        BytePosition {
+            row,
+            col: boundaries[random(boundaries.len())],
+        }
+    };
+    let cursor = (CursorPosition::default(), CursorPosition::default());
+
+    for _ in 0..200 {
+        let original = (0..1 + random(4)).map(|_| text(&mut random)).collect::<Vec<_>>();
+        let mut lines = original.clone();
+        let mut done = Vec::new();
+        for _ in 0..10 {
+            let at = position(&lines, &mut random);
+            let action = match random(8) {
+                0 => HistoryAction::InsertChar {
+                    char: text(&mut random).chars().next().unwrap_or('ü'),
+                    position: at,
+                    cursor,
+                },
+                1 if at.col < lines[at.row].len() => HistoryAction::RemoveChar {
+                    char: lines[at.row][at.col..].chars().next().unwrap(),
+                    position: at,
+                    cursor,
+                },
+                2 => HistoryAction::InsertLinebreak { position: at, cursor },
+                3 if at.row + 1 < lines.len() => HistoryAction::RemoveLinebreak {
+                    position: BytePosition {
+                        row: at.row,
+                        col: lines[at.row].len(),
+                    },
+                    cursor,
+                },
+                4 => HistoryAction::InsertLines {
+                    lines: (0..1 + random(3)).map(|_| text(&mut random)).collect(),
+                    position: at,
+                    cursor,
+                },
+                5 => HistoryAction::InsertFullLines {
+                    row: random(lines.len() + 1),
+                    lines: (0..random(3)).map(|_| text(&mut random)).collect(),
+                    cursor,
+                },
+                // all but at least one line, which removing every line would leave behind
+                6 if lines.len() > 1 => {
+                    let len = 1 + random(lines.len() - 1);
+                    let row = random(lines.len() + 1 - len);
+                    HistoryAction::RemoveFullLines {
+                        row,
+                        lines: lines[row..row + len].to_vec(),
+                        cursor,
+                    }
+                }
+                _ => {
+                    // the text between two positions, the way a selection is removed
+                    let end = position(&lines, &mut random);
+                    let (start, end) = if (end.row, end.col) < (at.row, at.col) {
+                        (end, at)
+                    } else {
+                        (at, end)
+                    };
+                    let removed = if start.row == end.row {
+                        vec![lines[start.row][start.col..end.col].to_string()]
+                    } else {
+                        let mut removed = vec![lines[start.row][start.col..].to_string()];
+                        removed.extend(lines[start.row + 1..end.row].iter().cloned());
+                        removed.push(lines[end.row][..end.col].to_string());
+                        removed
+                    };
+                    HistoryAction::RemoveLines {
+                        lines: removed,
+                        position: start,
+                        cursor,
+                    }
+                }
+            };
+            let before = lines.clone();
+            let rows = action.rows_before(&lines);
+            assert!(action.apply(&mut lines).is_some(), "{action:?}");
+            let end = rows.end + lines.len() - before.len();
+            assert_eq!(lines[..rows.start], before[..rows.start], "{action:?}");
+            assert_eq!(lines[end..], before[rows.end..], "{action:?}");
+            done.push(action);
+        }
+
+        for action in done.into_iter().rev() {
+            assert!(action.invert().apply(&mut lines).is_some());
         }
+        assert_eq!(lines, original);
+    }
+}
+
+#[test]
+fn stale_actions() {
+    let cursor = (CursorPosition::default(), CursorPosition::default());
+    let position = |row, col| BytePosition { row, col };
+
+    // lines appended past the end come off again
+    let mut lines = vec!["é".to_string()];
+    let append = HistoryAction::InsertLines {
+        lines: vec!["x".into(), "ü".into(), String::new()],
+        position: position(1, 0),
+        cursor,
+    };
+    assert!(append.apply(&mut lines).is_some());
+    assert_eq!(lines, ["é", "x", "ü"]);
+    assert!(append.invert().apply(&mut lines).is_some());
+    assert_eq!(lines, ["é"]);
+
+    // actions made for some other text leave this one as it is
+    for action in [
+        HistoryAction::InsertChar {
+            char: 'a',
+            position: position(0, 1),
+            cursor,
+        },
+        HistoryAction::RemoveLines {
+            lines: vec!["é€".into()],
+            position: position(5, 2),
+            cursor,
+        },
+        HistoryAction::RemoveLines {
+            lines: vec!["é".into(), "x".into()],
+            position: position(0, 0),
+            cursor,
+        },
+        HistoryAction::RemoveChar {
+            char: 'a',
+            position: position(0, 0),
+            cursor,
+        },
+        HistoryAction::RemoveLinebreak {
+            position: position(0, 2),
+            cursor,
+        },
+        HistoryAction::SwapLines { lines: (0, 3), cursor },
+        HistoryAction::InsertFullLines {
+            row: 7,
+            lines: vec!["b".into()],
+            cursor,
+        },
+        HistoryAction::RemoveFullLines {
+            row: 0,
+            lines: vec!["é".into(), "b".into()],
+            cursor,
+        },
+    ] {
+        assert!(!action.fits(&lines), "{action:?}");
+        assert_eq!(action.apply(&mut lines), None, "{action:?}");
+        assert_eq!(lines, ["é"]);
+    }
+
+    // removing every line leaves an empty one
+    HistoryAction::RemoveFullLines {
+        row: 0,
+        lines: vec!["é".into()],
+        cursor,
     }
+    .apply(&mut lines);
+    assert_eq!(lines, [""]);
 }
@@ -1,4 +1,6 @@
-#[derive(Debug, Clone)]
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Indent {
     Tabs,
     Spaces(String),
@@ -11,6 +13,54 @@ impl Indent {
             Indent::Spaces(spaces) => spaces,
         }
     }
+
+    /// The next style in the cycle tabs, 2, 4 and 8 spaces.
+    pub fn cycle(&self) -> Self {
+        match self {
+            Indent::Tabs => 2.into(),
+            Indent::Spaces(spaces) if spaces.len() < 4 => 4.into(),
+            Indent::Spaces(spaces) if spaces.len() < 8 => 8.into(),
+            Indent::Spaces(_) => Indent::Tabs,
+        }
+    }
+
+    /// Display width of the leading whitespace of `line`.
+    pub fn leading_width(&self, line: &str) -> usize {
+        let tab_width = self.spaces().len();
+        line.chars()
+            .take_while(|c| c.is_whitespace())
+            .map(|c| if c == '\t' { tab_width } else { 1 })
+            .sum()
+    }
+
+    /// Whether the leading whitespace of `line` isn't how this style indents, like a tab when indenting with spaces, or
+    /// spaces where a tab would go when indenting with tabs.
+    pub fn is_mixed(&self, line: &str) -> bool {
+        let leading = &line[..line.len() - line.trim_start_matches([' ', '\t']).len()];
+        !leading.is_empty() && leading != self.whitespace(self.leading_width(leading))
+    }
+
+    /// Leading whitespace of the given display width, in this indent style.
+    pub fn whitespace(&self, width: usize) -> String {
+        match self {
+            Indent::Tabs => {
+                let tab_width = self.spaces().len();
+                let mut whitespace = "\t".repeat(width / tab_width);
+                whitespace.push_str(&" ".repeat(width % tab_width));
+                whitespace
+            }
+            Indent::Spaces(_) => " ".repeat(width),
+        }
+    }
+}
+
+impl fmt::Display for Indent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Indent::Tabs => write!(f, "tabs"),
+            Indent::Spaces(spaces) => write!(f, "{} spaces", spaces.len()),
+        }
+    }
 }
 
 impl Default for Indent {
@@ -0,0 +1,109 @@
+use serde::de::IgnoredAny;
+
+/// Why text isn't valid JSON, with the 1-based line and the 0-based byte column in it where that was noticed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct JsonError {
+    pub message: String,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Reformats the JSON in `text`, indented with `indent` for each level or on a single line without one. Only the
+/// whitespace between tokens changes, so numbers, strings and duplicate keys are kept exactly as they were written.
+pub fn format(text: &str, indent: Option<&str>) -> Result<String, JsonError> {
+    serde_json::from_str::<IgnoredAny>(text).map_err(|err| JsonError {
+        message: err.to_string(),
+        line: err.line(),
+        col: err.column().saturating_sub(1),
+    })?;
+
+    let mut out = String::with_capacity(text.len());
+    let mut level = 0;
+    let newline = |out: &mut String, level: usize| {
+        if let Some(indent) = indent {
+            out.push('\n');
+            out.extend(std::iter::repeat_n(indent, level));
+        }
+    };
+    let mut chars = text.char_indices().peekable();
+    while let Some((idx, char)) = chars.next() {
+        match char {
+            '"' => {
+                // the text is valid JSON, so the string ends at the first quote that isn't escaped
+                let mut escaped = false;
+                let end = chars
+                    .by_ref()
+                    .find(|&(_, char)| {
+                        let end = char == '"' && !escaped;
+                        escaped = char == '\\' && !escaped;
+                        end
+                    })
+                    .map_or(text.len(), |(end, _)| end + 1);
+                out.push_str(&text[idx..end]);
+            }
+            '{' | '[' => {
+                out.push(char);
+                while chars.next_if(|(_, char)| char.is_ascii_whitespace()).is_some() {}
+                if chars.peek().is_some_and(|&(_, next)| matches!(next, '}' | ']')) {
+                    // empty, kept on one line
+                    continue;
+                }
+                level += 1;
+                newline(&mut out, level);
+            }
+            '}' | ']' => {
+                if !out.ends_with(['{', '[']) {
+                    level -= 1;
+                    newline(&mut out, level);
+                }
+                out.push(char);
+            }
+            ',' => {
+                out.push(char);
+                newline(&mut out, level);
+            }
+            ':' => out.push_str(if indent.is_some() { ": " } else { ":" }),
+            char if char.is_ascii_whitespace() => {}
+            char => out.push(char),
+        }
+    }
+    Ok(out)
+}
+
+#[test]
+fn test() {
+    assert_eq!(
+        format(r#"{"b":[1,2],"a":{}}"#, Some("\t")).unwrap(),
+        "{\n\t\"b\": [\n\t\t1,\n\t\t2\n\t],\n\t\"a\": {}\n}"
+    );
+    assert_eq!(
+        format("{\n  \"b\": [1, 2],\n  \"a\": null\n}", None).unwrap(),
+        r#"{"b":[1,2],"a":null}"#
+    );
+    assert_eq!(format("[ ]", Some("  ")).unwrap(), "[]");
+    assert_eq!(
+        format(r#"{"a \"[1, 2]\"": "x\\", "b": "{ }"}"#, None).unwrap(),
+        r#"{"a \"[1, 2]\"":"x\\","b":"{ }"}"#
+    );
+
+    // numbers are kept as written, and keys that appear twice are kept both times
+    assert_eq!(
+        format("[1E5, 100000000000000000000000, -0, 1.50, 1e400]", None).unwrap(),
+        "[1E5,100000000000000000000000,-0,1.50,1e400]"
+    );
+    assert_eq!(
+        format(r#"{"a": 1, "a": 2}"#, Some(" ")).unwrap(),
+        "{\n \"a\": 1,\n \"a\": 2\n}"
+    );
+
+    let err = format("{\n  \"a\": 1,\n  \"b\" 2\n}", None).unwrap_err();
+    assert_eq!((err.line, err.col), (3, 6));
+    assert!(format("[1] 2", None).is_err());
+
+    // a long minified line round-trips
+    let items = (0..5000).map(|i| format!(r#"{{"id":{i},"name":"item {i}","tags":["x","y"]}}"#));
+    let minified = format!("[{}]", items.collect::<Vec<_>>().join(","));
+    let pretty = format(&minified, Some("  ")).unwrap();
+    assert_eq!(pretty.lines().count(), 5000 * 8 + 2);
+    assert_eq!(format(&pretty, None).unwrap(), minified);
+}
@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+
+/// Rearranges whole lines, for the lines of a selection or the whole text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineOrder {
+    Reverse,
+    /// Removes lines equal to the one before them.
+    DedupConsecutive,
+    /// Removes lines equal to any earlier one, keeping the first.
+    DedupAll,
+    Shuffle,
+}
+
+impl fmt::Display for LineOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LineOrder::Reverse => write!(f, "reverse"),
+            LineOrder::DedupConsecutive => write!(f, "remove repeated"),
+            LineOrder::DedupAll => write!(f, "remove duplicate"),
+            LineOrder::Shuffle => write!(f, "shuffle"),
+        }
+    }
+}
+
+impl LineOrder {
+    pub fn apply(self, lines: &mut Vec<String>) {
+        match self {
+            LineOrder::Reverse => lines.reverse(),
+            LineOrder::DedupConsecutive => lines.dedup(),
+            LineOrder::DedupAll => {
+                let mut seen = HashSet::new();
+                lines.retain(|line| seen.insert(line.clone()));
+            }
+            LineOrder::Shuffle => {
+                // randomly seeded hashes, so there's no need for a random number crate
+                let random = RandomState::new();
+                for idx in (1..lines.len()).rev() {
+                    let other = random.hash_one(idx) as usize % (idx + 1);
+                    lines.swap(idx, other);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test() {
+    let lines = ["b", "a", "a", "c", "a"].map(String::from).to_vec();
+    let apply = |order: LineOrder| {
+        let mut lines = lines.clone();
+        order.apply(&mut lines);
+        lines
+    };
+
+    assert_eq!(apply(LineOrder::Reverse), ["a", "c", "a", "a", "b"]);
+    assert_eq!(apply(LineOrder::DedupConsecutive), ["b", "a", "c", "a"]);
+    assert_eq!(apply(LineOrder::DedupAll), ["b", "a", "c"]);
+
+    let mut shuffled = apply(LineOrder::Shuffle);
+    shuffled.sort();
+    assert_eq!(shuffled, ["a", "a", "a", "b", "c"]);
+}
@@ -0,0 +1,84 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// The lines of a [`TextArea`](super::TextArea), which background work like counting matches can hold on to without
+/// copying them. Changing them while they are held copies them first, so that work goes on with the text it started
+/// on.
+#[derive(Clone, Default)]
+pub struct Lines(Arc<Vec<String>>);
+
+impl Lines {
+    /// The lines as they are now, for another thread.
+    pub fn share(&self) -> Arc<Vec<String>> {
+        Arc::clone(&self.0)
+    }
+}
+
+impl Deref for Lines {
+    type Target = Vec<String>;
+
+    fn deref(&self) -> &Vec<String> {
+        &self.0
+    }
+}
+
+impl DerefMut for Lines {
+    fn deref_mut(&mut self) -> &mut Vec<String> {
+        Arc::make_mut(&mut self.0)
+    }
+}
+
+impl From<Vec<String>> for Lines {
+    fn from(lines: Vec<String>) -> Self {
+        Self(Arc::new(lines))
+    }
+}
+
+impl<const N: usize> From<[String; N]> for Lines {
+    fn from(lines: [String; N]) -> Self {
+        Self(Arc::new(lines.into()))
+    }
+}
+
+impl FromIterator<String> for Lines {
+    fn from_iter<I: IntoIterator<Item = String>>(lines: I) -> Self {
+        Self(Arc::new(lines.into_iter().collect()))
+    }
+}
+
+impl<T> PartialEq<T> for Lines
+where
+    Vec<String>: PartialEq<T>,
+{
+    fn eq(&self, other: &T) -> bool {
+        *self.0 == *other
+    }
+}
+
+impl PartialEq<Lines> for Vec<String> {
+    fn eq(&self, other: &Lines) -> bool {
+        *self == *other.0
+    }
+}
+
+impl fmt::Debug for Lines {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[test]
+fn test() {
+    let mut lines = Lines::from(vec!["a".to_string()]);
+    let shared = lines.share();
+    lines.push("b".to_string());
+    assert_eq!(lines, ["a", "b"]);
+    assert_eq!(*shared, ["a"]);
+
+    // not copied again once nothing else holds them
+    drop(shared);
+    let ptr = lines.as_ptr();
+    lines[0].push('x');
+    assert_eq!(lines.as_ptr(), ptr);
+}
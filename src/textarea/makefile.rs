@@ -0,0 +1,58 @@
+use std::ops::Range;
+
+/// Whether `line` starts a rule, like `target: prerequisites`, rather than being a variable assignment.
+fn is_rule(line: &str) -> bool {
+    if line.starts_with(|c: char| c.is_whitespace() || c == '#') {
+        return false;
+    }
+
+    match line.split_once(':') {
+        Some((target, rest)) => !target.contains('=') && !rest.starts_with('='),
+        None => false,
+    }
+}
+
+/// The rows in `rows` that are recipe lines of a rule but indented with spaces, which make rejects, with the number of
+/// leading spaces.
+pub fn space_indented_recipes(lines: &[String], rows: Range<usize>) -> Vec<(usize, usize)> {
+    // whether the lines before `rows` are inside a rule, from the last line that isn't indented
+    let mut in_rule = lines[..rows.start]
+        .iter()
+        .rev()
+        .find(|line| !line.trim().is_empty() && !line.starts_with(char::is_whitespace))
+        .is_some_and(|line| is_rule(line));
+
+    let mut recipes = Vec::new();
+    for (row, line) in lines[rows.clone()].iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            in_rule = is_rule(line);
+        } else if in_rule && line.starts_with(' ') {
+            recipes.push((rows.start + row, line.chars().take_while(|&c| c == ' ').count()));
+        }
+    }
+    recipes
+}
+
+#[test]
+fn recipes() {
+    let lines = [
+        "CC := gcc",
+        "  CFLAGS = -O2",
+        "all: main.o",
+        "\t$(CC) -o main main.o",
+        "",
+        "    echo done",
+        "clean:",
+        "  rm -f main",
+        "x=a:b",
+        "  y",
+    ]
+    .map(String::from);
+
+    assert_eq!(space_indented_recipes(&lines, 0..lines.len()), [(5, 4), (7, 2)]);
+    assert_eq!(space_indented_recipes(&lines, 4..6), [(5, 4)]);
+}
@@ -1,13 +1,32 @@
 mod byte_index;
 mod char_slice;
+mod clipboard;
+mod conflict;
 mod cursor;
+mod display_width;
+mod expanded_line;
 mod history;
 mod indent;
+mod json;
+mod line_order;
+mod lines;
+mod makefile;
+mod reflow;
+mod spell;
+mod stats;
+mod surround;
 mod textarea;
 mod word;
 
 pub use byte_index::ByteIndex;
+pub use clipboard::Clip;
+pub use conflict::Side;
 pub use cursor::CursorPosition;
+pub use display_width::DisplayWidth;
 pub use history::{BytePosition, HistoryAction};
 pub use indent::Indent;
+pub use line_order::LineOrder;
+pub use spell::Dictionary;
+pub use stats::Stats;
+pub use surround::{Delimiters, matching_open};
 pub use textarea::TextArea;
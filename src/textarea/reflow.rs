@@ -0,0 +1,97 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::display_width::DisplayWidth;
+
+/// Indentation, then a comment or quote marker and the spaces after it, kept at the start of every reflowed line.
+static PREFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:(?://[/!]?|#+|>|--|;+|\*|%)[ \t]*)?").unwrap());
+/// Like [`PREFIX`] for prose, where `#` starts a heading and `*` a list item, which only the first line keeps.
+static PROSE_PREFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:>[ \t]*)?").unwrap());
+
+/// Re-wraps the paragraphs in `lines` to `width` display columns. The prefix of the first line, like `// ` or `> `, is
+/// taken off every line and put back in front of the wrapped ones, only quotes counting as one in `prose`. Lines that
+/// are blank after the prefix separate paragraphs and are kept as they are. Words longer than the width get a line of
+/// their own.
+pub fn reflow(lines: &[String], width: usize, tab_width: usize, prose: bool) -> Vec<String> {
+    let first = lines
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .map_or("", String::as_str);
+    let prefix = if prose { &PROSE_PREFIX } else { &PREFIX };
+    let prefix = prefix.find(first).map_or("", |m| m.as_str());
+    let available = width.saturating_sub(prefix.display_col(usize::MAX, tab_width));
+
+    let mut reflowed = Vec::new();
+    let mut words = Vec::new();
+    for line in lines {
+        let content = line
+            .strip_prefix(prefix)
+            .or_else(|| line.strip_prefix(prefix.trim_end()))
+            .unwrap_or_else(|| line.trim_start());
+        if content.trim().is_empty() {
+            wrap(&mut reflowed, &mut words, prefix, available);
+            reflowed.push(line.clone());
+        } else {
+            words.extend(content.split_whitespace());
+        }
+    }
+    wrap(&mut reflowed, &mut words, prefix, available);
+    reflowed
+}
+
+/// Appends `words` as lines of at most `width` columns after `prefix`, emptying it.
+fn wrap(lines: &mut Vec<String>, words: &mut Vec<&str>, prefix: &str, width: usize) {
+    let mut line = String::new();
+    let mut line_width = 0;
+    for word in words.drain(..) {
+        let word_width = word.display_col(usize::MAX, 1);
+        if line_width > 0 && line_width + 1 + word_width > width {
+            lines.push(format!("{prefix}{line}"));
+            line.clear();
+            line_width = 0;
+        }
+        if line_width > 0 {
+            line.push(' ');
+            line_width += 1;
+        }
+        line.push_str(word);
+        line_width += word_width;
+    }
+    if !line.is_empty() {
+        lines.push(format!("{prefix}{line}"));
+    }
+}
+
+#[test]
+fn test() {
+    let lines = |text: &str| text.split('\n').map(String::from).collect::<Vec<_>>();
+
+    assert_eq!(
+        reflow(&lines("one two three\nfour five six seven"), 14, 4, false),
+        ["one two three", "four five six", "seven"]
+    );
+    assert_eq!(
+        reflow(
+            &lines("    // one two\n    //   three four five\n    //\n    // six"),
+            20,
+            4,
+            false
+        ),
+        ["    // one two three", "    // four five", "    //", "    // six"]
+    );
+    assert_eq!(
+        reflow(&lines("> quoted text, and a\n> reply"), 12, 4, true),
+        ["> quoted", "> text, and", "> a reply"]
+    );
+    // too long to break
+    assert_eq!(
+        reflow(&lines("# see https://example.com/a/long/path here"), 16, 4, false),
+        ["# see", "# https://example.com/a/long/path", "# here"]
+    );
+    assert_eq!(reflow(&lines("\ta\n\tb c"), 8, 4, false), ["\ta b", "\tc"]);
+
+    // headings and list items in prose aren't comments
+    assert_eq!(reflow(&lines("# A long heading"), 10, 4, true), ["# A long", "heading"]);
+    assert_eq!(reflow(&lines("* one two three"), 10, 4, true), ["* one two", "three"]);
+}
@@ -0,0 +1,251 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use super::word::Word;
+
+/// Word lists tried in order: plain lists with one word per line, or hunspell dictionaries whose affix flags are
+/// ignored.
+const SYSTEM_DICTIONARIES: &[&str] = &[
+    "/usr/share/dict/words",
+    "/usr/share/dict/web2",
+    "/usr/share/hunspell/en_US.dic",
+    "/usr/share/myspell/en_US.dic",
+    "/Library/Spelling/en_US.dic",
+];
+
+/// Endings stripped to find the stem of a word, with what to put back, for dictionaries that only list stems.
+const SUFFIXES: &[(&str, &[&str])] = &[
+    ("ies", &["y"]),
+    ("es", &[""]),
+    ("s", &[""]),
+    ("ied", &["y"]),
+    ("ed", &["", "e"]),
+    ("ing", &["", "e"]),
+    ("er", &["", "e"]),
+    ("est", &["", "e"]),
+    ("ly", &[""]),
+];
+
+/// Known words for spell checking, plus the words added to a personal dictionary file.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    words: HashSet<String>,
+    /// Set for hunspell dictionaries, which list stems and derive the other forms with affix rules.
+    stems_only: bool,
+    personal: RefCell<HashSet<String>>,
+    personal_path: Option<PathBuf>,
+}
+
+impl Dictionary {
+    pub fn new(words: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            words: words.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Loads the first of [`SYSTEM_DICTIONARIES`] that exists and the words in `personal_path`, if there is one.
+    pub fn load(personal_path: Option<PathBuf>) -> Option<Self> {
+        let (path, text) = SYSTEM_DICTIONARIES
+            .iter()
+            .find_map(|path| Some((Path::new(path), fs::read_to_string(path).ok()?)))?;
+
+        let stems_only = path.extension().is_some_and(|ext| ext == "dic");
+        let words = if stems_only {
+            // the first line is the number of words
+            text.lines()
+                .skip(1)
+                .map(|line| line.split('/').next().unwrap_or_default().to_string())
+                .collect()
+        } else {
+            text.lines().map(str::to_string).collect()
+        };
+        let personal = personal_path
+            .as_deref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(str::to_string).collect())
+            .unwrap_or_default();
+
+        Some(Self {
+            words,
+            stems_only,
+            personal: RefCell::new(personal),
+            personal_path,
+        })
+    }
+
+    /// Whether `word` is spelled right. Capitalized words are also looked up in lower case, for the start of sentences.
+    pub fn is_known(&self, word: &str) -> bool {
+        let word = word
+            .strip_suffix("'s")
+            .or_else(|| word.strip_suffix("’s"))
+            .unwrap_or(word)
+            .replace('’', "'");
+        if self.contains(&word) || self.contains(&word.to_lowercase()) {
+            return true;
+        }
+
+        self.stems_only
+            && SUFFIXES.iter().any(|(suffix, endings)| {
+                word.to_lowercase().strip_suffix(suffix).is_some_and(|stem| {
+                    stem.chars().count() > 1 && endings.iter().any(|ending| self.contains(&format!("{stem}{ending}")))
+                })
+            })
+    }
+
+    fn contains(&self, word: &str) -> bool {
+        self.words.contains(word) || self.personal.borrow().contains(word)
+    }
+
+    /// Adds `word` to the personal dictionary, appending it to its file so it's known next time too.
+    pub fn add(&self, word: &str) -> io::Result<()> {
+        if !self.personal.borrow_mut().insert(word.to_string()) {
+            return Ok(());
+        }
+
+        let Some(path) = &self.personal_path else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{word}")
+    }
+
+    pub fn personal_path(&self) -> Option<&Path> {
+        self.personal_path.as_deref()
+    }
+
+    /// Known words one edit away from `word`, or two if there are none, best first. Capitalization is kept.
+    pub fn suggestions(&self, word: &str) -> Vec<String> {
+        const MAX: usize = 8;
+
+        let lower = word.to_lowercase();
+        let mut found = edits(&lower)
+            .into_iter()
+            .filter(|candidate| self.is_known(candidate))
+            .collect::<Vec<_>>();
+        if found.is_empty() {
+            found = edits(&lower)
+                .iter()
+                .flat_map(|edit| edits(edit))
+                .filter(|candidate| self.is_known(candidate))
+                .collect();
+        }
+
+        // words with the same first letter and length are more likely to be what was meant
+        let first = lower.chars().next();
+        let len = lower.chars().count();
+        found.sort_by_key(|candidate| {
+            (
+                candidate.chars().next() != first,
+                candidate.chars().count().abs_diff(len),
+            )
+        });
+        let mut seen = HashSet::new();
+        found.retain(|candidate| *candidate != lower && seen.insert(candidate.clone()));
+        found.truncate(MAX);
+
+        if word.starts_with(char::is_uppercase) {
+            for candidate in &mut found {
+                let mut chars = candidate.chars();
+                if let Some(first) = chars.next() {
+                    *candidate = first.to_uppercase().chain(chars).collect();
+                }
+            }
+        }
+        found
+    }
+
+    /// Char ranges of the misspelled words in `line`. Words with digits, underscores or capitals after the first
+    /// letter are skipped as code, and so are parts of paths, URLs and email addresses.
+    pub fn misspellings(&self, line: &str) -> Vec<Range<usize>> {
+        let chars = line.chars().collect::<Vec<_>>();
+        line.words()
+            .into_iter()
+            .filter(|range| {
+                let word = &chars[range.clone()];
+                let before = range.start.checked_sub(1).map(|idx| chars[idx]);
+                let after = chars.get(range.end).copied();
+
+                word.len() > 1
+                    && word.iter().all(|c| c.is_alphabetic() || matches!(c, '\'' | '’'))
+                    && !word[1..].iter().any(|c| c.is_uppercase())
+                    && !before.is_some_and(|c| matches!(c, '/' | '\\' | '@' | '.' | '-'))
+                    && !after.is_some_and(|c| matches!(c, '/' | '\\' | '@' | ':'))
+                    && !self.is_known(&word.iter().collect::<String>())
+            })
+            .collect()
+    }
+}
+
+/// Every string one deletion, transposition, replacement or insertion of a lowercase letter away from `word`.
+fn edits(word: &str) -> Vec<String> {
+    let chars = word.chars().collect::<Vec<_>>();
+    let string = |chars: &[char]| chars.iter().collect::<String>();
+    let mut edits = Vec::new();
+
+    for idx in 0..=chars.len() {
+        let (before, after) = chars.split_at(idx);
+        if !after.is_empty() {
+            edits.push(string(&[before, &after[1..]].concat()));
+        }
+        if after.len() > 1 {
+            edits.push(string(&[before, &[after[1], after[0]], &after[2..]].concat()));
+        }
+        for c in 'a'..='z' {
+            if !after.is_empty() {
+                edits.push(string(&[before, &[c], &after[1..]].concat()));
+            }
+            edits.push(string(&[before, &[c], after].concat()));
+        }
+    }
+    edits
+}
+
+#[test]
+fn test() {
+    let dictionary =
+        Dictionary::new(["the", "quick", "brown", "fox", "jumps", "don't", "I", "Paris"].map(String::from));
+
+    assert!(dictionary.is_known("The"));
+    assert!(dictionary.is_known("fox's"));
+    assert!(dictionary.is_known("don’t"));
+    assert!(!dictionary.is_known("paris"));
+    assert!(!dictionary.is_known("foxes"));
+
+    let line = "Teh quikc brown fox, don't see src/fxo.rs or fooBar x_y HTTP teh2 Jmups";
+    let misspelled = dictionary
+        .misspellings(line)
+        .into_iter()
+        .map(|range| line.chars().skip(range.start).take(range.len()).collect::<String>())
+        .collect::<Vec<_>>();
+    assert_eq!(misspelled, ["Teh", "quikc", "see", "or", "Jmups"]);
+
+    assert_eq!(dictionary.suggestions("Teh"), ["The"]);
+    assert_eq!(dictionary.suggestions("quikc"), ["quick"]);
+    assert_eq!(dictionary.suggestions("browm"), ["brown"]);
+    // two edits away
+    assert_eq!(dictionary.suggestions("jmusp"), ["jumps"]);
+    assert!(dictionary.suggestions("zzzzzz").is_empty());
+
+    dictionary.add("fxo").unwrap();
+    assert!(dictionary.is_known("fxo"));
+}
+
+#[test]
+fn stems_only() {
+    let dictionary = Dictionary {
+        stems_only: true,
+        ..Dictionary::new(["file", "copy", "open"].map(String::from))
+    };
+    for word in ["files", "copies", "copied", "opened", "opening", "filed"] {
+        assert!(dictionary.is_known(word), "{word}");
+    }
+    assert!(!dictionary.is_known("opend"));
+}
@@ -0,0 +1,39 @@
+use super::word::Word;
+
+/// Size of a piece of text, as reported to the user.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+}
+
+impl Stats {
+    /// Counts `lines` as if joined by `\n`.
+    pub fn new<'a>(lines: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut stats = lines.into_iter().fold(Self::default(), |stats, line| Self {
+            lines: stats.lines + 1,
+            words: stats.words + line.word_count(),
+            chars: stats.chars + line.chars().count() + 1,
+            bytes: stats.bytes + line.len() + 1,
+        });
+        stats.chars = stats.chars.saturating_sub(1);
+        stats.bytes = stats.bytes.saturating_sub(1);
+        stats
+    }
+}
+
+#[test]
+fn test() {
+    assert_eq!(Stats::new([]), Stats::default());
+    assert_eq!(
+        Stats::new(["héllo world", "", "x"]),
+        Stats {
+            lines: 3,
+            words: 3,
+            chars: 14,
+            bytes: 15
+        }
+    );
+}
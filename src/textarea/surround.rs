@@ -0,0 +1,280 @@
+use super::cursor::CursorPosition;
+
+const BRACKETS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+/// How many lines before or after a position are looked through for a bracket, so typing in a big file doesn't scan
+/// all of it.
+const SCAN_LINES: usize = 1000;
+
+/// What to put around text: typed as the opening side, the closing side is derived from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delimiters {
+    pub open: String,
+    pub close: String,
+    /// An HTML tag, which goes on lines of its own around several lines.
+    pub tag: bool,
+}
+
+impl Delimiters {
+    /// Brackets get their counterpart, in either direction, `<em>` or `<a href="x">` gets an end tag, and anything
+    /// else is mirrored, so `**` surrounds with `**` and `({` with `})`.
+    pub fn parse(text: &str) -> Self {
+        if let Some(inner) = text.strip_prefix('<').and_then(|text| text.strip_suffix('>'))
+            && inner.starts_with(char::is_alphabetic)
+        {
+            let name = inner.split_whitespace().next().unwrap_or(inner);
+            return Self {
+                open: text.to_string(),
+                close: format!("</{name}>"),
+                tag: true,
+            };
+        }
+
+        let opening = |c| {
+            BRACKETS
+                .iter()
+                .find(|(_, close)| *close == c)
+                .map_or(c, |(open, _)| *open)
+        };
+        let closing = |c| {
+            BRACKETS
+                .iter()
+                .find(|(open, _)| *open == c)
+                .map_or(c, |(_, close)| *close)
+        };
+        Self {
+            open: text.chars().map(opening).collect(),
+            close: text.chars().rev().map(closing).collect(),
+            tag: false,
+        }
+    }
+}
+
+/// Positions of the innermost quotes or brackets around `cursor`. Brackets may span lines, quotes are only looked
+/// for on the cursor's line. Angle brackets are left out, being comparisons more often than not.
+pub fn enclosing_pair(lines: &[String], cursor: CursorPosition) -> Option<(CursorPosition, CursorPosition)> {
+    let bracket = enclosing_brackets(lines, cursor);
+    let quote = enclosing_quotes(&lines[cursor.row], cursor.col).map(|(open, close)| {
+        (
+            CursorPosition { row: cursor.row, col: open },
+            CursorPosition { row: cursor.row, col: close },
+        )
+    });
+
+    match (bracket, quote) {
+        // quotes inside the brackets, or a bracket opened in a string but closed after it, which is just text
+        (Some(bracket), Some(quote)) if quote.0 > bracket.0 || quote.1 < bracket.1 => Some(quote),
+        (bracket, quote) => bracket.or(quote),
+    }
+}
+
+fn enclosing_brackets(lines: &[String], cursor: CursorPosition) -> Option<(CursorPosition, CursorPosition)> {
+    unclosed_before(lines, cursor, &BRACKETS[..3])
+        .find_map(|(open, pair)| matching_close(lines, open, pair).map(|close| (open, close)))
+}
+
+/// The bracket that `close` typed at `cursor` would close: the nearest one before it that isn't closed already.
+pub fn matching_open(lines: &[String], cursor: CursorPosition, close: char) -> Option<CursorPosition> {
+    let &pair = BRACKETS.iter().find(|(_, c)| *c == close)?;
+    unclosed_before(lines, cursor, &[pair]).next().map(|(open, _)| open)
+}
+
+/// Opening brackets of `pairs` before `cursor` that aren't closed before it, nearest first, with their pair.
+fn unclosed_before<'a>(
+    lines: &'a [String],
+    cursor: CursorPosition,
+    pairs: &'a [(char, char)],
+) -> impl Iterator<Item = (CursorPosition, (char, char))> + 'a {
+    let mut depths = vec![0usize; pairs.len()];
+    brackets_before(lines, cursor).filter_map(move |(position, c)| {
+        if let Some(kind) = pairs.iter().position(|(_, close)| *close == c) {
+            depths[kind] += 1;
+        } else if let Some(kind) = pairs.iter().position(|(open, _)| *open == c) {
+            if depths[kind] == 0 {
+                return Some((position, pairs[kind]));
+            }
+            depths[kind] -= 1;
+        }
+        None
+    })
+}
+
+/// The bracket closing the one at `open`.
+fn matching_close(
+    lines: &[String],
+    open: CursorPosition,
+    (open_char, close_char): (char, char),
+) -> Option<CursorPosition> {
+    let mut depth = 0;
+    for (position, c) in brackets_after(lines, open) {
+        if c == open_char {
+            depth += 1;
+        } else if c == close_char {
+            if depth == 0 {
+                return Some(position);
+            }
+            depth -= 1;
+        }
+    }
+    None
+}
+
+/// Brackets before `cursor` outside of strings, nearest first, up to [`SCAN_LINES`] lines back.
+fn brackets_before(lines: &[String], cursor: CursorPosition) -> impl Iterator<Item = (CursorPosition, char)> {
+    let first = cursor.row.saturating_sub(SCAN_LINES);
+    lines[first..=cursor.row]
+        .iter()
+        .zip(first..cursor.row + 1)
+        .rev()
+        .flat_map(move |(line, row)| {
+            // a string the cursor is in counts as open until the end
+            let end = if row == cursor.row { cursor.col } else { usize::MAX };
+            code_brackets(line, end)
+                .into_iter()
+                .rev()
+                .map(move |(col, c)| (CursorPosition { row, col }, c))
+        })
+}
+
+/// Brackets after `position` outside of strings, up to [`SCAN_LINES`] lines on.
+fn brackets_after(lines: &[String], position: CursorPosition) -> impl Iterator<Item = (CursorPosition, char)> {
+    lines[position.row..]
+        .iter()
+        .zip(position.row..)
+        .take(SCAN_LINES + 1)
+        .flat_map(move |(line, row)| {
+            code_brackets(line, usize::MAX)
+                .into_iter()
+                .filter(move |&(col, _)| row > position.row || col > position.col)
+                .map(move |(col, c)| (CursorPosition { row, col }, c))
+        })
+}
+
+/// Char columns of the brackets among the first `end` chars of `line` that aren't in a string. Strings are told apart
+/// on the line alone, so one that isn't closed runs to the end.
+fn code_brackets(line: &str, end: usize) -> Vec<(usize, char)> {
+    let chars = line.chars().take(end).collect::<Vec<_>>();
+    let mut brackets = Vec::new();
+    let mut col = 0;
+    while col < chars.len() {
+        if let Some(string_end) = string_end(&chars, col) {
+            col = string_end + 1;
+            continue;
+        }
+        if BRACKETS
+            .iter()
+            .any(|&(open, close)| chars[col] == open || chars[col] == close)
+        {
+            brackets.push((col, chars[col]));
+        }
+        col += 1;
+    }
+    brackets
+}
+
+/// Where the string starting at `start` ends, past the end of `chars` when it isn't closed, or `None` when there is
+/// no string there. Single quotes only start char literals like `'{'`, being apostrophes and lifetimes otherwise.
+fn string_end(chars: &[char], start: usize) -> Option<usize> {
+    match chars[start] {
+        quote @ ('"' | '`') => {
+            let mut idx = start + 1;
+            while idx < chars.len() && chars[idx] != quote {
+                idx += if chars[idx] == '\\' { 2 } else { 1 };
+            }
+            Some(idx)
+        }
+        '\'' => {
+            let end = start + if chars.get(start + 1) == Some(&'\\') { 3 } else { 2 };
+            (chars.get(end) == Some(&'\'')).then_some(end)
+        }
+        _ => None,
+    }
+}
+
+/// Char columns of the innermost pair of quotes around `col` in `line`, ignoring escaped ones.
+fn enclosing_quotes(line: &str, col: usize) -> Option<(usize, usize)> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let escaped = |idx: usize| chars[..idx].iter().rev().take_while(|&&c| c == '\\').count() % 2 == 1;
+
+    QUOTES
+        .iter()
+        .filter_map(|&quote| {
+            let positions = (0..chars.len())
+                .filter(|&idx| chars[idx] == quote && !escaped(idx))
+                .collect::<Vec<_>>();
+            let before = positions.iter().filter(|&&idx| idx < col).count();
+            (before % 2 == 1).then_some(())?;
+            Some((positions[before - 1], *positions.get(before)?))
+        })
+        .max()
+}
+
+#[test]
+fn parse() {
+    let parse = |text| {
+        let Delimiters { open, close, tag } = Delimiters::parse(text);
+        (open, close, tag)
+    };
+    assert_eq!(parse("("), ("(".into(), ")".into(), false));
+    assert_eq!(parse("]"), ("[".into(), "]".into(), false));
+    assert_eq!(parse("**"), ("**".into(), "**".into(), false));
+    assert_eq!(parse("({"), ("({".into(), "})".into(), false));
+    assert_eq!(parse("<em>"), ("<em>".into(), "</em>".into(), true));
+    assert_eq!(
+        parse(r#"<a href="x">"#),
+        (r#"<a href="x">"#.into(), "</a>".into(), true)
+    );
+    assert_eq!(parse("<"), ("<".into(), ">".into(), false));
+}
+
+#[test]
+fn enclosing() {
+    let lines = ["f(a, [b, \"c \\\" e\"], {", "  g)", "}", "(\"a)\")"].map(String::from);
+    let pair = |row, col| {
+        enclosing_pair(&lines, CursorPosition { row, col })
+            .map(|(open, close)| ((open.row, open.col), (close.row, close.col)))
+    };
+
+    assert_eq!(pair(0, 0), None);
+    assert_eq!(pair(0, 3), Some(((0, 1), (1, 3))));
+    assert_eq!(pair(0, 7), Some(((0, 5), (0, 17))));
+    // escaped quotes don't count
+    assert_eq!(pair(0, 14), Some(((0, 9), (0, 16))));
+    assert_eq!(pair(1, 2), Some(((0, 20), (2, 0))));
+    assert_eq!(pair(2, 1), None);
+    // the bracket in the string is just text
+    assert_eq!(pair(3, 3), Some(((3, 1), (3, 4))));
+}
+
+#[test]
+fn matching() {
+    let lines = ["fn f() {", "    if x {", "        g(y[0]);", "    }", "    "].map(String::from);
+    let open =
+        |row, col, close| matching_open(&lines, CursorPosition { row, col }, close).map(|open| (open.row, open.col));
+
+    assert_eq!(open(4, 4, '}'), Some((0, 7)));
+    assert_eq!(open(3, 4, '}'), Some((1, 9)));
+    assert_eq!(open(2, 14, ')'), Some((2, 9)));
+    assert_eq!(open(2, 13, ']'), Some((2, 11)));
+    // a pair closed before the cursor is skipped
+    assert_eq!(open(2, 16, ')'), None);
+    assert_eq!(open(2, 8, ']'), None);
+    assert_eq!(open(0, 3, ')'), None);
+    assert_eq!(open(4, 4, '>'), None);
+
+    // brackets in strings and char literals are just text, apostrophes and lifetimes don't start one
+    let lines = [
+        "fn f<'a>(x: &'a str) {",
+        "    let s = \"{ \\\" (\";",
+        "    if c == '{' || c == '\\'' { g(\"}\") }",
+        "    // don't (",
+        "    ",
+    ]
+    .map(String::from);
+    let open =
+        |row, col, close| matching_open(&lines, CursorPosition { row, col }, close).map(|open| (open.row, open.col));
+    assert_eq!(open(2, 32, '}'), Some((2, 29)));
+    assert_eq!(open(4, 4, '}'), Some((0, 21)));
+    assert_eq!(open(4, 4, ')'), Some((3, 13)));
+    assert_eq!(open(2, 28, ')'), None);
+}
@@ -1,61 +1,255 @@
-use std::{cell::Cell, cmp, num::NonZeroU8};
+use std::{
+    borrow::Cow,
+    cell::{Cell, Ref, RefCell},
+    cmp,
+    collections::HashMap,
+    iter, mem,
+    num::NonZeroU8,
+    ops::{Deref, DerefMut, Range, RangeInclusive},
+    rc::Rc,
+};
 
 use anyhow::Result;
-use arboard::Clipboard;
 use ratatui::buffer::Buffer;
 use ratatui::layout::{Position, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
 use ratatui::widgets::{Paragraph, Widget};
-use regex::Regex;
-use unicode_width::UnicodeWidthStr;
+use regex::{Regex, RegexBuilder};
+use unicode_width::UnicodeWidthChar;
 
 use super::char_slice::CharSlice;
+use super::clipboard::{Clip, Clipboard};
+use super::conflict::{self, Conflict, Side};
 use super::cursor::CursorPosition;
+use super::display_width::DisplayWidth;
+use super::expanded_line::ExpandedLine;
 use super::history::HistoryAction;
 use super::indent::Indent;
+use super::json;
+use super::line_order::LineOrder;
+use super::lines::Lines;
+use super::makefile;
+use super::reflow;
+use super::spell::Dictionary;
+use super::stats::Stats;
+use super::surround::{self, Delimiters};
 use super::word::Word;
 use crate::input::{Input, Key};
 use crate::textarea::{ByteIndex, BytePosition};
+use crate::theme::Theme;
 
 #[derive(Default, Debug, Clone)]
 struct View {
     position: Cell<CursorPosition>,
     width: Cell<usize>,
     height: Cell<usize>,
+    /// Set to center the cursor vertically on the next render instead of scrolling minimally.
+    center: Cell<bool>,
+}
+
+/// An action in the undo or redo history.
+#[derive(Debug)]
+struct Done {
+    action: HistoryAction,
+    /// Joined to the action before it into one undo step.
+    chain: bool,
+    /// The cursor and selection before and after the step, on the first action of a step made in a [`Chain`].
+    marks: Option<Marks>,
+}
+
+type Marks = [(CursorPosition, Option<CursorPosition>); 2];
+
+/// How many lines have leading whitespace other than the indent style's, and the text version, number of lines and
+/// style they were counted for.
+struct MixedIndent {
+    version: usize,
+    lines: usize,
+    indent: Indent,
+    count: usize,
+}
+
+impl MixedIndent {
+    fn is_for(&self, textarea: &TextArea) -> bool {
+        self.version == textarea.version && self.lines == textarea.lines.len() && self.indent == textarea.indent
+    }
+}
+
+/// Joins the actions done through it into one undo step once committed or dropped, so an early return can't split
+/// the step. Undoing and redoing the step put back the cursor and selection from before and after it.
+pub struct Chain<'a> {
+    textarea: &'a mut TextArea,
+    undo_len: usize,
+    before: (CursorPosition, Option<CursorPosition>),
+}
+
+impl Chain<'_> {
+    /// Ends the undo step, which dropping the chain does too.
+    pub fn commit(self) {}
+}
+
+impl Deref for Chain<'_> {
+    type Target = TextArea;
+
+    fn deref(&self) -> &TextArea {
+        self.textarea
+    }
+}
+
+impl DerefMut for Chain<'_> {
+    fn deref_mut(&mut self) -> &mut TextArea {
+        self.textarea
+    }
+}
+
+impl Drop for Chain<'_> {
+    fn drop(&mut self) {
+        self.textarea.join_step(self.undo_len, self.before);
+    }
+}
+
+/// What a transaction started from, to go back to if it fails. Made by [`TextArea::begin_transaction`].
+pub struct Transaction {
+    undo_len: usize,
+    before: (CursorPosition, Option<CursorPosition>),
+    /// Set aside, as any action clears it, and put back unless the transaction changed the text.
+    redo_history: Vec<Done>,
+}
+
+/// The most recent paste, remembered so Ctrl+Shift+V can swap it for an older clipboard entry.
+struct Paste {
+    start: CursorPosition,
+    cursor: (CursorPosition, CursorPosition),
+    lines: Vec<String>,
+    history_idx: usize,
+    reindent: bool,
+    undo_len: usize,
+}
+
+/// The most recent spelling suggestion put in place of a word, remembered so pressing the key again cycles on.
+struct Suggestion {
+    row: usize,
+    start: usize,
+    original: String,
+    suggestions: Vec<String>,
+    /// Into `suggestions`, or its length for the original word.
+    idx: usize,
+    undo_len: usize,
 }
 
 pub struct TextArea {
-    pub lines: Vec<String>,
+    pub lines: Lines,
     cursor: CursorPosition,
     selection: Option<CursorPosition>,
     view: View,
 
-    undo_history: Vec<(HistoryAction, bool)>,
-    redo_history: Vec<(HistoryAction, bool)>,
+    undo_history: Vec<Done>,
+    redo_history: Vec<Done>,
 
     pub clipboard: Clipboard,
+    last_paste: Option<Paste>,
     search_pattern: Option<Regex>,
 
     pub indent: Indent,
     pub line_numbers: bool,
+    /// Display columns to draw a vertical guide at, text past the first one is tinted.
+    pub rulers: Vec<usize>,
+    pub indent_guides: bool,
+    /// Tints spaces and tabs at the end of lines, except on the cursor line, where they are likely about to be typed
+    /// past.
+    pub trailing_whitespace: bool,
+    pub theme: Theme,
+    /// Marks recipe lines indented with spaces, for Makefiles.
+    pub makefile: bool,
+    /// Underlines misspelled words when set, for prose.
+    pub dictionary: Option<Rc<Dictionary>>,
+    suggestion: Option<Suggestion>,
+    /// Typing replaces the character under the cursor instead of inserting, toggled with Insert.
+    pub overwrite: bool,
+    /// Whether Backspace deletes in overwrite mode, rather than just moving left.
+    pub overwrite_backspace_deletes: bool,
+    /// Keeps the text on one line, for prompts: linebreaks are rejected and pasted lines are joined with spaces.
+    pub single_line: bool,
+
+    /// Set whenever something visible changes, so callers can skip redrawing after no-op keys.
+    redraw: bool,
+    /// Set when a key had nothing to act on, like Backspace at the start of the text, so callers can signal it.
+    rejected: bool,
+    /// Lines highlighted until the next key or [`TextArea::clear_flash`], to show what undo or redo changed.
+    flash: Option<RangeInclusive<usize>>,
+    /// Bumped on every edit, to tell when cached values are stale.
+    version: usize,
+    selection_stats: Cell<Option<(SelectionKey, Stats)>>,
+    /// Byte offset of the start of each line and the text version they were counted for, so byte offsets don't add
+    /// up every line before the cursor each time.
+    line_offsets: RefCell<(usize, Vec<usize>)>,
+    /// The lines last drawn, and the cursor line, as drawn.
+    expanded_lines: RefCell<HashMap<usize, Rc<ExpandedLine>>>,
+    /// Byte ranges of the search pattern's matches in the lines searched so far, and the text version they were found
+    /// in. Emptied when the pattern changes.
+    search_matches: RefCell<(usize, HashMap<usize, LineMatches>)>,
+    /// Kept up to date by each action, so the whole text is only counted again after it was changed some other way or
+    /// the indent style changed.
+    mixed_indent: Cell<Option<MixedIndent>>,
+}
+
+/// What [`TextArea::undo`] or [`TextArea::redo`] did.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UndoStep {
+    /// How many actions were undone or redone together, more than one for a chained group like a replace-all.
+    pub actions: usize,
+    /// How many steps are left to undo or redo the same way.
+    pub remaining: usize,
 }
 
+/// Bytes a compiled search pattern and its DFA cache may take. Patterns bigger than that, like a long bounded repeat
+/// of a Unicode class, are also slow to run on every line drawn, so they are refused instead.
+const SEARCH_SIZE_LIMIT: usize = 1 << 20;
+
+const NO_SURROUNDING_PAIR: &str = "No quotes or brackets around the cursor";
+
+/// What the selection stats depend on: both ends of the selection and the text version.
+type SelectionKey = (CursorPosition, CursorPosition, usize);
+
+/// Byte ranges of the search pattern's matches in a line.
+type LineMatches = Rc<[Range<usize>]>;
+
 impl Default for TextArea {
     fn default() -> Self {
         Self {
-            lines: vec![String::new()],
+            lines: vec![String::new()].into(),
             cursor: Default::default(),
             selection: Default::default(),
             view: Default::default(),
 
             undo_history: Default::default(),
             redo_history: Default::default(),
-            clipboard: Clipboard::new().unwrap(),
+            clipboard: Default::default(),
+            last_paste: Default::default(),
             search_pattern: Default::default(),
 
             indent: Default::default(),
             line_numbers: true,
+            rulers: Vec::new(),
+            indent_guides: false,
+            trailing_whitespace: false,
+            theme: Theme::default(),
+            makefile: false,
+            dictionary: None,
+            suggestion: None,
+            overwrite: false,
+            overwrite_backspace_deletes: false,
+            single_line: false,
+
+            redraw: false,
+            rejected: false,
+            flash: None,
+            version: 0,
+            selection_stats: Cell::new(None),
+            line_offsets: RefCell::new((0, Vec::new())),
+            expanded_lines: RefCell::default(),
+            search_matches: RefCell::default(),
+            mixed_indent: Cell::new(None),
         }
     }
 }
@@ -71,38 +265,139 @@ impl TextArea {
         self.selection
     }
 
+    /// Moves the cursor, clamped to the text in release builds. With `shift` the selection is kept, or started where the cursor was.
     pub fn set_cursor(&mut self, cursor: CursorPosition, shift: bool) {
-        match self.selection {
-            Some(_) if !shift => self.selection = None,
-            None if shift => self.selection = Some(self.cursor),
-            _ => {}
-        }
+        self.debug_assert_in_text(cursor);
+        let cursor = self.clamp_to_text(cursor);
+        let selection = match self.selection {
+            Some(_) if !shift => None,
+            None if shift => Some(self.cursor),
+            selection => selection,
+        };
 
+        self.set_selection(selection);
+        self.redraw |= self.cursor != cursor;
         self.cursor = cursor;
     }
 
+    /// Sets the other end of the selection. A position outside the text fails an assertion in debug builds and is
+    /// clamped to the text otherwise, as it is for [`Self::set_cursor`].
     pub fn set_selection(&mut self, selection: Option<CursorPosition>) {
+        if let Some(selection) = selection {
+            self.debug_assert_in_text(selection);
+        }
+        let selection = selection.map(|selection| self.clamp_to_text(selection));
+        self.redraw |= self.selection != selection;
         self.selection = selection;
     }
 
+    /// `position` moved onto the last line if it is past it, and to the end of its line if it is past that. Columns
+    /// count chars, not bytes.
+    fn clamp_to_text(&self, position: CursorPosition) -> CursorPosition {
+        let row = cmp::min(position.row, self.lines.len().saturating_sub(1));
+        let len = self.lines.get(row).map_or(0, |line| line.chars().count());
+        CursorPosition {
+            row,
+            col: cmp::min(position.col, len),
+        }
+    }
+
+    /// Checks in debug builds that `position` is on a line and its column, which counts chars, not bytes, is within it,
+    /// so a caller that works one out wrong is caught rather than quietly clamped.
+    fn debug_assert_in_text(&self, position: CursorPosition) {
+        debug_assert!(
+            self.lines
+                .get(position.row)
+                .is_some_and(|line| position.col <= line.chars().count()),
+            "{position:?} is outside the text"
+        );
+    }
+
+    /// Clamps the cursor and selection after the lines changed under them.
+    fn clamp_cursor(&mut self) {
+        self.cursor = self.clamp_to_text(self.cursor);
+        self.selection = self.selection.map(|selection| self.clamp_to_text(selection));
+    }
+
+    /// Returns and resets whether anything visible changed since the last call.
+    pub fn take_redraw(&mut self) -> bool {
+        mem::take(&mut self.redraw)
+    }
+
+    /// Returns and resets whether a key was rejected since the last call, as opposed to changing nothing.
+    pub fn take_rejected(&mut self) -> bool {
+        mem::take(&mut self.rejected)
+    }
+
+    /// Marks the key being handled as rejected. Returns `false` for "not modified", to return from [`TextArea::input`].
+    fn reject(&mut self) -> bool {
+        self.rejected = true;
+        false
+    }
+
+    /// The start and end of the selection, whichever way round it was made.
+    pub fn selection_range(&self) -> Option<(CursorPosition, CursorPosition)> {
+        let selection = self.selection()?;
+        Some((cmp::min(self.cursor, selection), cmp::max(self.cursor, selection)))
+    }
+
+    /// Moves the cursor to `row` and `col`, clamped to the text, and centers it on the next render.
+    pub fn goto(&mut self, row: usize, col: usize) {
+        let row = cmp::min(row, self.lines.len() - 1);
+        let col = cmp::min(col, self.lines[row].chars().count());
+        self.set_cursor(CursorPosition { row, col }, false);
+        self.center_view();
+    }
+
+    /// Centers the cursor vertically on the next render instead of scrolling minimally.
+    pub fn center_view(&mut self) {
+        self.view.center.set(true);
+        self.redraw = true;
+    }
+
+    /// Removes the highlight undo and redo leave on the lines they changed, returning whether there was one.
+    pub fn clear_flash(&mut self) -> bool {
+        let cleared = self.flash.take().is_some();
+        self.redraw |= cleared;
+        cleared
+    }
+
+    /// Moves the cursor to where undo or redo left it, centering it if that is out of view, so what changed is in
+    /// the middle of the screen rather than at its edge.
+    fn set_cursor_after_undo(&mut self, cursor: CursorPosition) {
+        self.set_cursor(cursor, false);
+        let top = self.view.position.get().row;
+        if !(top..top.saturating_add(self.view.height.get())).contains(&self.cursor.row) {
+            self.center_view();
+        }
+    }
+
+    /// Scrolls the view just far enough to show the cursor, so a view the cursor is still inside is left untouched.
+    /// Returns the top left and bottom right of the view, in rows and display columns of the text.
     pub fn update_size(&self, width: usize, height: usize) -> (CursorPosition, CursorPosition) {
         self.view.width.set(width);
         self.view.height.set(height);
 
         let cursor = self.cursor();
-        let position = self.view.position.get();
+        let mut position = self.view.position.get();
+        if self.view.center.take() {
+            position.row = cursor.row.saturating_sub(height / 2);
+        }
 
-        let slice = self.lines[cursor.row].char_slice(..cursor.col);
-        let tabs = slice.chars().filter(|&c| c == '\t').count();
-        let tab_width = self.indent.spaces().len();
-        let col = slice.width() + tabs * (tab_width - 1);
+        let expanded = self.expanded_line(cursor.row);
+        let col = expanded.display_col(cursor.col);
+        // all of a wide char under the cursor is shown, past the end of the line a column for the cursor itself
+        let char_width = cmp::max(expanded.display_col(cursor.col + 1) - col, 1);
+        let text_width = width.saturating_sub(usize::from(self.gutter_width()));
 
+        // a view too small to show anything still keeps the cursor's line and column at its top left
         self.view.position.set(CursorPosition {
-            row: position.row.clamp(cursor.row.saturating_sub(height - 1), cursor.row),
-            col: position.col.clamp(
-                col.saturating_sub(width - usize::from(num_digits(self.lines.len())) - 1),
-                col,
-            ),
+            row: position
+                .row
+                .clamp(cursor.row.saturating_sub(height.saturating_sub(1)), cursor.row),
+            col: position
+                .col
+                .clamp(cmp::min((col + char_width).saturating_sub(text_width), col), col),
         });
 
         let position = self.view.position.get();
@@ -110,67 +405,202 @@ impl TextArea {
             position,
             CursorPosition {
                 row: position.row.saturating_add(height),
-                col: position.col.saturating_add(width),
+                col: position.col.saturating_add(text_width),
             },
         )
     }
 
-    pub fn terminal_cursor_position(&self) -> Position {
-        let offset = if self.line_numbers {
+    /// How far PageUp and PageDown move: a screen, less two lines so one line of the old screen stays in view, but
+    /// at least one line on a tiny one.
+    fn page_step(&self) -> usize {
+        cmp::max(self.view.height.get().saturating_sub(2), 1)
+    }
+
+    /// Scrolls the view a page along with the cursor, so the cursor stays on the same screen row unless the text ends
+    /// first.
+    fn scroll_page(&self, forward: bool) {
+        let mut position = self.view.position.get();
+        position.row = if forward {
+            let last_top = self.lines.len().saturating_sub(self.view.height.get());
+            cmp::min(position.row + self.page_step(), cmp::max(position.row, last_top))
+        } else {
+            position.row.saturating_sub(self.page_step())
+        };
+        self.view.position.set(position);
+    }
+
+    /// Width of the line numbers and the space after them, 0 without line numbers.
+    pub fn gutter_width(&self) -> u16 {
+        if self.line_numbers {
             u16::from(num_digits(self.lines.len())) + 1
         } else {
             0
-        };
+        }
+    }
 
-        let position = self.view.position.get();
-        let cursor = self.cursor();
-        let tab_width = self.indent.spaces().len();
+    /// The line shown `y` rows into the area last rendered to, clamped to the last line.
+    pub fn line_at(&self, y: u16) -> usize {
+        cmp::min(self.view.position.get().row + usize::from(y), self.lines.len() - 1)
+    }
 
-        let col = {
-            let slice = self.lines[cursor.row].char_slice(..cursor.col);
-            let tabs = slice.chars().filter(|&c| c == '\t').count();
-            slice.width() + tabs * (tab_width - 1)
+    /// Selects the whole lines from `anchor` to `row`, either way round, ending before the line after them like a
+    /// selection made with Shift+Down.
+    pub fn select_lines(&mut self, anchor: usize, row: usize) {
+        let last = self.lines.len() - 1;
+        let (anchor, row) = (cmp::min(anchor, last), cmp::min(row, last));
+        let after = |row: usize| {
+            if row < last {
+                CursorPosition { row: row + 1, col: 0 }
+            } else {
+                CursorPosition {
+                    row,
+                    col: self.lines[row].chars().count(),
+                }
+            }
+        };
+        let (start, end) = if row >= anchor {
+            (CursorPosition { row: anchor, col: 0 }, after(row))
+        } else {
+            (after(anchor), CursorPosition { row, col: 0 })
         };
+        self.set_cursor(start, false);
+        self.set_cursor(end, true);
+    }
+
+    /// The line a line selection made with [`TextArea::select_lines`] was started from, or the cursor line.
+    pub fn line_selection_anchor(&self) -> usize {
+        match self.selection {
+            // selected upwards, so the anchor is just after the first line
+            Some(CursorPosition { row, col: 0 }) if row > 0 && self.selection > Some(self.cursor) => row - 1,
+            Some(CursorPosition { row, .. }) => row,
+            None => self.cursor.row,
+        }
+    }
 
-        let line = self.lines[cursor.row].replace("\t", self.indent.spaces());
-        let slice = line.as_str().char_slice(position.col..col);
+    pub fn terminal_cursor_position(&self) -> Position {
+        let offset = self.gutter_width();
 
-        let tabs = slice.chars().filter(|&c| c == '\t').count();
-        let line_width = slice.width() + tabs * (tab_width - 1);
+        let position = self.view.position.get();
+        let cursor = self.cursor();
+        let col = self.expanded_line(cursor.row).display_col(cursor.col);
 
+        // kept inside the view, even when it is too narrow or hasn't caught up with the cursor yet
+        let x = cmp::min(
+            usize::from(offset) + col.saturating_sub(position.col),
+            self.view.width.get().saturating_sub(1),
+        );
+        let y = cmp::min(
+            cursor.row.saturating_sub(position.row),
+            self.view.height.get().saturating_sub(1),
+        );
         Position {
-            x: offset + u16::try_from(line_width).unwrap(),
-            y: u16::try_from(cursor.row - position.row).unwrap(),
+            x: u16::try_from(x).unwrap_or(u16::MAX),
+            y: u16::try_from(y).unwrap_or(u16::MAX),
         }
     }
 
     pub fn set_search_pattern(&mut self, pattern: &str) -> Result<()> {
         match &self.search_pattern {
             Some(r) if r.as_str() == pattern => {}
-            _ if pattern.is_empty() => self.search_pattern = None,
-            _ => self.search_pattern = Some(Regex::new(pattern)?),
+            _ if pattern.is_empty() => self.replace_search_pattern(None),
+            _ => {
+                let regex = RegexBuilder::new(pattern)
+                    .size_limit(SEARCH_SIZE_LIMIT)
+                    .dfa_size_limit(SEARCH_SIZE_LIMIT)
+                    .build()?;
+                self.replace_search_pattern(Some(regex));
+            }
         }
+        self.redraw = true;
         Ok(())
     }
 
+    fn replace_search_pattern(&mut self, pattern: Option<Regex>) {
+        self.search_pattern = pattern;
+        self.search_matches.get_mut().1.clear();
+    }
+
+    /// Byte ranges of the search pattern's matches in line `row`, found once per edit and pattern, so drawing,
+    /// searching and counting share them.
+    fn search_matches(&self, row: usize) -> LineMatches {
+        let Some(pattern) = &self.search_pattern else {
+            return Rc::new([]);
+        };
+
+        let mut cache = self.search_matches.borrow_mut();
+        if cache.0 != self.version {
+            *cache = (self.version, HashMap::new());
+        }
+        let matches = cache
+            .1
+            .entry(row)
+            .or_insert_with(|| pattern.find_iter(&self.lines[row]).map(|m| m.range()).collect());
+        Rc::clone(matches)
+    }
+
+    pub fn search_pattern(&self) -> Option<&Regex> {
+        self.search_pattern.as_ref()
+    }
+
+    /// Counts the matches of `pattern`, returning their number and the rows they are on. Empty matches aren't counted,
+    /// as [`Self::replace_all`] doesn't replace them.
+    pub fn count_matches(&self, pattern: &Regex) -> (usize, Vec<usize>) {
+        let is_search_pattern = self
+            .search_pattern
+            .as_ref()
+            .is_some_and(|p| p.as_str() == pattern.as_str());
+        let mut count = 0;
+        let mut rows = Vec::new();
+        for (row, line) in self.lines.iter().enumerate() {
+            let matches = if is_search_pattern {
+                self.search_matches(row).iter().filter(|m| !m.is_empty()).count()
+            } else {
+                pattern.find_iter(line).filter(|m| !m.is_empty()).count()
+            };
+            if matches > 0 {
+                count += matches;
+                rows.push(row);
+            }
+        }
+        (count, rows)
+    }
+
+    /// The run of alphanumeric characters and underscores the cursor is in or right after.
+    pub fn word_at_cursor(&self) -> Option<&str> {
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let line = &self.lines[self.cursor.row];
+        let cursor = line.byte_index(self.cursor.col);
+
+        let start = line[..cursor]
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_word(c))
+            .last()
+            .map_or(cursor, |(idx, _)| idx);
+        let end = line[cursor..]
+            .char_indices()
+            .find(|&(_, c)| !is_word(c))
+            .map_or(line.len(), |(idx, _)| cursor + idx);
+        (start < end).then(|| &line[start..end])
+    }
+
     pub fn search_forward(&self) -> Option<(CursorPosition, CursorPosition)> {
         let search_pattern = self.search_pattern.as_ref()?;
 
         let cursor_line = self.lines.get(self.cursor.row)?;
-        let lines_after_cursor = self.lines.split_at_checked(self.cursor.row + 1)?.1;
 
+        // a match may start inside an earlier one, so the cursor line is searched from the cursor on
         search_pattern
-            .find_at(cursor_line, self.cursor.col + 1)
-            .map(|m| (self.cursor.row, m, cursor_line))
+            .find_at(cursor_line, cursor_line.byte_index(self.cursor.col + 1))
+            .map(|m| (self.cursor.row, m.range()))
             .or_else(|| {
-                lines_after_cursor
-                    .iter()
-                    .enumerate()
-                    .find_map(|(i, line)| search_pattern.find(line).map(|m| (self.cursor.row + 1 + i, m, line)))
+                (self.cursor.row + 1..self.lines.len())
+                    .find_map(|row| Some((row, self.search_matches(row).first()?.clone())))
             })
-            .map(|(row, m, line)| {
-                let start_col = line[0..m.start()].chars().count();
-                let end_col = start_col + line[m.start()..m.end()].chars().count();
+            .map(|(row, m)| {
+                let line = &self.lines[row];
+                let start_col = line[0..m.start].chars().count();
+                let end_col = start_col + line[m.start..m.end].chars().count();
                 (
                     CursorPosition { row, col: start_col },
                     CursorPosition { row, col: end_col },
@@ -179,87 +609,321 @@ impl TextArea {
     }
 
     pub fn search_backward(&self) -> Option<(CursorPosition, CursorPosition)> {
-        let search_pattern = self.search_pattern.as_ref()?;
-
-        let cursor_line = self
-            .lines
-            .get(self.cursor.row)?
-            .split_at_checked(self.cursor.col.saturating_sub(1))?
-            .0;
-        let lines_before_cursor = self.lines.split_at_checked(self.cursor.row)?.0;
+        self.search_pattern.as_ref()?;
+        let cursor_line = self.lines.get(self.cursor.row)?;
 
-        search_pattern
-            .find_iter(cursor_line)
+        // any match starting before the cursor, the whole line is searched so it may run past the cursor
+        let cursor_idx = cursor_line.byte_index(self.cursor.col);
+        self.search_matches(self.cursor.row)
+            .iter()
+            .take_while(|m| m.start < cursor_idx)
             .last()
-            .map(|m| (self.cursor.row, m, cursor_line))
+            .map(|m| (self.cursor.row, m.clone()))
             .or_else(|| {
-                lines_before_cursor.iter().rev().enumerate().find_map(|(i, line)| {
-                    search_pattern
-                        .find_iter(line)
-                        .last()
-                        .map(|m| (self.cursor.row - i - 1, m, line.as_str()))
-                })
+                (0..self.cursor.row)
+                    .rev()
+                    .find_map(|row| Some((row, self.search_matches(row).last()?.clone())))
+            })
+            .map(|(row, m)| {
+                let line = &self.lines[row];
+                let start_col = line[0..m.start].chars().count();
+                let end_col = start_col + line[m.start..m.end].chars().count();
+                (
+                    CursorPosition { row, col: start_col },
+                    CursorPosition { row, col: end_col },
+                )
             })
-            .map(|(row, m, line)| {
-                let start_col = line[0..m.start()].chars().count();
-                let end_col = start_col + line[m.start()..m.end()].chars().count();
+    }
+
+    /// Finds the first match in the buffer, regardless of the cursor.
+    pub fn search_first(&self) -> Option<(CursorPosition, CursorPosition)> {
+        self.search_pattern.as_ref()?;
+
+        self.lines.iter().enumerate().find_map(|(row, line)| {
+            self.search_matches(row).first().map(|m| {
+                let start_col = line[0..m.start].chars().count();
+                let end_col = start_col + line[m.start..m.end].chars().count();
                 (
                     CursorPosition { row, col: start_col },
                     CursorPosition { row, col: end_col },
                 )
             })
+        })
+    }
+
+    /// Selects a search match with the cursor at its start. A match outside the view is scrolled to a third of the way
+    /// down, so the lines after it are visible too.
+    pub fn select_match(&mut self, (start, end): (CursorPosition, CursorPosition)) {
+        self.set_cursor(start, false);
+        self.set_selection(Some(end));
+
+        let (top, height) = (self.view.position.get().row, self.view.height.get());
+        if start.row < top || start.row >= top + height {
+            self.set_view_row(start.row.saturating_sub(height / 3));
+        }
+    }
+
+    /// Scrolls so `row` is the first visible line, as far as the cursor stays visible.
+    pub fn set_view_row(&mut self, row: usize) {
+        let position = self.view.position.get();
+        self.view.position.set(CursorPosition { row, ..position });
+        self.redraw = true;
+    }
+
+    /// Scrolls as little as possible to show all of `rows`, or centers them if they don't fit, as far as the cursor
+    /// stays visible. Does nothing before the first render, when the view's height isn't known.
+    pub fn show_rows(&mut self, rows: RangeInclusive<usize>) {
+        let (top, height) = (self.view.position.get().row, self.view.height.get());
+        let (start, end) = (*rows.start(), *rows.end());
+        if height == 0 {
+            return;
+        }
+        if end - start >= height {
+            self.set_view_row((start + (end - start) / 2).saturating_sub(height / 2));
+        } else if start < top {
+            self.set_view_row(start);
+        } else if end >= top + height {
+            self.set_view_row(end + 1 - height);
+        }
     }
 
     pub fn do_action(&mut self, history_action: HistoryAction) -> CursorPosition {
+        if self.single_line && history_action.adds_lines() {
+            return self.cursor;
+        }
+        let Some(cursor) = self.apply(&history_action) else {
+            return self.cursor;
+        };
         self.redo_history.clear();
-
-        let cursor = history_action.apply(&mut self.lines);
-        self.undo_history.push((history_action, false));
+        self.undo_history.push(Done {
+            action: history_action,
+            chain: false,
+            marks: None,
+        });
+        self.clamp_cursor();
         cursor
     }
 
     pub fn do_action_chain(&mut self, history_action: HistoryAction) -> CursorPosition {
+        if self.single_line && history_action.adds_lines() {
+            return self.cursor;
+        }
+        let Some(cursor) = self.apply(&history_action) else {
+            return self.cursor;
+        };
         self.redo_history.clear();
+        self.undo_history.push(Done {
+            action: history_action,
+            chain: true,
+            marks: None,
+        });
+        self.clamp_cursor();
+        cursor
+    }
+
+    /// Does `action` to the text, updating the count of lines with mixed indentation from the lines it changes. One
+    /// that doesn't [fit](HistoryAction::fits) the text, made for some other text before it was replaced, is left
+    /// undone and returns `None`.
+    fn apply(&mut self, action: &HistoryAction) -> Option<CursorPosition> {
+        // no lines at all stand for an empty one, which the action puts in
+        if !self.lines.is_empty() && !action.fits(&self.lines) {
+            return None;
+        }
+        let counted = self
+            .mixed_indent
+            .take()
+            .filter(|counted| counted.is_for(self) && !self.lines.is_empty());
+        let (len, rows) = (self.lines.len(), action.rows_before(&self.lines));
+        let before = counted.as_ref().map(|_| self.count_mixed_indent(rows.clone()));
+
+        let cursor = action.apply(&mut self.lines);
+        self.redraw = true;
+        self.version += 1;
 
-        let cursor = history_action.apply(&mut self.lines);
-        self.undo_history.push((history_action, true));
+        if let (Some(counted), Some(before)) = (counted, before)
+            && let Some(end) = (rows.end + self.lines.len()).checked_sub(len)
+            && end >= rows.start
+        {
+            self.mixed_indent.set(Some(MixedIndent {
+                version: self.version,
+                lines: self.lines.len(),
+                count: counted.count - before + self.count_mixed_indent(rows.start..end),
+                ..counted
+            }));
+        }
         cursor
     }
 
-    pub fn undo_action(&mut self) -> Option<CursorPosition> {
-        let mut chain;
-        loop {
-            let (action, next_chain) = self.undo_history.pop()?;
-            chain = next_chain;
+    fn count_mixed_indent(&self, rows: Range<usize>) -> usize {
+        self.lines[rows]
+            .iter()
+            .filter(|line| self.indent.is_mixed(line))
+            .count()
+    }
+
+    /// How many lines have leading whitespace other than the indent style's, like tabs in a file indented with
+    /// spaces.
+    pub fn mixed_indent_lines(&self) -> usize {
+        let counted = self
+            .mixed_indent
+            .take()
+            .filter(|counted| counted.is_for(self))
+            .unwrap_or_else(|| MixedIndent {
+                version: self.version,
+                lines: self.lines.len(),
+                indent: self.indent.clone(),
+                count: self.count_mixed_indent(0..self.lines.len()),
+            });
+        let count = counted.count;
+        self.mixed_indent.set(Some(counted));
+        count
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo_history.len()
+    }
 
-            let inverse_action = action.invert();
-            let cursor = inverse_action.apply(&mut self.lines);
-            self.redo_history.push((inverse_action, chain));
+    /// Joins the actions done since the undo history was `undo_len` long into one undo step.
+    pub fn chain_undo_since(&mut self, undo_len: usize) {
+        for done in self.undo_history.iter_mut().skip(undo_len + 1) {
+            done.chain = true;
+        }
+    }
 
-            if !chain {
-                return Some(cursor);
-            }
+    /// Joins the actions done since the undo history was `undo_len` long into one undo step, remembering the cursor
+    /// and selection from `before` it and from now.
+    fn join_step(&mut self, undo_len: usize, before: (CursorPosition, Option<CursorPosition>)) {
+        self.chain_undo_since(undo_len);
+        if let Some(first) = self.undo_history.get_mut(undo_len) {
+            first.marks = Some([before, (self.cursor, self.selection)]);
         }
     }
 
-    pub fn redo_action(&mut self) -> Option<CursorPosition> {
-        let mut chain;
-        loop {
-            let (action, next_chain) = self.redo_history.pop()?;
-            chain = next_chain;
+    /// Does what `f` does as one undo step, or if it fails, takes it back, leaving the text, the cursor, the selection
+    /// and the undo and redo history as they were before it.
+    pub fn transaction<T, E>(&mut self, f: impl FnOnce(&mut TextArea) -> Result<T, E>) -> Result<T, E> {
+        let transaction = self.begin_transaction();
+        let result = f(self);
+        self.end_transaction(transaction, result.is_ok());
+        result
+    }
 
-            let inverse_action = action.invert();
-            let cursor = inverse_action.apply(&mut self.lines);
-            self.undo_history.push((inverse_action, chain));
+    /// Starts a transaction like [`TextArea::transaction`] for callers that also need what is around the text area,
+    /// ended with [`TextArea::end_transaction`].
+    pub fn begin_transaction(&mut self) -> Transaction {
+        Transaction {
+            undo_len: self.undo_history.len(),
+            before: (self.cursor, self.selection),
+            redo_history: mem::take(&mut self.redo_history),
+        }
+    }
 
-            if !chain {
-                return Some(cursor);
+    /// Joins what was done since `transaction` began into one undo step if `keep`, or otherwise takes it back.
+    pub fn end_transaction(&mut self, transaction: Transaction, keep: bool) {
+        let Transaction { undo_len, before, redo_history } = transaction;
+        if keep {
+            self.join_step(undo_len, before);
+        } else {
+            let undo_len = cmp::min(undo_len, self.undo_history.len());
+            let undone = self.undo_history.split_off(undo_len);
+            for done in undone.into_iter().rev() {
+                self.apply(&done.action.invert());
             }
+            (self.cursor, self.selection) = before;
+            self.clamp_cursor();
+        }
+        if self.undo_history.len() == undo_len {
+            self.redo_history = redo_history;
+        }
+    }
+
+    /// Starts an undo step that everything done through the returned [`Chain`] joins.
+    pub fn begin_chain(&mut self) -> Chain<'_> {
+        Chain {
+            undo_len: self.undo_history.len(),
+            before: (self.cursor, self.selection),
+            textarea: self,
         }
     }
 
+    /// Undoes the last undo step like Ctrl+Z, moving the cursor to it.
+    pub fn undo(&mut self) -> Option<UndoStep> {
+        let redo_len = self.redo_history.len();
+        let (cursor, selection) = self.undo_action()?;
+        self.set_cursor_after_undo(cursor);
+        self.set_selection(selection);
+        Some(UndoStep {
+            actions: self.redo_history.len() - redo_len,
+            remaining: steps(self.undo_history.iter()),
+        })
+    }
+
+    /// Redoes the last undone step like Ctrl+Y, moving the cursor to it.
+    pub fn redo(&mut self) -> Option<UndoStep> {
+        let undo_len = self.undo_history.len();
+        let (cursor, selection) = self.redo_action()?;
+        self.set_cursor_after_undo(cursor);
+        self.set_selection(selection);
+        Some(UndoStep {
+            actions: self.undo_history.len() - undo_len,
+            // redo takes steps from the other end
+            remaining: steps(self.redo_history.iter().rev()),
+        })
+    }
+
+    /// Undoes the last undo step, highlighting the lines it changed. Returns where the cursor was before it, and the
+    /// selection then if the step was made in a [`Chain`], which is otherwise dropped.
+    pub fn undo_action(&mut self) -> Option<(CursorPosition, Option<CursorPosition>)> {
+        let redo_len = self.redo_history.len();
+        let mut cursor = None;
+        let mut marks = None;
+        while let Some(done) = self.undo_history.pop_if(|_| cursor.is_none_or(|(_, chain)| chain)) {
+            let action = done.action.invert();
+            let Some(after) = self.apply(&action) else {
+                // the text was replaced since, and the rest of the history is for the old one
+                self.undo_history.clear();
+                break;
+            };
+            cursor = Some((after, done.chain));
+            // the step's first action comes off last
+            marks = done.marks.or(marks);
+            self.redo_history.push(Done { action, ..done });
+        }
+        self.clamp_cursor();
+        self.flash = changed_rows(&self.redo_history[redo_len..]);
+        let (cursor, _) = cursor?;
+        Some(marks.map_or((cursor, None), |[before, _]| before))
+    }
+
+    /// Redoes the last undone step, highlighting the lines it changed. Returns where the cursor ends up, and the
+    /// selection after the step if it was made in a [`Chain`], which is otherwise dropped.
+    pub fn redo_action(&mut self) -> Option<(CursorPosition, Option<CursorPosition>)> {
+        // undo took a chain back from its end, so its first action comes back first and the chained ones follow it
+        let undo_len = self.undo_history.len();
+        let mut cursor = None;
+        let mut marks = None;
+        while let Some(done) = self.redo_history.pop_if(|done| cursor.is_none() || done.chain) {
+            let action = done.action.invert();
+            let Some(after) = self.apply(&action) else {
+                // the text was replaced since, and the rest of the history is for the old one
+                self.redo_history.clear();
+                break;
+            };
+            cursor = Some(after);
+            marks = marks.or(done.marks);
+            self.undo_history.push(Done { action, ..done });
+        }
+        self.clamp_cursor();
+        self.flash = changed_rows(&self.undo_history[undo_len..]);
+        Some(marks.map_or((cursor?, None), |[_, after]| after))
+    }
+
     pub fn input(&mut self, input: Input) -> bool {
+        let last_paste = self.last_paste.take();
+        self.clear_flash();
+
         match input {
+            Input { key: Key::Up | Key::Down, .. } if self.single_line => false,
             Input {
                 key: Key::Up,
                 shift,
@@ -272,7 +936,7 @@ impl TextArea {
                     self.set_cursor(
                         CursorPosition {
                             row: cursor.row - 1,
-                            col: cursor.col.min(lines[cursor.row - 1].len()),
+                            col: cursor.col.min(lines[cursor.row - 1].chars().count()),
                         },
                         shift,
                     );
@@ -295,7 +959,7 @@ impl TextArea {
                     .skip_while(|(_, line)| line.trim_start().is_empty())
                     .find_map(|(idx, line)| line.trim_start().is_empty().then_some(idx + 1))
                     .unwrap_or(0);
-                let col = cursor.col.min(lines[row].len());
+                let col = cursor.col.min(lines[row].chars().count());
 
                 self.set_cursor(CursorPosition { row, col }, shift);
                 false
@@ -311,7 +975,7 @@ impl TextArea {
                 self.set_cursor(
                     CursorPosition {
                         row: 0,
-                        col: cursor.col.min(lines[0].len()),
+                        col: cursor.col.min(lines[0].chars().count()),
                     },
                     shift,
                 );
@@ -329,7 +993,7 @@ impl TextArea {
                     self.set_cursor(
                         CursorPosition {
                             row: cursor.row + 1,
-                            col: cursor.col.min(lines[cursor.row + 1].len()),
+                            col: cursor.col.min(lines[cursor.row + 1].chars().count()),
                         },
                         shift,
                     );
@@ -364,7 +1028,7 @@ impl TextArea {
                 self.set_cursor(
                     CursorPosition {
                         row,
-                        col: cursor.col.min(lines[row].len()),
+                        col: cursor.col.min(lines[row].chars().count()),
                     },
                     shift,
                 );
@@ -380,8 +1044,8 @@ impl TextArea {
                 let cursor = self.cursor();
                 self.set_cursor(
                     CursorPosition {
-                        row: lines.len().saturating_sub(1),
-                        col: cursor.col.min(lines[0].len()),
+                        row: lines.len() - 1,
+                        col: cursor.col.min(lines.last().unwrap().chars().count()),
                     },
                     shift,
                 );
@@ -411,7 +1075,7 @@ impl TextArea {
                                 self.set_cursor(
                                     CursorPosition {
                                         row: cursor.row - 1,
-                                        col: lines[cursor.row - 1].len(),
+                                        col: lines[cursor.row - 1].chars().count(),
                                     },
                                     shift,
                                 );
@@ -437,7 +1101,7 @@ impl TextArea {
                     None if cursor.col > 0 => CursorPosition { col: 0, ..cursor },
                     None if cursor.row > 0 => CursorPosition {
                         row: cursor.row - 1,
-                        col: lines[cursor.row - 1].len(),
+                        col: lines[cursor.row - 1].chars().count(),
                     },
                     None => cursor,
                 };
@@ -463,7 +1127,7 @@ impl TextArea {
                         }
                     }
                     _ => {
-                        if cursor.col == lines[cursor.row].len() {
+                        if cursor.col == lines[cursor.row].chars().count() {
                             if cursor.row < lines.len() - 1 {
                                 self.set_cursor(CursorPosition { row: cursor.row + 1, col: 0 }, shift);
                             }
@@ -485,8 +1149,8 @@ impl TextArea {
 
                 let cursor = match lines[cursor.row].next_word(cursor.col) {
                     Some(col) => CursorPosition { col, ..cursor },
-                    None if cursor.col < lines[cursor.row].len() => CursorPosition {
-                        col: lines[cursor.row].len(),
+                    None if cursor.col < lines[cursor.row].chars().count() => CursorPosition {
+                        col: lines[cursor.row].chars().count(),
                         ..cursor
                     },
                     None if cursor.row < lines.len() - 1 => CursorPosition { row: cursor.row + 1, col: 0 },
@@ -496,6 +1160,46 @@ impl TextArea {
                 self.set_cursor(cursor, shift);
                 false
             }
+            Input {
+                key: Key::Left,
+                shift,
+                alt: true,
+                ctrl: false,
+            } => {
+                let lines = &self.lines;
+                let cursor = self.cursor();
+
+                let cursor = match lines[cursor.row].previous_subword(cursor.col) {
+                    Some(col) => CursorPosition { col, ..cursor },
+                    None if cursor.col > 0 => CursorPosition { col: 0, ..cursor },
+                    None if cursor.row > 0 => CursorPosition {
+                        row: cursor.row - 1,
+                        col: lines[cursor.row - 1].chars().count(),
+                    },
+                    None => cursor,
+                };
+                self.set_cursor(cursor, shift);
+                false
+            }
+            Input {
+                key: Key::Right,
+                shift,
+                alt: true,
+                ctrl: false,
+            } => {
+                let lines = &self.lines;
+                let cursor = self.cursor();
+                let len = lines[cursor.row].chars().count();
+
+                let cursor = match lines[cursor.row].next_subword(cursor.col) {
+                    Some(col) => CursorPosition { col, ..cursor },
+                    None if cursor.col < len => CursorPosition { col: len, ..cursor },
+                    None if cursor.row < lines.len() - 1 => CursorPosition { row: cursor.row + 1, col: 0 },
+                    None => cursor,
+                };
+                self.set_cursor(cursor, shift);
+                false
+            }
             Input {
                 key: Key::Home,
                 shift,
@@ -503,7 +1207,14 @@ impl TextArea {
                 ctrl: false,
             } => {
                 let cursor = self.cursor();
-                self.set_cursor(CursorPosition { col: 0, ..cursor }, shift);
+                let line = &self.lines[cursor.row];
+
+                // toggle between the first non-whitespace character and the start of the line
+                let col = match line.chars().position(|c| !c.is_whitespace()) {
+                    Some(col) if col != cursor.col => col,
+                    _ => 0,
+                };
+                self.set_cursor(CursorPosition { col, ..cursor }, shift);
                 false
             }
             Input {
@@ -529,14 +1240,13 @@ impl TextArea {
                 alt: false,
                 ctrl: false,
             } => {
-                let lines = &self.lines;
                 let cursor = self.cursor();
-
-                let row = cursor.row.saturating_sub(self.view.height.get());
+                let row = cursor.row.saturating_sub(self.page_step());
+                self.scroll_page(false);
                 self.set_cursor(
                     CursorPosition {
                         row,
-                        col: cursor.col.min(lines[row].chars().count()),
+                        col: cursor.col.min(self.lines[row].chars().count()),
                     },
                     shift,
                 );
@@ -548,14 +1258,13 @@ impl TextArea {
                 alt: false,
                 ctrl: false,
             } => {
-                let lines = &self.lines;
                 let cursor = self.cursor();
-
-                let row = std::cmp::min(lines.len() - 1, cursor.row + self.view.height.get());
+                let row = cmp::min(self.lines.len() - 1, cursor.row + self.page_step());
+                self.scroll_page(true);
                 self.set_cursor(
                     CursorPosition {
                         row,
-                        col: cursor.col.min(lines[row].chars().count()),
+                        col: cursor.col.min(self.lines[row].chars().count()),
                     },
                     shift,
                 );
@@ -570,7 +1279,7 @@ impl TextArea {
                 self.set_cursor(
                     CursorPosition {
                         row: self.lines.len() - 1,
-                        col: self.lines.last().unwrap().len(),
+                        col: self.lines.last().unwrap().chars().count(),
                     },
                     false,
                 );
@@ -582,37 +1291,24 @@ impl TextArea {
                 ctrl: true,
                 alt: false,
                 shift: false,
-            } => {
-                if let Some(cursor) = self.undo_action() {
-                    self.set_cursor(cursor, false);
-                    true
-                } else {
-                    false
-                }
-            }
+            } => self.undo().is_some(),
             Input {
                 key: Key::Char('y'),
                 ctrl: true,
                 alt: false,
                 shift: false,
-            } => {
-                if let Some(cursor) = self.redo_action() {
-                    self.set_cursor(cursor, false);
-                    true
-                } else {
-                    false
-                }
-            }
+            } => self.redo().is_some(),
             Input {
                 key: Key::Char('c'),
                 ctrl: true,
                 alt: false,
                 shift: false,
-            } => {
+            }
+            | Input { key: Key::Copy, .. } => {
                 if let Some(selected_text) = self.selected_text(false) {
-                    _ = self.clipboard.set_text(selected_text.join("\n"));
+                    self.clipboard.set_text(selected_text.join("\n"));
                 } else {
-                    _ = self.clipboard.set_text(&self.lines[self.cursor.row]);
+                    self.clipboard.set_line(&self.lines[self.cursor.row]);
                 }
                 false
             }
@@ -621,24 +1317,76 @@ impl TextArea {
                 ctrl: true,
                 alt: false,
                 shift: false,
-            } => {
-                if let Some((selection, selected_text)) = self.selection().zip(self.selected_text(false)) {
-                    let lines = &self.lines;
-                    let cursor = self.cursor();
+            }
+            | Input { key: Key::Cut, .. } => {
+                let cursor = self.cursor();
 
+                if let Some((selection, selected_text)) = self.selection().zip(self.selected_text(false)) {
                     let start = if cursor < selection { cursor } else { selection };
 
-                    _ = self.clipboard.set_text(selected_text.join("\n"));
+                    self.clipboard.set_text(selected_text.join("\n"));
                     let cursor = self.do_action(HistoryAction::RemoveLines {
                         lines: selected_text,
-                        position: BytePosition {
-                            row: cursor.row,
-                            col: lines[cursor.row].byte_index(start.col),
-                        },
+                        position: BytePosition::from_line(start, &self.lines[start.row]),
                         cursor: (cursor, start),
                     });
                     self.set_cursor(cursor, false);
+                } else {
+                    let lines = &self.lines;
+                    let line = lines[cursor.row].clone();
+                    self.clipboard.set_line(&line);
+
+                    let action = if cursor.row < lines.len() - 1 {
+                        HistoryAction::RemoveLines {
+                            lines: vec![line, String::new()],
+                            position: BytePosition { row: cursor.row, col: 0 },
+                            cursor: (
+                                cursor,
+                                CursorPosition {
+                                    col: cursor.col.min(lines[cursor.row + 1].chars().count()),
+                                    ..cursor
+                                },
+                            ),
+                        }
+                    } else if cursor.row > 0 {
+                        // no following line, so remove the linebreak before the line instead
+                        HistoryAction::RemoveLines {
+                            lines: vec![String::new(), line],
+                            position: BytePosition {
+                                row: cursor.row - 1,
+                                col: lines[cursor.row - 1].len(),
+                            },
+                            cursor: (
+                                cursor,
+                                CursorPosition {
+                                    row: cursor.row - 1,
+                                    col: cursor.col.min(lines[cursor.row - 1].chars().count()),
+                                },
+                            ),
+                        }
+                    } else {
+                        HistoryAction::RemoveLines {
+                            lines: vec![line],
+                            position: BytePosition { row: 0, col: 0 },
+                            cursor: (cursor, CursorPosition { row: 0, col: 0 }),
+                        }
+                    };
+
+                    let cursor = self.do_action(action);
+                    self.set_cursor(cursor, false);
+                }
 
+                true
+            }
+            Input {
+                key: Key::Char('v'),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            }
+            | Input { key: Key::Paste, .. } => {
+                if let Some(clip) = self.clipboard.get() {
+                    self.paste(clip, 0, false, true);
                     true
                 } else {
                     false
@@ -646,69 +1394,128 @@ impl TextArea {
             }
             Input {
                 key: Key::Char('v'),
+                ctrl: false,
+                alt: true,
+                shift: false,
+            } => {
+                if let Some(clip) = self.clipboard.get() {
+                    self.paste(clip, 0, false, false);
+                    true
+                } else {
+                    false
+                }
+            }
+            Input {
+                key: Key::Char('V'),
                 ctrl: true,
                 alt: false,
-                shift: false,
+                shift: true,
             } => {
-                if let Ok(text) = self.clipboard.get_text() {
-                    let text = text
-                        .split('\n')
-                        .map(|l| l.trim_end_matches('\r').to_string())
-                        .collect::<Vec<_>>();
-
-                    let cursor = self.cursor();
-                    let (cursor, chain) = match self.selection().zip(self.selected_text(true)) {
-                        Some((selection, selected_text)) => {
-                            let start = if cursor < selection { cursor } else { selection };
-                            (
-                                self.do_action(HistoryAction::RemoveLines {
-                                    lines: selected_text,
-                                    position: BytePosition::from_line(start, &self.lines[start.row]),
-                                    cursor: (cursor, start),
-                                }),
-                                true,
-                            )
-                        }
-                        None => (cursor, false),
-                    };
+                let Some(paste) = last_paste
+                    .filter(|paste| paste.undo_len == self.undo_history.len() && paste.cursor.1 == self.cursor)
+                else {
+                    return false;
+                };
 
-                    let cursor_after = if text.len() > 1 {
-                        CursorPosition {
-                            row: cursor.row + text.len() - 1,
-                            col: text.last().unwrap().chars().count(),
-                        }
-                    } else {
-                        CursorPosition {
-                            col: cursor.col + text[0].len(),
-                            ..cursor
-                        }
-                    };
+                let history_idx = (paste.history_idx + 1) % self.clipboard.history_len();
+                let Some(clip) = self.clipboard.history(history_idx).cloned() else {
+                    return false;
+                };
 
-                    let cursor = if chain {
-                        self.do_action_chain(HistoryAction::InsertLines {
-                            lines: text,
-                            position: BytePosition {
-                                row: cursor.row,
-                                col: self.lines[cursor.row].byte_index(cursor.col),
-                            },
-                            cursor: (cursor, cursor_after),
-                        })
-                    } else {
-                        self.do_action(HistoryAction::InsertLines {
-                            lines: text,
-                            position: BytePosition {
-                                row: cursor.row,
-                                col: self.lines[cursor.row].byte_index(cursor.col),
-                            },
-                            cursor: (cursor, cursor_after),
-                        })
-                    };
-                    self.set_cursor(cursor, false);
+                let cursor = self.do_action(HistoryAction::RemoveLines {
+                    lines: paste.lines,
+                    position: BytePosition::from_line(paste.start, &self.lines[paste.start.row]),
+                    cursor: (paste.cursor.1, paste.cursor.0),
+                });
+                self.set_cursor(cursor, false);
+                self.paste(clip, history_idx, true, paste.reindent);
 
-                    true
+                true
+            }
+            Input {
+                key: Key::Char('k'),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            } => {
+                if self.remove_selection() {
+                    return true;
+                }
+
+                let lines = &self.lines;
+                let cursor = self.cursor();
+                let line = &lines[cursor.row];
+
+                let removed = if cursor.col < line.chars().count() {
+                    vec![line.char_slice(cursor.col..).to_string()]
+                } else if cursor.row < lines.len() - 1 {
+                    // already at the end, join with the next line
+                    vec![String::new(), String::new()]
                 } else {
-                    false
+                    return false;
+                };
+
+                let cursor = self.do_action(HistoryAction::RemoveLines {
+                    lines: removed,
+                    position: BytePosition::from_line(cursor, line),
+                    cursor: (cursor, cursor),
+                });
+                self.set_cursor(cursor, false);
+                true
+            }
+            Input {
+                key: Key::Char('u'),
+                ctrl: true,
+                alt: false,
+                shift: false,
+            }
+            | Input {
+                key: Key::Backspace,
+                ctrl: true,
+                alt: false,
+                shift: true,
+            } => {
+                if self.remove_selection() {
+                    return true;
                 }
+
+                let cursor = self.cursor();
+                if cursor.col == 0 {
+                    return false;
+                }
+
+                let cursor = self.do_action(HistoryAction::RemoveLines {
+                    lines: vec![self.lines[cursor.row].char_slice(..cursor.col).to_string()],
+                    position: BytePosition { row: cursor.row, col: 0 },
+                    cursor: (cursor, CursorPosition { col: 0, ..cursor }),
+                });
+                self.set_cursor(cursor, false);
+                true
+            }
+            // each press calms down one thing: first the selection, then search highlights
+            Input {
+                key: Key::Esc,
+                ctrl: false,
+                alt: false,
+                shift: false,
+            } => {
+                if self.selection.is_some() {
+                    self.set_selection(None);
+                } else if self.search_pattern.is_some() {
+                    self.replace_search_pattern(None);
+                    self.redraw = true;
+                }
+                false
+            }
+            Input {
+                key: Key::Insert,
+                ctrl: false,
+                alt: false,
+                shift: false,
+            } => {
+                self.overwrite = !self.overwrite;
+                self.redraw = true;
+                false
             }
             Input { key: Key::Char(char), .. } => {
                 let cursor = self.cursor();
@@ -732,17 +1539,94 @@ impl TextArea {
                         self.set_cursor(cursor, false);
                     }
                     None => {
-                        let cursor = self.do_action(HistoryAction::InsertChar {
+                        let position = BytePosition::from_line(cursor, &self.lines[cursor.row]);
+                        let replaced = self.lines[cursor.row]
+                            .chars()
+                            .nth(cursor.col)
+                            .filter(|_| self.overwrite);
+                        if let Some(replaced) = replaced {
+                            self.do_action(HistoryAction::RemoveChar {
+                                char: replaced,
+                                position,
+                                cursor: (cursor, cursor),
+                            });
+                        }
+
+                        let action = HistoryAction::InsertChar {
                             char,
-                            position: BytePosition::from_line(cursor, &self.lines[cursor.row]),
+                            position,
                             cursor: (cursor, CursorPosition { col: cursor.col + 1, ..cursor }),
-                        });
+                        };
+                        let cursor = if replaced.is_some() {
+                            self.do_action_chain(action)
+                        } else {
+                            self.do_action(action)
+                        };
                         self.set_cursor(cursor, false);
                     }
                 }
 
                 true
             }
+            Input {
+                key: Key::Backspace,
+                ctrl: false,
+                alt: false,
+                shift: false,
+            } if self.overwrite && !self.overwrite_backspace_deletes && self.selection.is_none() => {
+                let cursor = self.cursor();
+                if cursor.col > 0 {
+                    self.set_cursor(CursorPosition { col: cursor.col - 1, ..cursor }, false);
+                }
+                false
+            }
+            Input {
+                key: Key::Backspace,
+                alt: true,
+                ctrl: false,
+                shift: false,
+            } => {
+                if self.remove_selection() {
+                    return true;
+                }
+
+                let lines = &self.lines;
+                let cursor = self.cursor();
+
+                let action = match lines[cursor.row].previous_subword(cursor.col) {
+                    Some(col) => HistoryAction::RemoveLines {
+                        lines: vec![lines[cursor.row].char_slice(col..cursor.col).to_string()],
+                        position: BytePosition {
+                            row: cursor.row,
+                            col: lines[cursor.row].byte_index(col),
+                        },
+                        cursor: (cursor, CursorPosition { col, ..cursor }),
+                    },
+                    None if cursor.col > 0 => HistoryAction::RemoveLines {
+                        lines: vec![lines[cursor.row].char_slice(..cursor.col).to_string()],
+                        position: BytePosition { row: cursor.row, col: 0 },
+                        cursor: (cursor, CursorPosition { col: 0, ..cursor }),
+                    },
+                    None if cursor.row > 0 => HistoryAction::RemoveLinebreak {
+                        position: BytePosition {
+                            row: cursor.row - 1,
+                            col: lines[cursor.row - 1].len(),
+                        },
+                        cursor: (
+                            cursor,
+                            CursorPosition {
+                                row: cursor.row - 1,
+                                col: lines[cursor.row - 1].chars().count(),
+                            },
+                        ),
+                    },
+                    None => return self.reject(),
+                };
+
+                let cursor = self.do_action(action);
+                self.set_cursor(cursor, false);
+                true
+            }
             Input {
                 key: Key::Backspace,
                 alt: false,
@@ -797,15 +1681,17 @@ impl TextArea {
                         None => None,
                     };
 
-                    if let Some(action) = action {
-                        let cursor = self.do_action(action);
-                        self.set_cursor(cursor, false);
+                    match action {
+                        Some(action) => {
+                            let cursor = self.do_action(action);
+                            self.set_cursor(cursor, false);
+                            true
+                        }
+                        None => self.reject(),
                     }
-
-                    true
                 } else {
                     match cursor {
-                        CursorPosition { row: 0, col: 0 } => false,
+                        CursorPosition { row: 0, col: 0 } => self.reject(),
                         CursorPosition { col: 0, .. } => {
                             let cursor = self.do_action(HistoryAction::RemoveLinebreak {
                                 position: BytePosition {
@@ -877,7 +1763,7 @@ impl TextArea {
                             },
                             cursor: (cursor, cursor),
                         }),
-                        None if cursor.col < lines[cursor.row].len() => Some(HistoryAction::RemoveLines {
+                        None if cursor.col < lines[cursor.row].chars().count() => Some(HistoryAction::RemoveLines {
                             lines: vec![lines[cursor.row].char_slice(cursor.col..).to_string()],
                             position: BytePosition {
                                 row: cursor.row,
@@ -895,18 +1781,22 @@ impl TextArea {
                         None => None,
                     };
 
-                    if let Some(action) = action {
-                        let cursor = self.do_action(action);
-                        self.set_cursor(cursor, false);
+                    match action {
+                        Some(action) => {
+                            let cursor = self.do_action(action);
+                            self.set_cursor(cursor, false);
+                            true
+                        }
+                        None => self.reject(),
                     }
-
-                    true
                 } else {
                     match cursor {
-                        CursorPosition { row, col } if row == lines.len() - 1 && col == lines.last().unwrap().len() => {
-                            false
+                        CursorPosition { row, col }
+                            if row == lines.len() - 1 && col == lines.last().unwrap().chars().count() =>
+                        {
+                            self.reject()
                         }
-                        CursorPosition { col, .. } if col == lines[cursor.row].len() => {
+                        CursorPosition { col, .. } if col == lines[cursor.row].chars().count() => {
                             let cursor = self.do_action(HistoryAction::RemoveLinebreak {
                                 position: BytePosition {
                                     row: cursor.row,
@@ -936,277 +1826,3088 @@ impl TextArea {
             _ => false,
         }
     }
-}
 
-// render Widget
-impl TextArea {
-    fn render_line<'l>(&self, line: &'l str, line_info: LineNumber) -> Line<'l> {
-        const SELECT: Style = Style::new().bg(Color::LightBlue);
+    /// Pastes after the cursor like vim's `p`: whole lines below the cursor line with the cursor on the first of them,
+    /// anything else after the char under the cursor. Returns whether there was anything to paste.
+    pub fn paste_after(&mut self) -> bool {
+        let Some(clip) = self.clipboard.get() else {
+            return false;
+        };
+        let cursor = self.cursor();
+        let line_len = self.lines[cursor.row].chars().count();
+        if !clip.linewise {
+            self.set_cursor(
+                CursorPosition {
+                    col: cmp::min(cursor.col + 1, line_len),
+                    ..cursor
+                },
+                false,
+            );
+            self.paste(clip, 0, false, true);
+            return true;
+        }
 
-        let position = self.view.position.get();
-        if let Some(selection) = self.selection {
-            let selected_range = if self.cursor < selection
-                && self.cursor.row <= line_info.line_number
-                && line_info.line_number <= selection.row
-            {
-                let start = if line_info.current_line { self.cursor.col } else { 0 };
-
-                let end = if selection.row == line_info.line_number {
-                    selection.col
-                } else {
-                    line.chars().count()
-                };
+        let below = CursorPosition { row: cursor.row + 1, col: 0 };
+        if below.row < self.lines.len() {
+            self.set_cursor(below, false);
+            self.paste(clip, 0, false, true);
+        } else {
+            // the last line has none to paste above, so the lines go in after its end instead
+            let text = clip.text.strip_suffix('\n').unwrap_or(&clip.text);
+            self.set_cursor(CursorPosition { col: line_len, ..cursor }, false);
+            self.paste(
+                Clip {
+                    text: format!("\n{text}"),
+                    linewise: false,
+                },
+                0,
+                false,
+                true,
+            );
+        }
+        self.set_cursor(below, false);
+        true
+    }
 
-                let tabs_before_selection = self.lines[line_info.line_number]
-                    .char_slice(..start)
-                    .chars()
-                    .filter(|&c| c == '\t')
-                    .count();
-                let tabs_in_selection = self.lines[line_info.line_number]
-                    .char_slice(start..end)
-                    .chars()
-                    .filter(|&c| c == '\t')
-                    .count();
-                let tab_width = self.indent.spaces().len();
-
-                Some((
-                    (start + (tabs_before_selection * (tab_width - 1))).saturating_sub(position.col),
-                    (end + ((tabs_before_selection + tabs_in_selection) * (tab_width - 1)))
-                        .saturating_sub(position.col),
-                ))
-            } else if selection < self.cursor
-                && selection.row <= line_info.line_number
-                && line_info.line_number <= self.cursor.row
-            {
-                let start = if selection.row == line_info.line_number {
-                    selection.col
-                } else {
-                    0
-                };
+    fn paste(&mut self, clip: Clip, history_idx: usize, chain: bool, reindent: bool) {
+        let mut clip = clip;
+        if self.single_line {
+            let text = clip.text.strip_suffix('\n').unwrap_or(&clip.text);
+            clip = Clip {
+                text: text.lines().collect::<Vec<_>>().join(" "),
+                linewise: false,
+            };
+        }
+        let text = clip
+            .text
+            .split('\n')
+            .map(|l| l.trim_end_matches('\r').to_string())
+            .collect::<Vec<_>>();
 
-                let end = if line_info.current_line {
-                    self.cursor.col
-                } else {
-                    line.chars().count()
-                };
+        let cursor = self.cursor();
+        let (cursor, chain, linewise) = match self.selection().zip(self.selected_text(true)) {
+            Some((selection, selected_text)) => {
+                let start = if cursor < selection { cursor } else { selection };
+                (
+                    self.do_action(HistoryAction::RemoveLines {
+                        lines: selected_text,
+                        position: BytePosition::from_line(start, &self.lines[start.row]),
+                        cursor: (cursor, start),
+                    }),
+                    true,
+                    false,
+                )
+            }
+            None => (cursor, chain, clip.linewise),
+        };
+        let text = if reindent {
+            self.reindent(text, linewise, cursor.row)
+        } else {
+            text
+        };
 
-                let tabs_before_selection = self.lines[line_info.line_number]
-                    .char_slice(..start)
-                    .chars()
-                    .filter(|&c| c == '\t')
-                    .count();
-                let tabs_in_selection = self.lines[line_info.line_number]
-                    .char_slice(start..end)
-                    .chars()
-                    .filter(|&c| c == '\t')
-                    .count();
-                let tab_width = self.indent.spaces().len();
-
-                Some((
-                    (start + (tabs_before_selection * (tab_width - 1))).saturating_sub(position.col),
-                    (end + ((tabs_before_selection + tabs_in_selection) * (tab_width - 1)))
-                        .saturating_sub(position.col),
-                ))
-            } else {
-                None
-            };
+        // whole lines go above the cursor line, leaving the cursor where it was in the text
+        let start = if linewise {
+            CursorPosition { col: 0, ..cursor }
+        } else {
+            cursor
+        };
+        // counted from where the text goes in, which is the start of the selection if one was replaced
+        let last_len = text.last().unwrap().chars().count();
+        let cursor_after = if linewise {
+            CursorPosition {
+                row: start.row + text.len() - 1,
+                ..cursor
+            }
+        } else if text.len() > 1 {
+            CursorPosition {
+                row: start.row + text.len() - 1,
+                col: last_len,
+            }
+        } else {
+            CursorPosition {
+                col: start.col + last_len,
+                ..start
+            }
+        };
 
-            match selected_range {
-                Some((start, end)) if start == 0 && end == 0 && line.is_empty() => {
-                    return Line::from_iter([Span::from(line_info), Span::from(" ").style(SELECT)]);
-                }
-                Some((start, end)) => {
-                    return match &self.search_pattern {
-                        Some(pattern) => {
-                            let mut spans = Vec::new();
-                            spans.push(Span::from(line_info));
+        let action = HistoryAction::InsertLines {
+            lines: text.clone(),
+            position: BytePosition::from_line(start, &self.lines[start.row]),
+            cursor: (cursor, cursor_after),
+        };
+        let cursor_after = if chain {
+            self.do_action_chain(action)
+        } else {
+            self.do_action(action)
+        };
+        self.set_cursor(cursor_after, false);
 
-                            Self::mark_matches(&mut spans, line.char_slice(..start), pattern);
-                            spans.push(Span::from(line.char_slice(start..end)).style(SELECT));
-                            Self::mark_matches(&mut spans, line.char_slice(end..), pattern);
+        self.last_paste = Some(Paste {
+            start,
+            cursor: (cursor, cursor_after),
+            lines: text,
+            history_idx,
+            reindent,
+            undo_len: self.undo_history.len(),
+        });
+    }
 
-                            Line::from(spans)
-                        }
-                        None => Line::from_iter([
-                            Span::from(line_info),
-                            Span::from(line.char_slice(..start)),
-                            Span::from(line.char_slice(start..end)).style(SELECT),
-                            Span::from(line.char_slice(end..)),
-                        ]),
-                    };
+    /// Moves pasted lines from the indentation they were copied at to the indentation of the line at `row`.
+    fn reindent(&self, text: Vec<String>, linewise: bool, row: usize) -> Vec<String> {
+        if text.len() < 2 {
+            return text;
+        }
+
+        let line = &self.lines[row];
+        let indent = &line[..line.len() - line.trim_start().len()];
+
+        // a first line copied from after its indentation says nothing about the block's indentation
+        let skip_first = !linewise && !text[0].starts_with(char::is_whitespace);
+        let common = text
+            .iter()
+            .skip(usize::from(skip_first))
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| self.indent.leading_width(l))
+            .min()
+            .unwrap_or(0);
+
+        let last = text.len() - 1;
+        text.into_iter()
+            .enumerate()
+            .map(|(i, l)| {
+                let content = l.trim_start();
+                let relative = self
+                    .indent
+                    .whitespace(self.indent.leading_width(&l).saturating_sub(common));
+
+                if i == 0 && !linewise {
+                    if skip_first { l } else { relative + content }
+                } else if content.is_empty() {
+                    // the text after the cursor ends up behind the last line, so it still needs indenting
+                    if i == last && !linewise {
+                        indent.to_string()
+                    } else {
+                        String::new()
+                    }
+                } else {
+                    String::from_iter([indent, &relative, content])
                 }
-                _ => {}
+            })
+            .collect()
+    }
+}
+
+// render Widget
+impl TextArea {
+    /// Draws the display columns `cols` of the line `expanded` was made from.
+    fn render_line<'l>(&self, expanded: &'l ExpandedLine, cols: Range<usize>, line_info: LineNumber) -> Line<'l> {
+        const FLASH: Style = Style::new().bg(Color::Yellow).fg(Color::Black);
+
+        let row = line_info.line_number;
+        let mut rendered = self.render_line_text(expanded, cols, line_info);
+        if self.flash.as_ref().is_some_and(|rows| rows.contains(&row)) {
+            // the first span is the line number
+            if rendered.spans[1..].iter().all(|span| span.content.is_empty()) {
+                rendered.spans.push(Span::from(" "));
             }
+            rendered.spans[1..]
+                .iter_mut()
+                .for_each(|span| span.style = FLASH.patch(span.style));
         }
+        rendered
+    }
 
-        match &self.search_pattern {
-            Some(pattern) => {
-                let mut spans = Vec::new();
-                spans.push(Span::from(line_info));
-                Self::mark_matches(&mut spans, line, pattern);
+    /// The line with its selection and search matches, before [`TextArea::render_line`] adds the flash.
+    ///
+    /// Both are worked out in display columns of the whole line, so they line up with the text however far it is
+    /// scrolled, and search patterns see the line as it is rather than the part in view.
+    fn render_line_text<'l>(&self, expanded: &'l ExpandedLine, cols: Range<usize>, line_info: LineNumber) -> Line<'l> {
+        let select = Style::new().bg(self.theme.selection_bg);
+        let found = Style::new().bg(self.theme.match_bg);
+
+        let row = line_info.line_number;
+        let (start_col, text) = expanded.display_slice(cols.clone());
 
-                Line::from(spans)
+        // later ones take precedence
+        let mut highlights = Vec::new();
+        if self.trailing_whitespace && row != self.cursor.row && expanded.trailing < expanded.width {
+            let trailing = Style::new().bg(self.theme.trailing_whitespace_bg);
+            highlights.push((expanded.trailing..expanded.width, trailing));
+        }
+        if self.search_pattern.is_some() {
+            let matches = self.search_matches(row);
+            let found = matches
+                .iter()
+                .map(|m| {
+                    (
+                        expanded.byte_display_col(m.start)..expanded.byte_display_col(m.end),
+                        found,
+                    )
+                })
+                .skip_while(|(range, _)| range.end <= cols.start)
+                .take_while(|(range, _)| range.start < cols.end);
+            highlights.extend(found);
+        }
+        if let Some((start, end)) = self.selection_range()
+            && (start.row..=end.row).contains(&row)
+        {
+            let start = if start.row == row {
+                expanded.display_col(start.col)
+            } else {
+                0
+            };
+            let end = if end.row == row {
+                expanded.display_col(end.col)
+            } else {
+                expanded.width
+            };
+            // nothing to highlight, so a space stands in for the selected linebreak
+            if text.is_empty() && end <= cols.start {
+                return Line::from_iter([Span::from(line_info), Span::from(" ").style(select)]);
+            }
+            highlights.push((start..end, select));
+        }
+
+        let mut spans = vec![Span::from(line_info)];
+        // a wide char cut off by the left edge leaves a gap
+        if start_col > cols.start {
+            spans.push(Span::raw(spaces(start_col - cols.start)));
+        }
+        let style_at = |col| {
+            highlights
+                .iter()
+                .rev()
+                .find(|(range, _)| range.contains(&col))
+                .map(|&(_, style)| style)
+        };
+        let (mut span_start, mut style) = (0, style_at(start_col));
+        let mut col = start_col;
+        for (idx, c) in text.char_indices() {
+            let char_style = style_at(col);
+            if char_style != style {
+                spans.push(Span::styled(&text[span_start..idx], style.unwrap_or_default()));
+                (span_start, style) = (idx, char_style);
             }
-            None => Line::from_iter([Span::from(line_info), Span::from(line)]),
+            col += c.width().unwrap_or(0);
         }
+        spans.push(Span::styled(&text[span_start..], style.unwrap_or_default()));
+
+        Line::from(spans)
     }
 
-    fn mark_matches<'l>(spans: &mut Vec<Span<'l>>, line: &'l str, pattern: &Regex) {
-        const FOUND: Style = Style::new().bg(Color::Magenta);
+    /// Whether `cell` has no highlight other than the cursor line's.
+    fn is_plain(&self, cell: &ratatui::buffer::Cell) -> bool {
+        cell.bg == Color::Reset || cell.bg == self.theme.current_line_bg
+    }
 
-        let mut prev_end = 0;
-        for m in pattern.find_iter(line) {
-            spans.push(Span::from(&line[prev_end..m.start()]));
-            spans.push(Span::from(&line[m.start()..m.end()]).style(FOUND));
-            prev_end = m.end();
+    /// Colors the background of the cursor line at `y`, where nothing else is highlighted.
+    fn render_current_line(&self, text_area: Rect, buf: &mut Buffer, y: u16) {
+        if self.theme.current_line_bg == Color::Reset || self.single_line {
+            return;
+        }
+        for x in text_area.x..text_area.right() {
+            let cell = &mut buf[(x, y)];
+            if cell.bg == Color::Reset {
+                cell.set_bg(self.theme.current_line_bg);
+            }
         }
-        spans.push(Span::from(&line[prev_end..]));
     }
 
-    pub fn selected_text(&mut self, unselect: bool) -> Option<Vec<String>> {
-        let selection = self.selection()?;
-        if unselect {
-            self.set_selection(None);
+    /// Draws [`TextArea::rulers`] over already rendered `lines`.
+    fn render_rulers(&self, text_area: Rect, buf: &mut Buffer, lines: &[Rc<ExpandedLine>], scroll: usize) {
+        let ruler = Style::new().fg(self.theme.ruler);
+        let past_ruler = Style::new().bg(self.theme.ruler);
+
+        let Some(&first) = self.rulers.iter().min() else {
+            return;
+        };
+
+        for (y, line) in (text_area.y..text_area.bottom()).zip(lines) {
+            let line_width = line.width;
+            for x in text_area.x..text_area.right() {
+                let col = usize::from(x - text_area.x) + scroll;
+                let cell = &mut buf[(x, y)];
+                // selection and search highlights take precedence
+                if !self.is_plain(cell) {
+                    continue;
+                }
+
+                if col < line_width {
+                    if col >= first {
+                        cell.set_style(past_ruler);
+                    }
+                } else if self.rulers.contains(&col) {
+                    cell.set_symbol("│").set_style(ruler);
+                }
+            }
         }
+    }
 
-        let lines = &self.lines;
-        let cursor = self.cursor();
+    /// Draws a guide at every indent level within the leading whitespace of the lines in `rows`. Blank lines get the
+    /// guides of the shallower of the surrounding lines, so guides aren't interrupted by empty lines.
+    fn render_indent_guides(&self, text_area: Rect, buf: &mut Buffer, rows: Range<usize>, scroll: usize) {
+        let guide = Style::new().fg(self.theme.ruler).add_modifier(Modifier::DIM);
+        let indent_width = self.indent.spaces().len();
+        let depth = |line: &String| (!line.trim().is_empty()).then(|| self.indent.leading_width(line));
 
-        let (start, end) = if cursor < selection {
-            (cursor, selection)
+        let mut depths = self.lines[rows.clone()].iter().map(depth).collect::<Vec<_>>();
+        let mut prev = self.lines[..rows.start].iter().rev().find_map(depth).unwrap_or(0);
+        let mut before = Vec::with_capacity(depths.len());
+        for depth in &depths {
+            prev = depth.unwrap_or(prev);
+            before.push(prev);
+        }
+        let mut next = self.lines[rows.end..].iter().find_map(depth).unwrap_or(0);
+        for (depth, before) in depths.iter_mut().zip(before).rev() {
+            match depth {
+                Some(depth) => next = *depth,
+                None => *depth = Some(cmp::min(before, next)),
+            }
+        }
+
+        for (y, depth) in (text_area.y..text_area.bottom()).zip(depths) {
+            let depth = depth.unwrap_or(0);
+            for col in (0..depth).step_by(indent_width).skip_while(|&col| col < scroll) {
+                let Ok(x) = u16::try_from(col - scroll) else {
+                    break;
+                };
+                if x >= text_area.width {
+                    break;
+                }
+
+                let cell = &mut buf[(text_area.x + x, y)];
+                if self.is_plain(cell) && matches!(cell.symbol(), " " | "·") {
+                    cell.set_symbol("│").set_style(guide);
+                }
+            }
+        }
+    }
+
+    /// Colors the leading spaces of recipe lines in `rows`, since make only accepts tabs there.
+    fn render_recipe_errors(&self, text_area: Rect, buf: &mut Buffer, rows: Range<usize>, scroll: usize) {
+        const RECIPE_ERROR: Style = Style::new().bg(Color::Red);
+
+        for (row, spaces) in makefile::space_indented_recipes(&self.lines, rows.clone()) {
+            let y = text_area.y + u16::try_from(row - rows.start).unwrap();
+            for col in scroll..spaces {
+                let Ok(x) = u16::try_from(col - scroll) else {
+                    break;
+                };
+                if x >= text_area.width {
+                    break;
+                }
+
+                let cell = &mut buf[(text_area.x + x, y)];
+                if self.is_plain(cell) {
+                    cell.set_style(RECIPE_ERROR);
+                }
+            }
+        }
+    }
+
+    /// Underlines the misspelled words in `rows`. Only the visible lines are checked, so big files stay fast.
+    fn render_misspellings(
+        &self,
+        text_area: Rect,
+        buf: &mut Buffer,
+        rows: Range<usize>,
+        scroll: usize,
+        dictionary: &Dictionary,
+    ) {
+        const MISSPELLED: Style = Style::new().fg(Color::Red);
+
+        for (y, row) in (text_area.y..text_area.bottom()).zip(rows) {
+            let expanded = self.expanded_line(row);
+            for range in dictionary.misspellings(&self.lines[row]) {
+                let start = expanded.display_col(range.start);
+                let end = expanded.display_col(range.end);
+                for col in cmp::max(start, scroll)..end {
+                    let Ok(x) = u16::try_from(col - scroll) else {
+                        break;
+                    };
+                    if x >= text_area.width {
+                        break;
+                    }
+
+                    let cell = &mut buf[(text_area.x + x, y)];
+                    cell.modifier.insert(Modifier::UNDERLINED);
+                    if self.is_plain(cell) {
+                        cell.set_style(MISSPELLED);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Selects the next misspelled word after the cursor, wrapping around at the end. Returns whether there was one.
+    pub fn next_misspelling(&mut self, dictionary: &Dictionary) -> bool {
+        let cursor = self.cursor;
+        let rows = (cursor.row..self.lines.len()).chain(0..=cursor.row);
+        let found = rows.enumerate().find_map(|(idx, row)| {
+            let misspellings = dictionary.misspellings(&self.lines[row]);
+            let range = match idx {
+                0 => misspellings.into_iter().find(|range| range.start > cursor.col),
+                _ if row == cursor.row => misspellings.into_iter().find(|range| range.start <= cursor.col),
+                _ => misspellings.into_iter().next(),
+            }?;
+            Some((row, range))
+        });
+
+        let Some((row, range)) = found else {
+            return false;
+        };
+        self.select_match((
+            CursorPosition { row, col: range.start },
+            CursorPosition { row, col: range.end },
+        ));
+        self.redraw = true;
+        true
+    }
+
+    /// Puts the first spelling suggestion in place of the misspelled word at the cursor. Pressing again right after
+    /// replaces it with the next one, and after the last brings back the original word. Returns what the word is now,
+    /// as `(word, idx, count)` with `idx` being zero for the original.
+    pub fn cycle_suggestion(&mut self, dictionary: &Dictionary) -> Result<(String, usize, usize), String> {
+        let cursor = self.cursor;
+        let current = self.suggestion.take().filter(|suggestion| {
+            let word = suggestion
+                .suggestions
+                .get(suggestion.idx)
+                .unwrap_or(&suggestion.original);
+            suggestion.undo_len == self.undo_len()
+                && cursor.row == suggestion.row
+                && (suggestion.start..=suggestion.start + word.chars().count()).contains(&cursor.col)
+        });
+
+        let (mut suggestion, chain) = match current {
+            Some(suggestion) => (suggestion, true),
+            None => {
+                let line = &self.lines[cursor.row];
+                let range = dictionary
+                    .misspellings(line)
+                    .into_iter()
+                    .find(|range| range.contains(&cursor.col) || range.end == cursor.col)
+                    .ok_or("No misspelled word at the cursor")?;
+                let original = line.char_slice(range.clone()).to_string();
+                let suggestions = dictionary.suggestions(&original);
+                if suggestions.is_empty() {
+                    return Err(format!("No suggestions for {original}"));
+                }
+                let idx = suggestions.len();
+                let suggestion = Suggestion {
+                    row: cursor.row,
+                    start: range.start,
+                    original,
+                    suggestions,
+                    idx,
+                    undo_len: 0,
+                };
+                (suggestion, false)
+            }
+        };
+
+        let old = suggestion
+            .suggestions
+            .get(suggestion.idx)
+            .unwrap_or(&suggestion.original)
+            .clone();
+        suggestion.idx = (suggestion.idx + 1) % (suggestion.suggestions.len() + 1);
+        let new = suggestion
+            .suggestions
+            .get(suggestion.idx)
+            .unwrap_or(&suggestion.original)
+            .clone();
+        self.replace_word(suggestion.row, suggestion.start, &old, &new, chain);
+
+        suggestion.undo_len = self.undo_len();
+        let count = suggestion.suggestions.len();
+        let idx = (suggestion.idx + 1) % (count + 1);
+        self.suggestion = Some(suggestion);
+        Ok((new, idx, count))
+    }
+
+    /// Puts `delimiters` around the selection as one undo step, keeping the text selected. Tags around several lines
+    /// go on lines of their own, indented like the first line. Returns whether the text changed, which it doesn't
+    /// without a selection or with empty delimiters.
+    pub fn surround(&mut self, delimiters: &Delimiters) -> bool {
+        let Some((start, end)) = self.selection_range() else {
+            return false;
+        };
+        if delimiters.open.is_empty() && delimiters.close.is_empty() {
+            return false;
+        }
+        let cursor_at_end = self.cursor == end;
+
+        let (start_after, end_after) = match self.selected_rows() {
+            Some((first, last)) if delimiters.tag && last > first => {
+                let line = &self.lines[first];
+                let indent = &line[..line.len() - line.trim_start().len()];
+                let (open, close) = (
+                    format!("{indent}{}", delimiters.open),
+                    format!("{indent}{}", delimiters.close),
+                );
+
+                let cursor = self.cursor;
+                self.do_action(HistoryAction::InsertLines {
+                    lines: vec![String::new(), close],
+                    position: BytePosition {
+                        row: last,
+                        col: self.lines[last].len(),
+                    },
+                    cursor: (cursor, cursor),
+                });
+                self.do_action_chain(HistoryAction::InsertLines {
+                    lines: vec![open, String::new()],
+                    position: BytePosition { row: first, col: 0 },
+                    cursor: (cursor, cursor),
+                });
+                let down = |position: CursorPosition| CursorPosition {
+                    row: position.row + 1,
+                    ..position
+                };
+                (down(start), down(end))
+            }
+            _ => {
+                self.insert_str(end, &delimiters.close, false);
+                self.insert_str(start, &delimiters.open, true);
+                let shift = |position: CursorPosition| CursorPosition {
+                    col: position.col + delimiters.open.chars().count(),
+                    ..position
+                };
+                (shift(start), if end.row == start.row { shift(end) } else { end })
+            }
+        };
+
+        let (cursor, selection) = if cursor_at_end {
+            (end_after, start_after)
         } else {
-            (selection, cursor)
+            (start_after, end_after)
         };
+        self.set_cursor(cursor, false);
+        self.set_selection(Some(selection));
+        true
+    }
 
-        if start.row == end.row {
-            return Some(vec![lines[start.row].char_slice(start.col..end.col).to_string()]);
+    /// Replaces the innermost quotes or brackets around the cursor with `delimiters`, as one undo step.
+    pub fn change_surrounding(&mut self, delimiters: &Delimiters) -> Result<(), &'static str> {
+        let (open, close) = surround::enclosing_pair(&self.lines, self.cursor).ok_or(NO_SURROUNDING_PAIR)?;
+        let cursor = self.cursor;
+
+        self.remove_char_at(close, false);
+        self.insert_str(close, &delimiters.close, true);
+        self.remove_char_at(open, true);
+        self.insert_str(open, &delimiters.open, true);
+
+        let col = if cursor.row == open.row && cursor.col > open.col {
+            cursor.col + delimiters.open.chars().count() - 1
+        } else {
+            cursor.col
+        };
+        self.set_cursor(CursorPosition { col, ..cursor }, false);
+        Ok(())
+    }
+
+    /// Removes the innermost quotes or brackets around the cursor, as one undo step.
+    pub fn delete_surrounding(&mut self) -> Result<(), &'static str> {
+        let (open, close) = surround::enclosing_pair(&self.lines, self.cursor).ok_or(NO_SURROUNDING_PAIR)?;
+        let cursor = self.cursor;
+
+        self.remove_char_at(close, false);
+        self.remove_char_at(open, true);
+
+        let col = if cursor.row == open.row && cursor.col > open.col {
+            cursor.col - 1
+        } else {
+            cursor.col
+        };
+        self.set_cursor(CursorPosition { col, ..cursor }, false);
+        Ok(())
+    }
+
+    /// Inserts `text`, which has no linebreaks, char by char at `position`.
+    fn insert_str(&mut self, position: CursorPosition, text: &str, chain: bool) {
+        for (idx, char) in text.chars().enumerate() {
+            let at = CursorPosition {
+                col: position.col + idx,
+                ..position
+            };
+            let action = HistoryAction::InsertChar {
+                char,
+                position: BytePosition::from_line(at, &self.lines[at.row]),
+                cursor: (self.cursor, self.cursor),
+            };
+            if chain || idx > 0 {
+                self.do_action_chain(action);
+            } else {
+                self.do_action(action);
+            }
         }
+    }
 
-        let mut text = Vec::with_capacity(end.row - start.row + 1);
-        text.push(lines[start.row].char_slice(start.col..).to_string());
-        lines[start.row + 1..end.row]
-            .iter()
-            .for_each(|line| text.push(line.to_string()));
-        text.push(lines[end.row].char_slice(..end.col).to_string());
+    fn remove_char_at(&mut self, position: CursorPosition, chain: bool) {
+        let line = &self.lines[position.row];
+        let action = HistoryAction::RemoveChar {
+            char: line.chars().nth(position.col).unwrap(),
+            position: BytePosition::from_line(position, line),
+            cursor: (self.cursor, self.cursor),
+        };
+        if chain {
+            self.do_action_chain(action);
+        } else {
+            self.do_action(action);
+        }
+    }
 
-        Some(text)
+    /// Replaces `old`, starting at char `start` of `row`, with `new`, leaving the cursor after it.
+    fn replace_word(&mut self, row: usize, start: usize, old: &str, new: &str, chain: bool) {
+        let start = CursorPosition { row, col: start };
+        let position = BytePosition::from_line(start, &self.lines[row]);
+        let after = CursorPosition {
+            col: start.col + new.chars().count(),
+            ..start
+        };
+        let cursor = (self.cursor, after);
+
+        let remove = HistoryAction::RemoveLines {
+            lines: vec![old.to_string()],
+            position,
+            cursor,
+        };
+        if chain {
+            self.do_action_chain(remove);
+        } else {
+            self.do_action(remove);
+        }
+        self.do_action_chain(HistoryAction::InsertLines {
+            lines: vec![new.to_string()],
+            position,
+            cursor,
+        });
+        self.set_cursor(after, false);
     }
 
-    pub fn selected_text_single_line(&self) -> Option<&str> {
-        let lines = &self.lines;
+    /// Removes the selected text, returning whether there was a selection.
+    fn remove_selection(&mut self) -> bool {
         let cursor = self.cursor();
-        let selection = self.selection();
+        let Some((selection, selected_text)) = self.selection().zip(self.selected_text(true)) else {
+            return false;
+        };
 
-        if let Some(selection) = selection {
-            if cursor.row != selection.row {
-                return None;
-            }
+        let start = if cursor < selection { cursor } else { selection };
+        let cursor = self.do_action(HistoryAction::RemoveLines {
+            lines: selected_text,
+            position: BytePosition::from_line(start, &self.lines[start.row]),
+            cursor: (cursor, start),
+        });
+        self.set_cursor(cursor, false);
+        true
+    }
 
-            if selection < cursor {
-                Some(lines[cursor.row].char_slice(selection.col..cursor.col))
+    /// Moves the cursor to the start of the next or previous merge conflict, returning whether there was one.
+    pub fn jump_to_conflict(&mut self, forward: bool) -> bool {
+        let row = self.cursor.row;
+        let found = if forward {
+            self.lines
+                .iter()
+                .enumerate()
+                .skip(row + 1)
+                .find(|(_, line)| conflict::is_start(line))
+        } else {
+            self.lines[..row]
+                .iter()
+                .enumerate()
+                .rev()
+                .find(|(_, line)| conflict::is_start(line))
+        };
+
+        let Some((row, _)) = found else {
+            return false;
+        };
+        self.set_cursor(CursorPosition { row, col: 0 }, false);
+        true
+    }
+
+    /// Moves the cursor to the start of the next line with leading whitespace other than the indent style's, going
+    /// on from the top after the last line. Returns whether there is one.
+    pub fn jump_to_mixed_indent(&mut self) -> bool {
+        let row = self.cursor.row;
+        let found = (row + 1..self.lines.len())
+            .chain(0..=row)
+            .find(|&row| self.indent.is_mixed(&self.lines[row]));
+
+        let Some(row) = found else {
+            return false;
+        };
+        self.set_cursor(CursorPosition { row, col: 0 }, false);
+        true
+    }
+
+    /// Resolves the merge conflict under the cursor by removing its markers and the halves not kept, as one undo step.
+    pub fn resolve_conflict(&mut self, side: Side) -> Result<(), &'static str> {
+        let conflict = Conflict::at(&self.lines, self.cursor.row)?;
+        let rows_to_remove = conflict.rows_to_remove(side);
+
+        let removed = rows_to_remove.iter().map(|rows| rows.clone().count()).sum::<usize>();
+        let row = cmp::min(conflict.start, self.lines.len().saturating_sub(removed + 1));
+        let cursor = (self.cursor, CursorPosition { row, col: 0 });
+
+        for (idx, rows) in rows_to_remove.into_iter().enumerate() {
+            let (first, last) = (*rows.start(), *rows.end());
+            let mut lines = self.lines[rows].to_vec();
+            let position = if last + 1 < self.lines.len() {
+                lines.push(String::new());
+                BytePosition { row: first, col: 0 }
+            } else if first > 0 {
+                // no following line, so remove the linebreak before the lines instead
+                lines.insert(0, String::new());
+                BytePosition {
+                    row: first - 1,
+                    col: self.lines[first - 1].len(),
+                }
             } else {
-                Some(lines[cursor.row].char_slice(cursor.col..selection.col))
+                BytePosition { row: 0, col: 0 }
+            };
+
+            let action = HistoryAction::RemoveLines { lines, position, cursor };
+            if idx == 0 {
+                self.do_action(action);
+            } else {
+                self.do_action_chain(action);
             }
-        } else {
-            None
         }
+
+        self.set_cursor(cursor.1, false);
+        Ok(())
     }
-}
 
-impl Widget for &TextArea {
-    fn render(self, area: Rect, buf: &mut Buffer)
-    where
-        Self: Sized,
-    {
-        let (top_left, bottom_right) = self.update_size(usize::from(area.width), area.height.into());
+    /// Rewrites the leading whitespace of every line with tabs or with spaces at the current indent width, as one
+    /// undo step, and indents with that style from then on. Returns the number of lines changed.
+    ///
+    /// Mixed leading tabs and spaces are expanded to the indent width first, then split into whole indents.
+    pub fn convert_indent(&mut self, tabs: bool) -> usize {
+        let width = self.indent.spaces().len();
+        let target = if tabs { Indent::Tabs } else { Indent::from(width) };
+        let whitespace = |columns: usize| match target {
+            Indent::Tabs => "\t".repeat(columns / width) + &" ".repeat(columns % width),
+            Indent::Spaces(_) => " ".repeat(columns),
+        };
+        let leading = |line: &str| line.chars().take_while(|&c| c == ' ' || c == '\t').count();
+        let expanded_width = |whitespace: &str| whitespace.chars().map(|c| if c == '\t' { width } else { 1 }).sum();
 
-        let start = cmp::min(top_left.row, self.lines.len());
-        let end = cmp::min(bottom_right.row, self.lines.len());
+        let changes = self
+            .lines
+            .iter()
+            .enumerate()
+            .filter_map(|(row, line)| {
+                let old = &line[..leading(line)];
+                let new = whitespace(expanded_width(old));
+                (old != new).then(|| (row, old.to_string(), new))
+            })
+            .collect::<Vec<_>>();
+
+        // keep the cursor and selection over the same character
+        let remap = |position: CursorPosition| {
+            let Some((_, old, new)) = changes.iter().find(|(row, _, _)| *row == position.row) else {
+                return position;
+            };
+            let old_len = old.chars().count();
+            let col = if position.col >= old_len {
+                position.col - old_len + new.chars().count()
+            } else {
+                let target_width = expanded_width(old.char_slice(..position.col));
+                new.chars()
+                    .scan(0, |columns, c| {
+                        *columns += if c == '\t' { width } else { 1 };
+                        Some(*columns)
+                    })
+                    .take_while(|&columns| columns <= target_width)
+                    .count()
+            };
+            CursorPosition { col, ..position }
+        };
+        let cursor = (self.cursor, remap(self.cursor));
+        let selection = self.selection.map(remap);
+
+        let count = changes.len();
+        let mut textarea = self.replace_line_starts(&changes, cursor);
+        textarea.indent = target;
+        textarea.set_selection(selection);
+        textarea.commit();
+        count
+    }
 
-        let lines = self.lines[start..end]
+    /// Replaces every non-empty match of `pattern` in the text as one undo step, returning the number of matches
+    /// replaced. `replacement` can refer to capture groups like in [`Regex::replace_all`]. A `limit` other than 0 only
+    /// replaces that many matches on each line, like [`Regex::replacen`].
+    pub fn replace_all(&mut self, pattern: &Regex, replacement: &str, limit: usize) -> usize {
+        let mut replaced = 0;
+        let changes = self
+            .lines
             .iter()
-            .map(|line| {
-                let trimmed = line.trim_end();
-                let tabs = line[trimmed.len()..].chars().filter(|&c| c == '\t').count();
-                let tab_width = self.indent.spaces().len();
-
-                String::from_iter([
-                    &trimmed.replace('\t', self.indent.spaces()),
-                    dots(
-                        (line.chars().count() - trimmed.chars().count() + (tabs * (tab_width - 1)))
-                            .try_into()
-                            .unwrap(),
-                    ),
-                ])
+            .enumerate()
+            .filter_map(|(row, line)| {
+                let mut new = String::new();
+                let mut prev_end = 0;
+                let mut count = 0;
+                for captures in pattern.captures_iter(line) {
+                    let m = captures.get(0).unwrap();
+                    if m.is_empty() {
+                        continue;
+                    }
+                    if limit != 0 && count == limit {
+                        break;
+                    }
+                    count += 1;
+                    new.push_str(&line[prev_end..m.start()]);
+                    captures.expand(replacement, &mut new);
+                    prev_end = m.end();
+                    replaced += 1;
+                }
+                new.push_str(&line[prev_end..]);
+                (new != *line).then(|| (row, line.clone(), new))
             })
             .collect::<Vec<_>>();
 
-        let line_number_len: Option<NonZeroU8> = if self.line_numbers {
-            num_digits(self.lines.len()).try_into().ok()
-        } else {
-            None
+        let cursor = self.cursor;
+        let col = cmp::min(
+            cursor.col,
+            changes
+                .iter()
+                .find(|(row, _, _)| *row == cursor.row)
+                .map_or(usize::MAX, |(_, _, new)| new.chars().count()),
+        );
+        self.replace_line_starts(&changes, (cursor, CursorPosition { col, ..cursor }))
+            .commit();
+        replaced
+    }
+
+    /// Replaces `old` at the start of each row with `new`, in an undo step the caller can add to before committing it.
+    fn replace_line_starts(
+        &mut self,
+        changes: &[(usize, String, String)],
+        cursor: (CursorPosition, CursorPosition),
+    ) -> Chain<'_> {
+        let mut textarea = self.begin_chain();
+        for (row, old, new) in changes {
+            let position = BytePosition { row: *row, col: 0 };
+            textarea.do_action(HistoryAction::RemoveLines {
+                lines: vec![old.clone()],
+                position,
+                cursor,
+            });
+            textarea.do_action(HistoryAction::InsertLines {
+                lines: vec![new.clone()],
+                position,
+                cursor,
+            });
+        }
+        textarea.set_cursor(cursor.1, false);
+        textarea
+    }
+
+    /// The first and last row the selection touches. A selection ending at the start of a line leaves that line out,
+    /// like it does when selecting whole lines.
+    fn selected_rows(&self) -> Option<(usize, usize)> {
+        match self.selection_range()? {
+            (start, end) if end.col == 0 && end.row > start.row => Some((start.row, end.row - 1)),
+            (start, end) => Some((start.row, end.row)),
+        }
+    }
+
+    /// Pads the selected lines with spaces so the first `needle` on each starts at the same display column, as one
+    /// undo step. Lines without it are left alone. Returns the number of lines changed.
+    pub fn align(&mut self, needle: &str) -> usize {
+        let Some((first, last)) = self.selected_rows() else {
+            return 0;
         };
+        let tab_width = self.indent.spaces().len();
 
-        let lines = lines.iter().zip(start..end).map(|(line, line_number)| {
-            self.render_line(
-                line.char_slice(top_left.col..bottom_right.col),
-                LineNumber {
-                    line_number,
-                    line_number_len,
-                    current_line: line_number == self.cursor().row,
+        // the char and display column of each needle
+        let found = (first..=last)
+            .filter_map(|row| {
+                let line = &self.lines[row];
+                let col = line[..line.find(needle)?].chars().count();
+                Some((row, col, line.display_col(col, tab_width)))
+            })
+            .collect::<Vec<_>>();
+        let Some(target) = found.iter().map(|&(_, _, display_col)| display_col).max() else {
+            return 0;
+        };
+        let changes = found
+            .into_iter()
+            .filter(|&(_, _, display_col)| display_col < target)
+            .map(|(row, col, display_col)| (row, col, target - display_col))
+            .collect::<Vec<_>>();
+
+        // keep the cursor and selection on the same characters
+        let remap = |position: CursorPosition| match changes.iter().find(|(row, _, _)| *row == position.row) {
+            Some(&(_, col, padding)) if position.col >= col => CursorPosition {
+                col: position.col + padding,
+                ..position
+            },
+            _ => position,
+        };
+        let (cursor, selection) = (self.cursor, self.selection);
+        let new_cursor = remap(cursor);
+
+        for (idx, &(row, col, padding)) in changes.iter().enumerate() {
+            let action = HistoryAction::InsertLines {
+                lines: vec![" ".repeat(padding)],
+                position: BytePosition {
+                    row,
+                    col: self.lines[row].byte_index(col),
                 },
-            )
-        });
+                cursor: (cursor, new_cursor),
+            };
+            if idx == 0 {
+                self.do_action(action);
+            } else {
+                self.do_action_chain(action);
+            }
+        }
+        self.cursor = new_cursor;
+        self.set_selection(selection.map(remap));
+        changes.len()
+    }
 
-        Paragraph::new(Text::from_iter(lines)).render(area, buf);
+    /// Rearranges the lines the selection touches, or all lines without one, as one undo step. The result is selected.
+    /// Returns how many lines were removed.
+    pub fn reorder_lines(&mut self, order: LineOrder) -> usize {
+        let (first, last) = self.selected_rows().unwrap_or((0, self.lines.len() - 1));
+
+        let old = self.lines[first..=last].to_vec();
+        let mut new = old.clone();
+        order.apply(&mut new);
+        let removed = old.len() - new.len();
+
+        let start = CursorPosition { row: first, col: 0 };
+        let end = CursorPosition {
+            row: first + new.len() - 1,
+            col: new.last().unwrap().chars().count(),
+        };
+        if new != old {
+            let position = BytePosition { row: first, col: 0 };
+            let cursor = (self.cursor, end);
+            self.do_action(HistoryAction::RemoveLines { lines: old, position, cursor });
+            self.do_action_chain(HistoryAction::InsertLines { lines: new, position, cursor });
+        }
+        self.set_cursor(start, false);
+        self.set_cursor(end, true);
+        removed
     }
-}
 
-struct LineNumber {
-    line_number: usize,
-    line_number_len: Option<NonZeroU8>,
-    current_line: bool,
-}
+    /// Reformats the selected JSON, or the whole text without a selection, as one undo step. Pretty-printing indents
+    /// in the buffer's style, otherwise it all goes on one line. Invalid JSON is left alone and the cursor is moved to
+    /// where the error was noticed.
+    pub fn format_json(&mut self, pretty: bool) -> Result<(), String> {
+        let selected = self.selection_range();
+        let (start, end) = selected.unwrap_or_else(|| {
+            let row = self.lines.len() - 1;
+            let end = CursorPosition {
+                row,
+                col: self.lines[row].chars().count(),
+            };
+            (CursorPosition::default(), end)
+        });
+        let old = if start.row == end.row {
+            vec![self.lines[start.row].char_slice(start.col..end.col).to_string()]
+        } else {
+            iter::once(self.lines[start.row].char_slice(start.col..).to_string())
+                .chain(self.lines[start.row + 1..end.row].iter().cloned())
+                .chain(iter::once(self.lines[end.row].char_slice(..end.col).to_string()))
+                .collect()
+        };
 
-impl From<LineNumber> for Span<'static> {
-    fn from(value: LineNumber) -> Self {
-        const LINE_NUMBER_STYLE_SELECTED: Style = Style::new().fg(Color::DarkGray);
-        const LINE_NUMBER_STYLE: Style = LINE_NUMBER_STYLE_SELECTED.add_modifier(Modifier::DIM);
+        let indent = self.indent.whitespace(self.indent.spaces().len());
+        let new = match json::format(&old.join("\n"), pretty.then_some(indent.as_str())) {
+            Ok(new) => new.split('\n').map(String::from).collect::<Vec<_>>(),
+            Err(err) => {
+                let idx = cmp::min(err.line.saturating_sub(1), old.len() - 1);
+                let line = &old[idx];
+                let col = line[..line.floor_char_boundary(err.col)].chars().count();
+                let col = if idx == 0 { start.col + col } else { col };
+                self.set_cursor(CursorPosition { row: start.row + idx, col }, false);
+                return Err(format!("Invalid JSON: {}", err.message));
+            }
+        };
+        if new == old {
+            return Ok(());
+        }
 
-        match value.line_number_len {
-            Some(line_number_len) => Span::styled(
-                format!(
-                    "{}{} ",
-                    spaces(u8::from(line_number_len) - num_digits(value.line_number)),
-                    value.line_number
-                ),
-                if value.current_line {
-                    LINE_NUMBER_STYLE_SELECTED
-                } else {
-                    LINE_NUMBER_STYLE
-                },
-            ),
-            None => Span::from(""),
+        let new_end = CursorPosition {
+            row: start.row + new.len() - 1,
+            col: new.last().unwrap().chars().count() + if new.len() == 1 { start.col } else { 0 },
+        };
+        let position = BytePosition::from_line(start, &self.lines[start.row]);
+        self.do_action(HistoryAction::RemoveLines {
+            lines: old,
+            position,
+            cursor: (self.cursor, start),
+        });
+        self.do_action_chain(HistoryAction::InsertLines {
+            lines: new,
+            position,
+            cursor: (start, new_end),
+        });
+        self.set_cursor(start, false);
+        if selected.is_some() {
+            self.set_cursor(new_end, true);
         }
+        Ok(())
     }
-}
 
-pub fn num_digits(i: usize) -> u8 {
-    const { assert!(usize::ilog10(usize::MAX) <= (u8::MAX as u32)) }
+    /// The first and last row of the paragraph at `row`, delimited by blank lines like for Ctrl+Up and Ctrl+Down.
+    fn paragraph_rows(&self, row: usize) -> Option<(usize, usize)> {
+        let blank = |line: &String| line.trim_start().is_empty();
+        if blank(&self.lines[row]) {
+            return None;
+        }
 
-    if i == 0 {
-        return 1;
+        let first = self.lines[..row].iter().rposition(blank).map_or(0, |idx| idx + 1);
+        let last = self.lines[row..]
+            .iter()
+            .position(blank)
+            .map_or(self.lines.len(), |idx| row + idx)
+            - 1;
+        Some((first, last))
     }
 
-    (usize::ilog10(i) + 1) as u8
-}
+    /// Selects the paragraph the cursor is in, returning whether there is one.
+    pub fn select_paragraph(&mut self) -> bool {
+        let Some((first, last)) = self.paragraph_rows(self.cursor.row) else {
+            return false;
+        };
+        self.set_cursor(CursorPosition { row: first, col: 0 }, false);
+        let end = CursorPosition {
+            row: last,
+            col: self.lines[last].chars().count(),
+        };
+        self.set_cursor(end, true);
+        true
+    }
 
-pub fn spaces(size: u8) -> &'static str {
-    const SPACES: &str = "                                                                                                                                                                                                                                                                ";
-    &SPACES[..size.into()]
-}
+    /// Sets the whole text without an undo step, for text that is loaded or generated rather than edited. Assigning
+    /// [`TextArea::lines`] directly leaves cached matches and offsets for the old text.
+    pub fn set_lines(&mut self, lines: Vec<String>) {
+        self.lines = lines.into();
+        self.text_replaced();
+    }
 
-pub fn dots(size: u8) -> &'static str {
+    /// Adds `lines` after the last one without an undo step, like [`TextArea::set_lines`].
+    pub fn extend_lines(&mut self, lines: Vec<String>) {
+        if lines.is_empty() {
+            return;
+        }
+        self.lines.extend(lines);
+        self.text_replaced();
+    }
+
+    fn text_replaced(&mut self) {
+        self.version += 1;
+        self.redraw = true;
+    }
+
+    /// Replaces the whole text with `lines` as one undo step, keeping the cursor row, clamped to the new text, and
+    /// the view. Returns whether the text changed.
+    pub fn replace_lines(&mut self, lines: Vec<String>) -> bool {
+        if self.lines == lines {
+            return false;
+        }
+
+        let row = cmp::min(self.cursor.row, lines.len() - 1);
+        let cursor = CursorPosition {
+            row,
+            col: cmp::min(self.cursor.col, lines[row].chars().count()),
+        };
+        let position = BytePosition { row: 0, col: 0 };
+        self.do_action(HistoryAction::RemoveLines {
+            lines: self.lines.to_vec(),
+            position,
+            cursor: (self.cursor, cursor),
+        });
+        self.do_action_chain(HistoryAction::InsertLines {
+            lines,
+            position,
+            cursor: (self.cursor, cursor),
+        });
+        self.set_cursor(cursor, false);
+        true
+    }
+
+    /// Re-wraps the selected lines, or else the paragraph at the cursor, to `width` columns as one undo step. The
+    /// cursor ends up at the end of the reflowed lines. Returns whether there was anything to reflow. `prose` keeps
+    /// `#` and `*` from being taken for comment markers.
+    pub fn reflow(&mut self, width: usize, prose: bool) -> bool {
+        let Some((first, last)) = self.selected_rows().or_else(|| self.paragraph_rows(self.cursor.row)) else {
+            return false;
+        };
+
+        let old = self.lines[first..=last].to_vec();
+        let new = reflow::reflow(&old, width, self.indent.spaces().len(), prose);
+        let end = CursorPosition {
+            row: first + new.len() - 1,
+            col: new.last().map_or(0, |line| line.chars().count()),
+        };
+        if new != old {
+            let position = BytePosition { row: first, col: 0 };
+            self.do_action(HistoryAction::RemoveLines {
+                lines: old,
+                position,
+                cursor: (self.cursor, end),
+            });
+            self.do_action_chain(HistoryAction::InsertLines {
+                lines: new,
+                position,
+                cursor: (self.cursor, end),
+            });
+        }
+        self.set_cursor(end, false);
+        true
+    }
+
+    /// Line `row` as drawn, made again only if it changed since it was last asked for.
+    fn expanded_line(&self, row: usize) -> Rc<ExpandedLine> {
+        let (line, tab_width) = (&self.lines[row], self.indent.spaces().len());
+        let mut cache = self.expanded_lines.borrow_mut();
+        match cache.get(&row) {
+            Some(expanded) if expanded.is_of(line, tab_width) => Rc::clone(expanded),
+            _ => {
+                let expanded = Rc::new(ExpandedLine::new(line, tab_width));
+                cache.insert(row, Rc::clone(&expanded));
+                expanded
+            }
+        }
+    }
+
+    /// Byte offsets of the start of every line, with a newline after each.
+    fn line_offsets(&self) -> Ref<'_, Vec<usize>> {
+        {
+            let mut cached = self.line_offsets.borrow_mut();
+            if cached.0 != self.version || cached.1.len() != self.lines.len() {
+                let offsets = self
+                    .lines
+                    .iter()
+                    .scan(0, |offset, line| Some(mem::replace(offset, *offset + line.len() + 1)))
+                    .collect();
+                *cached = (self.version, offsets);
+            }
+        }
+        Ref::map(self.line_offsets.borrow(), |(_, offsets)| offsets)
+    }
+
+    /// Counts changes to the text, for telling whether something derived from it is out of date.
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    /// How many bytes into the text the cursor is, counting a newline after each line.
+    pub fn byte_offset(&self) -> usize {
+        let line = &self.lines[self.cursor.row];
+        self.line_offsets()[self.cursor.row] + line.byte_index(self.cursor.col)
+    }
+
+    /// Moves the cursor to `offset` bytes into the text, like [`TextArea::goto`]. An offset inside a char goes to its
+    /// start, one on a newline to the end of its line, and one past the end to the end of the text.
+    pub fn goto_byte_offset(&mut self, offset: usize) {
+        let (row, mut col) = {
+            let offsets = self.line_offsets();
+            let row = offsets.partition_point(|&start| start <= offset) - 1;
+            (row, offset - offsets[row])
+        };
+        let line = &self.lines[row];
+        col = cmp::min(col, line.len());
+        while !line.is_char_boundary(col) {
+            col -= 1;
+        }
+        let col = line[..col].chars().count();
+        self.goto(row, col);
+    }
+
+    /// Counts the whole text. This is linear in its size, so only call it on demand.
+    pub fn stats(&self) -> Stats {
+        Stats::new(self.lines.iter().map(String::as_str))
+    }
+
+    /// Counts the selected text, cached so it is cheap to call on every render.
+    pub fn selection_stats(&self) -> Option<Stats> {
+        let (start, end) = self.selection_range()?;
+        let key = (start, end, self.version);
+        if let Some((cached_key, stats)) = self.selection_stats.get()
+            && cached_key == key
+        {
+            return Some(stats);
+        }
+
+        let stats = if start.row == end.row {
+            Stats::new([self.lines[start.row].char_slice(start.col..end.col)])
+        } else {
+            Stats::new(
+                iter::once(self.lines[start.row].char_slice(start.col..))
+                    .chain(self.lines[start.row + 1..end.row].iter().map(String::as_str))
+                    .chain(iter::once(self.lines[end.row].char_slice(..end.col))),
+            )
+        };
+        self.selection_stats.set(Some((key, stats)));
+        Some(stats)
+    }
+
+    pub fn selected_text(&mut self, unselect: bool) -> Option<Vec<String>> {
+        let selection = self.selection()?;
+        if unselect {
+            self.set_selection(None);
+        }
+
+        let lines = &self.lines;
+        let cursor = self.cursor();
+
+        let (start, end) = if cursor < selection {
+            (cursor, selection)
+        } else {
+            (selection, cursor)
+        };
+
+        if start.row == end.row {
+            return Some(vec![lines[start.row].char_slice(start.col..end.col).to_string()]);
+        }
+
+        let mut text = Vec::with_capacity(end.row - start.row + 1);
+        text.push(lines[start.row].char_slice(start.col..).to_string());
+        lines[start.row + 1..end.row]
+            .iter()
+            .for_each(|line| text.push(line.to_string()));
+        text.push(lines[end.row].char_slice(..end.col).to_string());
+
+        Some(text)
+    }
+
+    pub fn selected_text_single_line(&self) -> Option<&str> {
+        let lines = &self.lines;
+        let cursor = self.cursor();
+        let selection = self.selection();
+
+        if let Some(selection) = selection {
+            if cursor.row != selection.row {
+                return None;
+            }
+
+            if selection < cursor {
+                Some(lines[cursor.row].char_slice(selection.col..cursor.col))
+            } else {
+                Some(lines[cursor.row].char_slice(cursor.col..selection.col))
+            }
+        } else {
+            None
+        }
+    }
+}
+
+/// How many undo steps `history` holds: its first action starts one, and every other action does unless chained to
+/// the one before it.
+fn steps<'a>(history: impl Iterator<Item = &'a Done>) -> usize {
+    history
+        .enumerate()
+        .filter(|&(idx, done)| idx == 0 || !done.chain)
+        .count()
+}
+
+/// All the lines `actions` changed once done, from the first to the last.
+fn changed_rows(actions: &[Done]) -> Option<RangeInclusive<usize>> {
+    actions
+        .iter()
+        .map(|done| done.action.rows())
+        .reduce(|a, b| cmp::min(*a.start(), *b.start())..=cmp::max(*a.end(), *b.end()))
+}
+
+impl Widget for &TextArea {
+    fn render(self, area: Rect, buf: &mut Buffer)
+    where
+        Self: Sized,
+    {
+        let (top_left, bottom_right) = self.update_size(usize::from(area.width), area.height.into());
+
+        let start = cmp::min(top_left.row, self.lines.len());
+        let end = cmp::min(bottom_right.row, self.lines.len());
+
+        let expanded_lines = (start..end).map(|row| self.expanded_line(row)).collect::<Vec<_>>();
+        // only what is on screen is worth keeping
+        let cursor_row = self.cursor().row;
+        self.expanded_lines
+            .borrow_mut()
+            .retain(|&row, _| (start..end).contains(&row) || row == cursor_row);
+
+        let line_number_len: Option<NonZeroU8> = if self.line_numbers {
+            num_digits(self.lines.len()).try_into().ok()
+        } else {
+            None
+        };
+
+        let lines = expanded_lines.iter().zip(start..end).map(|(line, line_number)| {
+            const CONFLICT_MARKER: Style = Style::new().fg(Color::Red).add_modifier(Modifier::BOLD);
+
+            let marker = conflict::is_marker(&self.lines[line_number]);
+            let mut line = self.render_line(
+                line,
+                top_left.col..bottom_right.col,
+                LineNumber {
+                    line_number,
+                    line_number_len,
+                    style: if line_number == cursor_row {
+                        Style::new().fg(self.theme.gutter_current_fg)
+                    } else {
+                        Style::new().fg(self.theme.gutter_fg).add_modifier(Modifier::DIM)
+                    },
+                },
+            );
+
+            // the first span is the line number
+            if marker {
+                line.spans[1..]
+                    .iter_mut()
+                    .for_each(|span| span.style = CONFLICT_MARKER.patch(span.style));
+            }
+            line
+        });
+
+        Paragraph::new(Text::from_iter(lines)).render(area, buf);
+
+        let gutter = line_number_len.map_or(0, |len| u16::from(u8::from(len)) + 1);
+        let text_area = Rect {
+            x: area.x + gutter,
+            width: area.width.saturating_sub(gutter),
+            ..area
+        };
+        if (start..end).contains(&cursor_row) {
+            let y = text_area.y + u16::try_from(cursor_row - start).unwrap();
+            self.render_current_line(text_area, buf, y);
+        }
+        if self.indent_guides {
+            self.render_indent_guides(text_area, buf, start..end, top_left.col);
+        }
+        if self.makefile {
+            self.render_recipe_errors(text_area, buf, start..end, top_left.col);
+        }
+        if let Some(dictionary) = &self.dictionary {
+            self.render_misspellings(text_area, buf, start..end, top_left.col, dictionary);
+        }
+        self.render_rulers(text_area, buf, &expanded_lines, top_left.col);
+    }
+}
+
+struct LineNumber {
+    line_number: usize,
+    line_number_len: Option<NonZeroU8>,
+    /// Dimmed, unless it is the cursor line.
+    style: Style,
+}
+
+impl From<LineNumber> for Span<'static> {
+    fn from(value: LineNumber) -> Self {
+        match value.line_number_len {
+            Some(line_number_len) => Span::styled(
+                format!(
+                    "{}{} ",
+                    spaces(usize::from(u8::from(line_number_len) - num_digits(value.line_number))),
+                    value.line_number
+                ),
+                value.style,
+            ),
+            None => Span::from(""),
+        }
+    }
+}
+
+pub fn num_digits(i: usize) -> u8 {
+    const { assert!(usize::ilog10(usize::MAX) <= (u8::MAX as u32)) }
+
+    if i == 0 {
+        return 1;
+    }
+
+    (usize::ilog10(i) + 1) as u8
+}
+
+/// `size` spaces, borrowed for the usual short runs.
+pub fn spaces(size: usize) -> Cow<'static, str> {
+    const SPACES: &str = "                                                                                                                                                                                                                                                                ";
+    SPACES
+        .get(..size)
+        .map_or_else(|| " ".repeat(size).into(), Cow::Borrowed)
+}
+
+/// `size` middle dots standing in for trailing whitespace, borrowed for the usual short runs.
+pub fn dots(size: usize) -> Cow<'static, str> {
     const DOTS: &str = "································································································································································································································································";
-    &DOTS[..('·'.len_utf8() * usize::from(size))]
+    DOTS.get(..'·'.len_utf8() * size)
+        .map_or_else(|| "·".repeat(size).into(), Cow::Borrowed)
+}
+
+#[test]
+fn cut_and_paste_line() {
+    let ctrl = |char| Input {
+        key: Key::Char(char),
+        ctrl: true,
+        ..Default::default()
+    };
+
+    let mut textarea = TextArea {
+        lines: vec!["äa".to_string(), "bbbb".to_string(), "c".to_string()].into(),
+        ..Default::default()
+    };
+
+    textarea.set_cursor(CursorPosition { row: 1, col: 3 }, false);
+    textarea.input(ctrl('x'));
+    assert_eq!(textarea.lines, ["äa", "c"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 1, col: 1 });
+
+    textarea.input(ctrl('v'));
+    assert_eq!(textarea.lines, ["äa", "bbbb", "c"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 2, col: 1 });
+
+    textarea.input(ctrl('z'));
+    textarea.input(ctrl('z'));
+    assert_eq!(textarea.lines, ["äa", "bbbb", "c"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 1, col: 3 });
+
+    textarea.set_cursor(CursorPosition { row: 2, col: 1 }, false);
+    textarea.input(ctrl('x'));
+    assert_eq!(textarea.lines, ["äa", "bbbb"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 1, col: 1 });
+    textarea.input(ctrl('z'));
+    assert_eq!(textarea.lines, ["äa", "bbbb", "c"]);
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    textarea.input(ctrl('c'));
+    textarea.input(ctrl('v'));
+    assert_eq!(textarea.lines, ["äa", "äa", "bbbb", "c"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 1, col: 1 });
+
+    textarea.lines = vec!["only".to_string()].into();
+    textarea.set_cursor(CursorPosition { row: 0, col: 2 }, false);
+    textarea.input(ctrl('x'));
+    assert_eq!(textarea.lines, [""]);
+    textarea.input(ctrl('z'));
+    assert_eq!(textarea.lines, ["only"]);
+}
+
+#[test]
+fn paste_reindented() {
+    let mut textarea = TextArea {
+        lines: vec!["fn main() {".to_string(), "  ".to_string(), "}".to_string()].into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 1, col: 2 }, false);
+
+    textarea.clipboard.set_text("if x {\n\t\tfoo();\n\n\t}");
+    textarea.input(Input {
+        key: Key::Char('v'),
+        ctrl: true,
+        ..Default::default()
+    });
+    assert_eq!(
+        textarea.lines,
+        ["fn main() {", "  if x {", "      foo();", "", "  }", "}"]
+    );
+    assert_eq!(textarea.cursor(), CursorPosition { row: 4, col: 3 });
+
+    textarea.input(Input {
+        key: Key::Char('z'),
+        ctrl: true,
+        ..Default::default()
+    });
+    textarea.input(Input {
+        key: Key::Char('v'),
+        alt: true,
+        ..Default::default()
+    });
+    assert_eq!(
+        textarea.lines,
+        ["fn main() {", "  if x {", "\t\tfoo();", "", "\t}", "}"]
+    );
+}
+
+#[test]
+fn smart_home() {
+    let home = Input {
+        key: Key::Home,
+        ..Default::default()
+    };
+
+    let mut textarea = TextArea {
+        lines: vec!["\t  äbc".to_string(), "   ".to_string()].into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 0, col: 5 }, false);
+
+    textarea.input(home.clone());
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 3 });
+    textarea.input(home.clone());
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 0 });
+    textarea.input(Input { shift: true, ..home.clone() });
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 3 });
+    assert_eq!(textarea.selection(), Some(CursorPosition { row: 0, col: 0 }));
+
+    textarea.set_cursor(CursorPosition { row: 1, col: 2 }, false);
+    textarea.input(home);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 1, col: 0 });
+}
+
+#[test]
+fn delete_to_line_boundaries() {
+    let ctrl = |char| Input {
+        key: Key::Char(char),
+        ctrl: true,
+        ..Default::default()
+    };
+
+    let mut textarea = TextArea {
+        lines: vec!["äbc déf".to_string(), "ghi".to_string()].into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 0, col: 4 }, false);
+
+    textarea.input(ctrl('k'));
+    assert_eq!(textarea.lines, ["äbc ", "ghi"]);
+    textarea.input(ctrl('k'));
+    assert_eq!(textarea.lines, ["äbc ghi"]);
+    assert!(!textarea.input(Input {
+        key: Key::End,
+        ..Default::default()
+    }));
+    assert!(!textarea.input(ctrl('k')));
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 4 }, false);
+    textarea.input(ctrl('u'));
+    assert_eq!(textarea.lines, ["ghi"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 0 });
+
+    textarea.input(ctrl('z'));
+    assert_eq!(textarea.lines, ["äbc ghi"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 4 });
+    textarea.input(ctrl('z'));
+    textarea.input(ctrl('z'));
+    assert_eq!(textarea.lines, ["äbc déf", "ghi"]);
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    textarea.set_cursor(CursorPosition { row: 1, col: 1 }, true);
+    textarea.input(ctrl('k'));
+    assert_eq!(textarea.lines, ["ähi"]);
+}
+
+#[test]
+fn subword_motion() {
+    let alt = |key| Input {
+        key,
+        alt: true,
+        ..Default::default()
+    };
+
+    let mut textarea = TextArea {
+        lines: vec!["let cursor_start = setPosition;".to_string()].into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 0, col: 4 }, false);
+
+    textarea.input(alt(Key::Right));
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 10 });
+    textarea.input(Input { shift: true, ..alt(Key::Right) });
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 16 });
+    assert_eq!(textarea.selection(), Some(CursorPosition { row: 0, col: 10 }));
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 30 }, false);
+    textarea.input(alt(Key::Left));
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 22 });
+    textarea.input(alt(Key::Backspace));
+    assert_eq!(textarea.lines, ["let cursor_start = Position;"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 19 });
+}
+
+#[cfg(test)]
+fn render(textarea: &TextArea, width: u16, height: u16) -> Buffer {
+    let mut buf = Buffer::empty(Rect::new(0, 0, width, height));
+    textarea.render(buf.area, &mut buf);
+    buf
+}
+
+#[cfg(test)]
+fn selected_cells(buf: &Buffer, row: u16) -> Vec<u16> {
+    (0..buf.area.width)
+        .filter(|&x| buf[(x, row)].bg == Color::LightBlue)
+        .collect()
+}
+
+#[test]
+fn render_selection() {
+    let mut textarea = TextArea {
+        lines: vec!["\tab\tc".to_string(), "日本語abc".to_string()].into(),
+        ..Default::default()
+    };
+
+    // gutter is 2 wide, tabs 4
+    textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    textarea.set_cursor(CursorPosition { row: 0, col: 4 }, true);
+    let buf = render(&textarea, 20, 2);
+    assert_eq!(selected_cells(&buf, 0), (6..12).collect::<Vec<_>>());
+
+    textarea.set_cursor(CursorPosition { row: 1, col: 1 }, false);
+    textarea.set_cursor(CursorPosition { row: 1, col: 4 }, true);
+    let buf = render(&textarea, 20, 2);
+    // the second cell of a wide character isn't styled by ratatui
+    assert_eq!(buf[(4, 1)].symbol(), "本");
+    assert_eq!(selected_cells(&buf, 1), [4, 6, 8]);
+}
+
+#[test]
+fn render_selection_scrolled() {
+    let mut textarea = TextArea {
+        lines: vec!["a".repeat(20), "\t".repeat(2) + "日本語abc", "b".repeat(20)].into(),
+        ..Default::default()
+    };
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 15 }, false);
+    textarea.set_cursor(CursorPosition { row: 2, col: 20 }, true);
+    let buf = render(&textarea, 12, 3);
+
+    // the view starts at display column 11, leaving the cursor in the last column just right of the text
+    assert_eq!(selected_cells(&buf, 0), (6..11).collect::<Vec<_>>());
+    assert_eq!(buf[(3, 1)].symbol(), "語");
+    assert_eq!(selected_cells(&buf, 1), [3, 5, 6, 7]);
+    assert_eq!(selected_cells(&buf, 2), (2..11).collect::<Vec<_>>());
+    assert_eq!(textarea.terminal_cursor_position(), Position { x: 11, y: 2 });
+}
+
+#[test]
+fn render_rulers() {
+    let mut textarea = TextArea {
+        lines: vec!["abcdefgh".to_string(), "ab".to_string()].into(),
+        rulers: vec![4, 6],
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 0, col: 6 }, false);
+    textarea.set_cursor(CursorPosition { row: 0, col: 7 }, true);
+
+    let buf = render(&textarea, 12, 3);
+    // the gutter is two columns wide
+    let tinted = |row| {
+        (0..12)
+            .filter(|&x| buf[(x, row)].bg == Color::DarkGray)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(tinted(0), [6, 7, 9]);
+    assert_eq!(selected_cells(&buf, 0), [8]);
+    assert_eq!(buf[(6, 1)].symbol(), "│");
+    assert_eq!(buf[(8, 1)].symbol(), "│");
+    assert_eq!(buf[(7, 1)].symbol(), " ");
+
+    // scrolled right by 2 columns
+    textarea.line_numbers = false;
+    textarea.set_cursor(CursorPosition { row: 1, col: 2 }, false);
+    textarea.view.position.set(CursorPosition { row: 0, col: 2 });
+    let buf = render(&textarea, 4, 3);
+    assert_eq!(buf[(2, 1)].symbol(), "│");
+    assert_eq!(buf[(2, 0)].bg, Color::DarkGray);
+}
+
+#[test]
+fn render_indent_guides() {
+    let mut textarea = TextArea {
+        lines: ["a", "  b", "    c", "", "      ", "  d", "\te"]
+            .map(String::from)
+            .into(),
+        indent: 2.into(),
+        line_numbers: false,
+        indent_guides: true,
+        ..Default::default()
+    };
+
+    let guides = |buf: &Buffer, row| (0..8).filter(|&x| buf[(x, row)].symbol() == "│").collect::<Vec<_>>();
+    let buf = render(&textarea, 8, 7);
+    let rows = (0..7).map(|row| guides(&buf, row)).collect::<Vec<_>>();
+    assert_eq!(rows, [vec![], vec![0], vec![0, 2], vec![0], vec![0], vec![0], vec![0]]);
+
+    // tabs are as wide as the indent, and guides scroll with the text
+    textarea.set_cursor(CursorPosition { row: 2, col: 4 }, false);
+    textarea.view.position.set(CursorPosition { row: 0, col: 1 });
+    let buf = render(&textarea, 8, 7);
+    assert_eq!(guides(&buf, 2), [1]);
+}
+
+#[test]
+fn render_trailing_whitespace() {
+    let mut textarea = TextArea {
+        lines: ["ab\t\t", "cd  ", "ef", "gh "].map(String::from).into(),
+        line_numbers: false,
+        trailing_whitespace: true,
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 3, col: 3 }, false);
+
+    let tinted = |buf: &Buffer, row| (0..12).filter(|&x| buf[(x, row)].bg == Color::Red).collect::<Vec<_>>();
+    let buf = render(&textarea, 12, 4);
+    let rows = (0..4).map(|row| tinted(&buf, row)).collect::<Vec<_>>();
+    // not on the cursor line, where it's likely about to be typed past
+    assert_eq!(rows, [(2..10).collect(), vec![2, 3], vec![], vec![]]);
+
+    // the selection wins
+    textarea.set_cursor(CursorPosition { row: 0, col: 3 }, false);
+    textarea.set_cursor(CursorPosition { row: 3, col: 0 }, true);
+    let buf = render(&textarea, 12, 4);
+    assert_eq!(tinted(&buf, 0), (2..6).collect::<Vec<_>>());
+    assert_eq!(selected_cells(&buf, 0), (6..10).collect::<Vec<_>>());
+    assert!(tinted(&buf, 1).is_empty());
+
+    textarea.trailing_whitespace = false;
+    let buf = render(&textarea, 12, 4);
+    assert!(tinted(&buf, 0).is_empty());
+}
+
+#[test]
+fn resolve_conflict() {
+    let lines = ["a", "<<<<<<< HEAD", "ours", "=======", "theirs", ">>>>>>> branch"].map(String::from);
+    let mut textarea = TextArea {
+        lines: lines.to_vec().into(),
+        ..Default::default()
+    };
+
+    assert!(textarea.jump_to_conflict(true));
+    assert_eq!(textarea.cursor(), CursorPosition { row: 1, col: 0 });
+    assert!(!textarea.jump_to_conflict(true));
+
+    for (side, expected) in [
+        (Side::Ours, vec!["a", "ours"]),
+        (Side::Theirs, vec!["a", "theirs"]),
+        (Side::Both, vec!["a", "ours", "theirs"]),
+    ] {
+        textarea.set_cursor(CursorPosition { row: 3, col: 2 }, false);
+        assert_eq!(textarea.resolve_conflict(side), Ok(()));
+        assert_eq!(textarea.lines, expected);
+        assert_eq!(textarea.cursor(), CursorPosition { row: 1, col: 0 });
+
+        // one undo step restores everything
+        textarea.undo_action();
+        assert_eq!(textarea.lines, lines);
+    }
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 0 }, false);
+    assert_eq!(textarea.resolve_conflict(Side::Ours), Err("Not in a conflict"));
+    assert_eq!(textarea.lines, lines);
+}
+
+#[test]
+fn mixed_indent_count() {
+    let mut textarea = TextArea {
+        lines: ["\ta", "    b", "  \tc", "\t", ""].map(String::from).into(),
+        ..Default::default()
+    };
+    let recount = |textarea: &TextArea| {
+        textarea
+            .lines
+            .iter()
+            .filter(|line| textarea.indent.is_mixed(line))
+            .count()
+    };
+    assert_eq!(textarea.mixed_indent_lines(), 3);
+    // the count as the edits kept it, without the full recount mixed_indent_lines falls back to
+    let kept = |textarea: &TextArea| {
+        let counted = textarea
+            .mixed_indent
+            .take()
+            .filter(|counted| counted.is_for(textarea))
+            .expect("the count is kept up to date");
+        let count = counted.count;
+        textarea.mixed_indent.set(Some(counted));
+        count
+    };
+
+    // each edit updates the count from the lines it touched, rather than counting them all again
+    let edits = [
+        Input {
+            key: Key::Tab,
+            ..Default::default()
+        },
+        Input {
+            key: Key::Enter,
+            ..Default::default()
+        },
+        Input {
+            key: Key::Backspace,
+            ..Default::default()
+        },
+        Input {
+            key: Key::Char('d'),
+            ctrl: true,
+            ..Default::default()
+        },
+        Input {
+            key: Key::Down,
+            alt: true,
+            ..Default::default()
+        },
+        Input {
+            key: Key::Char('x'),
+            ctrl: true,
+            ..Default::default()
+        },
+    ];
+    textarea.set_cursor(CursorPosition { row: 1, col: 0 }, false);
+    for input in edits {
+        textarea.input(input);
+        assert_eq!(kept(&textarea), recount(&textarea), "{:?}", textarea.lines);
+    }
+    while textarea.undo_action().is_some() {
+        assert_eq!(kept(&textarea), recount(&textarea), "{:?}", textarea.lines);
+    }
+    assert_eq!(textarea.mixed_indent_lines(), 3);
+
+    // a change of style counts them all again
+    textarea.indent = Indent::Tabs;
+    assert_eq!(textarea.mixed_indent_lines(), 2);
+
+    textarea.set_cursor(CursorPosition { row: 1, col: 2 }, false);
+    assert!(textarea.jump_to_mixed_indent());
+    assert_eq!(textarea.cursor(), CursorPosition { row: 2, col: 0 });
+    assert!(textarea.jump_to_mixed_indent());
+    assert_eq!(textarea.cursor(), CursorPosition { row: 1, col: 0 });
+    textarea.lines = vec!["\ta".to_string()].into();
+    textarea.set_cursor(CursorPosition { row: 0, col: 0 }, false);
+    assert!(!textarea.jump_to_mixed_indent());
+}
+
+#[test]
+fn convert_indent() {
+    let lines = ["fn f() {", "\tlet x = 1;", "\t  y", "  \tz", "}"].map(String::from);
+    let mut textarea = TextArea {
+        lines: lines.to_vec().into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 2, col: 3 }, false);
+
+    assert_eq!(textarea.convert_indent(false), 3);
+    assert_eq!(
+        textarea.lines,
+        ["fn f() {", "    let x = 1;", "      y", "      z", "}"]
+    );
+    assert_eq!(textarea.cursor(), CursorPosition { row: 2, col: 6 });
+    assert_eq!(textarea.indent.spaces(), "    ");
+
+    assert_eq!(textarea.convert_indent(true), 3);
+    assert_eq!(textarea.lines, ["fn f() {", "\tlet x = 1;", "\t  y", "\t  z", "}"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 2, col: 3 });
+    assert!(matches!(textarea.indent, Indent::Tabs));
+    assert_eq!(textarea.convert_indent(true), 0);
+
+    // each conversion is a single undo step
+    textarea.undo_action();
+    textarea.undo_action();
+    assert_eq!(textarea.lines, lines);
+}
+
+#[test]
+fn render_recipe_errors() {
+    let textarea = TextArea {
+        lines: ["all:", "\tcc main.c", "  cc util.c"].map(String::from).into(),
+        line_numbers: false,
+        makefile: true,
+        ..Default::default()
+    };
+
+    let buf = render(&textarea, 12, 3);
+    let red = |row| (0..12).filter(|&x| buf[(x, row)].bg == Color::Red).collect::<Vec<_>>();
+    assert!(red(1).is_empty());
+    assert_eq!(red(2), [0, 1]);
+}
+
+#[test]
+fn overwrite() {
+    let mut textarea = TextArea {
+        lines: vec!["abc".to_string()].into(),
+        ..Default::default()
+    };
+    let input = |key| Input { key, ..Default::default() };
+
+    textarea.input(input(Key::Insert));
+    assert!(textarea.overwrite);
+    for char in "xyz!".chars() {
+        textarea.input(input(Key::Char(char)));
+    }
+    assert_eq!(textarea.lines, ["xyz!"]);
+
+    // Backspace only moves left, unless configured to delete
+    textarea.input(input(Key::Backspace));
+    assert_eq!(textarea.lines, ["xyz!"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 3 });
+    textarea.overwrite_backspace_deletes = true;
+    textarea.input(input(Key::Backspace));
+    assert_eq!(textarea.lines, ["xy!"]);
+
+    // each replaced character is undone in one step
+    textarea.undo_action();
+    textarea.undo_action();
+    assert_eq!(textarea.lines, ["xyz"]);
+    textarea.undo_action();
+    assert_eq!(textarea.lines, ["xyc"]);
+}
+
+#[test]
+fn clipboard_keys() {
+    let mut textarea = TextArea {
+        lines: vec!["ab".to_string()].into(),
+        ..Default::default()
+    };
+    let input = |key| Input { key, ..Default::default() };
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 1 }, true);
+    textarea.input(input(Key::Copy));
+    textarea.set_cursor(CursorPosition { row: 0, col: 2 }, false);
+    textarea.input(input(Key::Paste));
+    assert_eq!(textarea.lines, ["aba"]);
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    textarea.set_cursor(CursorPosition { row: 0, col: 0 }, true);
+    textarea.input(input(Key::Cut));
+    assert_eq!(textarea.lines, ["ba"]);
+}
+
+#[test]
+fn paste_previous() {
+    let mut textarea = TextArea {
+        lines: vec!["x".to_string()].into(),
+        ..Default::default()
+    };
+    let ctrl = |c: char| Input {
+        key: Key::Char(c),
+        ctrl: true,
+        shift: c.is_uppercase(),
+        ..Default::default()
+    };
+    for text in ["one", "two", "three"] {
+        textarea.clipboard.set_text(text);
+    }
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    assert!(textarea.input(ctrl('v')));
+    assert_eq!(textarea.lines, ["xthree"]);
+
+    // past the oldest entry it goes round to the newest again
+    for expected in ["xtwo", "xone", "xthree", "xtwo"] {
+        assert!(textarea.input(ctrl('V')));
+        assert_eq!(textarea.lines, [expected]);
+        assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: expected.len() });
+    }
+
+    // only right after a paste
+    textarea.set_cursor(CursorPosition { row: 0, col: 0 }, false);
+    assert!(!textarea.input(ctrl('V')));
+    assert_eq!(textarea.lines, ["xtwo"]);
+}
+
+#[test]
+fn escape() {
+    let mut textarea = TextArea {
+        lines: vec!["abc abc".to_string()].into(),
+        ..Default::default()
+    };
+    let esc = Input {
+        key: Key::Esc,
+        ..Default::default()
+    };
+    textarea.set_search_pattern("abc").unwrap();
+    textarea.set_cursor(CursorPosition { row: 0, col: 3 }, true);
+
+    textarea.input(esc.clone());
+    assert_eq!(textarea.selection(), None);
+    assert!(textarea.search_pattern.is_some());
+
+    textarea.input(esc.clone());
+    assert!(textarea.search_pattern.is_none());
+    assert!(textarea.take_redraw());
+
+    textarea.input(esc);
+    assert!(!textarea.take_redraw());
+}
+
+#[test]
+fn single_line() {
+    let mut textarea = TextArea {
+        single_line: true,
+        ..Default::default()
+    };
+
+    textarea.clipboard.set_text("foo\nbar\r\nbaz");
+    textarea.input(Input {
+        key: Key::Char('v'),
+        ctrl: true,
+        ..Default::default()
+    });
+    assert_eq!(textarea.lines, ["foo bar baz"]);
+
+    textarea.clipboard.set_line("qux");
+    textarea.input(Input {
+        key: Key::Char('v'),
+        ctrl: true,
+        ..Default::default()
+    });
+    assert_eq!(textarea.lines, ["foo bar bazqux"]);
+
+    let cursor = textarea.cursor();
+    textarea.do_action(HistoryAction::InsertLinebreak {
+        position: BytePosition { row: 0, col: 3 },
+        cursor: (cursor, CursorPosition { row: 1, col: 0 }),
+    });
+    assert_eq!(textarea.lines, ["foo bar bazqux"]);
+}
+
+#[test]
+fn select_match() {
+    let mut textarea = TextArea {
+        lines: (0..30).map(|i| format!("line {i}")).collect(),
+        line_numbers: false,
+        ..Default::default()
+    };
+    textarea.set_search_pattern("line 1[05]").unwrap();
+    render(&textarea, 10, 9);
+
+    // just below the view, so it is scrolled up to a third of the height
+    textarea.set_cursor(CursorPosition { row: 5, col: 0 }, false);
+    textarea.select_match(textarea.search_forward().unwrap());
+    assert_eq!(textarea.cursor(), CursorPosition { row: 10, col: 0 });
+    assert_eq!(textarea.selection(), Some(CursorPosition { row: 10, col: 7 }));
+    render(&textarea, 10, 9);
+    assert_eq!(textarea.view.position.get().row, 7);
+
+    // already visible, so the view stays
+    textarea.select_match(textarea.search_forward().unwrap());
+    assert_eq!(textarea.cursor(), CursorPosition { row: 15, col: 0 });
+    render(&textarea, 10, 9);
+    assert_eq!(textarea.view.position.get().row, 7);
+
+    // backward jumps also put the cursor at the start of the match
+    textarea.set_cursor(CursorPosition { row: 29, col: 0 }, false);
+    render(&textarea, 10, 9);
+    textarea.select_match(textarea.search_backward().unwrap());
+    assert_eq!(textarea.cursor(), CursorPosition { row: 15, col: 0 });
+    assert_eq!(textarea.selection(), Some(CursorPosition { row: 15, col: 7 }));
+    render(&textarea, 10, 9);
+    assert_eq!(textarea.view.position.get().row, 12);
+}
+
+#[test]
+fn replace_all() {
+    let lines = ["foo bar", "baz", "bar bar"].map(String::from);
+    let mut textarea = TextArea {
+        lines: lines.to_vec().into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 2, col: 7 }, false);
+
+    assert_eq!(textarea.replace_all(&Regex::new("ba(r)").unwrap(), "[$1]", 0), 3);
+    assert_eq!(textarea.lines, ["foo [r]", "baz", "[r] [r]"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 2, col: 7 });
+
+    textarea.undo_action();
+    assert_eq!(textarea.lines, lines);
+    assert_eq!(textarea.replace_all(&Regex::new("x*").unwrap(), "y", 0), 0);
+    assert_eq!(textarea.replace_all(&Regex::new("ba").unwrap(), "BA", 1), 3);
+    assert_eq!(textarea.lines, ["foo BAr", "BAz", "BAr bar"]);
+}
+
+#[test]
+fn count_matches() {
+    let mut textarea = TextArea {
+        lines: ["foo foo_bar", "bar", "foo(foo)"].map(String::from).into(),
+        ..Default::default()
+    };
+    assert_eq!(textarea.word_at_cursor(), Some("foo"));
+    textarea.set_cursor(CursorPosition { row: 0, col: 7 }, false);
+    assert_eq!(textarea.word_at_cursor(), Some("foo_bar"));
+    // right after a word still counts
+    textarea.set_cursor(CursorPosition { row: 2, col: 3 }, false);
+    assert_eq!(textarea.word_at_cursor(), Some("foo"));
+    textarea.set_cursor(CursorPosition { row: 0, col: 3 }, false);
+    assert_eq!(textarea.word_at_cursor(), Some("foo"));
+    textarea.lines[1] = " ".to_string();
+    textarea.set_cursor(CursorPosition { row: 1, col: 1 }, false);
+    assert_eq!(textarea.word_at_cursor(), None);
+
+    assert_eq!(textarea.count_matches(&Regex::new("foo").unwrap()), (4, vec![0, 2]));
+
+    // empty matches are left out, as replacing leaves them alone
+    let pattern = Regex::new("a*").unwrap();
+    assert_eq!(textarea.count_matches(&pattern), (1, vec![0]));
+    textarea.set_search_pattern("a*").unwrap();
+    assert_eq!(textarea.count_matches(&pattern), (1, vec![0]));
+    assert_eq!(textarea.replace_all(&pattern, "A", 0), 1);
+    assert_eq!(textarea.lines, ["foo foo_bAr", " ", "foo(foo)"]);
+}
+
+#[test]
+fn reorder_lines() {
+    let lines = ["a", "b", "b", "c", "d"].map(String::from);
+    let mut textarea = TextArea {
+        lines: lines.to_vec().into(),
+        ..Default::default()
+    };
+
+    // partly selected lines count whole, the line the selection ends at the start of doesn't
+    textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    textarea.set_cursor(CursorPosition { row: 3, col: 0 }, true);
+    assert_eq!(textarea.reorder_lines(LineOrder::Reverse), 0);
+    assert_eq!(textarea.lines, ["b", "b", "a", "c", "d"]);
+    assert_eq!(
+        textarea.selection_range(),
+        Some((CursorPosition { row: 0, col: 0 }, CursorPosition { row: 2, col: 1 }))
+    );
+
+    assert_eq!(textarea.reorder_lines(LineOrder::DedupAll), 1);
+    assert_eq!(textarea.lines, ["b", "a", "c", "d"]);
+    assert_eq!(
+        textarea.selection_range(),
+        Some((CursorPosition { row: 0, col: 0 }, CursorPosition { row: 1, col: 1 }))
+    );
+
+    // without a selection, all lines
+    textarea.set_selection(None);
+    textarea.reorder_lines(LineOrder::Reverse);
+    assert_eq!(textarea.lines, ["d", "c", "a", "b"]);
+
+    // each one step to undo
+    textarea.undo_action();
+    textarea.undo_action();
+    textarea.undo_action();
+    assert_eq!(textarea.lines, lines);
+}
+
+#[test]
+fn align() {
+    let lines = ["a = 1", "\tbb = 2", "no equals", "ccc += 3"].map(String::from);
+    let mut textarea = TextArea {
+        lines: lines.to_vec().into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 3, col: 8 }, false);
+    textarea.set_cursor(CursorPosition { row: 0, col: 4 }, true);
+
+    // the tab counts as wide as an indent
+    assert_eq!(textarea.align("="), 2);
+    assert_eq!(textarea.lines, ["a      = 1", "\tbb = 2", "no equals", "ccc +  = 3"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 9 });
+    assert_eq!(textarea.selection(), Some(CursorPosition { row: 3, col: 10 }));
+
+    // already aligned
+    assert_eq!(textarea.align("="), 0);
+
+    textarea.undo_action();
+    assert_eq!(textarea.lines, lines);
+}
+
+#[test]
+fn format_json() {
+    let mut textarea = TextArea {
+        lines: vec![r#"let x = {"a":[1,{"b":null}]};"#.to_string()].into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 0, col: 8 }, false);
+    textarea.set_cursor(CursorPosition { row: 0, col: 28 }, true);
+    textarea.indent = Indent::Tabs;
+
+    textarea.format_json(true).unwrap();
+    assert_eq!(
+        textarea.lines,
+        [
+            "let x = {",
+            "\t\"a\": [",
+            "\t\t1,",
+            "\t\t{",
+            "\t\t\t\"b\": null",
+            "\t\t}",
+            "\t]",
+            "};"
+        ]
+    );
+    assert_eq!(
+        textarea.selection_range(),
+        Some((CursorPosition { row: 0, col: 8 }, CursorPosition { row: 7, col: 1 }))
+    );
+
+    textarea.format_json(false).unwrap();
+    assert_eq!(textarea.lines, [r#"let x = {"a":[1,{"b":null}]};"#]);
+    textarea.undo_action();
+    assert_eq!(textarea.lines.len(), 8);
+
+    // without a selection the whole text, which isn't JSON here
+    textarea.set_selection(None);
+    let err = textarea.format_json(false).unwrap_err();
+    assert!(err.starts_with("Invalid JSON: expected value"), "{err}");
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 0 });
+    assert_eq!(textarea.lines.len(), 8);
+
+    // errors on later lines of the selection
+    textarea.set_cursor(CursorPosition { row: 0, col: 8 }, false);
+    textarea.set_cursor(CursorPosition { row: 4, col: 6 }, true);
+    textarea.format_json(true).unwrap_err();
+    assert_eq!(textarea.cursor().row, 4);
+}
+
+#[test]
+fn spell_check() {
+    let dictionary = Dictionary::new(["the", "brawn", "brown", "fox"].map(String::from));
+    let mut textarea = TextArea {
+        lines: ["the\tbrwn fox", "teh brown fox"].map(String::from).into(),
+        line_numbers: false,
+        dictionary: Some(Rc::new(Dictionary::new(["the", "fox", "brown"].map(String::from)))),
+        ..Default::default()
+    };
+
+    // after the tab expanded to 4 columns
+    let buf = render(&textarea, 16, 2);
+    let underlined = |row| {
+        (0..16)
+            .filter(|&x| buf[(x, row)].modifier.contains(Modifier::UNDERLINED))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(underlined(0), [7, 8, 9, 10]);
+    assert_eq!(underlined(1), [0, 1, 2]);
+
+    assert!(textarea.next_misspelling(&dictionary));
+    assert_eq!(
+        textarea.selection_range().map(|(start, end)| (start.col, end.col)),
+        Some((4, 8))
+    );
+    assert_eq!(textarea.cycle_suggestion(&dictionary), Ok(("brawn".to_string(), 1, 2)));
+    assert_eq!(textarea.cycle_suggestion(&dictionary), Ok(("brown".to_string(), 2, 2)));
+    assert_eq!(textarea.cycle_suggestion(&dictionary), Ok(("brwn".to_string(), 0, 2)));
+    assert_eq!(textarea.cycle_suggestion(&dictionary), Ok(("brawn".to_string(), 1, 2)));
+    assert_eq!(textarea.lines[0], "the\tbrawn fox");
+    // all of the cycling is one undo step
+    textarea.input(Input {
+        key: Key::Char('z'),
+        ctrl: true,
+        ..Default::default()
+    });
+    assert_eq!(textarea.lines[0], "the\tbrwn fox");
+
+    // wraps around to the start
+    textarea.set_cursor(CursorPosition { row: 1, col: 5 }, false);
+    assert!(textarea.next_misspelling(&dictionary));
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 4 });
+    assert_eq!(
+        textarea.cycle_suggestion(&Dictionary::default()),
+        Err("No suggestions for brwn".to_string())
+    );
+}
+
+#[test]
+fn surround() {
+    let mut textarea = TextArea {
+        lines: ["let x = f(a, 'b');", "  one", "  two"].map(String::from).into(),
+        ..Default::default()
+    };
+    let undo = |textarea: &mut TextArea| {
+        textarea.input(Input {
+            key: Key::Char('z'),
+            ctrl: true,
+            ..Default::default()
+        })
+    };
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 4 }, false);
+    textarea.set_cursor(CursorPosition { row: 0, col: 5 }, true);
+    assert!(!textarea.surround(&Delimiters::parse("")));
+    assert!(textarea.undo_history.is_empty());
+    assert!(textarea.surround(&Delimiters::parse("**")));
+    assert_eq!(textarea.lines[0], "let **x** = f(a, 'b');");
+    assert_eq!(textarea.selected_text_single_line(), Some("x"));
+    undo(&mut textarea);
+    assert_eq!(textarea.lines[0], "let x = f(a, 'b');");
+
+    textarea.set_cursor(CursorPosition { row: 1, col: 2 }, false);
+    textarea.set_cursor(CursorPosition { row: 2, col: 5 }, true);
+    assert!(textarea.surround(&Delimiters::parse("<ul class=\"x\">")));
+    assert_eq!(textarea.lines[1..], ["  <ul class=\"x\">", "  one", "  two", "  </ul>"]);
+    assert_eq!(textarea.cursor(), CursorPosition { row: 3, col: 5 });
+    undo(&mut textarea);
+    assert_eq!(textarea.lines[1..], ["  one", "  two"]);
+
+    // the innermost pair, quotes before brackets
+    textarea.set_cursor(CursorPosition { row: 0, col: 15 }, false);
+    assert_eq!(textarea.change_surrounding(&Delimiters::parse("\"")), Ok(()));
+    assert_eq!(textarea.lines[0], "let x = f(a, \"b\");");
+    textarea.set_cursor(CursorPosition { row: 0, col: 12 }, false);
+    assert_eq!(textarea.change_surrounding(&Delimiters::parse("[")), Ok(()));
+    assert_eq!(textarea.lines[0], "let x = f[a, \"b\"];");
+    assert_eq!(textarea.delete_surrounding(), Ok(()));
+    assert_eq!(textarea.lines[0], "let x = fa, \"b\";");
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 11 });
+    undo(&mut textarea);
+    assert_eq!(textarea.lines[0], "let x = f[a, \"b\"];");
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 2 }, false);
+    assert_eq!(textarea.delete_surrounding(), Err(NO_SURROUNDING_PAIR));
+}
+
+#[test]
+fn byte_offset() {
+    let mut textarea = TextArea {
+        lines: ["ab", "çé x", "", "z"].map(String::from).into(),
+        ..Default::default()
+    };
+    let offset_at = |textarea: &mut TextArea, row, col| {
+        textarea.set_cursor(CursorPosition { row, col }, false);
+        textarea.byte_offset()
+    };
+    assert_eq!(offset_at(&mut textarea, 0, 0), 0);
+    assert_eq!(offset_at(&mut textarea, 1, 2), 7);
+    assert_eq!(offset_at(&mut textarea, 3, 1), 12);
+
+    let mut cursor_at = |offset| {
+        textarea.goto_byte_offset(offset);
+        let CursorPosition { row, col } = textarea.cursor();
+        (row, col)
+    };
+    assert_eq!(cursor_at(2), (0, 2));
+    assert_eq!(cursor_at(3), (1, 0));
+    // inside é
+    assert_eq!(cursor_at(6), (1, 1));
+    assert_eq!(cursor_at(10), (2, 0));
+    assert_eq!(cursor_at(100), (3, 1));
+
+    // edits invalidate the cached offsets
+    textarea.insert_str(CursorPosition { row: 0, col: 0 }, "xyz", false);
+    assert_eq!(offset_at(&mut textarea, 1, 0), 6);
+}
+
+#[test]
+fn paste_over_selection_round_trip() {
+    let ctrl = |char| Input {
+        key: Key::Char(char),
+        ctrl: true,
+        ..Default::default()
+    };
+    let at = |row, col| CursorPosition { row, col };
+
+    // (lines, selection, cursor, pasted, lines after, cursor after)
+    let cases: [(&[&str], _, _, &str, &[&str], _); 4] = [
+        (&["äbcdé"], at(0, 1), at(0, 4), "xÿz", &["äxÿzé"], at(0, 4)),
+        (&["äbcdé"], at(0, 4), at(0, 1), "1\nü2", &["ä1", "ü2é"], at(1, 2)),
+        (&["äb", "cd", "éf"], at(0, 1), at(2, 1), "ÿ", &["äÿf"], at(0, 2)),
+        (
+            &["äb", "cd", "éf"],
+            at(2, 1),
+            at(0, 1),
+            "ü\nÿ\n",
+            &["äü", "ÿ", "f"],
+            at(2, 0),
+        ),
+    ];
+    for (lines, selection, cursor, pasted, pasted_lines, pasted_cursor) in cases {
+        let mut textarea = TextArea {
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+            ..Default::default()
+        };
+        textarea.set_cursor(selection, false);
+        textarea.set_cursor(cursor, true);
+        textarea.clipboard.set_text(pasted);
+
+        textarea.input(ctrl('v'));
+        assert_eq!(textarea.lines, pasted_lines);
+        assert_eq!(textarea.cursor(), pasted_cursor);
+
+        // the first undo takes back the whole paste, the second has nothing left
+        for _ in 0..2 {
+            textarea.input(ctrl('z'));
+            assert_eq!(textarea.lines, lines);
+            assert_eq!(textarea.cursor(), cursor);
+        }
+        for _ in 0..2 {
+            textarea.input(ctrl('y'));
+            assert_eq!(textarea.lines, pasted_lines);
+            assert_eq!(textarea.cursor(), pasted_cursor);
+        }
+    }
+}
+
+#[test]
+fn non_ascii_columns() {
+    let input = |key, ctrl, shift| Input { key, ctrl, alt: false, shift };
+    let at = |row, col| CursorPosition { row, col };
+    let mut textarea = TextArea {
+        lines: ["日本語のテキスト", "éa", "ascii only here", "ü"]
+            .map(String::from)
+            .into(),
+        ..Default::default()
+    };
+
+    // Ctrl+A ends after the last char, not the last byte
+    textarea.input(input(Key::Char('a'), true, false));
+    assert_eq!(textarea.cursor(), at(3, 1));
+    assert_eq!(textarea.selection(), Some(at(0, 0)));
+
+    textarea.set_cursor(at(0, 0), false);
+    textarea.input(input(Key::End, false, false));
+    assert_eq!(textarea.cursor(), at(0, 8));
+    // down onto a line with fewer chars but more bytes, and on to a longer one
+    textarea.input(input(Key::Down, false, false));
+    assert_eq!(textarea.cursor(), at(1, 2));
+    textarea.input(input(Key::Up, false, false));
+    assert_eq!(textarea.cursor(), at(0, 2));
+    textarea.input(input(Key::Down, false, true));
+    textarea.input(input(Key::Down, false, true));
+    assert_eq!(textarea.cursor(), at(2, 2));
+    textarea.input(input(Key::Down, false, true));
+    assert_eq!(textarea.cursor(), at(3, 1));
+    textarea.input(input(Key::Up, true, false));
+    assert_eq!(textarea.cursor(), at(0, 1));
+
+    // Right at the end of a line moves on to the next one, Ctrl+Right stops at the end first
+    textarea.set_cursor(at(1, 2), false);
+    textarea.input(input(Key::Right, false, false));
+    assert_eq!(textarea.cursor(), at(2, 0));
+    textarea.set_cursor(at(0, 5), false);
+    textarea.input(input(Key::Right, true, false));
+    assert_eq!(textarea.cursor(), at(0, 8));
+    textarea.input(input(Key::Left, false, false));
+    textarea.input(input(Key::Left, true, false));
+    assert_eq!(textarea.cursor(), at(0, 0));
+    textarea.set_cursor(at(1, 0), false);
+    textarea.input(input(Key::Left, false, false));
+    assert_eq!(textarea.cursor(), at(0, 8));
+
+    // deleting a selection ending at a line end takes exactly the selected chars
+    textarea.set_cursor(at(0, 3), false);
+    textarea.set_cursor(at(1, 2), true);
+    textarea.input(input(Key::Char('x'), false, false));
+    assert_eq!(textarea.lines, ["日本語x", "ascii only here", "ü"]);
+    assert_eq!(textarea.cursor(), at(0, 4));
+    textarea.input(input(Key::Delete, true, false));
+    assert_eq!(textarea.lines, ["日本語xascii only here", "ü"]);
+}
+
+#[test]
+fn page_moves_and_tiny_views() {
+    let at = |row, col| CursorPosition { row, col };
+    let page = |key| Input { key, ..Default::default() };
+    let mut textarea = TextArea {
+        lines: (0..30).map(|n| format!("line {n}")).collect(),
+        ..Default::default()
+    };
+
+    // nothing to show is still no reason to panic
+    for (width, height) in [(0, 0), (0, 1), (1, 0), (1, 1), (3, 2)] {
+        textarea.set_cursor(at(20, 4), false);
+        textarea.set_view_row(0);
+        let (top_left, _) = textarea.update_size(width, height);
+        assert_eq!(top_left.row, 20 + 1 - cmp::max(height, 1));
+        render(&textarea, width as u16, height as u16);
+        textarea.input(page(Key::PageUp));
+        assert_eq!(textarea.cursor(), at(19, 4));
+        textarea.input(page(Key::PageDown));
+        assert_eq!(textarea.cursor(), at(20, 4));
+    }
+
+    // a page keeps one line of overlap and the cursor on the same screen row
+    textarea.set_cursor(at(3, 2), false);
+    textarea.set_view_row(0);
+    textarea.update_size(20, 10);
+    textarea.input(page(Key::PageDown));
+    assert_eq!(textarea.cursor(), at(11, 2));
+    assert_eq!(textarea.update_size(20, 10).0.row, 8);
+    textarea.input(page(Key::PageDown));
+    assert_eq!(textarea.cursor(), at(19, 2));
+    assert_eq!(textarea.update_size(20, 10).0.row, 16);
+    // the last page doesn't scroll past the end of the text
+    textarea.input(page(Key::PageDown));
+    assert_eq!(textarea.cursor(), at(27, 2));
+    assert_eq!(textarea.update_size(20, 10).0.row, 20);
+    textarea.input(page(Key::PageDown));
+    assert_eq!(textarea.cursor(), at(29, 2));
+    textarea.input(page(Key::PageUp));
+    assert_eq!(textarea.cursor(), at(21, 2));
+    assert_eq!(textarea.update_size(20, 10).0.row, 12);
+    textarea.input(page(Key::PageUp));
+    textarea.input(page(Key::PageUp));
+    textarea.input(page(Key::PageUp));
+    assert_eq!(textarea.cursor(), at(0, 2));
+    assert_eq!(textarea.update_size(20, 10).0.row, 0);
+}
+
+#[test]
+fn render_long_lines() {
+    let mut textarea = TextArea {
+        lines: vec![
+            format!("a{}", " ".repeat(300)),
+            format!("b{}", "\t".repeat(300)),
+            "c".repeat(100_000),
+        ]
+        .into(),
+        line_numbers: true,
+        ..Default::default()
+    };
+    let buf = render(&textarea, 20, 4);
+    let row = |y| (0..20).map(|x| buf[(x, y)].symbol()).collect::<String>();
+    assert_eq!(row(0), format!("0 a{}", "·".repeat(17)));
+    assert_eq!(row(1), format!("1 b{}", "·".repeat(17)));
+    assert_eq!(dots(300).chars().count(), 300);
+    assert_eq!(spaces(300).len(), 300);
+
+    // trailing whitespace with the view scrolled into it, the cursor in the last column just right of the text
+    textarea.set_cursor(CursorPosition { row: 1, col: 301 }, false);
+    let buf = render(&textarea, 20, 4);
+    assert_eq!(
+        (0..20).map(|x| buf[(x, 1)].symbol()).collect::<String>(),
+        format!("1 {} ", "·".repeat(17))
+    );
+    assert_eq!(textarea.terminal_cursor_position(), Position::new(19, 1));
+
+    textarea.set_cursor(CursorPosition { row: 2, col: 100_000 }, false);
+    for (width, height) in [(u16::MAX, 3), (1, 1), (0, 0)] {
+        render(&textarea, width, height);
+        let position = textarea.terminal_cursor_position();
+        assert!(position.x <= width && position.y <= height.saturating_sub(1));
+    }
+}
+
+#[test]
+fn search_around_cursor() {
+    let mut textarea = TextArea {
+        lines: vec!["äb äb".to_string(), "xäbäb".to_string()].into(),
+        ..Default::default()
+    };
+    textarea.set_search_pattern("äb").unwrap();
+    let found = |row, col| (CursorPosition { row, col }, CursorPosition { row, col: col + 2 });
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 0 }, false);
+    assert_eq!(textarea.search_forward(), Some(found(0, 3)));
+    textarea.set_cursor(CursorPosition { row: 0, col: 3 }, false);
+    assert_eq!(textarea.search_forward(), Some(found(1, 1)));
+    textarea.set_cursor(CursorPosition { row: 1, col: 1 }, false);
+    assert_eq!(textarea.search_forward(), Some(found(1, 3)));
+    textarea.set_cursor(CursorPosition { row: 1, col: 3 }, false);
+    assert_eq!(textarea.search_forward(), None);
+
+    // backwards, a match just left of the cursor is found, one under it isn't
+    assert_eq!(textarea.search_backward(), Some(found(1, 1)));
+    textarea.set_cursor(CursorPosition { row: 1, col: 2 }, false);
+    assert_eq!(textarea.search_backward(), Some(found(1, 1)));
+    textarea.set_cursor(CursorPosition { row: 1, col: 1 }, false);
+    assert_eq!(textarea.search_backward(), Some(found(0, 3)));
+    textarea.set_cursor(CursorPosition { row: 0, col: 0 }, false);
+    assert_eq!(textarea.search_backward(), None);
+
+    // the line isn't cut at the cursor, so matches may run past it
+    textarea.set_search_pattern("b äb$").unwrap();
+    textarea.set_cursor(CursorPosition { row: 0, col: 2 }, false);
+    assert_eq!(
+        textarea.search_backward(),
+        Some((CursorPosition { row: 0, col: 1 }, CursorPosition { row: 0, col: 5 }))
+    );
+}
+
+#[test]
+fn cursor_clamped_to_text() {
+    let key = |key, ctrl| Input {
+        key,
+        ctrl,
+        ..Default::default()
+    };
+    let mut textarea = TextArea {
+        lines: vec!["one".to_string(), "two".to_string(), "three".to_string()].into(),
+        ..Default::default()
+    };
+
+    textarea.set_cursor(CursorPosition { row: 0, col: 1 }, false);
+    textarea.set_cursor(CursorPosition { row: 2, col: 4 }, true);
+    textarea.input(key(Key::Backspace, false));
+    assert_eq!(textarea.lines, ["oe"]);
+    textarea.input(key(Key::Char('z'), true));
+    textarea.input(key(Key::Char('y'), true));
+    textarea.input(key(Key::End, false));
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 2 });
+
+    // undone straight through the history, the cursor is left where the lines were
+    textarea.undo_action();
+    textarea.set_cursor(CursorPosition { row: 2, col: 5 }, false);
+    textarea.set_selection(Some(CursorPosition { row: 1, col: 2 }));
+    textarea.redo_action();
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 2 });
+    assert_eq!(textarea.selection(), Some(CursorPosition { row: 0, col: 2 }));
+    textarea.input(key(Key::End, false));
+    textarea.input(key(Key::Down, false));
+    assert_eq!(textarea.cursor(), CursorPosition { row: 0, col: 2 });
+}
+
+#[test]
+#[should_panic = "is outside the text"]
+#[cfg(debug_assertions)]
+fn cursor_outside_text() {
+    let mut textarea = TextArea {
+        lines: vec!["oe".to_string()].into(),
+        ..Default::default()
+    };
+    textarea.set_cursor(CursorPosition { row: 7, col: 9 }, false);
+}
+
+#[test]
+#[should_panic = "is outside the text"]
+#[cfg(debug_assertions)]
+fn selection_outside_text() {
+    let mut textarea = TextArea {
+        lines: vec!["oe".to_string()].into(),
+        ..Default::default()
+    };
+    textarea.set_selection(Some(CursorPosition { row: 0, col: 3 }));
+}
+
+#[test]
+#[ignore = "benchmark, run with cargo test --release -- --ignored --nocapture long_line"]
+fn long_line_benchmark() {
+    // about 1 MB on one line
+    let mut textarea = TextArea {
+        lines: vec!["let x = \tfoo(bar); ".repeat(1 << 16)].into(),
+        ..Default::default()
+    };
+    let len = textarea.lines[0].chars().count();
+    let left = Input {
+        key: Key::Left,
+        ..Default::default()
+    };
+
+    for (name, col) in [("start", 200), ("middle", len / 2), ("end", len)] {
+        textarea.set_cursor(CursorPosition { row: 0, col }, false);
+        render(&textarea, 120, 40);
+        let start = std::time::Instant::now();
+        for _ in 0..100 {
+            textarea.input(left.clone());
+            render(&textarea, 120, 40);
+            textarea.terminal_cursor_position();
+        }
+        println!("cursor near the {name}: {:?} per move", start.elapsed() / 100);
+    }
+}
+
+#[test]
+fn undo_centers_and_flashes() {
+    let key = |key, ctrl| Input {
+        key,
+        ctrl,
+        ..Default::default()
+    };
+    let mut textarea = TextArea {
+        lines: (0..100).map(|i| format!("line {i}")).collect(),
+        ..Default::default()
+    };
+    let flashed = |buf: &Buffer, y| buf[(4, y)].bg == Color::Yellow;
+
+    textarea.goto(10, 0);
+    textarea.input(key(Key::Char('x'), false));
+    textarea.goto(90, 0);
+    render(&textarea, 20, 10);
+
+    // far away, the change is brought to the middle of the view
+    textarea.input(key(Key::Char('z'), true));
+    assert_eq!(textarea.lines[10], "line 10");
+    let buf = render(&textarea, 20, 10);
+    assert_eq!(textarea.view.position.get().row, 5);
+    assert!(flashed(&buf, 5) && !flashed(&buf, 4) && !flashed(&buf, 6));
+
+    // the next key clears the highlight
+    textarea.take_redraw();
+    textarea.input(key(Key::Right, false));
+    assert!(!flashed(&render(&textarea, 20, 10), 5));
+    assert!(!textarea.clear_flash());
+
+    // already in view, the view stays put
+    textarea.input(key(Key::Char('y'), true));
+    let buf = render(&textarea, 20, 10);
+    assert_eq!(textarea.view.position.get().row, 5);
+    assert!(flashed(&buf, 5));
+    assert!(textarea.clear_flash() && textarea.take_redraw());
+    assert!(!flashed(&render(&textarea, 20, 10), 5));
+}
+
+#[test]
+fn undo_steps() {
+    let mut textarea = TextArea::default();
+    let type_char = |textarea: &mut TextArea, char| {
+        textarea.input(Input {
+            key: Key::Char(char),
+            ..Default::default()
+        })
+    };
+    assert_eq!(textarea.undo(), None);
+
+    for char in ['a', 'b', 'c'] {
+        type_char(&mut textarea, char);
+    }
+    textarea.chain_undo_since(0);
+    type_char(&mut textarea, 'x');
+    type_char(&mut textarea, 'y');
+    assert_eq!(steps(textarea.undo_history.iter()), 3);
+
+    assert_eq!(textarea.undo(), Some(UndoStep { actions: 1, remaining: 2 }));
+    assert_eq!(textarea.undo(), Some(UndoStep { actions: 1, remaining: 1 }));
+    assert_eq!(textarea.undo(), Some(UndoStep { actions: 3, remaining: 0 }));
+    assert_eq!(textarea.lines, [""]);
+    assert_eq!(textarea.undo(), None);
+
+    assert_eq!(textarea.redo(), Some(UndoStep { actions: 3, remaining: 2 }));
+    assert_eq!(textarea.lines, ["abc"]);
+    assert_eq!(textarea.redo(), Some(UndoStep { actions: 1, remaining: 1 }));
+    assert_eq!(textarea.redo(), Some(UndoStep { actions: 1, remaining: 0 }));
+    assert_eq!(textarea.redo(), None);
+}
+
+#[test]
+fn undo_after_text_replaced() {
+    let mut textarea = TextArea::default();
+    for char in ['a', 'b'] {
+        textarea.input(Input {
+            key: Key::Char(char),
+            ..Default::default()
+        });
+    }
+
+    // the history is for the old text, so it is dropped rather than done to the new one
+    textarea.set_lines(vec!["xyz".to_string()]);
+    assert_eq!(textarea.undo(), None);
+    assert_eq!(textarea.lines, ["xyz"]);
+    assert!(textarea.undo_history.is_empty());
+}
+
+#[test]
+fn chain_undo_step() {
+    let mut textarea = TextArea {
+        lines: vec!["ab".to_string()].into(),
+        ..Default::default()
+    };
+    let at = |row, col| CursorPosition { row, col };
+    let insert = |char, col| HistoryAction::InsertChar {
+        char,
+        position: BytePosition { row: 0, col },
+        cursor: (at(0, col), at(0, col + 1)),
+    };
+    let type_twice = |textarea: &mut TextArea| -> Option<()> {
+        let mut textarea = textarea.begin_chain();
+        textarea.do_action(insert('x', 0));
+        textarea.set_cursor(at(0, 1), false);
+        // leaving early still ends the step
+        textarea.lines.get(5)?;
+        textarea.do_action(insert('y', 1));
+        Some(())
+    };
+
+    textarea.set_cursor(at(0, 2), false);
+    textarea.set_cursor(at(0, 0), true);
+    type_twice(&mut textarea);
+    textarea.input(Input {
+        key: Key::Char('z'),
+        ..Default::default()
+    });
+    assert_eq!(textarea.lines, ["xzab"]);
+    assert_eq!(steps(textarea.undo_history.iter()), 2);
+
+    textarea.undo();
+    textarea.undo();
+    assert_eq!(textarea.lines, ["ab"]);
+    assert_eq!((textarea.cursor(), textarea.selection()), (at(0, 0), Some(at(0, 2))));
+    textarea.redo();
+    assert_eq!(textarea.lines, ["xab"]);
+    assert_eq!((textarea.cursor(), textarea.selection()), (at(0, 1), None));
+}
+
+#[test]
+fn transactions() {
+    let mut textarea = TextArea {
+        lines: vec!["ab".to_string(), "cd".to_string()].into(),
+        ..Default::default()
+    };
+    let at = |row, col| CursorPosition { row, col };
+    let type_char = |textarea: &mut TextArea, char| {
+        textarea.input(Input {
+            key: Key::Char(char),
+            ..Default::default()
+        })
+    };
+    type_char(&mut textarea, 'x');
+    type_char(&mut textarea, 'y');
+    textarea.undo();
+    textarea.set_cursor(at(1, 2), false);
+    textarea.set_cursor(at(1, 0), true);
+    let version = textarea.version();
+
+    // a failure after several changes takes all of them back, and leaves the history alone
+    let failed = textarea.transaction(|textarea| {
+        type_char(textarea, '1');
+        textarea.input(Input {
+            key: Key::Enter,
+            ..Default::default()
+        });
+        textarea.do_action(HistoryAction::SwapLines {
+            lines: (0, 2),
+            cursor: (at(0, 0), at(0, 0)),
+        });
+        Err::<(), _>("failed")
+    });
+    assert_eq!(failed, Err("failed"));
+    assert_eq!(textarea.lines, ["xab", "cd"]);
+    assert_eq!((textarea.cursor(), textarea.selection()), (at(1, 0), Some(at(1, 2))));
+    assert_eq!((textarea.undo_history.len(), textarea.redo_history.len()), (1, 1));
+    assert!(textarea.version() > version);
+
+    // one that succeeds is a single undo step
+    let done = textarea.transaction(|textarea| {
+        type_char(textarea, '1');
+        type_char(textarea, '2');
+        Ok::<_, ()>(textarea.lines[1].clone())
+    });
+    assert_eq!(done.as_deref(), Ok("12"));
+    assert_eq!(textarea.lines, ["xab", "12"]);
+    assert!(textarea.redo_history.is_empty());
+    textarea.undo();
+    assert_eq!(textarea.lines, ["xab", "cd"]);
+    assert_eq!((textarea.cursor(), textarea.selection()), (at(1, 0), Some(at(1, 2))));
+}
+
+#[test]
+fn render_highlights_with_tabs() {
+    let line = "\tab\t日\tc \t";
+    let mut textarea = TextArea {
+        lines: vec![line.to_string(); 2].into(),
+        line_numbers: false,
+        ..Default::default()
+    };
+    let tab_width = textarea.indent.spaces().len();
+    let chars = line.chars().count();
+    // the display columns each char starts at, worked out without anything the rendering uses
+    let mut starts = vec![0];
+    for c in line.chars() {
+        starts.push(starts.last().unwrap() + if c == '\t' { tab_width } else { c.width().unwrap() });
+    }
+
+    // checks the char at every column of `row` that is fully in view against its expected style
+    let check = |textarea: &TextArea, row: u16, highlighted: &dyn Fn(usize) -> bool, bg: Color| {
+        let buf = render(textarea, 8, 2);
+        let scroll = textarea.view.position.get().col;
+        for (idx, c) in line.chars().enumerate() {
+            let Some(x) = starts[idx]
+                .checked_sub(scroll)
+                .filter(|_| starts[idx + 1] - scroll <= 7)
+            else {
+                continue;
+            };
+            let cell = &buf[(x as u16, row)];
+            let symbol = match c {
+                '\t' if idx == chars - 1 => "·",
+                '\t' => " ",
+                ' ' => "·",
+                c => &c.to_string(),
+            };
+            assert_eq!(cell.symbol(), symbol, "{idx} scrolled by {scroll}");
+            assert_eq!(cell.bg == bg, highlighted(idx), "{idx} scrolled by {scroll}");
+        }
+        scroll
+    };
+
+    // selections starting and ending at every char, with the cursor scrolling the view along
+    let mut scrolls = std::collections::HashSet::new();
+    for anchor in 0..=chars {
+        for (cursor, back) in (0..=chars)
+            .map(|col| (col, false))
+            .chain((0..=chars).rev().map(|col| (col, true)))
+        {
+            textarea.set_cursor(CursorPosition { row: 0, col: anchor }, false);
+            textarea.set_cursor(CursorPosition { row: 1, col: cursor }, true);
+            let scroll = check(&textarea, 0, &|idx| idx >= anchor, Color::LightBlue);
+            check(&textarea, 1, &|idx| idx < cursor, Color::LightBlue);
+            scrolls.insert((scroll, back));
+        }
+    }
+    assert!(scrolls.len() > 4, "{scrolls:?}");
+
+    // matches are found in the whole line, so an anchored one stays put when scrolled
+    textarea.set_search_pattern(r"^\t|b\t|日").unwrap();
+    for col in (0..=chars).chain((0..=chars).rev()) {
+        textarea.set_cursor(CursorPosition { row: 0, col }, false);
+        check(&textarea, 0, &|idx| [0, 2, 3, 4].contains(&idx), Color::Magenta);
+    }
+}
+
+#[test]
+fn search_matches_cached() {
+    // wide lines and a pattern slow to match on them, though only ever linear
+    let mut textarea = TextArea {
+        lines: (0..60)
+            .map(|i| format!("{}end{i}", "word, \tand more ".repeat(150)))
+            .collect(),
+        ..Default::default()
+    };
+    textarea.set_search_pattern(r"(?:\w+\W+){1,10}end\d*5$").unwrap();
+    let down = Input {
+        key: Key::Down,
+        ..Default::default()
+    };
+
+    render(&textarea, 120, 20);
+    assert_eq!(textarea.search_matches.borrow().1.len(), 20);
+
+    // moving around the view doesn't search the lines again
+    for _ in 0..10 {
+        textarea.input(down.clone());
+        render(&textarea, 120, 20);
+    }
+    assert_eq!(textarea.search_matches.borrow().1.len(), 20);
+
+    // and neither do searching and counting what was already searched
+    assert_eq!(textarea.search_forward().map(|(start, _)| start.row), Some(15));
+    let (count, rows) = textarea.count_matches(&textarea.search_pattern.clone().unwrap());
+    assert_eq!((count, rows.len()), (6, 6));
+    assert_eq!(textarea.search_matches.borrow().1.len(), 60);
+
+    // gone with an edit or another pattern
+    textarea.input(Input {
+        key: Key::Char('x'),
+        ..Default::default()
+    });
+    assert_eq!(textarea.search_matches(5).len(), 1);
+    assert_eq!(textarea.search_matches.borrow().1.len(), 1);
+    textarea.set_search_pattern("word").unwrap();
+    assert!(textarea.search_matches.borrow().1.is_empty());
+    assert_eq!(textarea.search_matches(1).len(), 150);
+
+    // and with text set without an edit, whose lines can be shorter than the old matches
+    textarea.set_lines(vec!["x.txt".into(), "word".into()]);
+    textarea.set_cursor(CursorPosition::default(), false);
+    render(&textarea, 120, 20);
+    assert_eq!(textarea.search_forward().map(|(start, _)| start.row), Some(1));
+}
+
+#[test]
+fn render_theme() {
+    let mut textarea = TextArea {
+        lines: vec!["one two xx".into(), "two".into(), "  three".into()].into(),
+        rulers: vec![5],
+        indent_guides: true,
+        indent: Indent::from(2),
+        theme: Theme::HIGH_CONTRAST,
+        ..Default::default()
+    };
+    textarea.set_search_pattern("two").unwrap();
+    textarea.set_cursor(CursorPosition { row: 2, col: 4 }, false);
+    textarea.set_cursor(CursorPosition { row: 2, col: 7 }, true);
+    let buf = render(&textarea, 12, 3);
+    let bgs = |y| (0..12).map(|x| buf[(x, y)].bg).collect::<Vec<_>>();
+    let (select, found, ruler) = (Color::Blue, Color::Magenta, Color::DarkGray);
+    let (line, none) = (Color::Indexed(236), Color::Reset);
+
+    // matches, and text past the ruler, in the theme's colors
+    let row = [&[none; 6][..], &[found; 3], &[ruler; 3]].concat();
+    assert_eq!(bgs(0), row);
+    assert_eq!(bgs(1)[2..5], [found; 3]);
+    // the selection over the cursor line, which is marked to the right edge
+    let row = [&[none; 2][..], &[line; 4], &[select; 3], &[line; 3]].concat();
+    assert_eq!(bgs(2), row);
+    assert_eq!(buf[(2, 2)].symbol(), "│");
+    assert_eq!(buf[(2, 2)].fg, ruler);
+
+    // the line numbers, brighter for the cursor line
+    assert_eq!((buf[(0, 0)].fg, buf[(0, 2)].fg), (Color::White, Color::Yellow));
+    assert!(buf[(0, 0)].modifier.contains(Modifier::DIM));
+    assert!(!buf[(0, 2)].modifier.contains(Modifier::DIM));
+
+    textarea.theme = Theme::LIGHT;
+    let buf = render(&textarea, 12, 3);
+    assert_eq!(buf[(6, 0)].bg, Color::LightYellow);
+    assert_eq!(buf[(6, 2)].bg, Color::LightCyan);
+    assert_eq!(buf[(11, 2)].bg, Color::Reset);
+}
+
+#[test]
+fn render_wide_chars_scrolled() {
+    let lines = ["ab日本語cd", "日本x語ab漢字", "x日"];
+    let mut textarea = TextArea {
+        lines: lines.map(String::from).into(),
+        line_numbers: false,
+        ..Default::default()
+    };
+    // the chars each display column shows, worked out without anything the rendering uses
+    let columns = |line: &str| {
+        line.chars()
+            .flat_map(|c| match c.width().unwrap() {
+                2 => [Some(c), None].to_vec(),
+                _ => [Some(c)].to_vec(),
+            })
+            .collect::<Vec<_>>()
+    };
+
+    for (line_numbers, width) in [(false, 3), (false, 4), (false, 5), (true, 5), (true, 6)] {
+        textarea.line_numbers = line_numbers;
+        let gutter = textarea.gutter_width();
+        for (row, line) in lines.iter().enumerate() {
+            for col in 0..=line.chars().count() {
+                textarea.set_cursor(CursorPosition { row, col }, false);
+                let buf = render(&textarea, width, 3);
+                let scroll = textarea.view.position.get().col;
+                let cells = columns(line);
+                let y = u16::try_from(row).unwrap() - u16::try_from(textarea.view.position.get().row).unwrap();
+                let context = format!("{line} at {col} in {width} columns");
+
+                for x in gutter..width {
+                    let expected = match cells.get(scroll + usize::from(x - gutter)) {
+                        // a wide char shows in full or is left out for a space
+                        Some(Some(c)) if c.width() == Some(2) && x + 1 < width => c.to_string(),
+                        Some(Some(c)) if c.width() == Some(1) => c.to_string(),
+                        Some(None) if x > gutter => continue,
+                        _ => " ".to_string(),
+                    };
+                    assert_eq!(buf[(x, y)].symbol(), expected, "{x}: {context}");
+                }
+
+                // the cursor is on its char, all of which is in view
+                let position = textarea.terminal_cursor_position();
+                let cursor_col = line.display_col(col, 4);
+                assert_eq!(usize::from(position.x - gutter), cursor_col - scroll, "{context}");
+                if let Some(c) = line.chars().nth(col) {
+                    assert!(
+                        position.x + u16::try_from(c.width().unwrap()).unwrap() <= width,
+                        "{context}"
+                    );
+                    assert_eq!(buf[(position.x, y)].symbol(), c.to_string(), "{context}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn rejected_keys() {
+    let key = |key, ctrl, alt| Input {
+        key,
+        ctrl,
+        alt,
+        ..Default::default()
+    };
+    let mut textarea = TextArea {
+        lines: vec!["a".to_string(), "b".to_string()].into(),
+        ..Default::default()
+    };
+
+    // nothing to delete is told apart from a key that changes nothing
+    for (input, col) in [
+        (key(Key::Backspace, false, false), 0),
+        (key(Key::Backspace, true, false), 0),
+        (key(Key::Backspace, false, true), 0),
+        (key(Key::Delete, false, false), 1),
+        (key(Key::Delete, true, false), 1),
+    ] {
+        let row = col;
+        textarea.set_cursor(CursorPosition { row, col }, false);
+        assert!(!textarea.input(input.clone()), "{input:?}");
+        assert!(textarea.take_rejected(), "{input:?}");
+        assert!(!textarea.take_rejected());
+    }
+    textarea.set_cursor(CursorPosition { row: 0, col: 0 }, false);
+    assert!(!textarea.input(key(Key::Left, false, false)));
+    assert!(!textarea.take_rejected());
+    assert!(textarea.input(key(Key::Delete, false, false)));
+    assert!(!textarea.take_rejected());
+    assert_eq!(textarea.lines, ["", "b"]);
 }
@@ -1,11 +1,45 @@
+use std::ops::Range;
+
 pub trait Word {
     fn next_word(&self, start: usize) -> Option<usize>;
     fn previous_word(&self, start: usize) -> Option<usize>;
+    fn next_subword(&self, start: usize) -> Option<usize>;
+    fn previous_subword(&self, start: usize) -> Option<usize>;
+    fn word_count(&self) -> usize;
+    fn words(&self) -> Vec<Range<usize>>;
+}
+
+#[derive(PartialEq, Eq)]
+enum Class {
+    Whitespace,
+    Underscore,
+    Punctuation,
+    Upper,
+    Lower,
+    Digit,
+}
+
+impl From<char> for Class {
+    fn from(c: char) -> Self {
+        match c {
+            '_' => Class::Underscore,
+            c if c.is_whitespace() => Class::Whitespace,
+            c if c.is_uppercase() => Class::Upper,
+            c if c.is_numeric() => Class::Digit,
+            c if c.is_alphabetic() => Class::Lower,
+            _ => Class::Punctuation,
+        }
+    }
 }
 
 impl Word for str {
+    /// The char index of the end of the word or run of punctuation at or after `start`, a char index too.
     fn next_word(&self, start: usize) -> Option<usize> {
-        let mut iter = self.char_indices().skip(start).skip_while(|(_, c)| c.is_whitespace());
+        let mut iter = self
+            .chars()
+            .enumerate()
+            .skip(start)
+            .skip_while(|(_, c)| c.is_whitespace());
 
         if iter.next().is_some_and(|(_, c)| c.is_ascii_punctuation()) {
             iter.find_map(|(idx, c)| (!c.is_ascii_punctuation()).then_some(idx))
@@ -13,11 +47,14 @@ impl Word for str {
             iter.find_map(|(idx, c)| (c.is_ascii_punctuation() || c.is_ascii_whitespace()).then_some(idx))
         }
     }
+    /// The char index of the start of the word or run of punctuation before `start`, a char index too.
     fn previous_word(&self, start: usize) -> Option<usize> {
-        let mut iter = self
-            .char_indices()
+        let chars = self.chars().collect::<Vec<_>>();
+        let mut iter = chars[..start.min(chars.len())]
+            .iter()
+            .copied()
+            .enumerate()
             .rev()
-            .skip(self.len() - start)
             .skip_while(|(_, c)| c.is_whitespace());
 
         if iter.next().is_some_and(|(_, c)| c.is_ascii_punctuation()) {
@@ -26,6 +63,119 @@ impl Word for str {
             iter.find_map(|(idx, c)| (c.is_ascii_punctuation() || c.is_ascii_whitespace()).then_some(idx + 1))
         }
     }
+
+    /// Like [`Word::next_word`], but also stops at camelCase humps, underscores and digits. Works on char indices.
+    fn next_subword(&self, start: usize) -> Option<usize> {
+        let classes = self.chars().map(Class::from).collect::<Vec<_>>();
+        let class = |idx: usize| classes.get(idx);
+        let skip = |mut idx: usize, c: Class| {
+            while class(idx) == Some(&c) {
+                idx += 1;
+            }
+            idx
+        };
+
+        let mut idx = skip(start, Class::Whitespace);
+        if class(idx) == Some(&Class::Punctuation) {
+            idx = skip(idx, Class::Punctuation);
+        } else {
+            idx = skip(idx, Class::Underscore);
+            idx = match class(idx) {
+                Some(Class::Upper) if class(idx + 1) == Some(&Class::Lower) => skip(idx + 1, Class::Lower),
+                Some(Class::Upper) => {
+                    // in HTTPServer, the S already belongs to the next subword
+                    let end = skip(idx, Class::Upper);
+                    if end - idx > 1 && class(end) == Some(&Class::Lower) {
+                        end - 1
+                    } else {
+                        end
+                    }
+                }
+                Some(Class::Lower) => skip(idx, Class::Lower),
+                Some(Class::Digit) => skip(idx, Class::Digit),
+                _ => idx,
+            };
+        }
+
+        (idx < classes.len()).then_some(idx)
+    }
+
+    /// Like [`Word::previous_word`], but also stops at camelCase humps, underscores and digits. Works on char indices.
+    fn previous_subword(&self, start: usize) -> Option<usize> {
+        let classes = self.chars().map(Class::from).collect::<Vec<_>>();
+        let class = |idx: usize| idx.checked_sub(1).and_then(|idx| classes.get(idx));
+        let skip = |mut idx: usize, c: Class| {
+            while class(idx) == Some(&c) {
+                idx -= 1;
+            }
+            idx
+        };
+
+        let mut idx = skip(start.min(classes.len()), Class::Whitespace);
+        if class(idx) == Some(&Class::Punctuation) {
+            idx = skip(idx, Class::Punctuation);
+        } else {
+            idx = skip(idx, Class::Underscore);
+            idx = match class(idx) {
+                Some(Class::Lower) => {
+                    let idx = skip(idx, Class::Lower);
+                    if class(idx) == Some(&Class::Upper) {
+                        idx - 1
+                    } else {
+                        idx
+                    }
+                }
+                Some(Class::Upper) => skip(idx, Class::Upper),
+                Some(Class::Digit) => skip(idx, Class::Digit),
+                _ => idx,
+            };
+        }
+
+        (idx > 0).then_some(idx)
+    }
+
+    /// Number of words as [`Word::next_word`] sees them, not counting runs of punctuation.
+    fn word_count(&self) -> usize {
+        let is_word = |c: char| !c.is_whitespace() && !c.is_ascii_punctuation();
+        let mut prev = None;
+        self.chars()
+            .filter(|&c| {
+                let start = is_word(c) && !prev.is_some_and(is_word);
+                prev = Some(c);
+                start
+            })
+            .count()
+    }
+
+    /// Char ranges of the runs of alphanumeric characters and underscores, including apostrophes between letters like
+    /// in "don't".
+    fn words(&self) -> Vec<Range<usize>> {
+        let chars = self.chars().collect::<Vec<_>>();
+        let is_word = |idx: usize| {
+            let c = chars[idx];
+            c.is_alphanumeric()
+                || c == '_'
+                || matches!(c, '\'' | '’')
+                    && idx > 0
+                    && chars[idx - 1].is_alphabetic()
+                    && chars.get(idx + 1).is_some_and(|c| c.is_alphabetic())
+        };
+
+        let mut words = Vec::new();
+        let mut idx = 0;
+        while idx < chars.len() {
+            let start = idx;
+            while idx < chars.len() && is_word(idx) {
+                idx += 1;
+            }
+            if idx > start {
+                words.push(start..idx);
+            } else {
+                idx += 1;
+            }
+        }
+        words
+    }
 }
 
 #[test]
@@ -39,4 +189,51 @@ fn test() {
     assert_eq!("   a!bc ".previous_word(8), Some(5));
     assert_eq!("   bc!! ".previous_word(8), Some(5));
     assert_eq!("   !!   ".previous_word(8), Some(3));
+
+    assert_eq!("é日本 ü!x".next_word(0), Some(3));
+    assert_eq!("é日本 ü!x".next_word(3), Some(5));
+    assert_eq!("é日本 ü!x".previous_word(7), Some(6));
+    assert_eq!("é日本 ü!x".previous_word(5), Some(4));
+}
+
+#[test]
+fn subword() {
+    assert_eq!("setCursorPosition".next_subword(0), Some(3));
+    assert_eq!("setCursorPosition".next_subword(3), Some(9));
+    assert_eq!("setCursorPosition".next_subword(9), None);
+    assert_eq!("cursor_start_row".next_subword(0), Some(6));
+    assert_eq!("cursor_start_row".next_subword(6), Some(12));
+    assert_eq!("HTTPServer x".next_subword(0), Some(4));
+    assert_eq!("HTTPServer x".next_subword(4), Some(10));
+    assert_eq!("utf8Char".next_subword(0), Some(3));
+    assert_eq!("utf8Char".next_subword(3), Some(4));
+    assert_eq!("  foo(bar)".next_subword(0), Some(5));
+    assert_eq!("  foo(bar)".next_subword(5), Some(6));
+    assert_eq!("größeÄnderung".next_subword(0), Some(5));
+
+    assert_eq!("setCursorPosition".previous_subword(17), Some(9));
+    assert_eq!("setCursorPosition".previous_subword(9), Some(3));
+    assert_eq!("setCursorPosition".previous_subword(3), None);
+    assert_eq!("cursor_start_row".previous_subword(16), Some(13));
+    assert_eq!("cursor_start_row".previous_subword(13), Some(7));
+    assert_eq!("HTTPServer".previous_subword(10), Some(4));
+    assert_eq!("HTTPServer".previous_subword(4), None);
+    assert_eq!("utf8Char".previous_subword(4), Some(3));
+    assert_eq!("foo(bar)  ".previous_subword(10), Some(7));
+    assert_eq!("größeÄnderung".previous_subword(13), Some(5));
+}
+
+#[test]
+fn word_count() {
+    assert_eq!("".word_count(), 0);
+    assert_eq!("  -- ".word_count(), 0);
+    assert_eq!("one two  three".word_count(), 3);
+    // underscores are punctuation to next_word as well
+    assert_eq!("a.b(c) d_e, ünï".word_count(), 6);
+}
+
+#[test]
+fn words() {
+    assert!("".words().is_empty());
+    assert_eq!("don't 'quote' x_1, ünï".words(), [0..5, 7..12, 14..17, 19..22]);
 }
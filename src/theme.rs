@@ -0,0 +1,139 @@
+use ratatui::style::{Color, Modifier, Style};
+
+/// The colors the editor is drawn with. One of [`Theme::BUILT_IN`] is picked with `theme = "<name>"` in the global
+/// config file and cycled with F12, and single colors are changed with `theme.<slot> = "<color>"`, where the color is
+/// a name like `lightblue`, an index into the terminal's palette or `#rrggbb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub selection_bg: Color,
+    /// Search matches.
+    pub match_bg: Color,
+    /// Line numbers, which are dimmed except for the cursor line's.
+    pub gutter_fg: Color,
+    pub gutter_current_fg: Color,
+    /// The status line and the tab bar. A color given in the config file becomes its background.
+    pub status_style: Style,
+    /// Errors shown in the status line.
+    pub message_error: Color,
+    /// Rulers and indent guides, and the background of text past the first ruler.
+    pub ruler: Color,
+    /// Background of the cursor line, [`Color::Reset`] leaves it unmarked.
+    pub current_line_bg: Color,
+    /// Background of spaces and tabs at the end of a line.
+    pub trailing_whitespace_bg: Color,
+}
+
+impl Theme {
+    pub const DARK: Self = Self {
+        name: "dark",
+        selection_bg: Color::LightBlue,
+        match_bg: Color::Magenta,
+        gutter_fg: Color::DarkGray,
+        gutter_current_fg: Color::DarkGray,
+        status_style: Style::new().add_modifier(Modifier::REVERSED),
+        message_error: Color::Red,
+        ruler: Color::DarkGray,
+        current_line_bg: Color::Reset,
+        trailing_whitespace_bg: Color::Red,
+    };
+
+    pub const LIGHT: Self = Self {
+        name: "light",
+        selection_bg: Color::LightCyan,
+        match_bg: Color::LightYellow,
+        gutter_fg: Color::Gray,
+        gutter_current_fg: Color::Black,
+        status_style: Style::new().add_modifier(Modifier::REVERSED),
+        message_error: Color::Red,
+        ruler: Color::Gray,
+        current_line_bg: Color::Reset,
+        trailing_whitespace_bg: Color::LightRed,
+    };
+
+    pub const HIGH_CONTRAST: Self = Self {
+        name: "high-contrast",
+        selection_bg: Color::Blue,
+        match_bg: Color::Magenta,
+        gutter_fg: Color::White,
+        gutter_current_fg: Color::Yellow,
+        status_style: Style::new()
+            .fg(Color::Black)
+            .bg(Color::White)
+            .add_modifier(Modifier::BOLD),
+        message_error: Color::LightRed,
+        ruler: Color::DarkGray,
+        current_line_bg: Color::Indexed(236),
+        trailing_whitespace_bg: Color::Red,
+    };
+
+    pub const BUILT_IN: [Self; 3] = [Self::DARK, Self::LIGHT, Self::HIGH_CONTRAST];
+
+    pub fn named(name: &str) -> Option<Self> {
+        Self::BUILT_IN.into_iter().find(|theme| theme.name == name)
+    }
+
+    /// The built-in theme after this one, wrapping around.
+    pub fn next(&self) -> Self {
+        let idx = Self::BUILT_IN.iter().position(|theme| theme.name == self.name);
+        Self::BUILT_IN[idx.map_or(0, |idx| (idx + 1) % Self::BUILT_IN.len())]
+    }
+
+    /// Changes the color in `slot`, or fails if there is no such slot.
+    pub fn set(&mut self, slot: &str, color: Color) -> Result<(), String> {
+        let target = match slot {
+            "selection_bg" => &mut self.selection_bg,
+            "match_bg" => &mut self.match_bg,
+            "gutter_fg" => &mut self.gutter_fg,
+            "gutter_current_fg" => &mut self.gutter_current_fg,
+            "status_style" => {
+                self.status_style = Style::new().bg(color);
+                return Ok(());
+            }
+            "message_error" => &mut self.message_error,
+            "ruler" => &mut self.ruler,
+            "current_line_bg" => &mut self.current_line_bg,
+            "trailing_whitespace_bg" => &mut self.trailing_whitespace_bg,
+            _ => return Err(format!("unknown theme slot {slot}")),
+        };
+        *target = color;
+        Ok(())
+    }
+
+    /// This theme with `colors` changed, which were checked when they were read.
+    pub fn with_colors(mut self, colors: &[(String, Color)]) -> Self {
+        for (slot, color) in colors {
+            _ = self.set(slot, *color);
+        }
+        self
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::DARK
+    }
+}
+
+#[test]
+fn test() {
+    assert_eq!(Theme::named("light"), Some(Theme::LIGHT));
+    assert_eq!(Theme::named("solarized"), None);
+    assert_eq!(Theme::DARK.next(), Theme::LIGHT);
+    assert_eq!(Theme::HIGH_CONTRAST.next(), Theme::DARK);
+
+    let mut theme = Theme::DARK;
+    theme.set("match_bg", Color::Rgb(0x26, 0x4f, 0x78)).unwrap();
+    theme.set("status_style", Color::Blue).unwrap();
+    assert_eq!(theme.match_bg, Color::Rgb(0x26, 0x4f, 0x78));
+    assert_eq!(theme.status_style, Style::new().bg(Color::Blue));
+    assert_eq!(
+        theme.set("cursor", Color::Red).unwrap_err(),
+        "unknown theme slot cursor"
+    );
+
+    // the changed colors stay when cycling on
+    let colors = [("selection_bg".to_string(), Color::Green)];
+    let next = theme.next().with_colors(&colors);
+    assert_eq!((next.name, next.selection_bg), ("light", Color::Green));
+}
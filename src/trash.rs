@@ -0,0 +1,178 @@
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{self, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory next to the file that it is moved into when there is no trash to move it to.
+pub const FALLBACK_DIR: &str = ".ded-trash";
+
+/// Moves the file at `path` into the freedesktop trash at `trash_dir`, with the info file that file managers restore it
+/// from. Without a trash, or if the file is on another file system than the trash, it goes into [`FALLBACK_DIR`] next
+/// to the file instead. Returns where it went.
+pub fn trash(path: &Path, trash_dir: Option<&Path>) -> io::Result<PathBuf> {
+    let path = path::absolute(path)?;
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a file"));
+    };
+
+    // a trash that can't be made is as good as none
+    let trash_dir = trash_dir.filter(|trash_dir| {
+        fs::create_dir_all(trash_dir.join("files"))
+            .and_then(|()| fs::create_dir_all(trash_dir.join("info")))
+            .is_ok()
+    });
+    if let Some(trash_dir) = trash_dir {
+        match move_with_info(&path, name, trash_dir) {
+            // the trash only takes files from its own file system, anything else would have to be copied
+            Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {}
+            result => return result,
+        }
+    }
+
+    let fallback = dir.join(FALLBACK_DIR);
+    fs::create_dir_all(&fallback)?;
+    let to = candidates(name)
+        .map(|candidate| fallback.join(candidate))
+        .find(|to| fs::symlink_metadata(to).is_err())
+        .expect("there are always more names to try");
+    fs::rename(&path, &to)?;
+    Ok(to)
+}
+
+/// Moves `path` into the `files` directory of the trash, under the first name that has no info file yet.
+fn move_with_info(path: &Path, name: &OsStr, trash_dir: &Path) -> io::Result<PathBuf> {
+    let info = format!(
+        "[Trash Info]\nPath={}\nDeletionDate={}\n",
+        escape(path),
+        deletion_date(SystemTime::now())
+    );
+    for candidate in candidates(name) {
+        let to = trash_dir.join("files").join(&candidate);
+        let mut info_name = candidate;
+        info_name.push(".trashinfo");
+        let info_path = trash_dir.join("info").join(info_name);
+
+        // making the info file claims the name, so another program trashing a file at the same time can't take it
+        let mut file = match File::create_new(&info_path) {
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            result => result?,
+        };
+        let result = if fs::symlink_metadata(&to).is_ok() {
+            Err(io::Error::from(io::ErrorKind::AlreadyExists))
+        } else {
+            file.write_all(info.as_bytes()).and_then(|()| fs::rename(path, &to))
+        };
+        match result {
+            Ok(()) => return Ok(to),
+            Err(err) => {
+                _ = fs::remove_file(&info_path);
+                if err.kind() != io::ErrorKind::AlreadyExists {
+                    return Err(err);
+                }
+            }
+        }
+    }
+    unreachable!("there are always more names to try")
+}
+
+/// `name`, then `name.2`, `name.3` and so on.
+fn candidates(name: &OsStr) -> impl Iterator<Item = OsString> {
+    (1..).map(move |n: u64| {
+        let mut candidate = name.to_owned();
+        if n > 1 {
+            candidate.push(format!(".{n}"));
+        }
+        candidate
+    })
+}
+
+/// `path` as a URL path, with every byte other than letters, digits, `-_.~` and `/` percent-encoded.
+fn escape(path: &Path) -> String {
+    path.as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .map(|&byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => char::from(byte).to_string(),
+            byte => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// `time` as `YYYY-MM-DDThh:mm:ss`. The spec asks for local time, which takes a time zone database to work out, so
+/// this is UTC.
+fn deletion_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // days since 1970 to a date, from Howard Hinnant's civil_from_days, with years starting in March so leap days come
+    // last
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[test]
+fn test() {
+    let home = std::env::temp_dir().join("ded-test-trash");
+    _ = fs::remove_dir_all(&home);
+    let trash_dir = home.join(".local/share/Trash");
+    let dir = home.join("some dir");
+    fs::create_dir_all(&dir).unwrap();
+
+    let file = dir.join("scratch.txt");
+    fs::write(&file, "first").unwrap();
+    let to = trash(&file, Some(&trash_dir)).unwrap();
+    assert_eq!(to, trash_dir.join("files/scratch.txt"));
+    assert_eq!(fs::read_to_string(&to).unwrap(), "first");
+    assert!(!file.exists());
+    let info = fs::read_to_string(trash_dir.join("info/scratch.txt.trashinfo")).unwrap();
+    let path = info
+        .strip_prefix("[Trash Info]\nPath=")
+        .and_then(|info| info.split_once('\n'))
+        .map(|(path, _)| path);
+    assert_eq!(path, Some(escape(&file).as_str()));
+    assert!(path.unwrap().ends_with("/some%20dir/scratch.txt"));
+    assert!(info.contains("\nDeletionDate=2"));
+
+    // a file trashed under the same name again gets another one
+    fs::write(&file, "second").unwrap();
+    let to = trash(&file, Some(&trash_dir)).unwrap();
+    assert_eq!(to, trash_dir.join("files/scratch.txt.2"));
+    assert!(trash_dir.join("info/scratch.txt.2.trashinfo").exists());
+
+    // without a trash, or with one that can't be made, the file goes next to where it was
+    for trash_dir in [None, Some(file.join("Trash"))] {
+        fs::write(&file, "third").unwrap();
+        let to = trash(&file, trash_dir.as_deref()).unwrap();
+        assert_eq!(to.parent(), Some(dir.join(FALLBACK_DIR).as_path()));
+        assert!(!file.exists());
+    }
+    assert!(dir.join(FALLBACK_DIR).join("scratch.txt.2").exists());
+
+    assert!(trash(&file, Some(&trash_dir)).is_err());
+    fs::remove_dir_all(&home).unwrap();
+}
+
+#[test]
+fn dates() {
+    let at = |secs| deletion_date(UNIX_EPOCH + std::time::Duration::from_secs(secs));
+    assert_eq!(at(0), "1970-01-01T00:00:00");
+    assert_eq!(at(1_093_991_528), "2004-08-31T22:32:08");
+    // a leap day, and the last second of a leap year
+    assert_eq!(at(951_782_400), "2000-02-29T00:00:00");
+    assert_eq!(at(1_735_689_599), "2024-12-31T23:59:59");
+}
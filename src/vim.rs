@@ -0,0 +1,289 @@
+use std::fmt;
+
+use crate::editor::Editor;
+use crate::input::{Input, Key};
+use crate::textarea::CursorPosition;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mode::Normal => write!(f, "NORMAL"),
+            Mode::Insert => write!(f, "INSERT"),
+            Mode::Visual => write!(f, "VISUAL"),
+        }
+    }
+}
+
+/// Modal editing in front of [`Editor::input`], enabled with `--vim`.
+///
+/// Normal and visual mode keys are translated into the inputs the editor already handles, so everything they do is
+/// undoable like any other edit. Insert mode passes keys through unchanged.
+#[derive(Debug, Default)]
+pub struct Vim {
+    mode: Mode,
+    /// Keys of a command that isn't complete yet, like the `d` of `dw`.
+    pending: String,
+}
+
+impl Vim {
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn pending(&self) -> &str {
+        &self.pending
+    }
+
+    /// Handles `input`, returning whether the text was modified.
+    pub fn input(&mut self, editor: &mut Editor, input: Input) -> bool {
+        if self.mode == Mode::Insert {
+            if input.key == Key::Esc {
+                self.mode = Mode::Normal;
+                return false;
+            }
+            return editor.input(input);
+        }
+
+        let char = match input {
+            Input {
+                key: Key::Char(char),
+                ctrl: false,
+                alt: false,
+                ..
+            } => char,
+            Input { key: Key::Esc, .. } => {
+                self.pending.clear();
+                self.set_mode(editor, Mode::Normal);
+                return false;
+            }
+            // keys that type or delete text only do so in insert mode
+            Input {
+                key: Key::Backspace | Key::Delete | Key::Enter | Key::Tab | Key::BackTab,
+                ..
+            } => {
+                self.pending.clear();
+                return false;
+            }
+            // moving keys extend the selection in visual mode
+            Input {
+                key: Key::Left | Key::Right | Key::Up | Key::Down | Key::Home | Key::End | Key::PageUp | Key::PageDown,
+                ..
+            } => {
+                self.pending.clear();
+                let shift = input.shift || self.mode == Mode::Visual;
+                return editor.input(Input { shift, ..input });
+            }
+            // Ctrl and Alt shortcuts work as usual
+            input => {
+                self.pending.clear();
+                return editor.input(input);
+            }
+        };
+
+        self.pending.push(char);
+        let pending = std::mem::take(&mut self.pending);
+        let (operator, motion) = match (self.mode, pending.as_bytes()) {
+            (Mode::Normal, [b'd' | b'y'] | [b'd' | b'y', b'g']) | (_, [b'g']) => {
+                self.pending = pending;
+                return false;
+            }
+            (Mode::Normal, [operator @ (b'd' | b'y'), rest @ ..]) => (Some(*operator), rest),
+            (_, keys) => (None, keys),
+        };
+
+        match (self.mode, operator, motion) {
+            // a doubled operator works on the whole line
+            (Mode::Normal, Some(b'd'), b"d") => editor.input(ctrl('x')),
+            (Mode::Normal, Some(b'y'), b"y") => editor.input(ctrl('c')),
+            (Mode::Normal, Some(operator), motion) => {
+                let start = editor.textarea.cursor();
+                editor.textarea.set_selection(Some(start));
+                if !motion_input(editor, motion, true) || editor.textarea.selection_range().is_none() {
+                    editor.textarea.set_selection(None);
+                    return false;
+                }
+                self.apply(editor, operator)
+            }
+            (_, None, b"i") => {
+                self.set_mode(editor, Mode::Insert);
+                false
+            }
+            (_, None, b"a") => {
+                self.set_mode(editor, Mode::Insert);
+                let cursor = editor.textarea.cursor();
+                if cursor.col < editor.textarea.lines[cursor.row].chars().count() {
+                    editor.input(key(Key::Right));
+                }
+                false
+            }
+            (Mode::Normal, None, b"v") => {
+                self.mode = Mode::Visual;
+                let cursor = editor.textarea.cursor();
+                editor.textarea.set_selection(Some(cursor));
+                false
+            }
+            (Mode::Visual, None, b"v") => {
+                self.set_mode(editor, Mode::Normal);
+                false
+            }
+            (Mode::Visual, None, [operator @ (b'd' | b'x' | b'y')]) => {
+                self.mode = Mode::Normal;
+                self.apply(editor, *operator)
+            }
+            (Mode::Normal, None, b"x") => editor.input(key(Key::Delete)),
+            (Mode::Normal, None, b"p") => editor.textarea.paste_after(),
+            (Mode::Visual, None, b"p") => {
+                let modified = editor.input(ctrl('v'));
+                self.set_mode(editor, Mode::Normal);
+                modified
+            }
+            (_, None, b"u") => editor.input(ctrl('z')),
+            (mode, None, motion) => {
+                motion_input(editor, motion, mode == Mode::Visual);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Deletes or yanks the selection, leaving normal mode.
+    fn apply(&mut self, editor: &mut Editor, operator: u8) -> bool {
+        if operator == b'y' {
+            editor.input(ctrl('c'));
+            let (start, _) = editor.textarea.selection_range().unwrap_or_default();
+            editor.textarea.set_cursor(start, false);
+            false
+        } else {
+            editor.input(ctrl('x'))
+        }
+    }
+
+    fn set_mode(&mut self, editor: &mut Editor, mode: Mode) {
+        self.mode = mode;
+        editor.textarea.set_selection(None);
+    }
+}
+
+/// Moves the cursor by a motion, extending the selection if `select`. Returns whether `keys` is a motion.
+fn motion_input(editor: &mut Editor, keys: &[u8], select: bool) -> bool {
+    let input = |key, ctrl, alt| Input { key, ctrl, alt, shift: select };
+    let input = match keys {
+        b"h" => input(Key::Left, false, false),
+        b"j" => input(Key::Down, false, false),
+        b"k" => input(Key::Up, false, false),
+        b"l" => input(Key::Right, false, false),
+        b"w" => input(Key::Right, true, false),
+        b"b" => input(Key::Left, true, false),
+        b"$" => input(Key::End, false, false),
+        b"0" | b"gg" | b"G" => {
+            let textarea = &mut editor.textarea;
+            let cursor = textarea.cursor();
+            let cursor = match keys {
+                b"0" => CursorPosition { col: 0, ..cursor },
+                // like in vim, to the first non-blank character of the first or last line
+                _ => {
+                    let row = if keys == b"G" { textarea.lines.len() - 1 } else { 0 };
+                    let line = &textarea.lines[row];
+                    let col = line.chars().take_while(|c| c.is_whitespace()).count();
+                    CursorPosition { row, col }
+                }
+            };
+            textarea.set_cursor(cursor, select);
+            return true;
+        }
+        _ => return false,
+    };
+    editor.input(input);
+    true
+}
+
+fn key(key: Key) -> Input {
+    Input { key, ..Default::default() }
+}
+
+fn ctrl(char: char) -> Input {
+    Input {
+        key: Key::Char(char),
+        ctrl: true,
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+fn type_keys(vim: &mut Vim, editor: &mut Editor, keys: &str) {
+    for char in keys.chars() {
+        let key = if char == '\u{1b}' { Key::Esc } else { Key::Char(char) };
+        vim.input(editor, Input { key, ..Default::default() });
+    }
+}
+
+#[test]
+fn test() {
+    let mut vim = Vim::default();
+    let mut editor = Editor::default();
+    editor.textarea.lines = ["one two three", "four", "five"].map(String::from).into();
+
+    type_keys(&mut vim, &mut editor, "wdw");
+    assert_eq!(editor.textarea.lines, ["one three", "four", "five"]);
+    assert_eq!(vim.mode(), Mode::Normal);
+
+    type_keys(&mut vim, &mut editor, "d");
+    assert_eq!(vim.pending(), "d");
+    type_keys(&mut vim, &mut editor, "d");
+    assert_eq!(vim.pending(), "");
+    assert_eq!(editor.textarea.lines, ["four", "five"]);
+
+    type_keys(&mut vim, &mut editor, "Gix\u{1b}");
+    assert_eq!(editor.textarea.lines, ["four", "xfive"]);
+    assert_eq!(vim.mode(), Mode::Normal);
+
+    // selections don't include the character under the cursor
+    type_keys(&mut vim, &mut editor, "ggvld");
+    assert_eq!(editor.textarea.lines, ["our", "xfive"]);
+    assert_eq!(vim.mode(), Mode::Normal);
+
+    // pasted after the char under the cursor, or whole lines below the cursor line
+    type_keys(&mut vim, &mut editor, "y$jp");
+    assert_eq!(editor.textarea.lines, ["our", "xourfive"]);
+    type_keys(&mut vim, &mut editor, "ggyyp");
+    assert_eq!(editor.textarea.lines, ["our", "our", "xourfive"]);
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 1, col: 0 });
+    type_keys(&mut vim, &mut editor, "Gp");
+    assert_eq!(editor.textarea.lines, ["our", "our", "xourfive", "our"]);
+    assert_eq!(editor.textarea.cursor(), CursorPosition { row: 3, col: 0 });
+
+    // every edit is undoable
+    type_keys(&mut vim, &mut editor, "uuuuuuu");
+    assert_eq!(editor.textarea.lines, ["one two three", "four", "five"]);
+}
+
+#[test]
+fn keys_outside_insert_mode() {
+    let mut vim = Vim::default();
+    let mut editor = Editor::default();
+    editor.textarea.lines = ["one two", "three"].map(String::from).into();
+    editor.textarea.set_cursor(CursorPosition { row: 0, col: 3 }, false);
+
+    // keys that would type or delete text do nothing
+    for key in [Key::Backspace, Key::Delete, Key::Enter, Key::Tab] {
+        assert!(!vim.input(&mut editor, self::key(key)));
+    }
+    assert_eq!(editor.textarea.lines, ["one two", "three"]);
+
+    // arrows extend the selection in visual mode, while shortcuts act on it as they would otherwise
+    type_keys(&mut vim, &mut editor, "v");
+    vim.input(&mut editor, key(Key::End));
+    assert_eq!(editor.textarea.selection_range().map(|(start, _)| start.col), Some(3));
+    vim.input(&mut editor, key(Key::Delete));
+    assert_eq!(editor.textarea.lines, ["one two", "three"]);
+    assert!(vim.input(&mut editor, ctrl('x')));
+    assert_eq!(editor.textarea.lines, ["one", "three"]);
+}